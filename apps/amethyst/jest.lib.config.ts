@@ -14,6 +14,7 @@ const config: Config = {
           module: "commonjs",
           moduleResolution: "node",
           esModuleInterop: true,
+          resolveJsonModule: true,
           target: "es2020",
           lib: ["es2020"],
           types: ["jest", "node"],
@@ -0,0 +1,56 @@
+/**
+ * Golden corpus shared with the indexer (cadet), which runs the same cases
+ * in services/cadet/src/ingestors/teal/cleaning.rs. A case failing here and
+ * not there (or the other way round) means the app and the indexer clean
+ * names differently.
+ */
+
+import corpus from "@teal/cleaning-rules/corpus.json";
+import rules from "@teal/cleaning-rules/rules.json";
+
+import {
+  CLEANING_RULES_VERSION,
+  cleanArtistName,
+  cleanTrackName,
+  normalizeForComparison,
+} from "../musicbrainzCleaner";
+
+describe("cleaning rules", () => {
+  it("should be the version the cleaner understands", () => {
+    expect(rules.version).toBe(CLEANING_RULES_VERSION);
+    expect(corpus.version).toBe(CLEANING_RULES_VERSION);
+  });
+});
+
+describe("golden corpus", () => {
+  const artists = corpus.cases.filter((c) => c.kind === "artist");
+  const tracks = corpus.cases.filter((c) => c.kind === "track");
+  const comparisons = corpus.cases.filter((c) => c.kind === "comparison");
+
+  it("should only hold artist, track and comparison cases", () => {
+    expect(artists.length + tracks.length + comparisons.length).toBe(
+      corpus.cases.length,
+    );
+  });
+
+  it.each(artists.map((c) => [c.raw, c.cleaned]))(
+    "should clean artist %j to %j",
+    (raw, cleaned) => {
+      expect(cleanArtistName(raw)).toBe(cleaned);
+    },
+  );
+
+  it.each(tracks.map((c) => [c.raw, c.cleaned]))(
+    "should clean track %j to %j",
+    (raw, cleaned) => {
+      expect(cleanTrackName(raw)).toBe(cleaned);
+    },
+  );
+
+  it.each(comparisons.map((c) => [c.raw, c.cleaned]))(
+    "should normalize %j to %j for comparison",
+    (raw, normalized) => {
+      expect(normalizeForComparison(raw)).toBe(normalized);
+    },
+  );
+});
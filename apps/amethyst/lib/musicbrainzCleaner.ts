@@ -3,10 +3,27 @@
  * Ported from backend Rust implementation for improved search matching
  */
 
+import rules from "@teal/cleaning-rules/rules.json";
+
 // =============================================================================
-// CONFIGURATION CONSTANTS
+// CONFIGURATION
 // =============================================================================
 
+/**
+ * Version of the rules file format this cleaner understands.
+ *
+ * The cleaning rules live in packages/cleaning-rules/rules.json and are shared
+ * with the indexer (cadet), so names are cleaned the same way in the app and
+ * in the backend. Both run the golden corpus in packages/cleaning-rules/corpus.json.
+ */
+export const CLEANING_RULES_VERSION = 1;
+
+if (rules.version !== CLEANING_RULES_VERSION) {
+  throw new Error(
+    `Cleaning rules are version ${rules.version}, expected version ${CLEANING_RULES_VERSION}`,
+  );
+}
+
 /**
  * Threshold for "short" base names that need disambiguation info preserved.
  * Track names shorter than this are more likely to be generic (e.g., "High", "One")
@@ -16,160 +33,81 @@
  * - Preserving disambiguation for common short names
  * - Removing guff for longer, more specific names
  */
-const SHORT_NAME_THRESHOLD = 15;
+const SHORT_NAME_THRESHOLD = rules.thresholds.shortName;
 
 /**
  * Threshold for "common phrase" length (combined with word count check).
  * Names under this length with ≤3 words are considered common phrases
  * that may need disambiguation.
  */
-const COMMON_PHRASE_LENGTH = 20;
+const COMMON_PHRASE_LENGTH = rules.thresholds.commonPhraseLength;
 
 /**
  * Minimum word length to be considered a potential artist name in disambiguation.
  * Words shorter than this are likely articles/prepositions, not artist names.
  */
-const MIN_ARTIST_NAME_LENGTH = 4;
+const MIN_ARTIST_NAME_LENGTH = rules.thresholds.minArtistNameLength;
 
 /**
  * Generic track names that commonly need disambiguation info preserved.
  * These are words that appear in many different songs by different artists.
  */
-const GENERIC_TRACK_NAMES = [
-  "song", "track", "music", "beat", "sound", "tune", "piece",
-  "high", "low", "one", "two", "three", "four", "five",
-  "love", "time", "life", "home", "heart", "dream",
-] as const;
+const GENERIC_TRACK_NAMES: readonly string[] = rules.genericTrackNames;
 
 /**
  * Words commonly found in parenthetical/bracketed content that can be removed
  * without losing essential information for matching.
  * 
- * Categories:
- * - Audio quality/format: mono, stereo, remastered, etc.
- * - Version types: remix, edit, live, acoustic, etc.
- * - Production info: prod, produced, by
- * - Edition types: deluxe, expanded, anniversary, bonus
- * - Collaboration markers: feat, ft, featuring, vs, with
- * - Status/metadata: official, explicit, clean, etc.
+ * The rules file groups them by category: audio quality/format, version types,
+ * remix/edit variants, production credits, edition types, session/take variants,
+ * track structure, content ratings, collaboration markers, video/media and
+ * other metadata.
  */
-const GUFF_WORDS = [
-  // Audio quality/format
-  "mono",
-  "stereo",
-  "quadraphonic",
-  "remastered",
-  "remaster",
-  "master",
-  "hd",
-  "hifi",
-  "hi-fi",
-  
-  // Version types
-  "a cappella",
-  "acoustic",
-  "extended",
-  "instrumental",
-  "karaoke",
-  "live",
-  "orchestral",
-  "piano",
-  "unplugged",
-  "vocal",
-  
-  // Remix/edit variants
-  "club",
-  "clubmix",
-  "dance",
-  "edit",
-  "maxi",
-  "megamix",
-  "mix",
-  "radio",
-  "re-edit",
-  "reedit",
-  "refix",
-  "remake",
-  "remix",
-  "remixed",
-  "remode",
-  "reprise",
-  "rework",
-  "reworked",
-  "rmx",
-  
-  // Production credits (commonly in brackets)
-  "prod",
-  "produced",
-  "production",
-  
-  // Edition/release types
-  "anniversary",
-  "bonus",
-  "deluxe",
-  "edition",
-  "expanded",
-  "original",
-  "release",
-  "released",
-  "single",
-  "special",
-  "version",
-  "ver",
-  
-  // Session/take variants
-  "demo",
-  "outtake",
-  "outtakes",
-  "rehearsal",
-  "session",
-  "take",
-  "takes",
-  "tape",
-  "tryout",
-  
-  // Track structure
-  "composition",
-  "cut",
-  "dialogue",
-  "excerpt",
-  "interlude",
-  "intro",
-  "long",
-  "main",
-  "outro",
-  "rap",
-  "short",
-  "skit",
-  "studio",
-  "track",
-  
-  // Content ratings
-  "censored",
-  "clean",
-  "dirty",
-  "explicit",
-  "uncensored",
-  
-  // Collaboration/featuring
-  "feat",
-  "featuring",
-  "ft",
-  "vs",
-  "with",
-  "without",
-  
-  // Video/media
-  "official",
-  "video",
-  
-  // Other metadata
-  "reinterpreted",
-  "snippet",
-  "preview",
-  "unknown",
-  "untitled",
-] as const;
+const GUFF_WORDS: readonly string[] = Object.values(rules.guffWords).flat();
+const GUFF_WORD_SET = new Set<string>(GUFF_WORDS);
+
+const BRACKETS = rules.brackets as ReadonlyArray<readonly [string, string]>;
+
+const { remix: REMIX_RULES, version: VERSION_RULES } = rules.disambiguation;
+
+function escapeRegExp(text: string): string {
+  return text.replace(/[.*+?^${}()|[\]\\\-]/g, "\\$&");
+}
+
+/** Matches any of `words` anywhere, e.g. "rmx" in "vip rmx" */
+function anyOf(words: readonly string[]): RegExp {
+  return new RegExp(words.map(escapeRegExp).join("|"));
+}
+
+/** Matches any of `words` as a whole word, like `\b(?:a|b)\b` */
+function anyWordOf(words: readonly string[]): RegExp {
+  return new RegExp(`\\b(?:${words.map(escapeRegExp).join("|")})\\b`);
+}
+
+const REMIX_PATTERN = anyOf(REMIX_RULES.keywords);
+const FEAT_PATTERN = anyOf(rules.featMarkers);
+const VERSION_PATTERN = anyWordOf(VERSION_RULES.keywords);
+const DATED_VERSION_PATTERN = anyWordOf(VERSION_RULES.datedKeywords);
+const ALWAYS_KEEP_VERSION_PATTERN = anyWordOf(VERSION_RULES.alwaysKeep);
+const NAMED_VARIANT_PATTERN = anyWordOf(REMIX_RULES.namedVariantWords);
+const GENERIC_VARIANT_WORDS = new Set<string>(REMIX_RULES.genericVariantWords);
+const DASH_REMIX_PATTERN = new RegExp(anyWordOf(rules.dashRemixWords).source, "i");
+const YEAR_PATTERN = /(19|20)\d{2}/;
+
+/** Words that never count as an artist name, by disambiguation type */
+const KEYWORD_PATTERNS = {
+  remix: anyOf([...REMIX_RULES.keywords, ...REMIX_RULES.namedVariantWords]),
+  version: anyOf([...VERSION_RULES.keywords, ...VERSION_RULES.datedKeywords]),
+  feat: FEAT_PATTERN,
+} as const;
+
+/** Featuring markers with an optional "." between whitespace, e.g. " feat. " */
+const FEAT_PATTERNS = rules.featMarkers.map(
+  (marker) => new RegExp(`\\s+${escapeRegExp(marker)}\\.?\\s+`, "i"),
+);
+
+const LEADING_DECORATION = new RegExp(`^[\\s${escapeRegExp(rules.decorative.leading)}]+`);
+const TRAILING_DECORATION = new RegExp(`[\\s${escapeRegExp(rules.decorative.trailing)}]+$`);
 
 /**
  * Check if content should be kept for disambiguation (remix/feat info)
@@ -189,26 +127,26 @@ function shouldKeepForDisambiguation(
   // Instrumental/acoustic are always worth preserving regardless of base name length.
   // Eval: stripping instrumental has 0 better-position wins, 16 regressions (all from P@1).
   // These denote distinct recordings in MB (vocal vs instrumental, plugged vs acoustic).
-  if (type === "version" && /\b(?:instrumental|acoustic)\b/.test(contentLower)) {
+  if (type === "version" && ALWAYS_KEEP_VERSION_PATTERN.test(contentLower)) {
     return true;
   }
 
   // Check if content matches the type we're looking for
   const isRelevant = type === "remix"
-    ? /remix|rmx|rework|refix|remode/.test(contentLower)
+    ? REMIX_PATTERN.test(contentLower)
     : type === "version"
-    ? /\b(?:mono|stereo|quadraphonic)\b/.test(contentLower)
-      || (/\b(?:remaster(?:ed)?)\b/.test(contentLower) && /(19|20)\d{2}/.test(contentLower))
-    : /feat\.?|ft\.?|featuring/i.test(contentLower);
+    ? VERSION_PATTERN.test(contentLower)
+      || (DATED_VERSION_PATTERN.test(contentLower) && YEAR_PATTERN.test(contentLower))
+    : FEAT_PATTERN.test(contentLower);
 
   // "edition" and "mix" are remix-like only when accompanied by an artist name
   // (e.g., "Kaytranada Edition" / "Zomby mix" = named remix, "Deluxe Edition" / "Original Mix" = guff)
-  const GENERIC_MIX_WORDS = /^(?:edition|deluxe|special|expanded|anniversary|bonus|limited|original|remastered|collector|club|radio|dance|dub|instrumental|extended|vocal|single|version|maxi|mega|short|long|main|mix|\d+\w*)$/;
   const isNamedVariantWithArtist = type === "remix"
-    && /\b(?:edition|mix)\b/.test(contentLower)
+    && NAMED_VARIANT_PATTERN.test(contentLower)
     && contentLower.split(/\s+/).some(
       word => word.length >= MIN_ARTIST_NAME_LENGTH
-        && !GENERIC_MIX_WORDS.test(word)
+        && !GENERIC_VARIANT_WORDS.has(word)
+        && !/^\d+\w*$/.test(word)
     );
 
   if (!isRelevant && !isNamedVariantWithArtist) return false;
@@ -221,11 +159,7 @@ function shouldKeepForDisambiguation(
   const isCommonPhrase = baseName.split(/\s+/).length <= 3 && baseName.length < COMMON_PHRASE_LENGTH;
 
   // Check if content contains artist name (word ≥ MIN_ARTIST_NAME_LENGTH that's not a keyword)
-  const keywordPattern = type === "remix"
-    ? /remix|rmx|rework|refix|remode|edition/i
-    : type === "version"
-    ? /mono|stereo|quadraphonic|remaster(?:ed)?/i
-    : /feat\.?|ft\.?|featuring/i;
+  const keywordPattern = KEYWORD_PATTERNS[type];
   const hasArtistName = contentLower.split(/\s+/).some(
     word => word.length >= MIN_ARTIST_NAME_LENGTH && !keywordPattern.test(word)
   );
@@ -238,17 +172,16 @@ function shouldKeepForDisambiguation(
  */
 function isLikelyGuff(content: string): boolean {
   const contentLower = content.toLowerCase();
-  const words = contentLower.split(/\s+/);
+  const words = contentLower.trim().split(/\s+/);
 
   // Count guff words (strip trailing punctuation for matching: "prod." -> "prod")
-  const guffWordSet = new Set<string>(GUFF_WORDS);
   const guffWordCount = words.filter((word) => {
     const stripped = word.replace(/[.,!?;:]+$/, ""); // Strip trailing punctuation
-    return guffWordSet.has(word) || guffWordSet.has(stripped);
+    return GUFF_WORD_SET.has(word) || GUFF_WORD_SET.has(stripped);
   }).length;
 
   // Check for years (19XX or 20XX)
-  const hasYear = /(19|20)\d{2}/.test(contentLower);
+  const hasYear = YEAR_PATTERN.test(contentLower);
 
   // Consider it guff if >50% are guff words, or if it contains years, or if it's short and common
   return (
@@ -259,6 +192,47 @@ function isLikelyGuff(content: string): boolean {
   );
 }
 
+/**
+ * Find the top-level `open`/`close` groups of `text`, left to right, stopping
+ * at the first unbalanced one. Nested groups are part of their parent's content.
+ */
+function findBracketGroups(
+  text: string,
+  open: string,
+  close: string,
+): Array<{ start: number; end: number; content: string }> {
+  const groups: Array<{ start: number; end: number; content: string }> = [];
+  let i = 0;
+  while (i < text.length) {
+    if (text[i] === open) {
+      let depth = 1;
+      let j = i + 1;
+      while (j < text.length && depth > 0) {
+        if (text[j] === open) depth++;
+        else if (text[j] === close) depth--;
+        j++;
+      }
+      if (depth === 0) {
+        groups.push({ start: i, end: j - 1, content: text.substring(i + 1, j - 1) });
+        i = j;
+      } else {
+        break;
+      }
+    } else {
+      i++;
+    }
+  }
+  return groups;
+}
+
+function shouldKeepAny(content: string, baseName: string): boolean {
+  return (
+    shouldKeepForDisambiguation(content, baseName, "remix") ||
+    shouldKeepForDisambiguation(content, baseName, "feat") ||
+    shouldKeepForDisambiguation(content, baseName, "version")
+  );
+}
+
 /**
  * Clean artist name by removing common variations and guff
  */
@@ -266,61 +240,21 @@ export function cleanArtistName(name: string): string {
   let cleaned = name.trim();
 
   // Remove common featuring patterns
-  const featPatterns = [
-    /\s+feat\.?\s+/i,
-    /\s+ft\.?\s+/i,
-    /\s+featuring\s+/i,
-  ];
-  for (const pattern of featPatterns) {
+  for (const pattern of FEAT_PATTERNS) {
     const match = cleaned.match(pattern);
     if (match && match.index !== undefined) {
       cleaned = cleaned.substring(0, match.index).trim();
     }
   }
 
-  // Remove parenthetical content if it looks like guff
-  // Match backend behavior: only remove first occurrence to handle nested parentheses correctly
-  if (cleaned.includes("(") && cleaned.includes(")")) {
-    const start = cleaned.indexOf("(");
-    // Find matching closing paren (handle nested)
-    let depth = 1;
-    let end = start + 1;
-    while (end < cleaned.length && depth > 0) {
-      if (cleaned[end] === "(") depth++;
-      else if (cleaned[end] === ")") depth--;
-      end++;
-    }
-    if (depth === 0) {
-      end--; // Adjust for final increment
-      const parenContent = cleaned.substring(start + 1, end).toLowerCase();
-      if (isLikelyGuff(parenContent)) {
-        cleaned = (cleaned.substring(0, start) + cleaned.substring(end + 1)).trim();
-        // Normalize whitespace after removal (fixes double spaces)
-        cleaned = cleaned.replace(/\s+/g, " ").trim();
-      }
-    }
-  }
-
-  // Remove brackets with guff
-  // Match backend behavior: only remove first occurrence
-  if (cleaned.includes("[") && cleaned.includes("]")) {
-    const start = cleaned.indexOf("[");
-    // Find matching closing bracket (handle nested)
-    let depth = 1;
-    let end = start + 1;
-    while (end < cleaned.length && depth > 0) {
-      if (cleaned[end] === "[") depth++;
-      else if (cleaned[end] === "]") depth--;
-      end++;
-    }
-    if (depth === 0) {
-      end--; // Adjust for final increment
-      const bracketContent = cleaned.substring(start + 1, end).toLowerCase();
-      if (isLikelyGuff(bracketContent)) {
-        cleaned = (cleaned.substring(0, start) + cleaned.substring(end + 1)).trim();
-        // Normalize whitespace after removal
-        cleaned = cleaned.replace(/\s+/g, " ").trim();
-      }
+  // Remove bracketed content if it looks like guff.
+  // Match backend behavior: only remove the first group of each bracket type
+  for (const [open, close] of BRACKETS) {
+    const [group] = findBracketGroups(cleaned, open, close);
+    if (group && isLikelyGuff(group.content.toLowerCase())) {
+      cleaned = (cleaned.substring(0, group.start) + cleaned.substring(group.end + 1)).trim();
+      // Normalize whitespace after removal (fixes double spaces)
+      cleaned = cleaned.replace(/\s+/g, " ").trim();
     }
   }
 
@@ -341,88 +275,28 @@ export function cleanTrackName(name: string): string {
   let cleaned = name.trim();
 
   // Strip leading/trailing decorative characters (* ~ · • ★ etc.)
-  const stripped = cleaned.replace(/^[\s*~·•★☆♪♫|_>]+/, "").replace(/[\s*~·•★☆♪♫|_<]+$/, "").trim();
+  const stripped = cleaned.replace(LEADING_DECORATION, "").replace(TRAILING_DECORATION, "").trim();
   if (stripped.length > 0) cleaned = stripped;
 
-  // Remove parenthetical content if it looks like guff.
-  // Process all top-level parenthetical groups right-to-left so removals don't shift
+  // Remove bracketed content if it looks like guff, parentheses first.
+  // Process all top-level groups right-to-left so removals don't shift
   // earlier indices. Handles "Song (Remix) (Live Version)" removing "(Live Version)".
-  {
-    // Collect all top-level paren groups
-    const groups: Array<{ start: number; end: number; content: string }> = [];
-    let i = 0;
-    while (i < cleaned.length) {
-      if (cleaned[i] === "(") {
-        let depth = 1;
-        let j = i + 1;
-        while (j < cleaned.length && depth > 0) {
-          if (cleaned[j] === "(") depth++;
-          else if (cleaned[j] === ")") depth--;
-          j++;
-        }
-        if (depth === 0) {
-          groups.push({ start: i, end: j - 1, content: cleaned.substring(i + 1, j - 1) });
-          i = j;
-        } else {
-          break;
-        }
-      } else {
-        i++;
-      }
-    }
-
-    // Process right-to-left
+  for (const [open, close] of BRACKETS) {
+    const groups = findBracketGroups(cleaned, open, close);
+    let removed = false;
     for (let g = groups.length - 1; g >= 0; g--) {
       const { start, end, content } = groups[g];
       const baseName = cleaned.substring(0, start).trim();
-      const shouldKeepRemix = shouldKeepForDisambiguation(content, baseName, "remix");
-      const shouldKeepFeat = shouldKeepForDisambiguation(content, baseName, "feat");
-      const shouldKeepVersion = shouldKeepForDisambiguation(content, baseName, "version");
-      const shouldKeep = shouldKeepRemix || shouldKeepFeat || shouldKeepVersion;
+      const shouldKeep = shouldKeepAny(content, baseName);
       const wouldLeaveEmpty = baseName.length === 0 && cleaned.substring(end + 1).trim().length === 0;
       if (isLikelyGuff(content.toLowerCase()) && !shouldKeep && !wouldLeaveEmpty) {
-        cleaned = (cleaned.substring(0, start) + cleaned.substring(end + 1)).trim();
-        cleaned = cleaned.replace(/\s+/g, " ").trim();
+        cleaned = cleaned.substring(0, start) + cleaned.substring(end + 1);
+        removed = true;
       }
     }
-  }
-
-  // Remove brackets with guff (same right-to-left logic as parentheses)
-  {
-    const groups: Array<{ start: number; end: number; content: string }> = [];
-    let bi = 0;
-    while (bi < cleaned.length) {
-      if (cleaned[bi] === "[") {
-        let depth = 1;
-        let bj = bi + 1;
-        while (bj < cleaned.length && depth > 0) {
-          if (cleaned[bj] === "[") depth++;
-          else if (cleaned[bj] === "]") depth--;
-          bj++;
-        }
-        if (depth === 0) {
-          groups.push({ start: bi, end: bj - 1, content: cleaned.substring(bi + 1, bj - 1) });
-          bi = bj;
-        } else {
-          break;
-        }
-      } else {
-        bi++;
-      }
-    }
-
-    for (let g = groups.length - 1; g >= 0; g--) {
-      const { start, end, content } = groups[g];
-      const baseName = cleaned.substring(0, start).trim();
-      const shouldKeepRemix = shouldKeepForDisambiguation(content, baseName, "remix");
-      const shouldKeepFeat = shouldKeepForDisambiguation(content, baseName, "feat");
-      const shouldKeepVersion = shouldKeepForDisambiguation(content, baseName, "version");
-      const shouldKeep = shouldKeepRemix || shouldKeepFeat || shouldKeepVersion;
-      const wouldLeaveEmpty = baseName.length === 0 && cleaned.substring(end + 1).trim().length === 0;
-      if (isLikelyGuff(content.toLowerCase()) && !shouldKeep && !wouldLeaveEmpty) {
-        cleaned = (cleaned.substring(0, start) + cleaned.substring(end + 1)).trim();
-        cleaned = cleaned.replace(/\s+/g, " ").trim();
-      }
+    // Collapse whitespace once all groups are gone, so the indices above stay valid
+    if (removed) {
+      cleaned = cleaned.replace(/\s+/g, " ").trim();
     }
   }
 
@@ -436,14 +310,11 @@ export function cleanTrackName(name: string): string {
 
     if (baseName.length > 0 && suffix.length > 0) {
       // Treat the suffix like parenthetical content: strip if guff and not needed for disambiguation
-      const shouldKeepRemix = shouldKeepForDisambiguation(suffix, baseName, "remix");
-      const shouldKeepFeat = shouldKeepForDisambiguation(suffix, baseName, "feat");
-      const shouldKeepVersion = shouldKeepForDisambiguation(suffix, baseName, "version");
-      const shouldKeep = shouldKeepRemix || shouldKeepFeat || shouldKeepVersion;
+      const shouldKeep = shouldKeepAny(suffix, baseName);
 
       if (isLikelyGuff(suffix.toLowerCase()) && !shouldKeep) {
         cleaned = baseName;
-      } else if (/\b(?:remix|rmx|rework|re-edit|reedit|mix)\b/i.test(suffix)) {
+      } else if (DASH_REMIX_PATTERN.test(suffix)) {
         // Convert dash-separated remix/mix to parenthesized format.
         // Last.fm uses "Track - Artist Remix" but MusicBrainz often uses "Track (Artist Remix)"
         cleaned = `${baseName} (${suffix})`;
@@ -452,13 +323,7 @@ export function cleanTrackName(name: string): string {
   }
 
   // Remove featuring artists from track titles
-  const featPatterns = [
-    /\s+feat\.?\s+/i,
-    /\s+ft\.?\s+/i,
-    /\s+featuring\s+/i,
-  ];
-
-  for (const pattern of featPatterns) {
+  for (const pattern of FEAT_PATTERNS) {
     const match = cleaned.match(pattern);
     if (match && match.index !== undefined) {
       const baseName = cleaned.substring(0, match.index).trim();
@@ -488,38 +353,50 @@ export function cleanReleaseName(name: string): string {
   return cleanTrackName(name);
 }
 
+/**
+ * Letters folded when names are compared, for letters that don't decompose
+ * into a base letter and an accent (ø, ł, ß)
+ */
+const FOLDED_LETTERS = new Map<string, string>(Object.entries(rules.foldedLetters));
+
+/**
+ * Combining diacritical marks of Latin, Greek and Cyrillic letters. Kana
+ * voicing marks (U+3099, U+309A) are not among them, "ガ" and "カ" differ.
+ */
+const COMBINING_DIACRITICS = /[\u0300-\u036f\u1ab0-\u1aff\u1dc0-\u1dff\u20d0-\u20ff\ufe20-\ufe2f]/g;
+
 /**
  * Normalize text for comparison (remove special chars, lowercase, etc.)
- * Enhanced with Unicode normalization for accent-insensitive matching.
+ * so names differing only in case, accents, width or punctuation compare
+ * equal. Aligned with normalize_for_comparison in cadet's cleaning.rs.
  *
- * For Latin text: strips diacriticals, keeps [a-zA-Z0-9\s].
- * For non-Latin text (CJK, Cyrillic, etc.): keeps all Unicode
- * alphanumeric characters so that different non-Latin strings
- * remain distinguishable.
+ * Non-Latin text (CJK, Cyrillic, etc.) keeps all Unicode alphanumeric
+ * characters so that different non-Latin strings remain distinguishable.
  */
 export function normalizeForComparison(text: string): string {
   if (typeof text !== "string") {
     return "";
   }
 
-  // Step 1: NFD decomposition to separate base characters from accents
-  let normalized = text.normalize("NFD");
-
-  // Step 2: Remove combining diacritical marks (accents)
-  normalized = normalized.replace(/[\u0300-\u036f]/g, "");
+  // Step 1: NFKC folds full-width and half-width forms and ligatures
+  // ("ＡＢＣ" -> "ABC", "ｱｲﾄﾞﾙ" -> "アイドル", "ﬁ" -> "fi")
+  // Step 2: lowercase, then NFD to separate base characters from accents
+  const decomposed = text.normalize("NFKC").toLowerCase().normalize("NFD");
 
-  // Step 3: Keep all Unicode alphanumeric characters and whitespace.
-  // This preserves CJK, Cyrillic, Arabic, etc. while still stripping
-  // punctuation and symbols.
-  const filtered = Array.from(normalized)
-    .filter((c) => isUnicodeAlphanumeric(c) || /\s/.test(c))
+  // Step 3: remove combining diacritical marks (accents) and fold letters
+  // without a decomposition
+  const folded = Array.from(decomposed.replace(COMBINING_DIACRITICS, ""))
+    .map((c) => FOLDED_LETTERS.get(c) ?? c)
     .join("");
 
-  // Step 4: Re-compose (NFC) so that decomposed Hangul jamo and other
-  // scripts round-trip correctly.
-  return filtered
-    .normalize("NFC")
-    .toLowerCase()
+  // Step 4: re-compose (NFC) so that decomposed Hangul jamo and kana with
+  // voicing marks round-trip correctly
+  // Step 5: keep all Unicode alphanumeric characters and whitespace. This
+  // preserves CJK, Cyrillic, Arabic, etc. while still stripping punctuation
+  // and symbols.
+  return Array.from(folded.normalize("NFC"))
+    .filter((c) => isUnicodeAlphanumeric(c) || /\s/.test(c))
+    .join("")
     .split(/\s+/)
     .filter((w) => w.length > 0)
     .join(" ")
@@ -527,13 +404,13 @@ export function normalizeForComparison(text: string): string {
 }
 
 /**
- * Check if a character is alphanumeric in any script.
- * Uses Unicode category awareness: letters (L*) and numbers (N*).
+ * Check if a character is alphanumeric in any script, the way Rust's
+ * char::is_alphanumeric does: alphabetic (letters and the vowel signs of
+ * scripts like Devanagari) or a number.
  */
 function isUnicodeAlphanumeric(c: string): boolean {
   // Fast path for ASCII
   if (/[a-zA-Z0-9]/.test(c)) return true;
-  // Unicode letter or number (covers CJK, Cyrillic, Arabic, etc.)
-  // \p{L} = any Unicode letter, \p{N} = any Unicode number
-  return /[\p{L}\p{N}]/u.test(c);
+  // \p{Alphabetic} = letters and alphabetic marks, \p{N} = any Unicode number
+  return /[\p{Alphabetic}\p{N}]/u.test(c);
 }
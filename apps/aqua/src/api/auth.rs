@@ -0,0 +1,297 @@
+//! Authentication of API callers with atproto inter-service auth tokens.
+//!
+//! Callers ask their PDS for a token with `com.atproto.server.getServiceAuth`,
+//! with aqua's `did:web` (`PUBLIC_DID_WEB`) as the audience, and send it as
+//! `Authorization: Bearer <token>`. The token's signature is checked against
+//! the `#atproto` key in the issuer's DID document. DIDs listed in
+//! `ADMIN_DIDS` may also act as operators, e.g. to list every account's jobs.
+
+use anyhow::{Result, anyhow, bail};
+use axum::{
+    Json,
+    extract::FromRequestParts,
+    http::{StatusCode, header, request::Parts},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::OnceLock;
+use tracing::warn;
+
+use super::{ErrorResponse, app_host, resolve_did_document};
+
+/// Multicodec prefix of a compressed secp256k1 public key
+const SECP256K1_PUB: [u8; 2] = [0xe7, 0x01];
+/// Multicodec prefix of a compressed P-256 public key
+const P256_PUB: [u8; 2] = [0x80, 0x24];
+
+/// The DID of the authenticated caller.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthedDid(pub String);
+
+impl AuthedDid {
+    /// Whether the caller is an operator, listed in `ADMIN_DIDS`.
+    pub fn is_admin(&self) -> bool {
+        admin_dids().iter().any(|did| *did == self.0)
+    }
+
+    /// Reject a request made on behalf of a DID other than the caller's.
+    pub fn require(&self, did: &str) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+        if self.0 == did {
+            Ok(())
+        } else {
+            Err(auth_error(
+                StatusCode::FORBIDDEN,
+                format!("Authenticated as {}, not {}", self.0, did),
+            ))
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for AuthedDid {
+    type Rejection = (StatusCode, Json<ErrorResponse>);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| {
+                auth_error(StatusCode::UNAUTHORIZED, "Missing bearer token".to_string())
+            })?;
+
+        verify_token(token, &service_did(), chrono::Utc::now().timestamp())
+            .await
+            .map(AuthedDid)
+            .map_err(|e| {
+                warn!("Rejected service auth token: {}", e);
+                auth_error(StatusCode::UNAUTHORIZED, e.to_string())
+            })
+    }
+}
+
+/// The audience tokens must be issued for, from `PUBLIC_DID_WEB`.
+fn service_did() -> String {
+    std::env::var("PUBLIC_DID_WEB")
+        .ok()
+        .filter(|did| !did.trim().is_empty())
+        .unwrap_or_else(|| format!("did:web:{}", app_host()))
+}
+
+/// Operator DIDs, from the comma separated `ADMIN_DIDS`.
+fn admin_dids() -> &'static [String] {
+    static ADMIN_DIDS: OnceLock<Vec<String>> = OnceLock::new();
+    ADMIN_DIDS.get_or_init(|| parse_admin_dids(&std::env::var("ADMIN_DIDS").unwrap_or_default()))
+}
+
+fn parse_admin_dids(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|did| did.starts_with("did:"))
+        .map(str::to_string)
+        .collect()
+}
+
+fn auth_error(status: StatusCode, details: String) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        status,
+        Json(ErrorResponse {
+            error: "Authentication failed".to_string(),
+            details: Some(details),
+        }),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenHeader {
+    alg: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenClaims {
+    iss: String,
+    aud: String,
+    exp: i64,
+}
+
+/// A token split into its parts, before its signature is checked.
+#[derive(Debug)]
+struct ParsedToken<'a> {
+    alg: String,
+    claims: TokenClaims,
+    signed: &'a str,
+    signature: Vec<u8>,
+}
+
+fn parse_token(token: &str) -> Result<ParsedToken<'_>> {
+    let (signed, signature) = token
+        .rsplit_once('.')
+        .ok_or_else(|| anyhow!("Malformed token"))?;
+    let (header, claims) = signed
+        .split_once('.')
+        .ok_or_else(|| anyhow!("Malformed token"))?;
+
+    let header: TokenHeader = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header)?)?;
+    let claims: TokenClaims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims)?)?;
+    Ok(ParsedToken {
+        alg: header.alg,
+        claims,
+        signed,
+        signature: URL_SAFE_NO_PAD.decode(signature)?,
+    })
+}
+
+/// Check a token's audience, expiry and signature, returning its issuer.
+async fn verify_token(token: &str, audience: &str, now: i64) -> Result<String> {
+    let token = parse_token(token)?;
+    check_claims(&token.claims, audience, now)?;
+
+    // The issuer may name a service of the DID, e.g. `did:plc:abc#atproto_labeler`
+    let did = token
+        .claims
+        .iss
+        .split('#')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let doc = resolve_did_document(&did).await?;
+    let key = signing_key(&doc, &did)?;
+    verify_signature(&token.alg, &key, token.signed.as_bytes(), &token.signature)?;
+    Ok(did)
+}
+
+fn check_claims(claims: &TokenClaims, audience: &str, now: i64) -> Result<()> {
+    if claims.aud.split('#').next() != Some(audience) {
+        bail!("Token is for {}, not {}", claims.aud, audience);
+    }
+    if claims.exp <= now {
+        bail!("Token expired");
+    }
+    if !claims.iss.starts_with("did:") {
+        bail!("Token issuer {} is not a DID", claims.iss);
+    }
+    Ok(())
+}
+
+/// The `#atproto` verification key of a DID document, as multibase.
+fn signing_key(doc: &Value, did: &str) -> Result<String> {
+    doc["verificationMethod"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|method| {
+            let id = method["id"].as_str().unwrap_or_default();
+            id == "#atproto" || id == format!("{}#atproto", did)
+        })
+        .and_then(|method| method["publicKeyMultibase"].as_str())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("No atproto signing key in the DID document of {}", did))
+}
+
+fn verify_signature(alg: &str, key: &str, signed: &[u8], signature: &[u8]) -> Result<()> {
+    use k256::ecdsa::signature::Verifier;
+
+    let (_, key) = multibase::decode(key)?;
+    match (alg, key.split_at_checked(2)) {
+        ("ES256K", Some((prefix, key))) if prefix == SECP256K1_PUB => {
+            let key = k256::ecdsa::VerifyingKey::from_sec1_bytes(key)?;
+            let signature = k256::ecdsa::Signature::from_slice(signature)?;
+            key.verify(signed, &signature)?;
+        }
+        ("ES256", Some((prefix, key))) if prefix == P256_PUB => {
+            let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(key)?;
+            let signature = p256::ecdsa::Signature::from_slice(signature)?;
+            key.verify(signed, &signature)?;
+        }
+        _ => bail!("Token algorithm {} does not match the signing key", alg),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::{Signature, SigningKey, signature::Signer};
+    use serde_json::json;
+
+    const AUDIENCE: &str = "did:web:aqua.example";
+
+    fn test_key() -> SigningKey {
+        SigningKey::from_bytes(&[7u8; 32].into()).unwrap()
+    }
+
+    fn multikey(key: &SigningKey) -> String {
+        let mut bytes = SECP256K1_PUB.to_vec();
+        bytes.extend_from_slice(&key.verifying_key().to_encoded_point(true).to_bytes());
+        multibase::encode(multibase::Base::Base58Btc, bytes)
+    }
+
+    fn token(claims: Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(json!({"alg": "ES256K", "typ": "JWT"}).to_string());
+        let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let signed = format!("{}.{}", header, claims);
+        let signature: Signature = test_key().sign(signed.as_bytes());
+        format!(
+            "{}.{}",
+            signed,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        )
+    }
+
+    #[test]
+    fn test_verify_signed_token() {
+        let token = token(json!({"iss": "did:plc:abc", "aud": AUDIENCE, "exp": 2000}));
+        let parsed = parse_token(&token).unwrap();
+        check_claims(&parsed.claims, AUDIENCE, 1000).unwrap();
+        let key = multikey(&test_key());
+        verify_signature(
+            &parsed.alg,
+            &key,
+            parsed.signed.as_bytes(),
+            &parsed.signature,
+        )
+        .unwrap();
+
+        let tampered = format!("{}.", parsed.signed);
+        assert!(
+            verify_signature(&parsed.alg, &key, tampered.as_bytes(), &parsed.signature).is_err()
+        );
+    }
+
+    #[test]
+    fn test_reject_wrong_audience_or_expired() {
+        let claims = |aud: &str, exp: i64| TokenClaims {
+            iss: "did:plc:abc".to_string(),
+            aud: aud.to_string(),
+            exp,
+        };
+        assert!(check_claims(&claims(AUDIENCE, 2000), AUDIENCE, 1000).is_ok());
+        assert!(check_claims(&claims("did:web:aqua.example#teal", 2000), AUDIENCE, 1000).is_ok());
+        assert!(check_claims(&claims("did:web:other.example", 2000), AUDIENCE, 1000).is_err());
+        assert!(check_claims(&claims(AUDIENCE, 1000), AUDIENCE, 1000).is_err());
+    }
+
+    #[test]
+    fn test_parse_admin_dids() {
+        assert_eq!(
+            parse_admin_dids(" did:plc:abc, did:web:ops.example ,,handle.example"),
+            vec!["did:plc:abc".to_string(), "did:web:ops.example".to_string()]
+        );
+        assert!(parse_admin_dids("").is_empty());
+    }
+
+    #[test]
+    fn test_signing_key_from_did_document() {
+        let doc = json!({
+            "verificationMethod": [{
+                "id": "did:plc:abc#atproto",
+                "type": "Multikey",
+                "publicKeyMultibase": "zQ3sh"
+            }]
+        });
+        assert_eq!(signing_key(&doc, "did:plc:abc").unwrap(), "zQ3sh");
+        assert!(signing_key(&json!({}), "did:plc:abc").is_err());
+    }
+}
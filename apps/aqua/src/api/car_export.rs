@@ -0,0 +1,320 @@
+//! Export of an actor's indexed teal records as a CAR file.
+//!
+//! Records are encoded as DAG-CBOR and arranged in an atproto MST under their
+//! `fm.teal.*` collections, below a repo commit for the DID. The AppView does
+//! not hold the actor's signing key, so the commit is unsigned: the CAR is for
+//! recovering records (e.g. re-importing or re-publishing them to a PDS), not
+//! for serving as an authoritative repo.
+//!
+//! Only the commit and MST nodes are held in memory. Records are encoded once
+//! to hash them into the tree and again as they are written, so the CAR is
+//! streamed out without ever being built as a whole.
+
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use ipld_core::cid::{Cid, multihash::Multihash};
+use ipld_core::ipld::Ipld;
+use iroh_car::{CarHeader, CarWriter};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWrite;
+
+use crate::types::ExportRecord;
+
+const DAG_CBOR_CODEC: u64 = 0x71;
+const SHA2_256_CODE: u64 = 0x12;
+const TID_ALPHABET: &[u8; 32] = b"234567abcdefghijklmnopqrstuvwxyz";
+
+/// Convert atproto JSON to IPLD, turning `$link` and `$bytes` objects back into
+/// CID links and byte strings.
+fn json_to_ipld(value: &Value) -> Result<Ipld> {
+    Ok(match value {
+        Value::Null => Ipld::Null,
+        Value::Bool(b) => Ipld::Bool(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => Ipld::Integer(i.into()),
+            None => Ipld::Float(n.as_f64().ok_or_else(|| anyhow!("Invalid number {}", n))?),
+        },
+        Value::String(s) => Ipld::String(s.clone()),
+        Value::Array(items) => Ipld::List(items.iter().map(json_to_ipld).collect::<Result<_>>()?),
+        Value::Object(object) => {
+            if object.len() == 1 {
+                if let Some(Value::String(link)) = object.get("$link") {
+                    return Ok(Ipld::Link(Cid::try_from(link.as_str())?));
+                }
+                if let Some(Value::String(bytes)) = object.get("$bytes") {
+                    return Ok(Ipld::Bytes(
+                        base64::engine::general_purpose::STANDARD_NO_PAD
+                            .decode(bytes.trim_end_matches('='))?,
+                    ));
+                }
+            }
+            Ipld::Map(
+                object
+                    .iter()
+                    .map(|(k, v)| Ok((k.clone(), json_to_ipld(v)?)))
+                    .collect::<Result<_>>()?,
+            )
+        }
+    })
+}
+
+/// Commit and MST blocks of the CAR being built, in creation order. Identical
+/// blocks are only stored once.
+#[derive(Default)]
+struct BlockStore {
+    blocks: Vec<(Cid, Vec<u8>)>,
+    seen: HashSet<Cid>,
+}
+
+/// Encode a node as DAG-CBOR and compute its CID.
+fn encode(node: &Ipld) -> Result<(Cid, Vec<u8>)> {
+    let bytes = serde_ipld_dagcbor::to_vec(node)?;
+    let hash = Multihash::<64>::wrap(SHA2_256_CODE, &Sha256::digest(&bytes))?;
+    Ok((Cid::new_v1(DAG_CBOR_CODEC, hash), bytes))
+}
+
+impl BlockStore {
+    fn put(&mut self, node: &Ipld) -> Result<Cid> {
+        let (cid, bytes) = encode(node)?;
+        if self.seen.insert(cid) {
+            self.blocks.push((cid, bytes));
+        }
+        Ok(cid)
+    }
+}
+
+/// MST layer of a key: leading zero bits of its SHA-256, counted in pairs
+/// (fanout of 4).
+fn key_layer(key: &str) -> u32 {
+    let mut zeros = 0;
+    for byte in Sha256::digest(key.as_bytes()) {
+        zeros += byte.leading_zeros();
+        if byte != 0 {
+            break;
+        }
+    }
+    zeros / 2
+}
+
+fn link_or_null(cid: Option<Cid>) -> Ipld {
+    cid.map(Ipld::Link).unwrap_or(Ipld::Null)
+}
+
+/// Write the MST node at `layer` for sorted `entries` and its subtrees.
+/// Returns `None` for an empty subtree.
+fn build_mst_node(
+    store: &mut BlockStore,
+    entries: &[(String, Cid, u32)],
+    layer: u32,
+) -> Result<Option<Cid>> {
+    if entries.is_empty() {
+        return Ok(None);
+    }
+
+    let subtree = |store: &mut BlockStore, slice: &[(String, Cid, u32)]| match layer {
+        0 => Ok(None),
+        _ => build_mst_node(store, slice, layer - 1),
+    };
+
+    let mut start = entries
+        .iter()
+        .position(|(_, _, l)| *l == layer)
+        .unwrap_or(entries.len());
+    let left = subtree(store, &entries[..start])?;
+
+    let mut node_entries = Vec::new();
+    let mut previous_key: &str = "";
+    while start < entries.len() {
+        let (key, cid, _) = &entries[start];
+        let end = entries[start + 1..]
+            .iter()
+            .position(|(_, _, l)| *l == layer)
+            .map_or(entries.len(), |i| start + 1 + i);
+        let right = subtree(store, &entries[start + 1..end])?;
+
+        let prefix_len = key
+            .bytes()
+            .zip(previous_key.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        node_entries.push(Ipld::Map(BTreeMap::from([
+            ("p".to_string(), Ipld::Integer(prefix_len as i128)),
+            (
+                "k".to_string(),
+                Ipld::Bytes(key.as_bytes()[prefix_len..].to_vec()),
+            ),
+            ("v".to_string(), Ipld::Link(*cid)),
+            ("t".to_string(), link_or_null(right)),
+        ])));
+
+        previous_key = key;
+        start = end;
+    }
+
+    let node = Ipld::Map(BTreeMap::from([
+        ("l".to_string(), link_or_null(left)),
+        ("e".to_string(), Ipld::List(node_entries)),
+    ]));
+    Ok(Some(store.put(&node)?))
+}
+
+/// Build the MST for `collection/rkey` keys and return the root node CID.
+fn build_mst(store: &mut BlockStore, records: BTreeMap<String, Cid>) -> Result<Cid> {
+    let entries: Vec<(String, Cid, u32)> = records
+        .into_iter()
+        .map(|(key, cid)| {
+            let layer = key_layer(&key);
+            (key, cid, layer)
+        })
+        .collect();
+    let root_layer = entries.iter().map(|(_, _, l)| *l).max().unwrap_or(0);
+
+    match build_mst_node(store, &entries, root_layer)? {
+        Some(root) => Ok(root),
+        // An empty repo still has an (empty) root node
+        None => store.put(&Ipld::Map(BTreeMap::from([
+            ("l".to_string(), Ipld::Null),
+            ("e".to_string(), Ipld::List(vec![])),
+        ]))),
+    }
+}
+
+/// A TID for `micros` since the Unix epoch, with a 10-bit clock identifier.
+pub fn tid(micros: u64, clock_id: u16) -> String {
+    let value = ((micros & 0x1F_FFFF_FFFF_FFFF) << 10) | (clock_id as u64 & 0x3FF);
+    (0..13)
+        .rev()
+        .map(|i| TID_ALPHABET[((value >> (i * 5)) & 0x1F) as usize] as char)
+        .collect()
+}
+
+/// A TID for the commit revision, from the current time.
+fn now_tid() -> String {
+    tid(chrono::Utc::now().timestamp_micros() as u64, 0)
+}
+
+/// Write a CAR holding `records` for `did`, rooted at an unsigned commit.
+pub async fn write_actor_car<W>(did: &str, records: &[ExportRecord], out: W) -> Result<W>
+where
+    W: AsyncWrite + Send + Unpin,
+{
+    let mut store = BlockStore::default();
+
+    let mut keys = BTreeMap::new();
+    for record in records {
+        let (cid, _) = encode(&json_to_ipld(&record.record)?)?;
+        keys.insert(format!("{}/{}", record.collection, record.rkey), cid);
+    }
+    let data = build_mst(&mut store, keys)?;
+
+    let commit = store.put(&Ipld::Map(BTreeMap::from([
+        ("did".to_string(), Ipld::String(did.to_string())),
+        ("version".to_string(), Ipld::Integer(3)),
+        ("data".to_string(), Ipld::Link(data)),
+        ("rev".to_string(), Ipld::String(now_tid())),
+        ("prev".to_string(), Ipld::Null),
+        ("sig".to_string(), Ipld::Bytes(vec![])),
+    ])))?;
+
+    let mut writer = CarWriter::new(CarHeader::new_v1(vec![commit]), out);
+    // Commit first, then the tree and records, like a PDS export
+    for (cid, bytes) in store.blocks.iter().rev() {
+        writer.write(*cid, bytes).await?;
+    }
+    // Identical records (e.g. two plays with the same content) share a block
+    let mut written = HashSet::new();
+    for record in records {
+        let (cid, bytes) = encode(&json_to_ipld(&record.record)?)?;
+        if written.insert(cid) {
+            writer.write(cid, bytes).await?;
+        }
+    }
+    Ok(writer.finish().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_to_ipld_decodes_links_and_bytes() {
+        let cid = "bafyreie5737gdxlw5i64vzichcalba3z2v5n6icifvx5xytvske7mr3hpm";
+        let ipld = json_to_ipld(&json!({
+            "ref": { "$link": cid },
+            "data": { "$bytes": "aGk" },
+            "n": 3,
+        }))
+        .unwrap();
+
+        let Ipld::Map(map) = ipld else {
+            panic!("expected a map")
+        };
+        assert_eq!(map["ref"], Ipld::Link(Cid::try_from(cid).unwrap()));
+        assert_eq!(map["data"], Ipld::Bytes(b"hi".to_vec()));
+        assert_eq!(map["n"], Ipld::Integer(3));
+    }
+
+    #[test]
+    fn test_mst_root_is_deterministic() {
+        let records = |order: &[&str]| {
+            let mut store = BlockStore::default();
+            let keys = order
+                .iter()
+                .map(|rkey| {
+                    let cid = store.put(&Ipld::String(rkey.to_string())).unwrap();
+                    (format!("fm.teal.feed.play/{rkey}"), cid)
+                })
+                .collect();
+            build_mst(&mut store, keys).unwrap()
+        };
+
+        let rkeys: Vec<String> = (0..200).map(|i| format!("3k{i:011}")).collect();
+        let forward: Vec<&str> = rkeys.iter().map(String::as_str).collect();
+        let backward: Vec<&str> = forward.iter().rev().copied().collect();
+
+        assert_eq!(records(&forward), records(&backward));
+        assert_ne!(records(&forward), records(&forward[1..]));
+    }
+
+    #[tokio::test]
+    async fn test_write_actor_car_round_trip() {
+        let record = |rkey: &str, track: &str| ExportRecord {
+            collection: "fm.teal.feed.play".to_string(),
+            rkey: rkey.to_string(),
+            record: json!({ "$type": "fm.teal.feed.play", "trackName": track }),
+        };
+        let records = vec![
+            record("a", "Song"),
+            record("b", "Song"),
+            record("c", "Other"),
+        ];
+
+        let car = write_actor_car("did:plc:test", &records, Vec::new())
+            .await
+            .unwrap();
+
+        let mut reader = iroh_car::CarReader::new(car.as_slice()).await.unwrap();
+        let root = reader.header().roots()[0];
+        let mut blocks = HashSet::new();
+        while let Some((cid, _)) = reader.next_block().await.unwrap() {
+            assert!(blocks.insert(cid), "block {cid} written twice");
+        }
+        assert!(blocks.contains(&root));
+        // Both distinct records are present once each
+        for track in ["Song", "Other"] {
+            let (cid, _) = encode(&json_to_ipld(&record("x", track).record).unwrap()).unwrap();
+            assert!(blocks.contains(&cid));
+        }
+    }
+
+    #[test]
+    fn test_tid_shape() {
+        let tid = now_tid();
+        assert_eq!(tid.len(), 13);
+        assert!(tid.bytes().all(|c| TID_ALPHABET.contains(&c)));
+    }
+}
@@ -0,0 +1,292 @@
+//! Last.fm scrobble dumps. Last.fm has no export of its own, so these come
+//! from third-party tools in a few common shapes:
+//!
+//! - JSON from `user.getRecentTracks`: a single response, an array of page
+//!   responses, or a bare array of tracks
+//! - CSV with a `uts,utc_time,artist,artist_mbid,album,album_mbid,track,track_mbid`
+//!   header
+//! - headerless `artist,album,track,date` CSV with dates like `31 Jan 2021 12:34`
+//!
+//! Last.fm track MBIDs are taken as recording MBIDs.
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde_json::Value;
+
+use super::{HistoryEntry, ParsedHistory, non_empty, parse_mbid};
+
+const MUSIC_SERVICE_URI: &str = "https://www.last.fm";
+const HEADERLESS_DATE_FORMAT: &str = "%d %b %Y %H:%M";
+
+pub fn parse(file_name: &str, data: &[u8]) -> Result<ParsedHistory> {
+    let text = std::str::from_utf8(data)?.trim_start_matches('\u{feff}');
+    let looks_like_json = matches!(text.trim_start().chars().next(), Some('[' | '{'));
+    if file_name.to_lowercase().ends_with(".json") || looks_like_json {
+        parse_json(text)
+    } else {
+        parse_csv(text)
+    }
+}
+
+/// Text of a Last.fm field, which is either a string or `{"#text": ...}`
+/// (`{"name": ...}` for extended artist info).
+fn text_field(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(text) => non_empty(Some(text)),
+        object => non_empty(
+            object
+                .get("#text")
+                .or_else(|| object.get("name"))
+                .and_then(Value::as_str),
+        ),
+    }
+}
+
+fn json_track_to_entry(track: &Value) -> Option<HistoryEntry> {
+    // The currently playing track has no date and is not a scrobble yet
+    let uts = track.get("date")?.get("uts")?;
+    let seconds = match uts {
+        Value::String(s) => s.parse().ok()?,
+        other => other.as_i64()?,
+    };
+    let artist = track.get("artist");
+    let album = track.get("album");
+
+    Some(HistoryEntry {
+        track_name: text_field(track.get("name"))?,
+        artists: text_field(artist)
+            .map(|name| {
+                let mbid = parse_mbid(artist.and_then(|a| a.get("mbid")).and_then(Value::as_str));
+                vec![(name, mbid)]
+            })
+            .unwrap_or_default(),
+        release_name: text_field(album),
+        played_time: DateTime::from_timestamp(seconds, 0)?,
+        recording_mbid: parse_mbid(track.get("mbid").and_then(Value::as_str)),
+        release_mbid: parse_mbid(album.and_then(|a| a.get("mbid")).and_then(Value::as_str)),
+        music_service_uri: Some(MUSIC_SERVICE_URI.to_string()),
+        ..Default::default()
+    })
+}
+
+/// Tracks of a `user.getRecentTracks` response page.
+fn page_tracks(page: &Value) -> Vec<&Value> {
+    match page.get("recenttracks").and_then(|r| r.get("track")) {
+        Some(Value::Array(tracks)) => tracks.iter().collect(),
+        // A page with a single track has an object instead of an array
+        Some(track) => vec![track],
+        None => vec![],
+    }
+}
+
+fn parse_json(text: &str) -> Result<ParsedHistory> {
+    let root: Value =
+        serde_json::from_str(text).map_err(|e| anyhow!("Not a Last.fm JSON dump: {}", e))?;
+
+    let tracks: Vec<&Value> = match &root {
+        Value::Array(items) if items.iter().any(|i| i.get("recenttracks").is_some()) => {
+            items.iter().flat_map(page_tracks).collect()
+        }
+        Value::Array(items) => items.iter().collect(),
+        page if page.get("recenttracks").is_some() => page_tracks(page),
+        _ => return Err(anyhow!("Not a Last.fm JSON dump: no recenttracks")),
+    };
+
+    let mut parsed = ParsedHistory {
+        total: tracks.len() as u64,
+        ..Default::default()
+    };
+    for track in tracks {
+        match json_track_to_entry(track) {
+            Some(entry) => parsed.entries.push(entry),
+            None => parsed.skipped_non_track += 1,
+        }
+    }
+    Ok(parsed)
+}
+
+/// Column positions in a CSV dump.
+struct CsvColumns {
+    time: usize,
+    artist: usize,
+    album: usize,
+    track: usize,
+    artist_mbid: Option<usize>,
+    album_mbid: Option<usize>,
+    track_mbid: Option<usize>,
+    /// `time` holds Unix seconds rather than a formatted date
+    unix_time: bool,
+}
+
+impl CsvColumns {
+    fn from_header(header: &csv::StringRecord) -> Option<Self> {
+        let find = |name: &str| {
+            header
+                .iter()
+                .position(|column| column.trim().eq_ignore_ascii_case(name))
+        };
+        let (time, unix_time) = match find("uts") {
+            Some(index) => (index, true),
+            None => (find("utc_time").or_else(|| find("date"))?, false),
+        };
+        Some(Self {
+            time,
+            artist: find("artist")?,
+            album: find("album")?,
+            track: find("track")?,
+            artist_mbid: find("artist_mbid"),
+            album_mbid: find("album_mbid"),
+            track_mbid: find("track_mbid"),
+            unix_time,
+        })
+    }
+
+    fn headerless() -> Self {
+        Self {
+            artist: 0,
+            album: 1,
+            track: 2,
+            time: 3,
+            artist_mbid: None,
+            album_mbid: None,
+            track_mbid: None,
+            unix_time: false,
+        }
+    }
+
+    fn entry(&self, row: &csv::StringRecord) -> Option<HistoryEntry> {
+        let field = |index: Option<usize>| index.and_then(|i| row.get(i));
+        let time = row.get(self.time)?.trim();
+        let played_time = if self.unix_time {
+            DateTime::from_timestamp(time.parse().ok()?, 0)?
+        } else {
+            parse_csv_date(time)?
+        };
+
+        Some(HistoryEntry {
+            track_name: non_empty(row.get(self.track))?,
+            artists: non_empty(row.get(self.artist))
+                .map(|name| vec![(name, parse_mbid(field(self.artist_mbid)))])
+                .unwrap_or_default(),
+            release_name: non_empty(row.get(self.album)),
+            played_time,
+            recording_mbid: parse_mbid(field(self.track_mbid)),
+            release_mbid: parse_mbid(field(self.album_mbid)),
+            music_service_uri: Some(MUSIC_SERVICE_URI.to_string()),
+            ..Default::default()
+        })
+    }
+}
+
+/// Dates in CSV dumps are UTC, either like `31 Jan 2021 12:34` or RFC 3339.
+fn parse_csv_date(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value, HEADERLESS_DATE_FORMAT)
+        .map(|naive| naive.and_utc())
+        .ok()
+        .or_else(|| value.parse::<DateTime<Utc>>().ok())
+}
+
+fn parse_csv(text: &str) -> Result<ParsedHistory> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let mut rows = reader.records();
+
+    let first = match rows.next() {
+        Some(row) => row.map_err(|e| anyhow!("Not a Last.fm CSV dump: {}", e))?,
+        None => return Ok(ParsedHistory::default()),
+    };
+    let (columns, first_row) = match CsvColumns::from_header(&first) {
+        Some(columns) => (columns, None),
+        None if first.len() >= 4 => (CsvColumns::headerless(), Some(first)),
+        None => return Err(anyhow!("Not a Last.fm CSV dump: unrecognized columns")),
+    };
+
+    let mut parsed = ParsedHistory::default();
+    for row in first_row.into_iter().map(Ok).chain(rows) {
+        let row = row.map_err(|e| anyhow!("Not a Last.fm CSV dump: {}", e))?;
+        parsed.total += 1;
+        match columns.entry(&row) {
+            Some(entry) => parsed.entries.push(entry),
+            None => parsed.skipped_non_track += 1,
+        }
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_recent_tracks_pages() {
+        let json = r##"[{
+            "recenttracks": {
+                "track": [
+                    {
+                        "artist": {"mbid": "f22942a1-6f70-4f48-866e-238cb2308fbd", "#text": "Aphex Twin"},
+                        "album": {"mbid": "", "#text": "Windowlicker"},
+                        "name": "Windowlicker",
+                        "mbid": "b8ae5ba8-f6a4-4e51-9fb0-6ea4fc1bc9d8",
+                        "url": "https://www.last.fm/music/Aphex+Twin/_/Windowlicker",
+                        "@attr": {"nowplaying": "true"}
+                    },
+                    {
+                        "artist": {"mbid": "f22942a1-6f70-4f48-866e-238cb2308fbd", "#text": "Aphex Twin"},
+                        "album": {"mbid": "", "#text": "Windowlicker"},
+                        "name": "Windowlicker",
+                        "mbid": "b8ae5ba8-f6a4-4e51-9fb0-6ea4fc1bc9d8",
+                        "url": "https://www.last.fm/music/Aphex+Twin/_/Windowlicker",
+                        "date": {"uts": "1680350400", "#text": "01 Apr 2023, 12:00"}
+                    }
+                ],
+                "@attr": {"page": "1", "totalPages": "1"}
+            }
+        }]"##;
+        let parsed = parse("scrobbles.json", json.as_bytes()).unwrap();
+
+        assert_eq!(parsed.total, 2);
+        assert_eq!(parsed.skipped_non_track, 1);
+        let entry = &parsed.entries[0];
+        assert_eq!(entry.track_name, "Windowlicker");
+        assert!(entry.artists[0].1.is_some());
+        assert!(entry.recording_mbid.is_some());
+        assert!(entry.release_mbid.is_none());
+        assert_eq!(
+            entry.played_time,
+            "2023-04-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_csv_with_header() {
+        let csv = "uts,utc_time,artist,artist_mbid,album,album_mbid,track,track_mbid\n\
+                   1680350400,\"01 Apr 2023, 12:00\",Aphex Twin,f22942a1-6f70-4f48-866e-238cb2308fbd,\"Richard D. James Album\",,Fingerbib,\n";
+        let parsed = parse("scrobbles.csv", csv.as_bytes()).unwrap();
+
+        assert_eq!(parsed.entries.len(), 1);
+        let entry = &parsed.entries[0];
+        assert_eq!(entry.track_name, "Fingerbib");
+        assert_eq!(
+            entry.release_name.as_deref(),
+            Some("Richard D. James Album")
+        );
+        assert!(entry.artists[0].1.is_some());
+        assert!(entry.recording_mbid.is_none());
+    }
+
+    #[test]
+    fn test_parse_headerless_csv() {
+        let csv = "Aphex Twin,Selected Ambient Works 85-92,Xtal,01 Apr 2023 12:00\n\
+                   Aphex Twin,,,01 Apr 2023 12:05\n";
+        let parsed = parse("lastfm.csv", csv.as_bytes()).unwrap();
+
+        assert_eq!(parsed.total, 2);
+        assert_eq!(parsed.skipped_non_track, 1);
+        assert_eq!(
+            parsed.entries[0].played_time,
+            "2023-04-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+}
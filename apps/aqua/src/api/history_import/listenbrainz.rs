@@ -0,0 +1,282 @@
+//! ListenBrainz "export listens" files: either a JSON array of listens or
+//! JSON lines, one listen per line, as found in the export archive.
+//!
+//! MusicBrainz IDs come from `additional_info` (what the submitting client
+//! sent) and fall back to `mbid_mapping` (ListenBrainz's own matching), whose
+//! artist credits are also preferred over the flat `artist_name`.
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::{HistoryEntry, ParsedHistory, non_empty, parse_mbid};
+
+#[derive(Debug, Deserialize)]
+struct Listen {
+    listened_at: ListenedAt,
+    track_metadata: TrackMetadata,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ListenedAt {
+    Unix(i64),
+    Rfc3339(DateTime<Utc>),
+}
+
+/// `additional_info` and `mbid_mapping` are free-form and clients put all
+/// sorts of types in them, so they are read leniently.
+#[derive(Debug, Deserialize)]
+struct TrackMetadata {
+    artist_name: Option<String>,
+    track_name: Option<String>,
+    release_name: Option<String>,
+    #[serde(default)]
+    additional_info: Value,
+    #[serde(default)]
+    mbid_mapping: Value,
+}
+
+fn str_field<'a>(object: &'a Value, key: &str) -> Option<&'a str> {
+    object.get(key).and_then(Value::as_str)
+}
+
+fn mbid_list(object: &Value, key: &str) -> Vec<Option<Uuid>> {
+    object
+        .get(key)
+        .and_then(Value::as_array)
+        .map(|items| items.iter().map(|item| parse_mbid(item.as_str())).collect())
+        .unwrap_or_default()
+}
+
+/// Artist credits, preferring ListenBrainz's mapping, then the names and
+/// MBIDs the client sent, then the flat artist name.
+fn artists(metadata: &TrackMetadata) -> Vec<(String, Option<Uuid>)> {
+    let mapped: Vec<(String, Option<Uuid>)> = metadata
+        .mbid_mapping
+        .get("artists")
+        .and_then(Value::as_array)
+        .map(|artists| {
+            artists
+                .iter()
+                .filter_map(|artist| {
+                    let name = non_empty(str_field(artist, "artist_credit_name"))?;
+                    Some((name, parse_mbid(str_field(artist, "artist_mbid"))))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if !mapped.is_empty() {
+        return mapped;
+    }
+
+    let info = &metadata.additional_info;
+    let mut mbids = mbid_list(info, "artist_mbids");
+    if mbids.is_empty() {
+        mbids = mbid_list(&metadata.mbid_mapping, "artist_mbids");
+    }
+    let names: Vec<String> = info
+        .get("artist_names")
+        .and_then(Value::as_array)
+        .map(|names| {
+            names
+                .iter()
+                .filter_map(|name| non_empty(name.as_str()))
+                .collect()
+        })
+        .unwrap_or_default();
+    if !names.is_empty() {
+        let mbids = if mbids.len() == names.len() {
+            mbids
+        } else {
+            vec![None; names.len()]
+        };
+        return names.into_iter().zip(mbids).collect();
+    }
+
+    // An MBID for the flat name is only trusted when there is exactly one
+    let mbid = if mbids.len() == 1 { mbids[0] } else { None };
+    non_empty(metadata.artist_name.as_deref())
+        .map(|name| vec![(name, mbid)])
+        .unwrap_or_default()
+}
+
+/// Service URI for `music_service`, which is usually a bare domain.
+fn music_service_uri(service: &str) -> String {
+    if service.contains("://") {
+        service.to_string()
+    } else {
+        format!("https://{}", service)
+    }
+}
+
+fn listen_to_entry(listen: Listen) -> Option<HistoryEntry> {
+    let played_time = match listen.listened_at {
+        ListenedAt::Unix(seconds) => DateTime::from_timestamp(seconds, 0)?,
+        ListenedAt::Rfc3339(time) => time,
+    };
+    let metadata = &listen.track_metadata;
+    let track_name = non_empty(metadata.track_name.as_deref())?;
+    let info = &metadata.additional_info;
+    let mapping = &metadata.mbid_mapping;
+
+    let duration = info
+        .get("duration_ms")
+        .and_then(Value::as_i64)
+        .map(|ms| ms / 1000)
+        .or_else(|| info.get("duration").and_then(Value::as_i64))
+        .filter(|seconds| *seconds > 0);
+
+    Some(HistoryEntry {
+        artists: artists(metadata),
+        release_name: non_empty(metadata.release_name.as_deref()),
+        played_time,
+        recording_mbid: parse_mbid(str_field(info, "recording_mbid"))
+            .or_else(|| parse_mbid(str_field(mapping, "recording_mbid"))),
+        release_mbid: parse_mbid(str_field(info, "release_mbid"))
+            .or_else(|| parse_mbid(str_field(mapping, "release_mbid"))),
+        duration,
+        isrc: non_empty(str_field(info, "isrc")),
+        origin_uri: non_empty(str_field(info, "origin_url")),
+        music_service_uri: non_empty(str_field(info, "music_service"))
+            .map(|service| music_service_uri(&service)),
+        track_name,
+    })
+}
+
+pub fn parse(data: &[u8]) -> Result<ParsedHistory> {
+    let text = std::str::from_utf8(data)?;
+    let listens: Vec<Value> = if text.trim_start().starts_with('[') {
+        serde_json::from_str(text).map_err(|e| anyhow!("Not a ListenBrainz export: {}", e))?
+    } else {
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .map_err(|e| anyhow!("Not a ListenBrainz export (line {}): {}", i + 1, e))
+            })
+            .collect::<Result<_>>()?
+    };
+
+    let mut parsed = ParsedHistory {
+        total: listens.len() as u64,
+        ..Default::default()
+    };
+    for listen in listens {
+        match serde_json::from_value::<Listen>(listen)
+            .ok()
+            .and_then(listen_to_entry)
+        {
+            Some(entry) => parsed.entries.push(entry),
+            None => parsed.skipped_non_track += 1,
+        }
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"[
+        {
+            "listened_at": 1680350400,
+            "recording_msid": "0a8f2b1c-6bd8-4b86-b4b1-11b3bb3f6a3a",
+            "user_name": "someone",
+            "track_metadata": {
+                "artist_name": "Aphex Twin",
+                "track_name": "Windowlicker",
+                "release_name": "Windowlicker",
+                "additional_info": {
+                    "recording_mbid": "b8ae5ba8-f6a4-4e51-9fb0-6ea4fc1bc9d8",
+                    "release_mbid": "0ef2f1a3-e4c1-4b1e-9d4c-9b4d9b0f1c6e",
+                    "artist_mbids": ["f22942a1-6f70-4f48-866e-238cb2308fbd"],
+                    "duration_ms": 369000,
+                    "origin_url": "https://open.spotify.com/track/5ACZ2ge2tX8IMJx2Hhqtbp",
+                    "music_service": "spotify.com",
+                    "submission_client": "Web Scrobbler"
+                }
+            }
+        },
+        {
+            "listened_at": 1680354000,
+            "track_metadata": {
+                "artist_name": "Daft Punk feat. Pharrell Williams",
+                "track_name": "Get Lucky",
+                "additional_info": {},
+                "mbid_mapping": {
+                    "recording_mbid": "a2a6b5a1-9f3d-4e0b-9a4c-3c3c1d2f0b0e",
+                    "artists": [
+                        {
+                            "artist_mbid": "056e4f3e-d505-4dad-8ec1-d04f521cbb56",
+                            "artist_credit_name": "Daft Punk",
+                            "join_phrase": " feat. "
+                        },
+                        {
+                            "artist_mbid": "149f91ef-1287-46fd-9ee0-c6e3e2a4e6d6",
+                            "artist_credit_name": "Pharrell Williams",
+                            "join_phrase": ""
+                        }
+                    ]
+                }
+            }
+        },
+        {
+            "listened_at": 1680357600,
+            "track_metadata": { "artist_name": "Nobody" }
+        }
+    ]"#;
+
+    #[test]
+    fn test_parse_carries_mbids() {
+        let parsed = parse(EXPORT.as_bytes()).unwrap();
+        assert_eq!(parsed.total, 3);
+        assert_eq!(parsed.skipped_non_track, 1);
+
+        let first = &parsed.entries[0];
+        assert_eq!(first.track_name, "Windowlicker");
+        assert_eq!(
+            first.recording_mbid.unwrap().to_string(),
+            "b8ae5ba8-f6a4-4e51-9fb0-6ea4fc1bc9d8"
+        );
+        assert!(first.release_mbid.is_some());
+        assert!(first.artists[0].1.is_some());
+        assert_eq!(first.duration, Some(369));
+        assert_eq!(
+            first.music_service_uri.as_deref(),
+            Some("https://spotify.com")
+        );
+        assert_eq!(
+            first.played_time,
+            "2023-04-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+
+        let second = &parsed.entries[1];
+        let names: Vec<_> = second
+            .artists
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["Daft Punk", "Pharrell Williams"]);
+        assert!(second.artists.iter().all(|(_, mbid)| mbid.is_some()));
+        assert!(second.recording_mbid.is_some());
+    }
+
+    #[test]
+    fn test_parse_json_lines() {
+        let lines = r#"{"listened_at": 1680350400, "track_metadata": {"artist_name": "Aphex Twin", "track_name": "Flim"}}
+
+{"listened_at": "2023-04-01T13:00:00Z", "track_metadata": {"artist_name": "Aphex Twin", "track_name": "Xtal"}}
+"#;
+        let parsed = parse(lines.as_bytes()).unwrap();
+        assert_eq!(parsed.entries.len(), 2);
+        assert_eq!(parsed.entries[1].track_name, "Xtal");
+        assert_eq!(
+            parsed.entries[0].artists,
+            vec![("Aphex Twin".to_string(), None)]
+        );
+    }
+}
@@ -0,0 +1,751 @@
+//! Imports of listening history exported from other services.
+//!
+//! Each source has a parser turning its export files into [`HistoryEntry`]s.
+//! Entries become `fm.teal.feed.play` records with deterministic rkeys, plays
+//! the user already has are dropped, and the rest are queued for cadet, which
+//! stores them through its play ingestor so cleaning and artist matching apply
+//! like for any other play.
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+use axum::{
+    Extension, Json,
+    extract::{Multipart, multipart::Field},
+    http::StatusCode,
+};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use sha2::{Digest, Sha256};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use super::{ErrorResponse, auth::AuthedDid, car_export, read_text_field, resolve_handle_to_did};
+use crate::ctx::Context;
+use crate::types::{
+    CarImportJobStatus, ExistingPlays, HistoryImportJob, ImportedPlay, JobStatus, queue_keys,
+};
+
+pub mod lastfm;
+pub mod listenbrainz;
+pub mod spotify;
+
+const DEFAULT_MAX_UPLOAD_BYTES: usize = 512 * 1024 * 1024;
+const DEFAULT_MAX_FILE_BYTES: usize = 32 * 1024 * 1024;
+/// Existing plays of the same track within this many seconds of an imported
+/// one are taken to be the same listen.
+const DEFAULT_DEDUP_WINDOW_SECS: i64 = 120;
+const DEFAULT_JOB_PLAYS: usize = 5_000;
+
+/// Limit on the request body of a history upload, from
+/// `HISTORY_IMPORT_MAX_UPLOAD_BYTES`. Several years of history can span a
+/// dozen files of ~12 MB each.
+pub fn max_upload_bytes() -> usize {
+    std::env::var("HISTORY_IMPORT_MAX_UPLOAD_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_UPLOAD_BYTES)
+}
+
+/// Limit on one export file of an upload, from
+/// `HISTORY_IMPORT_MAX_FILE_BYTES`. Files are parsed from memory one at a
+/// time, so this bounds what an upload holds at once.
+pub fn max_file_bytes() -> usize {
+    std::env::var("HISTORY_IMPORT_MAX_FILE_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_FILE_BYTES)
+}
+
+/// Most plays queued in one history import job, from
+/// `HISTORY_IMPORT_JOB_PLAYS`.
+pub fn job_plays() -> usize {
+    std::env::var("HISTORY_IMPORT_JOB_PLAYS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|plays| *plays > 0)
+        .unwrap_or(DEFAULT_JOB_PLAYS)
+}
+
+pub fn dedup_window_secs() -> i64 {
+    std::env::var("HISTORY_IMPORT_DEDUP_WINDOW_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_DEDUP_WINDOW_SECS)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistorySource {
+    Spotify,
+    ListenBrainz,
+    LastFm,
+}
+
+impl HistorySource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HistorySource::Spotify => "spotify",
+            HistorySource::ListenBrainz => "listenbrainz",
+            HistorySource::LastFm => "lastfm",
+        }
+    }
+
+    fn submission_client_agent(&self) -> String {
+        format!(
+            "fm.teal.aqua.{}-import/{}",
+            self.as_str(),
+            env!("CARGO_PKG_VERSION")
+        )
+    }
+
+    fn parse_file(
+        &self,
+        file_name: &str,
+        data: &[u8],
+        options: &ImportOptions,
+    ) -> Result<ParsedHistory> {
+        match self {
+            HistorySource::Spotify => spotify::parse(data, options.min_ms_played),
+            HistorySource::ListenBrainz => listenbrainz::parse(data),
+            HistorySource::LastFm => lastfm::parse(file_name, data),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Spotify streams shorter than this are dropped as skips
+    pub min_ms_played: u64,
+}
+
+/// A listen from an export, normalized across sources.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistoryEntry {
+    pub track_name: String,
+    /// Artist names with their MusicBrainz IDs, in credit order
+    pub artists: Vec<(String, Option<Uuid>)>,
+    pub release_name: Option<String>,
+    /// When playback started
+    pub played_time: DateTime<Utc>,
+    pub recording_mbid: Option<Uuid>,
+    pub release_mbid: Option<Uuid>,
+    /// Track length in seconds
+    pub duration: Option<i64>,
+    pub isrc: Option<String>,
+    pub origin_uri: Option<String>,
+    pub music_service_uri: Option<String>,
+}
+
+/// Entries parsed from one export file, with the ones that were dropped.
+#[derive(Debug, Default)]
+pub struct ParsedHistory {
+    pub entries: Vec<HistoryEntry>,
+    /// Entries in the file, including dropped ones
+    pub total: u64,
+    pub skipped_short: u64,
+    pub skipped_non_track: u64,
+}
+
+/// Parse a MusicBrainz ID, ignoring the empty strings and junk exports are
+/// full of.
+pub(crate) fn parse_mbid(value: Option<&str>) -> Option<Uuid> {
+    value
+        .map(str::trim)
+        .map(|v| v.strip_prefix("mbid:").unwrap_or(v))
+        .and_then(|v| Uuid::parse_str(v).ok())
+        .filter(|uuid| !uuid.is_nil())
+}
+
+/// Trim a text field, treating blank values as missing.
+pub(crate) fn non_empty(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+impl HistoryEntry {
+    /// Deterministic rkey, so that uploading overlapping exports does not
+    /// duplicate plays: the start time is the TID timestamp and the track
+    /// (its service URI when known) picks the clock identifier.
+    fn rkey(&self) -> String {
+        let identity = match &self.origin_uri {
+            Some(origin_uri) => origin_uri.clone(),
+            None => format!(
+                "{}\n{}",
+                self.track_name.to_lowercase(),
+                self.artists
+                    .first()
+                    .map(|(name, _)| name.to_lowercase())
+                    .unwrap_or_default()
+            ),
+        };
+        let hash = Sha256::digest(identity.as_bytes());
+        let clock_id = u16::from_be_bytes([hash[0], hash[1]]);
+        car_export::tid(self.played_time.timestamp_micros() as u64, clock_id)
+    }
+
+    fn to_record(&self, submission_client_agent: &str) -> Value {
+        let mbid = |uuid: &Uuid| json!(format!("mbid:{uuid}"));
+
+        let mut record = Map::new();
+        record.insert("$type".into(), json!("fm.teal.feed.play"));
+        record.insert("trackName".into(), json!(self.track_name));
+        if !self.artists.is_empty() {
+            let artists: Vec<Value> = self
+                .artists
+                .iter()
+                .map(|(name, artist_mbid)| {
+                    let mut artist = Map::new();
+                    artist.insert("artistName".into(), json!(name));
+                    if let Some(artist_mbid) = artist_mbid {
+                        artist.insert("artistMbId".into(), mbid(artist_mbid));
+                    }
+                    Value::Object(artist)
+                })
+                .collect();
+            record.insert("artists".into(), Value::Array(artists));
+        }
+        if let Some(release_name) = &self.release_name {
+            record.insert("releaseName".into(), json!(release_name));
+        }
+        if let Some(recording_mbid) = &self.recording_mbid {
+            record.insert("recordingMbId".into(), mbid(recording_mbid));
+        }
+        if let Some(release_mbid) = &self.release_mbid {
+            record.insert("releaseMbId".into(), mbid(release_mbid));
+        }
+        if let Some(duration) = self.duration {
+            record.insert("duration".into(), json!(duration));
+        }
+        if let Some(isrc) = &self.isrc {
+            record.insert("isrc".into(), json!(isrc));
+        }
+        if let Some(origin_uri) = &self.origin_uri {
+            record.insert("originUri".into(), json!(origin_uri));
+        }
+        if let Some(music_service_uri) = &self.music_service_uri {
+            record.insert("musicServiceUri".into(), json!(music_service_uri));
+        }
+        record.insert(
+            "submissionClientAgent".into(),
+            json!(submission_client_agent),
+        );
+        record.insert(
+            "playedTime".into(),
+            json!(
+                self.played_time
+                    .to_rfc3339_opts(SecondsFormat::Millis, true)
+            ),
+        );
+        Value::Object(record)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub entries: u64,
+    pub plays: u64,
+    /// Streams shorter than the `ms_played` threshold (Spotify only)
+    pub skipped_short: u64,
+    /// Podcast episodes, audiobooks and entries without track metadata
+    pub skipped_non_track: u64,
+    /// Entries matching a play the user already has, or another entry
+    pub skipped_existing: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryImportResponse {
+    /// One job per `HISTORY_IMPORT_JOB_PLAYS` plays
+    pub job_ids: Vec<Uuid>,
+    pub did: String,
+    pub source: String,
+    pub status: String,
+    pub message: String,
+    pub summary: ImportSummary,
+}
+
+/// The earliest and latest play time of `entries`.
+fn played_range(entries: &[HistoryEntry]) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let first = entries.iter().map(|e| e.played_time).min()?;
+    let last = entries.iter().map(|e| e.played_time).max()?;
+    Some((first, last))
+}
+
+/// Turn entries into play records, dropping entries that repeat each other
+/// or an existing play of the same track and artist within `window` of it. `seen` holds
+/// the rkeys of entries from earlier files of the same upload.
+pub fn entries_to_plays(
+    entries: &[HistoryEntry],
+    seen: &mut HashSet<String>,
+    existing: &ExistingPlays,
+    window: Duration,
+    submission_client_agent: &str,
+) -> (Vec<ImportedPlay>, u64) {
+    let mut plays = Vec::new();
+    let mut skipped = 0;
+
+    for entry in entries {
+        let rkey = entry.rkey();
+        let artist_names: Vec<&str> = entry
+            .artists
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        if !seen.insert(rkey.clone())
+            || existing.has_play_near(&entry.track_name, &artist_names, entry.played_time, window)
+        {
+            skipped += 1;
+            continue;
+        }
+        plays.push(ImportedPlay {
+            rkey,
+            record: entry.to_record(submission_client_agent),
+        });
+    }
+
+    (plays, skipped)
+}
+
+pub async fn upload_spotify_history(
+    ctx: Extension<Context>,
+    auth: AuthedDid,
+    multipart: Multipart,
+) -> Result<Json<HistoryImportResponse>, (StatusCode, Json<ErrorResponse>)> {
+    upload_history(ctx, auth, multipart, HistorySource::Spotify).await
+}
+
+pub async fn upload_listenbrainz_history(
+    ctx: Extension<Context>,
+    auth: AuthedDid,
+    multipart: Multipart,
+) -> Result<Json<HistoryImportResponse>, (StatusCode, Json<ErrorResponse>)> {
+    upload_history(ctx, auth, multipart, HistorySource::ListenBrainz).await
+}
+
+pub async fn upload_lastfm_history(
+    ctx: Extension<Context>,
+    auth: AuthedDid,
+    multipart: Multipart,
+) -> Result<Json<HistoryImportResponse>, (StatusCode, Json<ErrorResponse>)> {
+    upload_history(ctx, auth, multipart, HistorySource::LastFm).await
+}
+
+/// Upload one or more export files for the authenticated user.
+///
+/// Multipart fields: `history_file` (repeatable), an optional `did` (DID or
+/// handle) that must be the caller's and, for Spotify, an optional
+/// `min_ms_played` overriding the skip threshold. The text fields must come
+/// before the first `history_file`, and each file may be at most
+/// [`max_file_bytes`]. The plays are queued as jobs whose status
+/// is served by `/api/car/job-status/{job_id}`.
+async fn upload_history(
+    Extension(ctx): Extension<Context>,
+    auth: AuthedDid,
+    mut multipart: Multipart,
+    source: HistorySource,
+) -> Result<Json<HistoryImportResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let api_error = |status: StatusCode, error: &str, details: Option<String>| {
+        (
+            status,
+            Json(ErrorResponse {
+                error: error.to_string(),
+                details,
+            }),
+        )
+    };
+
+    let did = auth.0.clone();
+    let inactive = ctx
+        .db
+        .inactive_dids(std::slice::from_ref(&did))
+        .await
+        .map_err(|e| {
+            error!("Failed to check account status of {}: {}", did, e);
+            api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to check account status",
+                Some(e.to_string()),
+            )
+        })?;
+    if !inactive.is_empty() {
+        return Err(api_error(
+            StatusCode::FORBIDDEN,
+            "Account is not active",
+            Some(format!("{} is deactivated, taken down or deleted", did)),
+        ));
+    }
+
+    let window = Duration::seconds(dedup_window_secs());
+    let mut options = ImportOptions {
+        min_ms_played: spotify::min_ms_played(),
+    };
+    let mut queue = ImportQueue::new(&ctx, &did, source);
+    let mut seen = HashSet::new();
+    let mut summary = ImportSummary::default();
+    let mut files = 0usize;
+
+    while let Some(mut field) = multipart.next_field().await.map_err(|e| {
+        api_error(
+            StatusCode::BAD_REQUEST,
+            "Invalid multipart body",
+            Some(e.to_string()),
+        )
+    })? {
+        let name = field.name().unwrap_or("").to_string();
+        if files > 0 && matches!(name.as_str(), "did" | "min_ms_played") {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                "Form fields must come before history_file",
+                Some(format!("{} follows a history_file field", name)),
+            ));
+        }
+
+        match name.as_str() {
+            "did" => {
+                let identity = read_text_field(&mut field)
+                    .await
+                    .map_err(|_| api_error(StatusCode::BAD_REQUEST, "Invalid did field", None))?;
+                let identity = identity.trim();
+                let requested = if identity.starts_with("did:") {
+                    identity.to_string()
+                } else {
+                    resolve_handle_to_did(identity).await.map_err(|e| {
+                        api_error(
+                            StatusCode::BAD_REQUEST,
+                            "Failed to resolve handle",
+                            Some(e.to_string()),
+                        )
+                    })?
+                };
+                auth.require(&requested)?;
+            }
+            "min_ms_played" => {
+                let text = read_text_field(&mut field).await.map_err(|_| {
+                    api_error(StatusCode::BAD_REQUEST, "Invalid min_ms_played field", None)
+                })?;
+                options.min_ms_played = text.trim().parse().map_err(|_| {
+                    api_error(
+                        StatusCode::BAD_REQUEST,
+                        "Invalid min_ms_played field",
+                        Some("Expected a number of milliseconds".to_string()),
+                    )
+                })?;
+            }
+            "history_file" => {
+                files += 1;
+                let (file_name, parsed) =
+                    parse_history_field(field, source, &options, max_file_bytes())
+                        .await
+                        .map_err(|e| {
+                            let (status, error) = if e.is::<FileTooLarge>() {
+                                (StatusCode::PAYLOAD_TOO_LARGE, "History file too large")
+                            } else {
+                                (StatusCode::BAD_REQUEST, "Invalid history file")
+                            };
+                            api_error(status, error, Some(queue.partial_details(e)))
+                        })?;
+                info!(
+                    "Parsed {} {} entries from {}",
+                    parsed.total,
+                    source.as_str(),
+                    file_name
+                );
+                summary.entries += parsed.total;
+                summary.skipped_short += parsed.skipped_short;
+                summary.skipped_non_track += parsed.skipped_non_track;
+
+                // Only plays the file's entries could repeat
+                let existing = match played_range(&parsed.entries) {
+                    Some((first, last)) => ctx
+                        .db
+                        .get_existing_plays(&did, first - window, last + window)
+                        .await
+                        .map_err(|e| {
+                            error!("Failed to load existing plays for {}: {}", did, e);
+                            api_error(
+                                StatusCode::INTERNAL_SERVER_ERROR,
+                                "Failed to load existing plays",
+                                Some(queue.partial_details(e)),
+                            )
+                        })?,
+                    None => Vec::new(),
+                };
+                let (plays, skipped_existing) = entries_to_plays(
+                    &parsed.entries,
+                    &mut seen,
+                    &ExistingPlays::from_rows(existing),
+                    window,
+                    &source.submission_client_agent(),
+                );
+                summary.plays += plays.len() as u64;
+                summary.skipped_existing += skipped_existing;
+                queue.push(plays).await.map_err(|e| {
+                    api_error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to queue history import",
+                        Some(queue.partial_details(e)),
+                    )
+                })?;
+            }
+            _ => {
+                // Ignore unknown fields
+            }
+        }
+    }
+
+    if files == 0 {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "Missing history_file field",
+            None,
+        ));
+    }
+    let job_ids = queue.finish().await.map_err(|e| {
+        api_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to queue history import",
+            Some(e.to_string()),
+        )
+    })?;
+    if job_ids.is_empty() {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "No new plays found",
+            Some(format!(
+                "{} entries: {} too short, {} not tracks, {} already imported",
+                summary.entries,
+                summary.skipped_short,
+                summary.skipped_non_track,
+                summary.skipped_existing
+            )),
+        ));
+    }
+
+    info!(
+        "Queued {} history import for {}: {} plays from {} files in {} jobs",
+        source.as_str(),
+        did,
+        summary.plays,
+        files,
+        job_ids.len()
+    );
+    Ok(Json(HistoryImportResponse {
+        job_ids,
+        did,
+        source: source.as_str().to_string(),
+        status: "queued".to_string(),
+        message: format!("{} plays queued for import", summary.plays),
+        summary,
+    }))
+}
+
+#[derive(Debug)]
+struct FileTooLarge {
+    file_name: String,
+    limit: usize,
+}
+
+impl std::fmt::Display for FileTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is larger than {} bytes", self.file_name, self.limit)
+    }
+}
+
+impl std::error::Error for FileTooLarge {}
+
+/// Read and parse one uploaded export file of at most `limit` bytes, without
+/// buffering any more of it.
+async fn parse_history_field(
+    mut field: Field<'_>,
+    source: HistorySource,
+    options: &ImportOptions,
+    limit: usize,
+) -> Result<(String, ParsedHistory)> {
+    let file_name = field.file_name().unwrap_or("history_file").to_string();
+    let mut data = Vec::new();
+    while let Some(chunk) = field.chunk().await? {
+        if data.len() + chunk.len() > limit {
+            return Err(FileTooLarge { file_name, limit }.into());
+        }
+        data.extend_from_slice(&chunk);
+    }
+    let parsed = source
+        .parse_file(&file_name, &data, options)
+        .map_err(|e| anyhow::anyhow!("{}: {}", file_name, e))?;
+    Ok((file_name, parsed))
+}
+
+/// Plays of an upload waiting to be queued, sent off in jobs of
+/// [`job_plays`] as they accumulate.
+struct ImportQueue<'a> {
+    ctx: &'a Context,
+    did: &'a str,
+    source: HistorySource,
+    pending: Vec<ImportedPlay>,
+    job_ids: Vec<Uuid>,
+    job_plays: usize,
+}
+
+impl<'a> ImportQueue<'a> {
+    fn new(ctx: &'a Context, did: &'a str, source: HistorySource) -> Self {
+        Self {
+            ctx,
+            did,
+            source,
+            pending: Vec::new(),
+            job_ids: Vec::new(),
+            job_plays: job_plays(),
+        }
+    }
+
+    async fn push(&mut self, plays: Vec<ImportedPlay>) -> Result<()> {
+        self.pending.extend(plays);
+        while self.pending.len() >= self.job_plays {
+            let rest = self.pending.split_off(self.job_plays);
+            let plays = std::mem::replace(&mut self.pending, rest);
+            self.queue(plays).await?;
+        }
+        Ok(())
+    }
+
+    /// Queue the remaining plays and return the IDs of every job.
+    async fn finish(mut self) -> Result<Vec<Uuid>> {
+        if !self.pending.is_empty() {
+            let plays = std::mem::take(&mut self.pending);
+            self.queue(plays).await?;
+        }
+        Ok(self.job_ids)
+    }
+
+    async fn queue(&mut self, plays: Vec<ImportedPlay>) -> Result<()> {
+        let job = HistoryImportJob {
+            request_id: Uuid::new_v4(),
+            did: self.did.to_string(),
+            source: self.source.as_str().to_string(),
+            created_at: Utc::now(),
+            plays,
+        };
+        queue_history_import(self.ctx, &job).await.map_err(|e| {
+            error!(
+                "Failed to queue {} history import for {}: {}",
+                self.source.as_str(),
+                self.did,
+                e
+            );
+            e
+        })?;
+        self.job_ids.push(job.request_id);
+        Ok(())
+    }
+
+    /// Error details noting the jobs already queued from earlier files.
+    /// Uploading the same files again does not duplicate their plays.
+    fn partial_details(&self, e: anyhow::Error) -> String {
+        if self.job_ids.is_empty() {
+            e.to_string()
+        } else {
+            let ids: Vec<String> = self.job_ids.iter().map(Uuid::to_string).collect();
+            format!("{} (already queued: {})", e, ids.join(", "))
+        }
+    }
+}
+
+/// Queue a history import job and give it a pending status.
+pub async fn queue_history_import(ctx: &Context, job: &HistoryImportJob) -> Result<()> {
+    let status = CarImportJobStatus {
+        status: JobStatus::Pending,
+        created_at: job.created_at,
+        started_at: None,
+        completed_at: None,
+        error_message: None,
+        progress: None,
+        preview: None,
+    };
+    ctx.jobs
+        .update_job_status(
+            &queue_keys::job_status_key(&job.request_id),
+            &serde_json::to_string(&status)?,
+            None,
+        )
+        .await?;
+    ctx.jobs
+        .queue_job(
+            queue_keys::HISTORY_IMPORT_JOBS,
+            &serde_json::to_string(job)?,
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ExistingPlay;
+
+    fn entry(track_name: &str, played_time: &str) -> HistoryEntry {
+        HistoryEntry {
+            track_name: track_name.to_string(),
+            artists: vec![("Aphex Twin".to_string(), None)],
+            played_time: played_time.parse().unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_entries_to_plays_skips_existing_and_repeated_listens() {
+        let existing = ExistingPlays::from_rows(vec![ExistingPlay {
+            uri: "at://did:plc:test/fm.teal.feed.play/3k2a".to_string(),
+            track_name: "Windowlicker".to_string(),
+            artist_names: vec!["Aphex Twin".to_string()],
+            played_time: Some("2023-04-01T12:00:40Z".parse().unwrap()),
+        }]);
+        let mut cover = entry("Windowlicker", "2023-04-01T12:01:00Z");
+        cover.artists = vec![("Vitamin String Quartet".to_string(), None)];
+        let entries = vec![
+            entry("windowlicker", "2023-04-01T12:00:00Z"),
+            entry("Flim", "2023-04-01T12:10:00Z"),
+            entry("Flim", "2023-04-01T12:10:00Z"),
+            entry("Windowlicker", "2023-04-01T18:00:00Z"),
+            cover,
+        ];
+
+        let (plays, skipped) = entries_to_plays(
+            &entries,
+            &mut HashSet::new(),
+            &existing,
+            Duration::seconds(120),
+            "test/1.0",
+        );
+        assert_eq!(skipped, 2);
+        let tracks: Vec<_> = plays.iter().map(|p| &p.record["trackName"]).collect();
+        assert_eq!(tracks, ["Flim", "Windowlicker", "Windowlicker"]);
+    }
+
+    #[test]
+    fn test_record_carries_mbids() {
+        let mbid = Uuid::parse_str("f22942a1-6f70-4f48-866e-238cb2308fbd").unwrap();
+        let mut entry = entry("Windowlicker", "2023-04-01T12:00:00Z");
+        entry.artists[0].1 = Some(mbid);
+        entry.recording_mbid = Some(mbid);
+
+        let record = entry.to_record("test/1.0");
+        assert_eq!(
+            record["artists"][0]["artistMbId"],
+            "mbid:f22942a1-6f70-4f48-866e-238cb2308fbd"
+        );
+        assert_eq!(
+            record["recordingMbId"],
+            "mbid:f22942a1-6f70-4f48-866e-238cb2308fbd"
+        );
+        assert!(record.get("releaseMbId").is_none());
+    }
+
+    #[test]
+    fn test_parse_mbid() {
+        assert!(parse_mbid(Some("")).is_none());
+        assert!(parse_mbid(Some("not-an-mbid")).is_none());
+        assert!(parse_mbid(Some("00000000-0000-0000-0000-000000000000")).is_none());
+        assert!(parse_mbid(Some("mbid:f22942a1-6f70-4f48-866e-238cb2308fbd")).is_some());
+    }
+}
@@ -0,0 +1,162 @@
+//! Spotify "Extended streaming history" exports (`endsong_*.json` /
+//! `Streaming_History_Audio_*.json`).
+//!
+//! Each file is a JSON array of streams. Track streams played for at least
+//! `SPOTIFY_IMPORT_MIN_MS_PLAYED` are kept, with the track URI as the play's
+//! `originUri`. Podcast episodes and audiobooks have no track metadata and
+//! are dropped.
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+use super::{HistoryEntry, ParsedHistory, non_empty};
+
+/// Streams shorter than this are treated as skips, matching the usual
+/// scrobbling rule of thumb.
+const DEFAULT_MIN_MS_PLAYED: u64 = 30_000;
+
+const MUSIC_SERVICE_URI: &str = "https://open.spotify.com";
+
+pub fn min_ms_played() -> u64 {
+    std::env::var("SPOTIFY_IMPORT_MIN_MS_PLAYED")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MIN_MS_PLAYED)
+}
+
+/// One entry of an extended streaming history file. Only the fields we map
+/// are read; IP addresses and other account details are ignored.
+#[derive(Debug, Clone, Deserialize)]
+struct SpotifyStream {
+    /// When the stream ended
+    ts: DateTime<Utc>,
+    ms_played: u64,
+    master_metadata_track_name: Option<String>,
+    master_metadata_album_artist_name: Option<String>,
+    master_metadata_album_album_name: Option<String>,
+    spotify_track_uri: Option<String>,
+}
+
+/// Map a stream to an entry. Returns `None` for streams that are not tracks.
+fn stream_to_entry(stream: &SpotifyStream) -> Option<HistoryEntry> {
+    let track_name = non_empty(stream.master_metadata_track_name.as_deref())?;
+    let track_uri = stream
+        .spotify_track_uri
+        .as_deref()
+        .filter(|uri| uri.starts_with("spotify:track:"))?;
+
+    Some(HistoryEntry {
+        track_name,
+        artists: non_empty(stream.master_metadata_album_artist_name.as_deref())
+            .map(|artist| vec![(artist, None)])
+            .unwrap_or_default(),
+        release_name: non_empty(stream.master_metadata_album_album_name.as_deref()),
+        // `ts` marks the end of the stream, the lexicon wants its start
+        played_time: stream.ts - Duration::milliseconds(stream.ms_played as i64),
+        origin_uri: Some(track_uri.to_string()),
+        music_service_uri: Some(MUSIC_SERVICE_URI.to_string()),
+        ..Default::default()
+    })
+}
+
+pub fn parse(data: &[u8], min_ms_played: u64) -> Result<ParsedHistory> {
+    let streams: Vec<SpotifyStream> = serde_json::from_slice(data)
+        .map_err(|e| anyhow!("Not a Spotify streaming history file: {}", e))?;
+
+    let mut parsed = ParsedHistory {
+        total: streams.len() as u64,
+        ..Default::default()
+    };
+    for stream in &streams {
+        let Some(entry) = stream_to_entry(stream) else {
+            parsed.skipped_non_track += 1;
+            continue;
+        };
+        if stream.ms_played < min_ms_played {
+            parsed.skipped_short += 1;
+            continue;
+        }
+        parsed.entries.push(entry);
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HISTORY: &str = r#"[
+        {
+            "ts": "2023-04-01T12:03:30Z",
+            "platform": "android",
+            "ms_played": 210000,
+            "conn_country": "GB",
+            "ip_addr": "192.0.2.1",
+            "master_metadata_track_name": "Windowlicker",
+            "master_metadata_album_artist_name": "Aphex Twin",
+            "master_metadata_album_album_name": "Windowlicker",
+            "spotify_track_uri": "spotify:track:5ACZ2ge2tX8IMJx2Hhqtbp",
+            "episode_name": null,
+            "spotify_episode_uri": null,
+            "reason_start": "clickrow",
+            "reason_end": "trackdone",
+            "shuffle": false,
+            "skipped": false
+        },
+        {
+            "ts": "2023-04-01T12:04:00Z",
+            "ms_played": 4000,
+            "master_metadata_track_name": "Flim",
+            "master_metadata_album_artist_name": "Aphex Twin",
+            "master_metadata_album_album_name": "Come to Daddy",
+            "spotify_track_uri": "spotify:track:2pQ4JkbvGGqsDPKsyxVzJM"
+        },
+        {
+            "ts": "2023-04-01T13:00:00Z",
+            "ms_played": 1800000,
+            "master_metadata_track_name": null,
+            "master_metadata_album_artist_name": null,
+            "master_metadata_album_album_name": null,
+            "spotify_track_uri": null,
+            "episode_name": "Episode 12",
+            "spotify_episode_uri": "spotify:episode:0Q86acNRm6V9GYx55SXKwf"
+        }
+    ]"#;
+
+    #[test]
+    fn test_parse_drops_skips_and_episodes() {
+        let parsed = parse(HISTORY.as_bytes(), DEFAULT_MIN_MS_PLAYED).unwrap();
+
+        assert_eq!(parsed.total, 3);
+        assert_eq!(parsed.skipped_short, 1);
+        assert_eq!(parsed.skipped_non_track, 1);
+        assert_eq!(parsed.entries.len(), 1);
+
+        let entry = &parsed.entries[0];
+        assert_eq!(entry.track_name, "Windowlicker");
+        assert_eq!(entry.artists, vec![("Aphex Twin".to_string(), None)]);
+        assert_eq!(entry.release_name.as_deref(), Some("Windowlicker"));
+        assert_eq!(
+            entry.origin_uri.as_deref(),
+            Some("spotify:track:5ACZ2ge2tX8IMJx2Hhqtbp")
+        );
+        // 210 seconds before the stream ended
+        assert_eq!(
+            entry.played_time,
+            "2023-04-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_threshold_is_configurable() {
+        let parsed = parse(HISTORY.as_bytes(), 0).unwrap();
+        assert_eq!(parsed.entries.len(), 2);
+        assert_eq!(parsed.skipped_short, 0);
+    }
+
+    #[test]
+    fn test_rejects_other_json() {
+        assert!(parse(br#"{"ts": "2023-04-01T12:03:30Z"}"#, 0).is_err());
+    }
+}
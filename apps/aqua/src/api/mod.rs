@@ -1,12 +1,27 @@
 use anyhow::Result;
-use axum::{Extension, Json, extract::Multipart, extract::Path, http::StatusCode};
+use axum::{
+    Extension, Json,
+    body::Body,
+    extract::{Multipart, Path, Query, multipart::Field},
+    http::{StatusCode, header},
+};
+use chrono::{DateTime, Utc};
+use common::car_spool::{SpoolError, SpooledCar, max_car_size_bytes};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use tokio_util::io::ReaderStream;
 use tracing::{error, info};
 
 use crate::ctx::Context;
-use crate::redis_client::RedisClient;
-use crate::types::CarImportJobStatus;
+use auth::AuthedDid;
+
+pub mod auth;
+pub mod car_export;
+pub mod history_import;
+
+use crate::types::{
+    CarImportJob, CarImportJobFilter, CarImportJobRecord, CarImportJobStatus, JobStatus, queue_keys,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MetaOsInfo {
@@ -58,11 +73,14 @@ pub async fn get_meta_info(
 }
 
 /// Get CAR import job status
+///
+/// Live status comes from the job queue. Once a finished job's status key has
+/// expired, the last state recorded in the job history is returned instead,
+/// with the preview of a dry run.
 pub async fn get_car_import_job_status(
+    Extension(ctx): Extension<Context>,
     Path(job_id): Path<String>,
 ) -> Result<Json<CarImportJobStatus>, (StatusCode, Json<ErrorResponse>)> {
-    use crate::types::queue_keys;
-
     info!("Getting status for job: {}", job_id);
 
     // Parse job ID
@@ -77,23 +95,9 @@ pub async fn get_car_import_job_status(
         }
     };
 
-    // Connect to Redis
-    let redis_url =
-        std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
-    let redis_client = match RedisClient::new(&redis_url) {
-        Ok(client) => client,
-        Err(e) => {
-            error!("Failed to connect to Redis: {}", e);
-            let error_response = ErrorResponse {
-                error: "Internal server error".to_string(),
-                details: Some("Failed to connect to Redis".to_string()),
-            };
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)));
-        }
-    };
-
     // Get job status
-    match redis_client
+    match ctx
+        .jobs
         .get_job_status(&queue_keys::job_status_key(&job_uuid))
         .await
     {
@@ -108,15 +112,34 @@ pub async fn get_car_import_job_status(
                 Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)))
             }
         },
-        Ok(None) => {
-            let error_response = ErrorResponse {
-                error: "Job not found".to_string(),
-                details: Some(format!("No job found with ID: {}", job_id)),
-            };
-            Err((StatusCode::NOT_FOUND, Json(error_response)))
-        }
+        Ok(None) => match ctx.db.get_car_import_job(job_uuid).await {
+            Ok(Some(record)) => Ok(Json(CarImportJobStatus {
+                status: record.status,
+                created_at: record.created_at,
+                started_at: record.started_at,
+                completed_at: record.completed_at,
+                error_message: record.error_message,
+                progress: record.progress,
+                preview: record.preview,
+            })),
+            Ok(None) => {
+                let error_response = ErrorResponse {
+                    error: "Job not found".to_string(),
+                    details: Some(format!("No job found with ID: {}", job_id)),
+                };
+                Err((StatusCode::NOT_FOUND, Json(error_response)))
+            }
+            Err(e) => {
+                error!("Failed to get job history: {}", e);
+                let error_response = ErrorResponse {
+                    error: "Failed to get job status".to_string(),
+                    details: Some(e.to_string()),
+                };
+                Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)))
+            }
+        },
         Err(e) => {
-            error!("Failed to get job status from Redis: {}", e);
+            error!("Failed to get job status from job queue: {}", e);
             let error_response = ErrorResponse {
                 error: "Failed to get job status".to_string(),
                 details: Some(e.to_string()),
@@ -126,6 +149,123 @@ pub async fn get_car_import_job_status(
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ListCarImportJobsQuery {
+    pub did: Option<String>,
+    pub status: Option<String>,
+    /// RFC 3339 timestamp, inclusive
+    pub since: Option<String>,
+    /// RFC 3339 timestamp, exclusive
+    pub until: Option<String>,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListCarImportJobsResponse {
+    pub jobs: Vec<CarImportJobRecord>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+/// List CAR import job history, newest first
+///
+/// Callers see their own jobs. Operators (`ADMIN_DIDS`) see every account's
+/// jobs, or one account's with `did`.
+pub async fn list_car_import_jobs(
+    Extension(ctx): Extension<Context>,
+    auth: AuthedDid,
+    Query(query): Query<ListCarImportJobsQuery>,
+) -> Result<Json<ListCarImportJobsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let (mut filter, limit, offset) = parse_list_car_import_jobs_query(&query).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid query".to_string(),
+                details: Some(e),
+            }),
+        )
+    })?;
+    if !auth.is_admin() {
+        if let Some(did) = &filter.did {
+            auth.require(did)?;
+        }
+        filter.did = Some(auth.0);
+    }
+
+    let mut jobs = ctx
+        .db
+        .list_car_import_jobs(&filter, limit + 1, offset)
+        .await
+        .map_err(|e| {
+            error!("Failed to list CAR import jobs: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Failed to list CAR import jobs".to_string(),
+                    details: Some(e.to_string()),
+                }),
+            )
+        })?;
+
+    let cursor = if jobs.len() > limit as usize {
+        jobs.truncate(limit as usize);
+        Some((offset + limit).to_string())
+    } else {
+        None
+    };
+
+    Ok(Json(ListCarImportJobsResponse { jobs, cursor }))
+}
+
+fn parse_list_car_import_jobs_query(
+    query: &ListCarImportJobsQuery,
+) -> Result<(CarImportJobFilter, i64, i64), String> {
+    fn parse_time(field: &str, value: Option<&str>) -> Result<Option<DateTime<Utc>>, String> {
+        value
+            .map(|v| {
+                DateTime::parse_from_rfc3339(v)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .map_err(|_| format!("{} must be an RFC 3339 timestamp", field))
+            })
+            .transpose()
+    }
+
+    let status = query
+        .status
+        .as_deref()
+        .map(str::parse::<JobStatus>)
+        .transpose()?;
+    let since = parse_time("since", query.since.as_deref())?;
+    let until = parse_time("until", query.until.as_deref())?;
+    if let (Some(since), Some(until)) = (since, until)
+        && since >= until
+    {
+        return Err("since must be before until".to_string());
+    }
+
+    let limit = query.limit.unwrap_or(25);
+    if !(1..=100).contains(&limit) {
+        return Err("limit must be between 1 and 100".to_string());
+    }
+    let offset = query
+        .cursor
+        .as_deref()
+        .unwrap_or("0")
+        .parse::<i64>()
+        .ok()
+        .filter(|offset| *offset >= 0)
+        .ok_or_else(|| "cursor must be a non-negative integer".to_string())?;
+
+    let filter = CarImportJobFilter {
+        did: query.did.clone().filter(|did| !did.is_empty()),
+        status,
+        since,
+        until,
+    };
+    Ok((filter, limit, offset))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CarImportRequest {
     pub import_id: Option<String>,
@@ -150,6 +290,7 @@ pub struct FetchCarRequest {
     pub user_identifier: String, // DID or handle
     pub since: Option<String>,   // Optional revision for diff
     pub debug: Option<bool>,     // Enable debug mode for more verbose errors
+    pub dry_run: Option<bool>,   // Queue a job reporting what the import would do
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -161,65 +302,190 @@ pub struct FetchCarResponse {
     pub message: String,
 }
 
+/// Default number of dry runs a DID may have queued or running at once
+const DEFAULT_MAX_ACTIVE_PREVIEWS: i64 = 2;
+
+/// Dry runs a DID may have queued or running at once, from
+/// `CAR_IMPORT_MAX_ACTIVE_PREVIEWS`.
+fn max_active_previews() -> i64 {
+    std::env::var("CAR_IMPORT_MAX_ACTIVE_PREVIEWS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_ACTIVE_PREVIEWS)
+}
+
+/// Upload a CAR of the caller's own repository to import, or only preview
+/// with `dry_run`.
 pub async fn upload_car_import(
     Extension(ctx): Extension<Context>,
+    auth: AuthedDid,
     mut multipart: Multipart,
-) -> Result<Json<CarImportResponse>, StatusCode> {
+) -> Result<Json<CarImportResponse>, (StatusCode, Json<ErrorResponse>)> {
     info!("Received CAR file upload request");
 
-    let mut car_data: Option<Vec<u8>> = None;
+    let bad_request = |error: &str| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: error.to_string(),
+                details: None,
+            }),
+        )
+    };
+
+    let mut car_data: Option<SpooledCar> = None;
     let mut import_id: Option<String> = None;
     let mut description: Option<String> = None;
+    let mut dry_run = false;
+    let mut did: Option<String> = None;
 
     // Process multipart form data
-    while let Some(field) = multipart
+    while let Some(mut field) = multipart
         .next_field()
         .await
-        .map_err(|_| StatusCode::BAD_REQUEST)?
+        .map_err(|_| bad_request("Invalid multipart body"))?
     {
         let name = field.name().unwrap_or("").to_string();
 
         match name.as_str() {
             "car_file" => {
-                let data = field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?;
-                car_data = Some(data.to_vec());
+                let mut spool = SpooledCar::new(max_car_size_bytes()).map_err(spool_error)?;
+                while let Some(chunk) = field
+                    .chunk()
+                    .await
+                    .map_err(|e| spool_error(SpoolError::Source(e.into())))?
+                {
+                    spool.write_chunk(&chunk).await.map_err(spool_error)?;
+                }
+                car_data = Some(spool);
             }
             "import_id" => {
-                let text = field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+                let text = read_text_field(&mut field)
+                    .await
+                    .map_err(|_| bad_request("Invalid import_id field"))?;
                 import_id = Some(text);
             }
             "description" => {
-                let text = field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+                let text = read_text_field(&mut field)
+                    .await
+                    .map_err(|_| bad_request("Invalid description field"))?;
                 description = Some(text);
             }
+            "dry_run" => {
+                let text = read_text_field(&mut field)
+                    .await
+                    .map_err(|_| bad_request("Invalid dry_run field"))?;
+                dry_run = matches!(text.trim(), "true" | "1");
+            }
+            "did" => {
+                let text = read_text_field(&mut field)
+                    .await
+                    .map_err(|_| bad_request("Invalid did field"))?;
+                did = Some(text);
+            }
             _ => {
                 // Ignore unknown fields
             }
         }
     }
 
-    let car_bytes = car_data.ok_or(StatusCode::BAD_REQUEST)?;
+    let mut car_file = car_data.ok_or_else(|| bad_request("Missing car_file field"))?;
     let final_import_id = import_id.unwrap_or_else(|| {
         // Generate a unique import ID
         format!("car-import-{}", chrono::Utc::now().timestamp())
     });
 
     // Validate CAR file format
-    match validate_car_file(&car_bytes).await {
-        Ok(_) => {
+    let commit_did = match validate_car_file(&mut car_file).await {
+        Ok(commit_did) => {
             info!(
-                "CAR file validation successful for import {}",
-                final_import_id
+                "CAR file validation successful for import {} ({} bytes)",
+                final_import_id,
+                car_file.len()
             );
+            commit_did
         }
         Err(e) => {
             error!("CAR file validation failed: {}", e);
-            return Err(StatusCode::BAD_REQUEST);
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: "Invalid CAR file".to_string(),
+                    details: Some(e.to_string()),
+                }),
+            ));
         }
+    };
+
+    // The commit is unsigned here, so its DID must be the caller's own
+    auth.require(&commit_did)?;
+
+    if dry_run {
+        if did.as_deref().is_some_and(|did| did != commit_did) {
+            return Err(bad_request("did does not match the repository in car_file"));
+        }
+        let active = ctx
+            .db
+            .count_active_car_import_previews(&commit_did)
+            .await
+            .map_err(|e| {
+                error!("Failed to count CAR import previews: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ErrorResponse {
+                        error: "Failed to queue CAR import preview".to_string(),
+                        details: Some(e.to_string()),
+                    }),
+                )
+            })?;
+        if active >= max_active_previews() {
+            return Err((
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(ErrorResponse {
+                    error: "Too many CAR import previews".to_string(),
+                    details: Some(format!(
+                        "{} already has {} previews queued or running",
+                        commit_did, active
+                    )),
+                }),
+            ));
+        }
+        // The preview runs in cadet, which reads the upload from the shared spool
+        let spool_file = car_file.keep().await.map_err(spool_error)?;
+        let job = CarImportJob {
+            request_id: uuid::Uuid::new_v4(),
+            identity: commit_did,
+            since: None,
+            created_at: Utc::now(),
+            description,
+            dry_run: true,
+            spool_file: Some(spool_file),
+        };
+        queue_car_import_job(&ctx, &job).await.map_err(|e| {
+            error!("Failed to queue CAR import preview: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Failed to queue CAR import preview".to_string(),
+                    details: Some(e.to_string()),
+                }),
+            )
+        })?;
+        return Ok(Json(CarImportResponse {
+            import_id: job.request_id.to_string(),
+            status: "queued".to_string(),
+            message: dry_run_message(&job),
+        }));
     }
 
     // Store CAR import request in database for processing
-    match store_car_import_request(&ctx, &final_import_id, &car_bytes, description.as_deref()).await
+    match store_car_import_request(
+        &ctx,
+        &final_import_id,
+        &mut car_file,
+        description.as_deref(),
+    )
+    .await
     {
         Ok(_) => {
             info!(
@@ -234,9 +500,58 @@ pub async fn upload_car_import(
         }
         Err(e) => {
             error!("Failed to store CAR import request: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Failed to store CAR import request".to_string(),
+                    details: Some(e.to_string()),
+                }),
+            ))
+        }
+    }
+}
+
+/// Longest accepted value of a form field other than an uploaded file
+const MAX_FORM_FIELD_BYTES: usize = 4 * 1024;
+
+/// Read a text form field of at most [`MAX_FORM_FIELD_BYTES`], without
+/// buffering any more of it.
+pub(crate) async fn read_text_field(field: &mut Field<'_>) -> Result<String> {
+    let mut text = Vec::new();
+    while let Some(chunk) = field.chunk().await? {
+        if text.len() + chunk.len() > MAX_FORM_FIELD_BYTES {
+            return Err(anyhow::anyhow!(
+                "Form field is longer than {} bytes",
+                MAX_FORM_FIELD_BYTES
+            ));
         }
+        text.extend_from_slice(&chunk);
     }
+    Ok(String::from_utf8(text)?)
+}
+
+/// Map a spooling failure to an API error, using 413 for oversized CAR files.
+fn spool_error(e: SpoolError) -> (StatusCode, Json<ErrorResponse>) {
+    let (status, error) = match e {
+        SpoolError::TooLarge { .. } => (StatusCode::PAYLOAD_TOO_LARGE, "CAR file too large"),
+        SpoolError::Io(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to spool CAR file",
+        ),
+        SpoolError::Source(_) => (StatusCode::BAD_REQUEST, "Failed to read CAR file"),
+        SpoolError::NotShared | SpoolError::InvalidName(_) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "CAR import previews are not configured",
+        ),
+    };
+    error!("{}: {}", error, e);
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            details: Some(e.to_string()),
+        }),
+    )
 }
 
 pub async fn get_car_import_status(
@@ -257,23 +572,86 @@ pub async fn get_car_import_status(
     }
 }
 
-async fn validate_car_file(car_data: &[u8]) -> Result<()> {
+/// Validate a spooled CAR file by streaming every block through `iroh-car`,
+/// returning the DID of the repository commit it is rooted at.
+async fn validate_car_file(car_file: &mut SpooledCar) -> Result<String> {
     use iroh_car::CarReader;
-    use std::io::Cursor;
 
-    let cursor = Cursor::new(car_data);
-    let reader = CarReader::new(cursor).await?;
-    let header = reader.header();
+    #[derive(Deserialize)]
+    struct RepoCommit {
+        did: String,
+    }
+
+    let mut reader = CarReader::new(car_file.reader().await?).await?;
 
     // Basic validation - ensure we have at least one root CID
-    if header.roots().is_empty() {
-        return Err(anyhow::anyhow!("CAR file has no root CIDs"));
+    let root = reader
+        .header()
+        .roots()
+        .first()
+        .copied()
+        .ok_or_else(|| anyhow::anyhow!("CAR file has no root CIDs"))?;
+
+    let mut blocks = 0usize;
+    let mut commit_did = None;
+    while let Some((cid, data)) = reader.next_block().await? {
+        if cid == root {
+            let commit: RepoCommit = serde_ipld_dagcbor::from_slice(&data)
+                .map_err(|e| anyhow::anyhow!("Root block is not a repository commit: {}", e))?;
+            commit_did = Some(commit.did);
+        }
+        blocks += 1;
     }
+    let commit_did =
+        commit_did.ok_or_else(|| anyhow::anyhow!("CAR file is missing its root block"))?;
+
+    info!(
+        "CAR file validated: repository {}, {} blocks",
+        commit_did, blocks
+    );
+    Ok(commit_did)
+}
+
+/// Queue a CAR import job for cadet, recording its pending status in the job
+/// queue and the job history.
+pub async fn queue_car_import_job(ctx: &Context, job: &CarImportJob) -> Result<()> {
+    let status = CarImportJobStatus {
+        status: JobStatus::Pending,
+        created_at: job.created_at,
+        started_at: None,
+        completed_at: None,
+        error_message: None,
+        progress: None,
+        preview: None,
+    };
 
-    info!("CAR file validated: {} root CIDs", header.roots().len());
+    ctx.jobs
+        .queue_job(queue_keys::CAR_IMPORT_JOBS, &serde_json::to_string(job)?)
+        .await?;
+    if let Err(e) = ctx
+        .jobs
+        .update_job_status(
+            &queue_keys::job_status_key(&job.request_id),
+            &serde_json::to_string(&status)?,
+            None,
+        )
+        .await
+    {
+        error!("Failed to set job status: {}", e);
+    }
+    if let Err(e) = ctx.db.record_car_import_job(job, &status).await {
+        error!("Failed to record job history: {}", e);
+    }
     Ok(())
 }
 
+fn dry_run_message(job: &CarImportJob) -> String {
+    format!(
+        "Dry run queued: nothing will be imported, the preview is reported at /api/car/job-status/{}",
+        job.request_id
+    )
+}
+
 #[derive(Debug)]
 struct ImportStatus {
     status: String,
@@ -283,7 +661,7 @@ struct ImportStatus {
 pub async fn store_car_import_request(
     _ctx: &Context,
     _import_id: &str,
-    _car_data: &[u8],
+    _car_file: &mut SpooledCar,
     _description: Option<&str>,
 ) -> Result<()> {
     // TODO: Implement database storage once tables are created
@@ -330,6 +708,36 @@ pub async fn fetch_car_from_user(
         request.user_identifier, user_did, pds_host
     );
 
+    if request.dry_run.unwrap_or(false) {
+        let job = CarImportJob {
+            request_id: uuid::Uuid::new_v4(),
+            identity: user_did.clone(),
+            since: None,
+            created_at: Utc::now(),
+            description: Some(format!(
+                "Dry run fetched from PDS {} for user {}",
+                pds_host, request.user_identifier
+            )),
+            dry_run: true,
+            spool_file: None,
+        };
+        if let Err(e) = queue_car_import_job(&ctx, &job).await {
+            error!("Failed to queue CAR import preview for {}: {}", user_did, e);
+            let error_response = ErrorResponse {
+                error: "Failed to queue CAR import preview".to_string(),
+                details: Some(e.to_string()),
+            };
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)));
+        }
+        return Ok(Json(FetchCarResponse {
+            import_id: job.request_id.to_string(),
+            user_did,
+            pds_host,
+            status: "queued".to_string(),
+            message: dry_run_message(&job),
+        }));
+    }
+
     // Generate import ID
     let import_id = format!(
         "pds-fetch-{}-{}",
@@ -339,11 +747,11 @@ pub async fn fetch_car_from_user(
 
     // Fetch CAR file from PDS
     match fetch_car_from_pds(&pds_host, &user_did, request.since.as_deref()).await {
-        Ok(car_data) => {
+        Ok(mut car_file) => {
             info!(
                 "Successfully fetched CAR file for {} ({} bytes)",
                 user_did,
-                car_data.len()
+                car_file.len()
             );
 
             // Store the fetched CAR file for processing
@@ -351,7 +759,7 @@ pub async fn fetch_car_from_user(
                 "Fetched from PDS {} for user {}",
                 pds_host, request.user_identifier
             ));
-            match store_car_import_request(&ctx, &import_id, &car_data, description.as_deref())
+            match store_car_import_request(&ctx, &import_id, &mut car_file, description.as_deref())
                 .await
             {
                 Ok(_) => {
@@ -374,6 +782,20 @@ pub async fn fetch_car_from_user(
                 }
             }
         }
+        Err(e)
+            if matches!(
+                e.downcast_ref::<SpoolError>(),
+                Some(SpoolError::TooLarge { .. })
+            ) =>
+        {
+            Err((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json(ErrorResponse {
+                    error: "CAR file too large".to_string(),
+                    details: Some(e.to_string()),
+                }),
+            ))
+        }
         Err(e) => {
             error!("Failed to fetch CAR file from PDS {}: {}", pds_host, e);
             let error_response = ErrorResponse {
@@ -385,6 +807,99 @@ pub async fn fetch_car_from_user(
     }
 }
 
+/// Export the caller's own indexed teal records as a CAR file
+///
+/// The CAR is streamed as it is written. Accounts that are deactivated, taken
+/// down or deleted cannot be exported.
+pub async fn export_actor_car(
+    Extension(ctx): Extension<Context>,
+    auth: AuthedDid,
+    Path(identity): Path<String>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let api_error = |status: StatusCode, error: &str, details: String| {
+        (
+            status,
+            Json(ErrorResponse {
+                error: error.to_string(),
+                details: Some(details),
+            }),
+        )
+    };
+
+    let did = if identity.starts_with("did:") {
+        identity.clone()
+    } else {
+        resolve_handle_to_did(&identity).await.map_err(|e| {
+            api_error(
+                StatusCode::BAD_REQUEST,
+                "Failed to resolve handle",
+                e.to_string(),
+            )
+        })?
+    };
+    auth.require(&did)?;
+
+    let inactive = ctx
+        .db
+        .inactive_dids(std::slice::from_ref(&did))
+        .await
+        .map_err(|e| {
+            error!("Failed to check account status of {}: {}", did, e);
+            api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to check account status",
+                e.to_string(),
+            )
+        })?;
+    if !inactive.is_empty() {
+        return Err(api_error(
+            StatusCode::FORBIDDEN,
+            "Account is not active",
+            format!("{} is deactivated, taken down or deleted", did),
+        ));
+    }
+
+    let records = ctx.db.get_actor_export_records(&did).await.map_err(|e| {
+        error!("Failed to load records for CAR export of {}: {}", did, e);
+        api_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to load records",
+            e.to_string(),
+        )
+    })?;
+    if records.is_empty() {
+        return Err(api_error(
+            StatusCode::NOT_FOUND,
+            "No teal records found",
+            format!("Nothing is indexed for {}", did),
+        ));
+    }
+
+    let filename = format!("{}.car", did.replace(':', "-"));
+    let (writer, reader) = tokio::io::duplex(EXPORT_BUFFER_BYTES);
+    tokio::spawn(async move {
+        // A failure mid-stream can only end the response early
+        match car_export::write_actor_car(&did, &records, writer).await {
+            Ok(_) => info!("Exported {} records for {}", records.len(), did),
+            Err(e) => error!("Failed to write CAR export for {}: {}", did, e),
+        }
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/vnd.ipld.car".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(reader)),
+    ))
+}
+
+/// Buffer between the CAR export writer and the response body
+const EXPORT_BUFFER_BYTES: usize = 64 * 1024;
+
 /// Resolve a user identifier (DID or handle) to their DID and PDS host
 pub async fn resolve_user_to_pds(user_identifier: &str) -> Result<(String, String)> {
     if user_identifier.starts_with("did:") {
@@ -423,50 +938,59 @@ async fn resolve_handle_to_did(handle: &str) -> Result<String> {
     Ok(did.to_string())
 }
 
-/// Resolve a DID to their PDS host using DID document
-async fn resolve_did_to_pds(did: &str) -> Result<String> {
-    // For DID:plc, use the PLC directory
-    if did.starts_with("did:plc:") {
-        let url = format!("https://plc.directory/{}", did);
+/// Fetch the DID document of a `did:plc` or `did:web` DID
+pub async fn resolve_did_document(did: &str) -> Result<Value> {
+    let url = if did.starts_with("did:plc:") {
+        format!("https://plc.directory/{}", did)
+    } else if let Some(host) = did.strip_prefix("did:web:") {
+        format!("https://{}/.well-known/did.json", host.replace("%3A", ":"))
+    } else {
+        return Err(anyhow::anyhow!("Unsupported DID method: {}", did));
+    };
 
-        let response = reqwest::get(&url).await?;
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "Failed to resolve DID {}: {}",
-                did,
-                response.status()
-            ));
-        }
+    let response = reqwest::get(&url).await?;
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to resolve DID {}: {}",
+            did,
+            response.status()
+        ));
+    }
+    Ok(response.json().await?)
+}
 
-        let doc: serde_json::Value = response.json().await?;
+/// Resolve a DID to their PDS host using DID document
+async fn resolve_did_to_pds(did: &str) -> Result<String> {
+    let doc = resolve_did_document(did).await?;
 
-        // Find the PDS service endpoint
-        if let Some(services) = doc["service"].as_array() {
-            for service in services {
-                if service["id"].as_str() == Some("#atproto_pds")
-                    && let Some(endpoint) = service["serviceEndpoint"].as_str()
-                {
-                    // Extract hostname from URL
-                    let url = url::Url::parse(endpoint)?;
-                    let host = url
-                        .host_str()
-                        .ok_or_else(|| anyhow::anyhow!("Invalid PDS endpoint URL: {}", endpoint))?;
-                    return Ok(host.to_string());
-                }
+    // Find the PDS service endpoint
+    if let Some(services) = doc["service"].as_array() {
+        for service in services {
+            if service["id"].as_str() == Some("#atproto_pds")
+                && let Some(endpoint) = service["serviceEndpoint"].as_str()
+            {
+                // Extract hostname from URL
+                let url = url::Url::parse(endpoint)?;
+                let host = url
+                    .host_str()
+                    .ok_or_else(|| anyhow::anyhow!("Invalid PDS endpoint URL: {}", endpoint))?;
+                return Ok(host.to_string());
             }
         }
-
-        Err(anyhow::anyhow!(
-            "No PDS service found in DID document for {}",
-            did
-        ))
-    } else {
-        Err(anyhow::anyhow!("Unsupported DID method: {}", did))
     }
+
+    Err(anyhow::anyhow!(
+        "No PDS service found in DID document for {}",
+        did
+    ))
 }
 
-/// Fetch CAR file from PDS using com.atproto.sync.getRepo
-pub async fn fetch_car_from_pds(pds_host: &str, did: &str, since: Option<&str>) -> Result<Vec<u8>> {
+/// Fetch CAR file from PDS using com.atproto.sync.getRepo, spooling it to disk
+pub async fn fetch_car_from_pds(
+    pds_host: &str,
+    did: &str,
+    since: Option<&str>,
+) -> Result<SpooledCar> {
     let mut url = format!(
         "https://{}/xrpc/com.atproto.sync.getRepo?did={}",
         pds_host, did
@@ -478,7 +1002,7 @@ pub async fn fetch_car_from_pds(pds_host: &str, did: &str, since: Option<&str>)
 
     info!("Fetching CAR file from: {}", url);
 
-    let response = reqwest::get(&url).await?;
+    let mut response = reqwest::get(&url).await?;
     if !response.status().is_success() {
         return Err(anyhow::anyhow!(
             "Failed to fetch CAR from PDS {}: {}",
@@ -498,8 +1022,25 @@ pub async fn fetch_car_from_pds(pds_host: &str, did: &str, since: Option<&str>)
         return Err(anyhow::anyhow!("Unexpected content type: {}", content_type));
     }
 
-    let car_data = response.bytes().await?;
-    Ok(car_data.to_vec())
+    let mut car_file = SpooledCar::new(max_car_size_bytes())?;
+    car_file.check_declared_len(response.content_length())?;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| SpoolError::Source(e.into()))?
+    {
+        car_file.write_chunk(&chunk).await?;
+    }
+
+    validate_car_file(&mut car_file).await?;
+    Ok(car_file)
+}
+
+/// Host aqua is served on, from `APP_HOST` or `HOST`
+pub fn app_host() -> String {
+    std::env::var("APP_HOST")
+        .or_else(|_| std::env::var("HOST"))
+        .unwrap_or_else(|_| "localhost:3000".to_string())
 }
 
 /// Generate a DID document for did:web
@@ -541,10 +1082,7 @@ fn generate_did_document(host: &str, pubkey: &str) -> Value {
 pub async fn get_did_document(
     Extension(_ctx): Extension<Context>,
 ) -> impl axum::response::IntoResponse {
-    // Get the host from environment variable or use default
-    let host = std::env::var("APP_HOST")
-        .or_else(|_| std::env::var("HOST"))
-        .unwrap_or_else(|_| "localhost:3000".to_string());
+    let host = app_host();
 
     // get pubkey from environment variable or use default
     let pubkey = std::env::var("TEST_PUBKEY").unwrap_or_else(|_| {
@@ -600,6 +1138,72 @@ mod tests {
         assert!(vm["publicKeyMultibase"].as_str().unwrap().starts_with("z"));
     }
 
+    fn list_query() -> ListCarImportJobsQuery {
+        ListCarImportJobsQuery {
+            did: None,
+            status: None,
+            since: None,
+            until: None,
+            limit: None,
+            cursor: None,
+        }
+    }
+
+    #[test]
+    fn test_list_car_import_jobs_query_defaults() {
+        let (filter, limit, offset) = parse_list_car_import_jobs_query(&list_query()).unwrap();
+        assert!(filter.did.is_none());
+        assert!(filter.status.is_none());
+        assert_eq!(limit, 25);
+        assert_eq!(offset, 0);
+    }
+
+    #[test]
+    fn test_list_car_import_jobs_query_filters() {
+        let query = ListCarImportJobsQuery {
+            did: Some("did:plc:abc".to_string()),
+            status: Some("Failed".to_string()),
+            since: Some("2025-01-01T00:00:00Z".to_string()),
+            until: Some("2025-02-01T00:00:00+00:00".to_string()),
+            limit: Some(10),
+            cursor: Some("20".to_string()),
+        };
+        let (filter, limit, offset) = parse_list_car_import_jobs_query(&query).unwrap();
+        assert_eq!(filter.did.as_deref(), Some("did:plc:abc"));
+        assert!(matches!(filter.status, Some(JobStatus::Failed)));
+        assert!(filter.since.unwrap() < filter.until.unwrap());
+        assert_eq!((limit, offset), (10, 20));
+    }
+
+    #[test]
+    fn test_list_car_import_jobs_query_rejects_invalid_values() {
+        for query in [
+            ListCarImportJobsQuery {
+                status: Some("running".to_string()),
+                ..list_query()
+            },
+            ListCarImportJobsQuery {
+                since: Some("yesterday".to_string()),
+                ..list_query()
+            },
+            ListCarImportJobsQuery {
+                since: Some("2025-02-01T00:00:00Z".to_string()),
+                until: Some("2025-01-01T00:00:00Z".to_string()),
+                ..list_query()
+            },
+            ListCarImportJobsQuery {
+                limit: Some(0),
+                ..list_query()
+            },
+            ListCarImportJobsQuery {
+                cursor: Some("-5".to_string()),
+                ..list_query()
+            },
+        ] {
+            assert!(parse_list_car_import_jobs_query(&query).is_err());
+        }
+    }
+
     #[test]
     fn test_did_document_context() {
         let host = "test.example.org";
@@ -1,20 +1,23 @@
 use std::sync::Arc;
 
+use common::job_queue::JobQueue;
+
 use crate::repos::DataSource;
 
 /// The raw context struct, used only to build the wrapped Context.
 pub struct RawContext {
     pub db: Box<dyn DataSource>, // Boxed trait object with thread safety traits
+    pub jobs: Arc<dyn JobQueue>,
 }
 
 /// The wrapped context, which is shared between all handlers.
 pub type Context = Arc<RawContext>;
 
 impl RawContext {
-    pub fn new(db: Box<dyn DataSource>) -> Self {
-        Self { db }
+    pub fn new(db: Box<dyn DataSource>, jobs: Arc<dyn JobQueue>) -> Self {
+        Self { db, jobs }
     }
-    // TODO add db/storage/redis
+    // TODO add storage
     pub fn build(self) -> Context {
         Arc::new(self)
     }
@@ -1,6 +1,6 @@
 use axum::{
     Router,
-    extract::Extension,
+    extract::{DefaultBodyLimit, Extension},
     routing::{get, post},
 };
 use chrono::Utc;
@@ -10,14 +10,12 @@ use tower_http::cors::CorsLayer;
 use uuid::Uuid;
 
 use ctx::RawContext;
-use redis_client::RedisClient;
 use repos::DataSource;
 use repos::pg::PgDataSource;
 
 mod api;
 mod ctx;
 mod db;
-mod redis_client;
 mod repos;
 mod types;
 mod xrpc;
@@ -39,15 +37,22 @@ async fn main() -> Result<(), String> {
                 .help("Import CAR file for a specific identity (handle or DID)")
                 .action(clap::ArgAction::Set),
         )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("With --import-identity-car, only report what the import would do")
+                .action(clap::ArgAction::SetTrue),
+        )
         .get_matches();
 
     let db = db::init_pool().await.expect("failed to init db");
     let pgds = PgDataSource::new(db.clone()).boxed();
-    let ctx = RawContext::new(pgds).build(); // Arc<RawContext>
+    let jobs = common::job_queue::job_queue_from_env(db.clone()).expect("failed to init job queue");
+    let ctx = RawContext::new(pgds, jobs).build(); // Arc<RawContext>
 
     // Check if we should import a CAR file instead of starting the server
     if let Some(identity) = matches.get_one::<String>("import-identity-car") {
-        return import_identity_car(&ctx, identity).await;
+        return import_identity_car(&ctx, identity, matches.get_flag("dry-run")).await;
     }
 
     // Normal server startup
@@ -56,7 +61,11 @@ async fn main() -> Result<(), String> {
     let app = Router::new()
         .route("/meta_info", get(api::get_meta_info))
         .route("/.well-known/did.json", get(api::get_did_document))
-        .route("/api/car/upload", post(api::upload_car_import))
+        // The upload handler spools the CAR to disk and enforces its own size limit
+        .route(
+            "/api/car/upload",
+            post(api::upload_car_import).layer(DefaultBodyLimit::disable()),
+        )
         .route("/api/car/fetch", post(api::fetch_car_from_user))
         .route(
             "/api/car/status/{import_id}",
@@ -66,6 +75,26 @@ async fn main() -> Result<(), String> {
             "/api/car/job-status/{job_id}",
             get(api::get_car_import_job_status),
         )
+        .route("/api/car/jobs", get(api::list_car_import_jobs))
+        .route("/api/car/export/{identity}", get(api::export_actor_car))
+        .route(
+            "/api/import/spotify",
+            post(api::history_import::upload_spotify_history).layer(DefaultBodyLimit::max(
+                api::history_import::max_upload_bytes(),
+            )),
+        )
+        .route(
+            "/api/import/listenbrainz",
+            post(api::history_import::upload_listenbrainz_history).layer(DefaultBodyLimit::max(
+                api::history_import::max_upload_bytes(),
+            )),
+        )
+        .route(
+            "/api/import/lastfm",
+            post(api::history_import::upload_lastfm_history).layer(DefaultBodyLimit::max(
+                api::history_import::max_upload_bytes(),
+            )),
+        )
         .nest("/xrpc/", xrpc::actor::actor_routes())
         .nest("/xrpc/", xrpc::feed::feed_routes())
         .nest("/xrpc/", xrpc::stats::stats_routes())
@@ -80,18 +109,16 @@ async fn main() -> Result<(), String> {
     Ok(())
 }
 
-async fn import_identity_car(_ctx: &ctx::Context, identity: &str) -> Result<(), String> {
-    use crate::types::{CarImportJob, CarImportJobStatus, JobStatus, queue_keys};
+async fn import_identity_car(
+    ctx: &ctx::Context,
+    identity: &str,
+    dry_run: bool,
+) -> Result<(), String> {
+    use crate::types::CarImportJob;
     use tracing::{error, info};
 
     info!("Submitting CAR import job for identity: {}", identity);
 
-    // Connect to Redis
-    let redis_url =
-        std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
-    let redis_client =
-        RedisClient::new(&redis_url).map_err(|e| format!("Failed to connect to Redis: {}", e))?;
-
     // Create job
     let job = CarImportJob {
         request_id: Uuid::new_v4(),
@@ -99,38 +126,12 @@ async fn import_identity_car(_ctx: &ctx::Context, identity: &str) -> Result<(),
         since: None,
         created_at: Utc::now(),
         description: Some(format!("CLI import request for {}", identity)),
+        dry_run,
+        spool_file: None,
     };
 
-    // Serialize job for queue
-    let job_data =
-        serde_json::to_string(&job).map_err(|e| format!("Failed to serialize job: {}", e))?;
-
-    // Initialize job status
-    let status = CarImportJobStatus {
-        status: JobStatus::Pending,
-        created_at: job.created_at,
-        started_at: None,
-        completed_at: None,
-        error_message: None,
-        progress: None,
-    };
-    let status_data =
-        serde_json::to_string(&status).map_err(|e| format!("Failed to serialize status: {}", e))?;
-
-    // Submit to queue and set initial status
-    match redis_client
-        .queue_job(queue_keys::CAR_IMPORT_JOBS, &job_data)
-        .await
-    {
-        Ok(_) => {
-            // Set initial status
-            if let Err(e) = redis_client
-                .set_job_status(&queue_keys::job_status_key(&job.request_id), &status_data)
-                .await
-            {
-                error!("Failed to set job status: {}", e);
-            }
-
+    match api::queue_car_import_job(ctx, &job).await {
+        Ok(()) => {
             info!("✅ CAR import job queued successfully!");
             info!("Job ID: {}", job.request_id);
             info!("Identity: {}", identity);
@@ -0,0 +1,51 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+
+use super::pg::PgDataSource;
+
+/// Account lifecycle as recorded from `#account` events. Data of accounts
+/// that are deactivated, taken down or deleted is hidden until they are
+/// active again.
+#[async_trait]
+pub trait AccountStatusRepo: Send + Sync {
+    /// The DIDs of `dids` whose account is not active.
+    async fn inactive_dids(&self, dids: &[String]) -> anyhow::Result<HashSet<String>>;
+}
+
+#[async_trait]
+impl AccountStatusRepo for PgDataSource {
+    async fn inactive_dids(&self, dids: &[String]) -> anyhow::Result<HashSet<String>> {
+        if dids.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let inactive: Vec<String> =
+            sqlx::query_scalar("SELECT did FROM account_status WHERE did = ANY($1) AND NOT active")
+                .bind(dids)
+                .fetch_all(&self.db)
+                .await?;
+        Ok(inactive.into_iter().collect())
+    }
+}
+
+impl PgDataSource {
+    /// Drop the items of inactive accounts, `did` giving each item's DID.
+    pub(crate) async fn visible<T>(
+        &self,
+        items: Vec<T>,
+        did: impl Fn(&T) -> Option<&str>,
+    ) -> anyhow::Result<Vec<T>> {
+        let dids: Vec<String> = items
+            .iter()
+            .filter_map(|item| did(item).map(str::to_string))
+            .collect();
+        let inactive = self.inactive_dids(&dids).await?;
+        if inactive.is_empty() {
+            return Ok(items);
+        }
+        Ok(items
+            .into_iter()
+            .filter(|item| did(item).is_none_or(|did| !inactive.contains(did)))
+            .collect())
+    }
+}
@@ -0,0 +1,206 @@
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{Map, Value, json};
+use uuid::Uuid;
+
+use super::pg::PgDataSource;
+use crate::types::ExportRecord;
+
+pub const PLAY_COLLECTION: &str = "fm.teal.feed.play";
+pub const PROFILE_COLLECTION: &str = "fm.teal.actor.profile";
+pub const STATUS_COLLECTION: &str = "fm.teal.actor.status";
+
+#[async_trait]
+pub trait ActorExportRepo: Send + Sync {
+    /// Rebuild every indexed teal record for a DID: plays, the profile and statuses.
+    async fn get_actor_export_records(&self, did: &str) -> anyhow::Result<Vec<ExportRecord>>;
+}
+
+#[derive(sqlx::FromRow)]
+struct PgExportPlayRow {
+    rkey: String,
+    track_name: String,
+    recording_mbid: Option<Uuid>,
+    duration: Option<i32>,
+    artists: Option<Value>,
+    release_name: Option<String>,
+    release_mbid: Option<Uuid>,
+    isrc: Option<String>,
+    origin_url: Option<String>,
+    music_service_base_domain: Option<String>,
+    submission_client_agent: Option<String>,
+    played_time: Option<DateTime<Utc>>,
+    track_discriminant: Option<String>,
+    release_discriminant: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct PgExportProfileRow {
+    display_name: Option<String>,
+    description: Option<String>,
+    description_facets: Option<Value>,
+    created_at: Option<DateTime<Utc>>,
+}
+
+fn datetime(dt: DateTime<Utc>) -> Value {
+    Value::String(dt.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
+/// Insert only the fields that are set, like the original record would.
+fn insert_opt(record: &mut Map<String, Value>, key: &str, value: Option<Value>) {
+    if let Some(value) = value {
+        record.insert(key.to_string(), value);
+    }
+}
+
+impl From<PgExportPlayRow> for ExportRecord {
+    fn from(row: PgExportPlayRow) -> Self {
+        let mut record = Map::new();
+        record.insert("$type".into(), json!(PLAY_COLLECTION));
+        record.insert("trackName".into(), json!(row.track_name));
+        insert_opt(
+            &mut record,
+            "recordingMbId",
+            row.recording_mbid.map(|m| json!(format!("mbid:{m}"))),
+        );
+        insert_opt(&mut record, "duration", row.duration.map(|d| json!(d)));
+        insert_opt(
+            &mut record,
+            "artists",
+            row.artists
+                .filter(|a| a.as_array().is_some_and(|a| !a.is_empty())),
+        );
+        insert_opt(
+            &mut record,
+            "releaseName",
+            row.release_name.map(Value::from),
+        );
+        insert_opt(
+            &mut record,
+            "releaseMbId",
+            row.release_mbid.map(|m| json!(format!("mbid:{m}"))),
+        );
+        insert_opt(&mut record, "isrc", row.isrc.map(Value::from));
+        insert_opt(&mut record, "originUri", row.origin_url.map(Value::from));
+        insert_opt(
+            &mut record,
+            "musicServiceUri",
+            row.music_service_base_domain.map(Value::from),
+        );
+        insert_opt(
+            &mut record,
+            "submissionClientAgent",
+            row.submission_client_agent.map(Value::from),
+        );
+        insert_opt(&mut record, "playedTime", row.played_time.map(datetime));
+        insert_opt(
+            &mut record,
+            "trackDiscriminant",
+            row.track_discriminant.map(Value::from),
+        );
+        insert_opt(
+            &mut record,
+            "releaseDiscriminant",
+            row.release_discriminant.map(Value::from),
+        );
+
+        Self {
+            collection: PLAY_COLLECTION.to_string(),
+            rkey: row.rkey,
+            record: Value::Object(record),
+        }
+    }
+}
+
+#[async_trait]
+impl ActorExportRepo for PgDataSource {
+    async fn get_actor_export_records(&self, did: &str) -> anyhow::Result<Vec<ExportRecord>> {
+        // Artists keep the order they were submitted in (`artist_names_raw`),
+        // and synthetic MBIDs never leave the AppView. Plays imported from
+        // other services' history were never repo records, so they stay out.
+        let plays = sqlx::query_as::<_, PgExportPlayRow>(
+            r#"
+            SELECT
+                p.rkey, p.track_name, p.recording_mbid, p.duration,
+                (
+                    SELECT jsonb_agg(
+                        jsonb_strip_nulls(jsonb_build_object(
+                            'artistName', pta.artist_name,
+                            'artistMbId', CASE WHEN ae.mbid_type = 'musicbrainz'
+                                               THEN 'mbid:' || ae.mbid::text END
+                        ))
+                        ORDER BY array_position(
+                            ARRAY(SELECT jsonb_array_elements_text(p.artist_names_raw)),
+                            pta.artist_name
+                        ) NULLS LAST, pta.artist_name
+                    )
+                    FROM play_to_artists_extended pta
+                    JOIN artists_extended ae ON ae.id = pta.artist_id
+                    WHERE pta.play_uri = p.uri
+                ) AS artists,
+                p.release_name, p.release_mbid, p.isrc, p.origin_url,
+                p.music_service_base_domain, p.submission_client_agent, p.played_time,
+                p.track_discriminant, p.release_discriminant
+            FROM plays p
+            WHERE p.did = $1 AND p.uri LIKE 'at://%'
+            ORDER BY p.rkey
+            "#,
+        )
+        .bind(did)
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut records: Vec<ExportRecord> = plays.into_iter().map(Into::into).collect();
+
+        let profile = sqlx::query_as::<_, PgExportProfileRow>(
+            "SELECT display_name, description, description_facets, created_at FROM profiles WHERE did = $1",
+        )
+        .bind(did)
+        .fetch_optional(&self.db)
+        .await?;
+
+        // Avatar and banner blobs are left out: only their CIDs are indexed, and
+        // a blob ref also needs the MIME type and size.
+        if let Some(profile) = profile {
+            let mut record = Map::new();
+            record.insert("$type".into(), json!(PROFILE_COLLECTION));
+            insert_opt(
+                &mut record,
+                "displayName",
+                profile.display_name.map(Value::from),
+            );
+            insert_opt(
+                &mut record,
+                "description",
+                profile.description.map(Value::from),
+            );
+            insert_opt(&mut record, "descriptionFacets", profile.description_facets);
+            insert_opt(&mut record, "createdAt", profile.created_at.map(datetime));
+            records.push(ExportRecord {
+                collection: PROFILE_COLLECTION.to_string(),
+                rkey: "self".to_string(),
+                record: Value::Object(record),
+            });
+        }
+
+        let statii = sqlx::query_as::<_, (String, Value)>(
+            "SELECT rkey, record FROM statii WHERE did = $1 ORDER BY rkey",
+        )
+        .bind(did)
+        .fetch_all(&self.db)
+        .await?;
+
+        for (rkey, mut record) in statii {
+            if let Some(object) = record.as_object_mut() {
+                object.insert("$type".into(), json!(STATUS_COLLECTION));
+            }
+            records.push(ExportRecord {
+                collection: STATUS_COLLECTION.to_string(),
+                rkey,
+                record,
+            });
+        }
+
+        Ok(records)
+    }
+}
@@ -92,17 +92,7 @@ impl ActorProfileRepo for PgDataSource {
         &self,
         identities: &[String],
     ) -> anyhow::Result<Vec<ProfileView>> {
-        // split identities into dids (prefixed with "did:") and handles (not prefixed) in one iteration
-        let mut dids = Vec::new();
-        let mut handles = Vec::new();
-        for id in identities.iter() {
-            if id.starts_with("did:") {
-                dids.push(id.clone());
-            } else {
-                handles.push(id.clone());
-            }
-        }
-
+        let (dids, handles) = split_identities(identities);
         let profiles = sqlx::query_as!(
             PgProfileRepoRows,
             "SELECT
@@ -123,6 +113,7 @@ impl ActorProfileRepo for PgDataSource {
         )
         .fetch_all(&self.db)
         .await?;
+        let profiles = self.visible(profiles, |p| p.did.as_deref()).await?;
         Ok(profiles.into_iter().map(|p| p.into()).collect())
     }
 
@@ -142,6 +133,7 @@ impl ActorProfileRepo for PgDataSource {
         )
         .fetch_all(&self.db)
         .await?;
+        let profiles = self.visible(profiles, |p| p.did.as_deref()).await?;
 
         Ok(profiles.into_iter().map(Into::into).collect())
     }
@@ -161,9 +153,14 @@ impl ActorProfileRepo for PgDataSource {
             r#"
             SELECT avatar, did, display_name, handle
             FROM profiles
-            WHERE display_name ILIKE '%' || $1 || '%' ESCAPE '!'
-               OR description ILIKE '%' || $1 || '%' ESCAPE '!'
-               OR handle ILIKE '%' || $1 || '%' ESCAPE '!'
+            WHERE (
+                    display_name ILIKE '%' || $1 || '%' ESCAPE '!'
+                    OR description ILIKE '%' || $1 || '%' ESCAPE '!'
+                    OR handle ILIKE '%' || $1 || '%' ESCAPE '!'
+                )
+              AND NOT EXISTS (
+                  SELECT 1 FROM account_status a WHERE a.did = profiles.did AND NOT a.active
+              )
             ORDER BY display_name NULLS LAST, did
             LIMIT $2 OFFSET $3
             "#,
@@ -178,14 +175,19 @@ impl ActorProfileRepo for PgDataSource {
     }
 }
 
-fn split_identities(identities: &[String]) -> (Vec<String>, Vec<String>) {
+/// Split identities into DIDs and handles. Handles are stored bare and
+/// lowercased, so `@Alice.example` and `at://alice.example` match too.
+pub(crate) fn split_identities(identities: &[String]) -> (Vec<String>, Vec<String>) {
     let mut dids = Vec::new();
     let mut handles = Vec::new();
     for identity in identities {
         if identity.starts_with("did:") {
             dids.push(identity.clone());
         } else {
-            handles.push(identity.clone());
+            let handle = identity.trim();
+            let handle = handle.strip_prefix("at://").unwrap_or(handle);
+            let handle = handle.strip_prefix('@').unwrap_or(handle);
+            handles.push(handle.to_ascii_lowercase());
         }
     }
     (dids, handles)
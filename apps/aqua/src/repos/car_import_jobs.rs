@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::car_import_jobs::{JobHistoryEntry, record_job_history};
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::pg::PgDataSource;
+use crate::types::{
+    CarImportJob, CarImportJobFilter, CarImportJobRecord, CarImportJobStatus, JobStatus,
+};
+
+#[async_trait]
+pub trait CarImportJobRepo: Send + Sync {
+    /// Insert or update the history row for a job.
+    async fn record_car_import_job(
+        &self,
+        job: &CarImportJob,
+        status: &CarImportJobStatus,
+    ) -> anyhow::Result<()>;
+    async fn get_car_import_job(&self, job_id: Uuid) -> anyhow::Result<Option<CarImportJobRecord>>;
+    /// Number of dry runs of `did` that are queued or running.
+    async fn count_active_car_import_previews(&self, did: &str) -> anyhow::Result<i64>;
+    /// List jobs newest first.
+    async fn list_car_import_jobs(
+        &self,
+        filter: &CarImportJobFilter,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<CarImportJobRecord>>;
+}
+
+#[derive(sqlx::FromRow)]
+struct PgCarImportJobRow {
+    job_id: Uuid,
+    identity: String,
+    did: Option<String>,
+    status: String,
+    description: Option<String>,
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    completed_at: Option<DateTime<Utc>>,
+    error_message: Option<String>,
+    progress: Option<Value>,
+    dry_run: bool,
+    preview: Option<Value>,
+}
+
+impl TryFrom<PgCarImportJobRow> for CarImportJobRecord {
+    type Error = anyhow::Error;
+
+    fn try_from(row: PgCarImportJobRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            job_id: row.job_id,
+            identity: row.identity,
+            did: row.did,
+            status: row
+                .status
+                .parse::<JobStatus>()
+                .map_err(anyhow::Error::msg)?,
+            description: row.description,
+            created_at: row.created_at,
+            started_at: row.started_at,
+            completed_at: row.completed_at,
+            error_message: row.error_message,
+            progress: row.progress.and_then(|v| serde_json::from_value(v).ok()),
+            dry_run: row.dry_run,
+            preview: row.preview.and_then(|v| serde_json::from_value(v).ok()),
+        })
+    }
+}
+
+#[async_trait]
+impl CarImportJobRepo for PgDataSource {
+    async fn record_car_import_job(
+        &self,
+        job: &CarImportJob,
+        status: &CarImportJobStatus,
+    ) -> anyhow::Result<()> {
+        let did = job
+            .identity
+            .starts_with("did:")
+            .then_some(job.identity.as_str());
+        let progress = status
+            .progress
+            .as_ref()
+            .map(serde_json::to_value)
+            .transpose()?;
+        let preview = status
+            .preview
+            .as_ref()
+            .map(serde_json::to_value)
+            .transpose()?;
+
+        record_job_history(
+            &self.db,
+            &JobHistoryEntry {
+                job_id: job.request_id,
+                identity: &job.identity,
+                did,
+                status: status.status.as_str(),
+                description: job.description.as_deref(),
+                created_at: job.created_at,
+                started_at: status.started_at,
+                completed_at: status.completed_at,
+                error_message: status.error_message.as_deref(),
+                progress,
+                dry_run: job.dry_run,
+                preview,
+            },
+        )
+        .await
+    }
+
+    async fn get_car_import_job(&self, job_id: Uuid) -> anyhow::Result<Option<CarImportJobRecord>> {
+        let row = sqlx::query_as::<_, PgCarImportJobRow>(
+            r#"
+            SELECT
+                job_id, identity, did, status, description,
+                created_at, started_at, completed_at, error_message, progress,
+                dry_run, preview
+            FROM car_import_jobs
+            WHERE job_id = $1
+            "#,
+        )
+        .bind(job_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        row.map(TryInto::try_into).transpose()
+    }
+
+    async fn count_active_car_import_previews(&self, did: &str) -> anyhow::Result<i64> {
+        let count: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM car_import_jobs
+            WHERE did = $1
+              AND dry_run
+              AND status IN ('pending', 'processing')
+            "#,
+        )
+        .bind(did)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn list_car_import_jobs(
+        &self,
+        filter: &CarImportJobFilter,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<CarImportJobRecord>> {
+        let rows = sqlx::query_as::<_, PgCarImportJobRow>(
+            r#"
+            SELECT
+                job_id, identity, did, status, description,
+                created_at, started_at, completed_at, error_message, progress,
+                dry_run, preview
+            FROM car_import_jobs
+            WHERE ($1::text IS NULL OR did = $1)
+              AND ($2::text IS NULL OR status = $2)
+              AND ($3::timestamptz IS NULL OR created_at >= $3)
+              AND ($4::timestamptz IS NULL OR created_at < $4)
+            ORDER BY created_at DESC, job_id DESC
+            LIMIT $5 OFFSET $6
+            "#,
+        )
+        .bind(filter.did.as_deref())
+        .bind(filter.status.as_ref().map(JobStatus::as_str))
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.db)
+        .await?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+}
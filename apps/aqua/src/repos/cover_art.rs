@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use types::fm_teal::feed::{CoverArt, Thumbnail};
+use uuid::Uuid;
+
+use super::{pg::PgDataSource, uri_value};
+
+/// Front cover art of releases, as looked up by cadet from the Cover Art
+/// Archive.
+#[async_trait]
+pub trait CoverArtRepo: Send + Sync {
+    /// The cover art of each of `releases` that has any.
+    async fn get_cover_art(&self, releases: &[Uuid]) -> anyhow::Result<HashMap<Uuid, CoverArt>>;
+}
+
+#[derive(sqlx::FromRow)]
+struct PgCoverArtRow {
+    release_mbid: Uuid,
+    image_url: String,
+    thumbnails: Value,
+}
+
+#[derive(Deserialize)]
+struct PgThumbnail {
+    size: i64,
+    url: String,
+}
+
+#[async_trait]
+impl CoverArtRepo for PgDataSource {
+    async fn get_cover_art(&self, releases: &[Uuid]) -> anyhow::Result<HashMap<Uuid, CoverArt>> {
+        if releases.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let rows: Vec<PgCoverArtRow> = sqlx::query_as(
+            r#"
+            SELECT
+                c.release_mbid, c.image_url,
+                COALESCE(
+                  json_agg(
+                    json_build_object('size', t.size, 'url', t.url) ORDER BY t.size
+                  ) FILTER (WHERE t.url IS NOT NULL),
+                  '[]'
+                ) AS thumbnails
+            FROM cover_art c
+            LEFT JOIN cover_art_thumbnails t ON t.release_mbid = c.release_mbid
+            WHERE c.release_mbid = ANY($1) AND c.found AND c.image_url IS NOT NULL
+            GROUP BY c.release_mbid
+            "#,
+        )
+        .bind(releases)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let thumbnails: Vec<PgThumbnail> =
+                    serde_json::from_value(row.thumbnails).unwrap_or_default();
+                let cover_art = CoverArt {
+                    image_url: uri_value(row.image_url),
+                    thumbnails: thumbnails
+                        .into_iter()
+                        .map(|thumbnail| Thumbnail {
+                            url: uri_value(thumbnail.url),
+                            size: thumbnail.size,
+                            extra_data: Default::default(),
+                        })
+                        .collect(),
+                    extra_data: Default::default(),
+                };
+                (row.release_mbid, cover_art)
+            })
+            .collect())
+    }
+}
+
+impl PgDataSource {
+    /// The cover art of the releases of `items`, `release` giving each item's
+    /// release.
+    pub(crate) async fn cover_art_of<T>(
+        &self,
+        items: &[T],
+        release: impl Fn(&T) -> Option<Uuid>,
+    ) -> anyhow::Result<HashMap<Uuid, CoverArt>> {
+        let mut releases: Vec<Uuid> = items.iter().filter_map(release).collect();
+        releases.sort_unstable();
+        releases.dedup();
+        self.get_cover_art(&releases).await
+    }
+}
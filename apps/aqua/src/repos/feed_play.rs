@@ -1,8 +1,16 @@
+use std::collections::HashMap;
+
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use jacquard_common::from_json_value;
-use types::fm_teal::feed::{Artist, PlayView};
+use serde_json::Value;
+use types::fm_teal::feed::{Artist, CoverArt, PlayView};
+use uuid::Uuid;
 
-use super::{mbid_uri, pg::PgDataSource, uri_value, utc_to_atrium_datetime};
+use super::{
+    account_status::AccountStatusRepo, actor_profile::split_identities, mbid_uri, pg::PgDataSource,
+    uri_value, utc_to_atrium_datetime,
+};
 
 #[async_trait]
 pub trait FeedPlayRepo: Send + Sync {
@@ -11,6 +19,53 @@ pub trait FeedPlayRepo: Send + Sync {
         &self,
         identities: &[String],
     ) -> anyhow::Result<Vec<PlayView>>;
+    /// Plays of a recording by ISRC, newest first: plays sent with the ISRC
+    /// and plays of any recording the ISRC index links it to.
+    async fn get_feed_plays_for_isrc(
+        &self,
+        isrc: &str,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<PlayView>>;
+}
+
+#[derive(sqlx::FromRow)]
+struct PgPlayRow {
+    did: String,
+    isrc: Option<String>,
+    duration: Option<i32>,
+    track_name: String,
+    played_time: Option<DateTime<Utc>>,
+    release_mbid: Option<Uuid>,
+    release_name: Option<String>,
+    recording_mbid: Option<Uuid>,
+    submission_client_agent: Option<String>,
+    music_service_base_domain: Option<String>,
+    origin_url: Option<String>,
+    artists: Value,
+}
+
+impl PgPlayRow {
+    fn into_view(self, cover_art: &HashMap<Uuid, CoverArt>) -> PlayView {
+        PlayView {
+            track_name: self.track_name.into(),
+            track_mb_id: self.recording_mbid.map(mbid_uri),
+            recording_mb_id: self.recording_mbid.map(mbid_uri),
+            duration: self.duration.map(|d| d as i64),
+            artists: from_json_value::<Vec<Artist>>(self.artists).unwrap_or_default(),
+            release_name: self.release_name.map(|s| s.into()),
+            release_mb_id: self.release_mbid.map(mbid_uri),
+            isrc: self.isrc.map(|s| s.into()),
+            origin_uri: self.origin_url.map(uri_value),
+            music_service_uri: self.music_service_base_domain.map(uri_value),
+            submission_client_agent: self.submission_client_agent.map(|s| s.into()),
+            played_time: self.played_time.map(utc_to_atrium_datetime),
+            cover_art: self
+                .release_mbid
+                .and_then(|mbid| cover_art.get(&mbid).cloned()),
+            extra_data: Default::default(),
+        }
+    }
 }
 
 #[async_trait]
@@ -43,6 +98,12 @@ impl FeedPlayRepo for PgDataSource {
         )
         .fetch_one(&self.db)
         .await?;
+        if !self.inactive_dids(&[row.did.clone()]).await?.is_empty() {
+            return Ok(None);
+        }
+        let cover_art = self
+            .cover_art_of(std::slice::from_ref(&row), |row| row.release_mbid)
+            .await?;
 
         let artists: Vec<Artist> = match row.artists {
             Some(value) => from_json_value::<Vec<Artist>>(value).unwrap_or_default(),
@@ -64,6 +125,9 @@ impl FeedPlayRepo for PgDataSource {
             played_time: row
                 .played_time
                 .map(|dt| utc_to_atrium_datetime(crate::repos::time_to_chrono_utc(dt))),
+            cover_art: row
+                .release_mbid
+                .and_then(|mbid| cover_art.get(&mbid).cloned()),
             extra_data: Default::default(),
         }))
     }
@@ -72,6 +136,7 @@ impl FeedPlayRepo for PgDataSource {
         &self,
         identities: &[String],
     ) -> anyhow::Result<Vec<PlayView>> {
+        let (dids, handles) = split_identities(identities);
         let rows = sqlx::query!(
             r#"
             SELECT
@@ -89,16 +154,21 @@ impl FeedPlayRepo for PgDataSource {
                 ) AS artists
             FROM plays
             LEFT JOIN play_to_artists as pta ON uri = pta.play_uri
-            WHERE did = ANY($1)
+            WHERE (did = ANY($1) OR did IN (SELECT p.did FROM profiles p WHERE p.handle = ANY($2)))
+              AND NOT EXISTS (
+                  SELECT 1 FROM account_status a WHERE a.did = plays.did AND NOT a.active
+              )
             GROUP BY uri, did, rkey, cid, isrc, duration, track_name, played_time, processed_time,
                      release_mbid, release_name, recording_mbid, submission_client_agent,
                      music_service_base_domain, origin_url
             ORDER BY processed_time desc
             "#,
-            identities
+            &dids,
+            &handles
         )
         .fetch_all(&self.db)
         .await?;
+        let cover_art = self.cover_art_of(&rows, |row| row.release_mbid).await?;
 
         let mut result = Vec::with_capacity(rows.len());
         for row in rows {
@@ -123,10 +193,59 @@ impl FeedPlayRepo for PgDataSource {
                 played_time: row
                     .played_time
                     .map(|dt| utc_to_atrium_datetime(crate::repos::time_to_chrono_utc(dt))),
+                cover_art: row
+                    .release_mbid
+                    .and_then(|mbid| cover_art.get(&mbid).cloned()),
                 extra_data: Default::default(),
             });
         }
 
         Ok(result)
     }
+
+    async fn get_feed_plays_for_isrc(
+        &self,
+        isrc: &str,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<PlayView>> {
+        let rows: Vec<PgPlayRow> = sqlx::query_as(
+            r#"
+            SELECT
+                did, isrc, duration, track_name, played_time, release_mbid, release_name,
+                recording_mbid, submission_client_agent, music_service_base_domain, origin_url,
+                COALESCE(
+                  json_agg(
+                    json_build_object(
+                      'artist_mbid', pta.artist_mbid,
+                      'artist_name', pta.artist_name
+                    )
+                  ) FILTER (WHERE pta.artist_name IS NOT NULL),
+                  '[]'
+                ) AS artists
+            FROM plays
+            LEFT JOIN play_to_artists as pta ON uri = pta.play_uri
+            WHERE (
+                    isrc = $1
+                    OR recording_mbid IN (SELECT recording_mbid FROM isrc_recordings WHERE isrc = $1)
+                )
+              AND NOT EXISTS (
+                  SELECT 1 FROM account_status a WHERE a.did = plays.did AND NOT a.active
+              )
+            GROUP BY uri
+            ORDER BY played_time DESC NULLS LAST, uri
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(isrc)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.db)
+        .await?;
+        let cover_art = self.cover_art_of(&rows, |row| row.release_mbid).await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| row.into_view(&cover_art))
+            .collect())
+    }
 }
@@ -0,0 +1,29 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::pg::PgDataSource;
+use crate::types::ExistingPlay;
+
+/// Plays already stored for a DID, to skip duplicates in imported history.
+#[async_trait]
+pub trait ImportPreviewRepo: Send + Sync {
+    /// Plays of `did` played between `from` and `to` (inclusive)
+    async fn get_existing_plays(
+        &self,
+        did: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<ExistingPlay>>;
+}
+
+#[async_trait]
+impl ImportPreviewRepo for PgDataSource {
+    async fn get_existing_plays(
+        &self,
+        did: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<ExistingPlay>> {
+        ExistingPlay::fetch(&self.db, did, Some((from, to))).await
+    }
+}
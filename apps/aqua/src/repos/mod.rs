@@ -1,17 +1,38 @@
+use account_status::AccountStatusRepo;
+use actor_export::ActorExportRepo;
 use actor_profile::ActorProfileRepo;
+use cover_art::CoverArtRepo;
 use jacquard_common::{deps::smol_str::SmolStr, types::string::UriValue};
 use uuid::Uuid;
 
+use crate::repos::car_import_jobs::CarImportJobRepo;
 use crate::repos::feed_play::FeedPlayRepo;
+use crate::repos::import_preview::ImportPreviewRepo;
 use crate::repos::stats::StatsRepo;
 
+pub mod account_status;
+pub mod actor_export;
 pub mod actor_profile;
+pub mod car_import_jobs;
+pub mod cover_art;
 pub mod feed_play;
+pub mod import_preview;
 pub mod pg;
 pub mod stats;
 
 #[async_trait::async_trait]
-pub trait DataSource: ActorProfileRepo + FeedPlayRepo + StatsRepo + Send + Sync {
+pub trait DataSource:
+    AccountStatusRepo
+    + ActorExportRepo
+    + ActorProfileRepo
+    + CarImportJobRepo
+    + CoverArtRepo
+    + FeedPlayRepo
+    + ImportPreviewRepo
+    + StatsRepo
+    + Send
+    + Sync
+{
     fn boxed(self) -> Box<dyn DataSource>
     where
         Self: Sized + Send + Sync + 'static,
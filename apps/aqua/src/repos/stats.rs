@@ -3,7 +3,9 @@ use jacquard_common::from_json_value;
 use types::fm_teal::feed::PlayView;
 use types::fm_teal::stats::{ArtistView, ReleaseView};
 
-use super::{mbid_uri, pg::PgDataSource, utc_to_atrium_datetime};
+use super::{
+    account_status::AccountStatusRepo, mbid_uri, pg::PgDataSource, utc_to_atrium_datetime,
+};
 
 #[async_trait]
 pub trait StatsRepo: Send + Sync {
@@ -37,6 +39,9 @@ impl StatsRepo for PgDataSource {
             INNER JOIN play_to_artists pta ON p.uri = pta.play_uri
             WHERE pta.artist_mbid IS NOT NULL
               AND pta.artist_name IS NOT NULL
+              AND NOT EXISTS (
+                  SELECT 1 FROM account_status a WHERE a.did = p.did AND NOT a.active
+              )
             GROUP BY pta.artist_mbid, pta.artist_name
             ORDER BY play_count DESC
             LIMIT $1
@@ -73,6 +78,9 @@ impl StatsRepo for PgDataSource {
             FROM plays p
             WHERE p.release_mbid IS NOT NULL
               AND p.release_name IS NOT NULL
+              AND NOT EXISTS (
+                  SELECT 1 FROM account_status a WHERE a.did = p.did AND NOT a.active
+              )
             GROUP BY p.release_mbid, p.release_name
             ORDER BY play_count DESC
             LIMIT $1
@@ -82,6 +90,8 @@ impl StatsRepo for PgDataSource {
         .fetch_all(&self.db)
         .await?;
 
+        let cover_art = self.cover_art_of(&rows, |row| row.mbid).await?;
+
         let mut result = Vec::with_capacity(rows.len());
         for row in rows {
             if let (Some(mbid), Some(name)) = (row.mbid, row.name) {
@@ -89,6 +99,7 @@ impl StatsRepo for PgDataSource {
                     mbid: Some(mbid_uri(mbid)),
                     name: Some(name.into()),
                     play_count: Some(row.play_count.unwrap_or(0)),
+                    cover_art: cover_art.get(&mbid).cloned(),
                     extra_data: Default::default(),
                 });
             }
@@ -102,6 +113,9 @@ impl StatsRepo for PgDataSource {
         did: &str,
         limit: Option<i32>,
     ) -> anyhow::Result<Vec<ArtistView>> {
+        if !self.inactive_dids(&[did.to_string()]).await?.is_empty() {
+            return Ok(Vec::new());
+        }
         let limit = limit.unwrap_or(50).min(100) as i64;
 
         let rows = sqlx::query!(
@@ -145,6 +159,9 @@ impl StatsRepo for PgDataSource {
         did: &str,
         limit: Option<i32>,
     ) -> anyhow::Result<Vec<ReleaseView>> {
+        if !self.inactive_dids(&[did.to_string()]).await?.is_empty() {
+            return Ok(Vec::new());
+        }
         let limit = limit.unwrap_or(50).min(100) as i64;
 
         let rows = sqlx::query!(
@@ -167,6 +184,8 @@ impl StatsRepo for PgDataSource {
         .fetch_all(&self.db)
         .await?;
 
+        let cover_art = self.cover_art_of(&rows, |row| row.mbid).await?;
+
         let mut result = Vec::with_capacity(rows.len());
         for row in rows {
             if let (Some(mbid), Some(name)) = (row.mbid, row.name) {
@@ -174,6 +193,7 @@ impl StatsRepo for PgDataSource {
                     mbid: Some(mbid_uri(mbid)),
                     name: Some(name.into()),
                     play_count: Some(row.play_count.unwrap_or(0)),
+                    cover_art: cover_art.get(&mbid).cloned(),
                     extra_data: Default::default(),
                 });
             }
@@ -202,6 +222,9 @@ impl StatsRepo for PgDataSource {
                 ) AS artists
             FROM plays p
             LEFT JOIN play_to_artists as pta ON p.uri = pta.play_uri
+            WHERE NOT EXISTS (
+                SELECT 1 FROM account_status a WHERE a.did = p.did AND NOT a.active
+            )
             GROUP BY uri, did, rkey, cid, isrc, duration, track_name, played_time, processed_time,
                      release_mbid, release_name, recording_mbid, submission_client_agent,
                      music_service_base_domain, origin_url
@@ -213,11 +236,14 @@ impl StatsRepo for PgDataSource {
         .fetch_all(&self.db)
         .await?;
 
+        let cover_art = self.cover_art_of(&rows, |row| row.release_mbid).await?;
+
         let mut result = Vec::with_capacity(rows.len());
         for row in rows {
             let artists = match row.artists {
-                Some(value) => from_json_value::<Vec<types::fm_teal::feed::Artist>>(value)
-                    .unwrap_or_default(),
+                Some(value) => {
+                    from_json_value::<Vec<types::fm_teal::feed::Artist>>(value).unwrap_or_default()
+                }
                 None => vec![],
             };
 
@@ -236,6 +262,9 @@ impl StatsRepo for PgDataSource {
                 played_time: row
                     .played_time
                     .map(|dt| utc_to_atrium_datetime(crate::repos::time_to_chrono_utc(dt))),
+                cover_art: row
+                    .release_mbid
+                    .and_then(|mbid| cover_art.get(&mbid).cloned()),
                 extra_data: Default::default(),
             });
         }
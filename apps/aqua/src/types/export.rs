@@ -0,0 +1,10 @@
+use serde_json::Value;
+
+/// A teal record rebuilt from the indexed rows, as it would appear in the
+/// actor's repository.
+#[derive(Debug, Clone)]
+pub struct ExportRecord {
+    pub collection: String,
+    pub rkey: String,
+    pub record: Value,
+}
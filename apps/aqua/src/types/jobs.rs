@@ -2,50 +2,38 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CarImportJob {
-    pub request_id: Uuid,
-    pub identity: String,
-    pub since: Option<DateTime<Utc>>,
-    pub created_at: DateTime<Utc>,
-    pub description: Option<String>,
-}
+use common::import_preview::ImportPreview;
+pub use common::jobs::{
+    CarImportJob, CarImportJobStatus, HistoryImportJob, ImportedPlay, JobProgress, JobStatus,
+    queue_keys,
+};
 
+/// A row of CAR import job history, as returned by the job listing endpoint.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CarImportJobStatus {
+pub struct CarImportJobRecord {
+    pub job_id: Uuid,
+    pub identity: String,
+    pub did: Option<String>,
     pub status: JobStatus,
+    pub description: Option<String>,
     pub created_at: DateTime<Utc>,
     pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
     pub error_message: Option<String>,
     pub progress: Option<JobProgress>,
+    pub dry_run: bool,
+    /// Report of a completed dry run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<ImportPreview>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum JobStatus {
-    Pending,
-    Processing,
-    Completed,
-    Failed,
-    Cancelled,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct JobProgress {
-    step: String,
-    pub user_did: Option<String>,
-    pub pds_host: Option<String>,
-    pub car_size_bytes: Option<u64>,
-    pub blocks_processed: Option<u64>,
-}
-
-pub mod queue_keys {
-    use uuid::Uuid;
-
-    pub const CAR_IMPORT_JOBS: &str = "car_import_jobs";
-    pub const CAR_IMPORT_STATUS_PREFIX: &str = "car_import_status";
-
-    pub fn job_status_key(job_id: &Uuid) -> String {
-        format!("{}:{}", CAR_IMPORT_STATUS_PREFIX, job_id)
-    }
+/// Filters for listing CAR import job history. Unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct CarImportJobFilter {
+    pub did: Option<String>,
+    pub status: Option<JobStatus>,
+    /// Inclusive lower bound on `created_at`
+    pub since: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on `created_at`
+    pub until: Option<DateTime<Utc>>,
 }
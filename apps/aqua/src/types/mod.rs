@@ -1,3 +1,6 @@
+pub mod export;
 pub mod jobs;
 
+pub use common::import_preview::*;
+pub use export::*;
 pub use jobs::*;
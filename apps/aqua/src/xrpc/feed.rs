@@ -1,5 +1,6 @@
 use crate::ctx::Context;
 use axum::{http::StatusCode, response::IntoResponse, routing::get, Extension};
+use common::isrc::normalize_isrc;
 use jacquard_common::IntoStatic;
 use serde::{Deserialize, Serialize};
 use types::fm_teal::feed::PlayView;
@@ -10,6 +11,7 @@ pub fn feed_routes() -> axum::Router {
         .route("/fm.teal.feed.getPlay", get(get_feed_play))
         .route("/fm.teal.feed.getPlays", get(get_feed_plays))
         .route("/fm.teal.feed.getActorFeed", get(get_actor_feed))
+        .route("/fm.teal.feed.getPlaysByIsrc", get(get_plays_by_isrc))
 }
 
 #[derive(Deserialize)]
@@ -133,3 +135,45 @@ pub async fn get_actor_feed(
         Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
     }
 }
+
+#[derive(Deserialize)]
+pub struct GetPlaysByIsrcQuery {
+    pub isrc: String,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct GetPlaysByIsrcResponse {
+    plays: Vec<PlayView>,
+}
+
+pub async fn get_plays_by_isrc(
+    Extension(ctx): Extension<Context>,
+    axum::extract::Query(query): axum::extract::Query<GetPlaysByIsrcQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    // Stored ISRCs are uppercase without hyphens
+    let isrc = normalize_isrc(&query.isrc)
+        .ok_or((StatusCode::BAD_REQUEST, "isrc is invalid".to_string()))?;
+
+    let limit = query.limit.unwrap_or(20);
+    if !(1..=50).contains(&limit) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "limit must be between 1 and 50".to_string(),
+        ));
+    }
+    let offset = query
+        .cursor
+        .as_deref()
+        .unwrap_or("0")
+        .parse::<i64>()
+        .ok()
+        .filter(|offset| *offset >= 0)
+        .ok_or((StatusCode::BAD_REQUEST, "cursor is invalid".to_string()))?;
+
+    match ctx.db.get_feed_plays_for_isrc(&isrc, limit, offset).await {
+        Ok(plays) => Ok(axum::Json(GetPlaysByIsrcResponse { plays })),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
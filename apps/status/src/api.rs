@@ -69,6 +69,10 @@ async fn retrieve_status(
     else {
         return Ok(None);
     };
+    // Deactivated, taken down and deleted accounts are hidden
+    if !db::account_is_active(&state.db, &did).await? {
+        return Ok(None);
+    }
 
     let status = db::current_status(&state.db, &did).await?;
     Ok(Some((did, status)))
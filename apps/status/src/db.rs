@@ -256,6 +256,36 @@ pub async fn apply_identity_event(pool: &PgPool, did: &str, handle: &str) -> Res
     Ok(())
 }
 
+/// Record an `#account` event; see `apply_account_event` in the migrations.
+pub async fn apply_account_event(
+    pool: &PgPool,
+    did: &str,
+    active: bool,
+    status: Option<&str>,
+    time: Option<&str>,
+) -> Result<()> {
+    sqlx::query("SELECT apply_account_event($1, $2, $3, $4::timestamptz)")
+        .bind(did)
+        .bind(active)
+        .bind(status)
+        .bind(time)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Whether the account is active. Accounts without lifecycle events are.
+pub async fn account_is_active(pool: &PgPool, did: &str) -> Result<bool> {
+    let active: Option<bool> =
+        sqlx::query_scalar("SELECT active FROM account_status WHERE did = $1")
+            .bind(did)
+            .fetch_optional(pool)
+            .await?;
+
+    Ok(active.unwrap_or(true))
+}
+
 pub async fn prune_non_current_statuses(pool: &PgPool) -> Result<u64> {
     let result = sqlx::query!(
         r#"
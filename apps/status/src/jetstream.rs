@@ -122,6 +122,9 @@ fn parse_event(text: &str) -> Result<JetstreamEvent> {
         .then(|| serde_json::from_value(payload.clone()))
         .transpose()?;
     let identity = (kind == "identity")
+        .then(|| serde_json::from_value(payload.clone()))
+        .transpose()?;
+    let account = (kind == "account")
         .then(|| serde_json::from_value(payload))
         .transpose()?;
 
@@ -131,6 +134,7 @@ fn parse_event(text: &str) -> Result<JetstreamEvent> {
         kind: kind.to_string(),
         commit,
         identity,
+        account,
     })
 }
 
@@ -180,7 +184,19 @@ async fn process_event(pool: &PgPool, event: &JetstreamEvent) -> Result<()> {
                 db::apply_identity_event(pool, &event.did, handle).await?;
             }
         }
-        "account" | "sync" => {}
+        "account" => {
+            if let Some(account) = &event.account {
+                db::apply_account_event(
+                    pool,
+                    &event.did,
+                    account.active,
+                    account.status.as_deref(),
+                    account.time.as_deref(),
+                )
+                .await?;
+            }
+        }
+        "sync" => {}
         other => warn!(kind = other, "Ignoring unknown Jetstream event kind"),
     }
 
@@ -199,6 +215,7 @@ struct JetstreamEvent {
     kind: String,
     commit: Option<Commit>,
     identity: Option<Identity>,
+    account: Option<Account>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -215,6 +232,13 @@ struct Identity {
     handle: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct Account {
+    active: bool,
+    status: Option<String>,
+    time: Option<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::{parse_event, stream_url};
@@ -233,6 +257,20 @@ mod tests {
         assert!(event.identity.is_none());
     }
 
+    #[test]
+    fn parse_event_reads_account_status() {
+        let event = parse_event(
+            r#"{"payload":{"$type":"com.atproto.sync.subscribeRepos#account","did":"did:plc:example","seq":7,"time":"2024-09-05T06:11:04.870Z","active":false,"status":"deactivated"}}"#,
+        )
+        .expect("valid account event");
+
+        assert_eq!(event.kind, "account");
+        assert_eq!(event.cursor, 7);
+        let account = event.account.expect("account payload");
+        assert!(!account.active);
+        assert_eq!(account.status.as_deref(), Some("deactivated"));
+    }
+
     #[test]
     fn stream_url_requests_both_status_collections() {
         let url = stream_url(
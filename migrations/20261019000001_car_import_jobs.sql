@@ -0,0 +1,22 @@
+-- Durable history of CAR import jobs. Redis only holds the live status of a
+-- job; this table is what the job listing endpoints query.
+CREATE TABLE IF NOT EXISTS car_import_jobs (
+    job_id UUID PRIMARY KEY,
+    identity TEXT NOT NULL,          -- handle or DID as submitted
+    did TEXT,                        -- resolved DID, once known
+    status TEXT NOT NULL,            -- pending, processing, completed, failed, cancelled
+    description TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    started_at TIMESTAMP WITH TIME ZONE,
+    completed_at TIMESTAMP WITH TIME ZONE,
+    error_message TEXT,
+    progress JSONB,
+    dry_run BOOLEAN NOT NULL DEFAULT FALSE,
+    preview JSONB                    -- report of a completed dry run
+);
+
+CREATE INDEX idx_car_import_jobs_created_at ON car_import_jobs (created_at DESC);
+CREATE INDEX idx_car_import_jobs_did_created_at ON car_import_jobs (did, created_at DESC);
+CREATE INDEX idx_car_import_jobs_status_created_at ON car_import_jobs (status, created_at DESC);
+CREATE INDEX idx_car_import_jobs_completed_at ON car_import_jobs (completed_at)
+    WHERE completed_at IS NOT NULL;
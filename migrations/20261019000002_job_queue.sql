@@ -0,0 +1,25 @@
+-- Postgres job queue backend (JOB_QUEUE_BACKEND=postgres). Mirrors the Redis
+-- lists and status keys so deployments can run without Redis. Jobs are leased
+-- instead of deleted when claimed, and only removed once the worker has
+-- handled them. A job whose lease expires is claimed again, up to
+-- JOB_MAX_ATTEMPTS times.
+CREATE TABLE IF NOT EXISTS job_queue (
+    id BIGSERIAL PRIMARY KEY,
+    queue TEXT NOT NULL,             -- queue name, e.g. car_import_jobs
+    payload TEXT NOT NULL,           -- serialized job
+    enqueued_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    locked_until TIMESTAMP WITH TIME ZONE,
+    attempts INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX idx_job_queue_queue_id ON job_queue (queue, id);
+
+CREATE TABLE IF NOT EXISTS job_queue_statuses (
+    status_key TEXT PRIMARY KEY,     -- same key as the Redis status key
+    status_data TEXT NOT NULL,       -- serialized job status
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_job_queue_statuses_expires_at ON job_queue_statuses (expires_at)
+    WHERE expires_at IS NOT NULL;
@@ -0,0 +1,8 @@
+-- Where cadet's live consumer left off, one row per ingest source. Written
+-- only after the events up to `cursor` have been stored.
+CREATE TABLE IF NOT EXISTS ingest_cursor (
+    source TEXT PRIMARY KEY,         -- jetstream or firehose
+    cursor BIGINT NOT NULL,          -- Jetstream time_us or firehose seq
+    replay_from BIGINT,              -- set by `cadet rewind`, applied on the next start
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,19 @@
+-- Records cadet could not ingest, kept so they can be retried with
+-- `cadet retry-failed` once the cause is fixed. One row per record; a record
+-- that fails again bumps `attempts`.
+CREATE TABLE IF NOT EXISTS failed_records (
+    id BIGSERIAL PRIMARY KEY,
+    did TEXT NOT NULL,
+    collection TEXT NOT NULL,
+    rkey TEXT NOT NULL,
+    rev TEXT NOT NULL DEFAULT '',
+    cid TEXT,
+    record JSONB,                    -- raw record; NULL for failed deletes
+    error TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 1,
+    first_failed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_failed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (did, collection, rkey)
+);
+
+CREATE INDEX IF NOT EXISTS idx_failed_records_collection ON failed_records (collection);
@@ -0,0 +1,15 @@
+-- Records that violate their lexicon and were not ingested. The record body
+-- is not kept: rejected records are often oversized on purpose.
+CREATE TABLE IF NOT EXISTS rejected_records (
+    did TEXT NOT NULL,
+    collection TEXT NOT NULL,
+    rkey TEXT NOT NULL,
+    cid TEXT,
+    path TEXT NOT NULL,              -- field that failed, e.g. artists[0].artistName
+    constraint_name TEXT NOT NULL,   -- lexicon keyword, e.g. maxLength
+    message TEXT NOT NULL,
+    rejected_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (did, collection, rkey)
+);
+
+CREATE INDEX IF NOT EXISTS idx_rejected_records_rejected_at ON rejected_records (rejected_at);
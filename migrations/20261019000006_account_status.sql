@@ -0,0 +1,52 @@
+-- Lifecycle of each account we have seen an #account event for. aqua and the
+-- status API hide accounts that are not active; cadet purges deleted ones
+-- once they have been deleted for a grace period. Accounts without a row are
+-- active.
+CREATE TABLE IF NOT EXISTS account_status (
+    did TEXT PRIMARY KEY,
+    active BOOLEAN NOT NULL,
+    status TEXT,                              -- deactivated, takendown, suspended, deleted, ...
+    inactive_since TIMESTAMP WITH TIME ZONE,  -- when the current status began
+    event_time TIMESTAMP WITH TIME ZONE NOT NULL, -- time of the latest applied event
+    purged_at TIMESTAMP WITH TIME ZONE,       -- data of a deleted account was removed
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_account_status_inactive ON account_status (did) WHERE NOT active;
+
+-- Apply an #account event. Both cadet and the status app consume these, and
+-- replays deliver them again, so events older than the stored one are ignored.
+CREATE OR REPLACE FUNCTION apply_account_event(
+    p_did TEXT,
+    p_active BOOLEAN,
+    p_status TEXT,
+    p_event_time TIMESTAMP WITH TIME ZONE
+) RETURNS VOID AS $$
+DECLARE
+    v_status TEXT := CASE WHEN p_active THEN NULL ELSE COALESCE(p_status, 'deactivated') END;
+    v_time TIMESTAMP WITH TIME ZONE := COALESCE(p_event_time, NOW());
+BEGIN
+    INSERT INTO account_status (did, active, status, inactive_since, event_time)
+    VALUES (
+        p_did,
+        p_active,
+        v_status,
+        CASE WHEN p_active THEN NULL ELSE v_time END,
+        v_time
+    )
+    ON CONFLICT (did) DO UPDATE SET
+        active = EXCLUDED.active,
+        status = EXCLUDED.status,
+        inactive_since = CASE
+            WHEN EXCLUDED.active THEN NULL
+            -- a repeated event keeps the time the status began
+            WHEN NOT account_status.active AND account_status.status = EXCLUDED.status
+                THEN account_status.inactive_since
+            ELSE EXCLUDED.inactive_since
+        END,
+        event_time = EXCLUDED.event_time,
+        purged_at = CASE WHEN EXCLUDED.active THEN NULL ELSE account_status.purged_at END,
+        updated_at = NOW()
+    WHERE EXCLUDED.event_time >= account_status.event_time;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,21 @@
+-- profiles.handle used to hold the raw first alsoKnownAs entry of the DID
+-- document (`at://alice.example`). cadet now stores the bare, lowercased
+-- handle once it resolves back to the DID, and keeps it current from
+-- #identity events.
+UPDATE profiles
+SET handle = lower(substring(handle FROM 6))
+WHERE handle LIKE 'at://%';
+
+-- A handle belongs to one DID at a time. Duplicates left over from before
+-- verification can't be told apart here, so clear them; they are set again
+-- on the account's next identity event or profile update.
+UPDATE profiles
+SET handle = NULL
+WHERE handle IN (
+    SELECT handle FROM profiles
+    WHERE handle IS NOT NULL
+    GROUP BY handle
+    HAVING COUNT(*) > 1
+);
+
+CREATE INDEX IF NOT EXISTS idx_profiles_handle ON profiles (handle);
@@ -0,0 +1,46 @@
+-- MusicBrainz enrichment. Plays only carry the names clients send; cadet looks
+-- each MBID up against a MusicBrainz mirror and fills in the canonical data.
+-- `name` keeps what clients sent, the mb_* columns hold MusicBrainz's version.
+ALTER TABLE recordings
+    ADD COLUMN IF NOT EXISTS mb_name TEXT,
+    ADD COLUMN IF NOT EXISTS mb_artist_credit TEXT,   -- e.g. "Daft Punk feat. Pharrell Williams"
+    ADD COLUMN IF NOT EXISTS length_ms INTEGER,
+    ADD COLUMN IF NOT EXISTS isrcs TEXT[],
+    ADD COLUMN IF NOT EXISTS enriched_at TIMESTAMP WITH TIME ZONE;
+
+ALTER TABLE releases
+    ADD COLUMN IF NOT EXISTS mb_name TEXT,
+    ADD COLUMN IF NOT EXISTS mb_artist_credit TEXT,
+    ADD COLUMN IF NOT EXISTS release_date TEXT,       -- YYYY, YYYY-MM or YYYY-MM-DD
+    ADD COLUMN IF NOT EXISTS release_group_mbid UUID,
+    ADD COLUMN IF NOT EXISTS enriched_at TIMESTAMP WITH TIME ZONE;
+
+ALTER TABLE artists_extended
+    ADD COLUMN IF NOT EXISTS mb_name TEXT,
+    ADD COLUMN IF NOT EXISTS sort_name TEXT,
+    ADD COLUMN IF NOT EXISTS enriched_at TIMESTAMP WITH TIME ZONE;
+
+-- Raw web service responses. found = false for MBIDs MusicBrainz doesn't know.
+CREATE TABLE IF NOT EXISTS mb_cache (
+    entity_type TEXT NOT NULL CHECK (entity_type IN ('recording', 'release', 'artist')),
+    mbid UUID NOT NULL,
+    found BOOLEAN NOT NULL,
+    response JSONB,
+    fetched_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (entity_type, mbid)
+);
+
+CREATE INDEX IF NOT EXISTS idx_mb_cache_fetched_at ON mb_cache (fetched_at);
+
+CREATE TABLE IF NOT EXISTS mb_enrichment_queue (
+    entity_type TEXT NOT NULL CHECK (entity_type IN ('recording', 'release', 'artist')),
+    mbid UUID NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    enqueued_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (entity_type, mbid)
+);
+
+CREATE INDEX IF NOT EXISTS idx_mb_enrichment_queue_next_attempt
+    ON mb_enrichment_queue (next_attempt_at);
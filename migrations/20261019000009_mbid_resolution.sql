@@ -0,0 +1,24 @@
+-- MBID resolution for plays submitted without MusicBrainz IDs. cadet matches
+-- them against the MusicBrainz mirror by ISRC or by name and records how
+-- sure it was.
+ALTER TABLE plays
+    ADD COLUMN IF NOT EXISTS mbid_match_confidence REAL,
+    ADD COLUMN IF NOT EXISTS mbid_match_method TEXT,     -- isrc or search; NULL when sent by the client
+    ADD COLUMN IF NOT EXISTS mbid_resolved_at TIMESTAMP WITH TIME ZONE,
+    -- The release is matched separately ('fuzzy'); NULL when sent by the client
+    ADD COLUMN IF NOT EXISTS release_match_method TEXT,
+    ADD COLUMN IF NOT EXISTS release_match_confidence REAL;
+
+CREATE TABLE IF NOT EXISTS mbid_resolution_queue (
+    play_uri TEXT PRIMARY KEY REFERENCES plays(uri) ON DELETE CASCADE,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    enqueued_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_mbid_resolution_queue_next_attempt
+    ON mbid_resolution_queue (next_attempt_at);
+
+-- ISRC lookups against recordings enriched earlier
+CREATE INDEX IF NOT EXISTS idx_recordings_isrcs ON recordings USING GIN (isrcs);
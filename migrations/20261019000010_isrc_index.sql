@@ -0,0 +1,47 @@
+-- ISRC index: the recordings each ISRC belongs to, from MusicBrainz
+-- enrichment and from plays that carry both. Plays with only an ISRC take
+-- their recording MBID from it (plays.mbid_match_method = 'isrc_index').
+-- A single client's ISRC and recording MBID pair doesn't enter the index:
+-- plays only add a pair once enough distinct accounts have sent it.
+CREATE TABLE IF NOT EXISTS isrc_recordings (
+    isrc TEXT NOT NULL,                 -- normalized: uppercase, no hyphens
+    recording_mbid UUID NOT NULL,
+    source TEXT NOT NULL CHECK (source IN ('musicbrainz', 'play')),
+    first_seen_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (isrc, recording_mbid, source)
+);
+
+CREATE INDEX IF NOT EXISTS idx_isrc_recordings_recording_mbid
+    ON isrc_recordings (recording_mbid);
+
+CREATE TABLE IF NOT EXISTS isrc_recording_reporters (
+    isrc TEXT NOT NULL,
+    recording_mbid UUID NOT NULL,
+    did TEXT NOT NULL,
+    first_seen_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (isrc, recording_mbid, did)
+);
+
+CREATE INDEX IF NOT EXISTS idx_plays_isrc ON plays (isrc) WHERE isrc IS NOT NULL;
+
+UPDATE plays SET isrc = UPPER(REPLACE(isrc, '-', ''))
+WHERE isrc IS NOT NULL AND isrc <> UPPER(REPLACE(isrc, '-', ''));
+
+INSERT INTO isrc_recording_reporters (isrc, recording_mbid, did)
+SELECT DISTINCT isrc, recording_mbid, did FROM plays
+WHERE isrc ~ '^[A-Z]{2}[A-Z0-9]{3}[0-9]{7}$' AND recording_mbid IS NOT NULL
+  AND mbid_match_method IS NULL
+ON CONFLICT DO NOTHING;
+
+-- Keep in step with isrc::PLAY_REPORTERS
+INSERT INTO isrc_recordings (isrc, recording_mbid, source)
+SELECT isrc, recording_mbid, 'play' FROM isrc_recording_reporters
+GROUP BY isrc, recording_mbid
+HAVING COUNT(*) >= 3
+ON CONFLICT DO NOTHING;
+
+INSERT INTO isrc_recordings (isrc, recording_mbid, source)
+SELECT DISTINCT UPPER(REPLACE(isrc, '-', '')), mbid, 'musicbrainz'
+FROM recordings, UNNEST(isrcs) AS isrc
+WHERE UPPER(REPLACE(isrc, '-', '')) ~ '^[A-Z]{2}[A-Z0-9]{3}[0-9]{7}$'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,27 @@
+-- Front cover art of releases from the Cover Art Archive, looked up by cadet
+-- and served on release and play views.
+CREATE TABLE IF NOT EXISTS cover_art (
+    release_mbid UUID PRIMARY KEY,
+    found BOOLEAN NOT NULL,              -- false when neither the release nor its group has a front image
+    source TEXT CHECK (source IN ('release', 'release_group')),
+    image_url TEXT,
+    fetched_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS cover_art_thumbnails (
+    release_mbid UUID NOT NULL REFERENCES cover_art(release_mbid) ON DELETE CASCADE,
+    size INTEGER NOT NULL,               -- longest side in pixels
+    url TEXT NOT NULL,
+    PRIMARY KEY (release_mbid, size)
+);
+
+CREATE TABLE IF NOT EXISTS cover_art_queue (
+    release_mbid UUID PRIMARY KEY,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    enqueued_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_cover_art_queue_next_attempt
+    ON cover_art_queue (next_attempt_at);
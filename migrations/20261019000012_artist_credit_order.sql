@@ -0,0 +1,9 @@
+-- Artist credits in order. cadet splits combined artist strings such as
+-- "A feat. B" into one link per artist; position orders a play's artists and
+-- join_phrase is the text joining each to the next (" feat. ", " & ").
+ALTER TABLE play_to_artists_extended
+    ADD COLUMN IF NOT EXISTS position SMALLINT,
+    ADD COLUMN IF NOT EXISTS join_phrase TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_play_to_artists_extended_play_position
+    ON play_to_artists_extended (play_uri, position);
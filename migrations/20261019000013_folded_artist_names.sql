@@ -0,0 +1,43 @@
+-- Folded artist names for fuzzy matching: cleaned, NFKC, lowercase, no
+-- accents or punctuation, so "Beyoncé", "BEYONCE" and "ＢＥＹＯＮＣＥ" find
+-- each other. cadet computes them (Postgres can't fold the same way), and
+-- fills in names left NULL, including rows renamed since they were folded.
+ALTER TABLE artists_extended ADD COLUMN IF NOT EXISTS name_folded TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_artists_extended_name_folded
+    ON artists_extended (name_folded);
+CREATE INDEX IF NOT EXISTS idx_artists_extended_name_folded_trgm
+    ON artists_extended USING gin (name_folded gin_trgm_ops);
+
+CREATE OR REPLACE FUNCTION clear_stale_name_folded() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.name IS DISTINCT FROM OLD.name
+       AND NEW.name_folded IS NOT DISTINCT FROM OLD.name_folded THEN
+        NEW.name_folded := NULL;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS artists_extended_clear_name_folded ON artists_extended;
+CREATE TRIGGER artists_extended_clear_name_folded
+    BEFORE UPDATE OF name ON artists_extended
+    FOR EACH ROW EXECUTE FUNCTION clear_stale_name_folded();
+
+-- MusicBrainz names and aliases of artists, folded the same way, so "Кино"
+-- matches Kino and "宇多田ヒカル" matches Utada Hikaru.
+CREATE TABLE IF NOT EXISTS artist_aliases (
+    artist_mbid UUID NOT NULL REFERENCES artists_extended (mbid) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    name_folded TEXT NOT NULL,
+    locale TEXT,
+    PRIMARY KEY (artist_mbid, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_artist_aliases_name_folded ON artist_aliases (name_folded);
+
+-- Artists looked up before aliases were requested are looked up again
+INSERT INTO mb_enrichment_queue (entity_type, mbid)
+SELECT entity_type, mbid FROM mb_cache
+WHERE entity_type = 'artist' AND found
+ON CONFLICT (entity_type, mbid) DO NOTHING;
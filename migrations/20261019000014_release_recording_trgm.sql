@@ -0,0 +1,7 @@
+-- Trigram indexes for matching plays without MBIDs to releases and recordings
+-- their artists were already played with (plays.mbid_match_method = 'fuzzy'
+-- for recordings), and for finding duplicates to consolidate.
+CREATE INDEX IF NOT EXISTS idx_releases_name_trgm
+    ON releases USING gin (LOWER(TRIM(name)) gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_recordings_name_trgm
+    ON recordings USING gin (LOWER(TRIM(name)) gin_trgm_ops);
@@ -0,0 +1,48 @@
+-- Audit log of entity merges, so curators can review them and undo mistaken
+-- ones with `cadet unmerge <id>`. source_row is the merged-away row as it was.
+-- Splits of combined credits and synthetic artists given their MusicBrainz ID
+-- in place are logged here too.
+CREATE TABLE IF NOT EXISTS entity_merges (
+    id BIGSERIAL PRIMARY KEY,
+    entity_type TEXT NOT NULL CHECK (entity_type IN ('artist', 'release', 'recording')),
+    method TEXT NOT NULL,               -- consolidation, resolver, split or resolver_upgrade
+    confidence REAL,
+    source_id INTEGER,                  -- artists_extended.id, for artists
+    source_mbid UUID,
+    source_name TEXT NOT NULL,
+    source_row JSONB NOT NULL,
+    target_id INTEGER,
+    target_mbid UUID,
+    target_name TEXT NOT NULL,
+    target_ids INTEGER[],               -- splits: the artists the combined artist was split into
+    merged_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    unmerged_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_entity_merges_merged_at ON entity_merges (merged_at DESC);
+
+-- Each play a merge rewrote, with what it linked to before
+CREATE TABLE IF NOT EXISTS entity_merge_plays (
+    merge_id BIGINT NOT NULL REFERENCES entity_merges (id) ON DELETE CASCADE,
+    play_uri TEXT NOT NULL,
+    -- Artist merges: the play's link to the merged-away artist. Dropped links
+    -- were removed instead of moved, as the play already credited the target.
+    artist_name TEXT,
+    position SMALLINT,
+    join_phrase TEXT,
+    dropped BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Release merges: the play's release name
+    release_name TEXT,
+    PRIMARY KEY (merge_id, play_uri)
+);
+
+-- Bumped whenever artists, releases or recordings are merged, split or
+-- unmerged, so every cadet process clears its entity cache before its next
+-- batch of plays, not only the process that changed them.
+CREATE TABLE IF NOT EXISTS entity_cache_generation (
+    id BOOLEAN PRIMARY KEY CHECK (id),
+    generation BIGINT NOT NULL
+);
+
+INSERT INTO entity_cache_generation (id, generation) VALUES (TRUE, 0)
+ON CONFLICT (id) DO NOTHING;
@@ -0,0 +1,186 @@
+//! Account lifecycle: `#account` events from Jetstream or the firehose.
+//!
+//! Each event updates `account_status` through the `apply_account_event`
+//! database function, which the status app calls as well. aqua and the status
+//! API hide accounts that are not active, so deactivation and takedowns take
+//! effect right away and reactivation restores everything without a
+//! re-import. Deleted accounts are purged once they have stayed deleted for
+//! `ACCOUNT_DELETION_GRACE_DAYS`.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::firehose::frame::AccountFrame;
+
+const DEFAULT_DELETION_GRACE_DAYS: i32 = 30;
+
+/// Days a deleted account's data is kept before it is purged.
+pub fn deletion_grace_days() -> i32 {
+    std::env::var("ACCOUNT_DELETION_GRACE_DAYS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|days| *days >= 0)
+        .unwrap_or(DEFAULT_DELETION_GRACE_DAYS)
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AccountEvent {
+    pub did: String,
+    pub active: bool,
+    /// Why the account is inactive, e.g. `deactivated`, `takendown`, `deleted`
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub time: Option<DateTime<Utc>>,
+}
+
+impl AccountEvent {
+    /// The account event of a Jetstream message, if it is one.
+    pub fn from_jetstream(message: &str) -> Option<Self> {
+        let mut event: Value = serde_json::from_str(message).ok()?;
+        if event["kind"] != "account" {
+            return None;
+        }
+        serde_json::from_value(event["account"].take()).ok()
+    }
+}
+
+impl From<&AccountFrame> for AccountEvent {
+    fn from(frame: &AccountFrame) -> Self {
+        Self {
+            did: frame.did.clone(),
+            active: frame.active,
+            status: frame.status.clone(),
+            time: DateTime::parse_from_rfc3339(&frame.time)
+                .ok()
+                .map(|time| time.with_timezone(&Utc)),
+        }
+    }
+}
+
+pub async fn apply_account_event(pool: &PgPool, event: &AccountEvent) -> Result<()> {
+    sqlx::query("SELECT apply_account_event($1, $2, $3, $4)")
+        .bind(&event.did)
+        .bind(event.active)
+        .bind(&event.status)
+        .bind(event.time)
+        .execute(pool)
+        .await?;
+    if !event.active {
+        info!(
+            "Account {} is now {}",
+            event.did,
+            event.status.as_deref().unwrap_or("inactive")
+        );
+    }
+    Ok(())
+}
+
+/// Remove everything indexed for accounts deleted more than `grace_days`
+/// ago. Returns the number of accounts purged.
+pub async fn purge_deleted_accounts(pool: &PgPool, grace_days: i32) -> Result<u64> {
+    let dids: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT did FROM account_status
+        WHERE NOT active
+          AND status = 'deleted'
+          AND purged_at IS NULL
+          AND inactive_since < NOW() - make_interval(days => $1)
+        "#,
+    )
+    .bind(grace_days)
+    .fetch_all(pool)
+    .await?;
+
+    for did in &dids {
+        let mut tx = pool.begin().await?;
+        sqlx::query(
+            "DELETE FROM play_to_artists WHERE play_uri IN (SELECT uri FROM plays WHERE did = $1)",
+        )
+        .bind(did)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM play_to_artists_extended WHERE play_uri IN (SELECT uri FROM plays WHERE did = $1)",
+        )
+        .bind(did)
+        .execute(&mut *tx)
+        .await?;
+        // Merge snapshots of the account's plays, so an unmerge can't bring them back
+        sqlx::query(
+            "DELETE FROM entity_merge_plays WHERE play_uri IN (SELECT uri FROM plays WHERE did = $1)",
+        )
+        .bind(did)
+        .execute(&mut *tx)
+        .await?;
+        for table in [
+            "plays",
+            "profiles",
+            "featured_items",
+            "statii",
+            "status_actors",
+            "failed_records",
+            "rejected_records",
+            "car_import_jobs",
+            "isrc_recording_reporters",
+        ] {
+            sqlx::query(&format!("DELETE FROM {} WHERE did = $1", table))
+                .bind(did)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("UPDATE account_status SET purged_at = NOW() WHERE did = $1")
+            .bind(did)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        info!("Purged data of deleted account {}", did);
+    }
+
+    if !dids.is_empty() {
+        for view in [
+            "mv_artist_play_counts",
+            "mv_release_play_counts",
+            "mv_recording_play_counts",
+            "mv_global_play_count",
+        ] {
+            sqlx::query(&format!("REFRESH MATERIALIZED VIEW {}", view))
+                .execute(pool)
+                .await?;
+        }
+    }
+    Ok(dids.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_event_from_jetstream() {
+        let event = AccountEvent::from_jetstream(
+            r#"{"did":"did:plc:test","time_us":1725516665333808,"kind":"account","account":{"active":false,"did":"did:plc:test","seq":1409753013,"status":"takendown","time":"2024-09-05T06:11:04.870Z"}}"#,
+        )
+        .expect("account event");
+        assert_eq!(event.did, "did:plc:test");
+        assert!(!event.active);
+        assert_eq!(event.status.as_deref(), Some("takendown"));
+        assert!(event.time.is_some());
+
+        let reactivated = AccountEvent::from_jetstream(
+            r#"{"did":"did:plc:test","time_us":1,"kind":"account","account":{"active":true,"did":"did:plc:test","seq":1,"time":"2024-09-06T00:00:00Z"}}"#,
+        )
+        .expect("account event");
+        assert!(reactivated.active);
+        assert!(reactivated.status.is_none());
+
+        assert!(AccountEvent::from_jetstream(
+            r#"{"did":"did:plc:test","time_us":1,"kind":"identity","identity":{"did":"did:plc:test","seq":1}}"#
+        )
+        .is_none());
+    }
+}
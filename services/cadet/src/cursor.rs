@@ -1,14 +1,213 @@
-pub async fn store_cursor(cursor: u64) -> anyhow::Result<()> {
-    // get cursor location from env CURSOR_FILE
-    let cursor_file = std::env::var("CURSOR_FILE").unwrap_or_else(|_| "./cursor.txt".to_string());
-    tokio::fs::write(cursor_file, cursor.to_string()).await?;
+//! Durable ingest cursors, one row per source in `ingest_cursor`.
+//!
+//! Jetstream cursors are event timestamps in microseconds and firehose
+//! cursors are relay sequence numbers. The stored cursor only ever points at
+//! events that have been ingested, and Jetstream resumes a few seconds before
+//! it since events near the cursor can arrive out of order. Replays are safe:
+//! every ingestor upserts.
+//!
+//! The ingest loops report each handled event to a [`HandledCursor`]. Live
+//! plays are written in batches, so while a play is waiting in the
+//! [`PlayBuffer`](crate::ingestors::teal::play_buffer::PlayBuffer) the
+//! cursor stays before it.
+
+use std::{sync::Mutex, time::Duration};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::DateTime;
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::firehose::IngestSource;
+
+/// Where the ingest loops report how far they have got.
+#[async_trait]
+pub trait HandledCursor: Send + Sync {
+    /// The event at `position` and every one before it have been handled.
+    async fn handled(&self, position: u64);
+}
+
+/// The cursor itself, for ingestors that write before they return.
+#[async_trait]
+impl HandledCursor for Mutex<Option<u64>> {
+    async fn handled(&self, position: u64) {
+        *self.lock().unwrap() = Some(position);
+    }
+}
+
+const DEFAULT_REWIND_SECS: u64 = 5;
+const DEFAULT_STORE_INTERVAL_SECS: u64 = 10;
+
+/// How far before the stored cursor Jetstream resumes.
+pub fn rewind_secs() -> u64 {
+    std::env::var("CURSOR_REWIND_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_REWIND_SECS)
+}
+
+/// How often the committed cursor is written to Postgres.
+pub fn store_interval() -> Duration {
+    Duration::from_secs(
+        std::env::var("CURSOR_STORE_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|secs| *secs > 0)
+            .unwrap_or(DEFAULT_STORE_INTERVAL_SECS),
+    )
+}
+
+/// Cursor to reconnect with after having ingested everything up to `cursor`.
+pub fn resume_point(source: IngestSource, cursor: u64, rewind_secs: u64) -> u64 {
+    match source {
+        IngestSource::Jetstream => cursor.saturating_sub(rewind_secs * 1_000_000),
+        // Sequence numbers are strictly ordered
+        IngestSource::Firehose => cursor,
+    }
+}
+
+/// Parse a `cadet rewind` argument: a timestamp (RFC 3339 or Unix
+/// microseconds) for Jetstream, a sequence number for the firehose.
+pub fn parse_replay_point(source: IngestSource, value: &str) -> Result<u64> {
+    if let Ok(cursor) = value.parse::<u64>() {
+        return Ok(cursor);
+    }
+    match source {
+        IngestSource::Jetstream => DateTime::parse_from_rfc3339(value)
+            .ok()
+            .and_then(|time| u64::try_from(time.timestamp_micros()).ok())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Expected an RFC 3339 timestamp or Unix microseconds, got {:?}",
+                    value
+                )
+            }),
+        IngestSource::Firehose => Err(anyhow!("Expected a sequence number, got {:?}", value)),
+    }
+}
+
+/// Cursor to start from: a pending replay request, then the stored cursor,
+/// then the file cursor of older versions.
+pub async fn load_cursor(pool: &PgPool, source: IngestSource) -> Result<Option<u64>> {
+    if let Some(replay_from) = take_replay_request(pool, source).await? {
+        info!(
+            "Replaying {} from {} as requested by `cadet rewind`",
+            source.as_str(),
+            replay_from
+        );
+        return Ok(Some(replay_from));
+    }
+
+    let stored: Option<i64> =
+        sqlx::query_scalar("SELECT cursor FROM ingest_cursor WHERE source = $1")
+            .bind(source.as_str())
+            .fetch_optional(pool)
+            .await?;
+    match stored {
+        Some(cursor) => Ok(Some(u64::try_from(cursor)?)),
+        None => Ok(load_legacy_cursor_file(source).await),
+    }
+}
+
+pub async fn store_cursor(pool: &PgPool, source: IngestSource, cursor: u64) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO ingest_cursor (source, cursor, updated_at)
+        VALUES ($1, $2, NOW())
+        ON CONFLICT (source) DO UPDATE
+        SET cursor = EXCLUDED.cursor, updated_at = NOW()
+        "#,
+    )
+    .bind(source.as_str())
+    .bind(i64::try_from(cursor)?)
+    .execute(pool)
+    .await?;
     Ok(())
 }
 
-pub async fn load_cursor() -> Option<u64> {
-    let cursor_file = std::env::var("CURSOR_FILE").unwrap_or_else(|_| "./cursor.txt".to_string());
-    tokio::fs::read_to_string(cursor_file)
+/// Ask the consumer to replay from `replay_from` the next time it starts.
+pub async fn request_replay(pool: &PgPool, source: IngestSource, replay_from: u64) -> Result<()> {
+    let replay_from = i64::try_from(replay_from)?;
+    sqlx::query(
+        r#"
+        INSERT INTO ingest_cursor (source, cursor, replay_from, updated_at)
+        VALUES ($1, $2, $2, NOW())
+        ON CONFLICT (source) DO UPDATE
+        SET replay_from = EXCLUDED.replay_from, updated_at = NOW()
+        "#,
+    )
+    .bind(source.as_str())
+    .bind(replay_from)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Clear and return a pending replay request, which also becomes the stored
+/// cursor.
+async fn take_replay_request(pool: &PgPool, source: IngestSource) -> Result<Option<u64>> {
+    let replay_from: Option<i64> = sqlx::query_scalar(
+        r#"
+        UPDATE ingest_cursor
+        SET cursor = replay_from, replay_from = NULL, updated_at = NOW()
+        WHERE source = $1 AND replay_from IS NOT NULL
+        RETURNING cursor
+        "#,
+    )
+    .bind(source.as_str())
+    .fetch_optional(pool)
+    .await?;
+    Ok(replay_from.map(u64::try_from).transpose()?)
+}
+
+/// Cursor file written by versions that did not store cursors in Postgres.
+async fn load_legacy_cursor_file(source: IngestSource) -> Option<u64> {
+    let (var, default) = match source {
+        IngestSource::Jetstream => ("CURSOR_FILE", "./cursor.txt"),
+        IngestSource::Firehose => ("FIREHOSE_CURSOR_FILE", "./firehose_cursor.txt"),
+    };
+    let path = std::env::var(var).unwrap_or_else(|_| default.to_string());
+    let cursor = tokio::fs::read_to_string(&path)
         .await
         .ok()
-        .and_then(|s| s.parse().ok())
+        .and_then(|s| s.trim().parse().ok());
+    if let Some(cursor) = cursor {
+        info!("Resuming {} from cursor file {}", source.as_str(), path);
+        Some(cursor)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_point_rewinds_jetstream_only() {
+        assert_eq!(
+            resume_point(IngestSource::Jetstream, 1_700_000_010_000_000, 5),
+            1_700_000_005_000_000
+        );
+        assert_eq!(resume_point(IngestSource::Jetstream, 3, 5), 0);
+        assert_eq!(resume_point(IngestSource::Firehose, 42, 5), 42);
+    }
+
+    #[test]
+    fn test_parse_replay_point() {
+        assert_eq!(
+            parse_replay_point(IngestSource::Jetstream, "2023-11-14T22:13:20Z").unwrap(),
+            1_700_000_000_000_000
+        );
+        assert_eq!(
+            parse_replay_point(IngestSource::Jetstream, "1700000000000000").unwrap(),
+            1_700_000_000_000_000
+        );
+        assert_eq!(
+            parse_replay_point(IngestSource::Firehose, "42").unwrap(),
+            42
+        );
+        assert!(parse_replay_point(IngestSource::Firehose, "2023-11-14T22:13:20Z").is_err());
+    }
 }
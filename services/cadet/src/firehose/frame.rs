@@ -0,0 +1,345 @@
+//! Frames of `com.atproto.sync.subscribeRepos`.
+//!
+//! Each binary WebSocket message is two DAG-CBOR values back to back: a
+//! header `{op, t}` and the message body. `op` is `1` for a message of type
+//! `t` (e.g. `#commit`) and `-1` for an error, after which the relay closes
+//! the connection.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Result};
+use ipld_core::{cid::Cid, ipld::Ipld};
+
+#[derive(Debug, Clone)]
+pub enum Frame {
+    Commit(CommitFrame),
+    Account(AccountFrame),
+    Identity(IdentityFrame),
+    Error {
+        error: String,
+        message: Option<String>,
+    },
+    /// Any other message type (`#sync`, `#info`)
+    Other {
+        kind: String,
+        seq: Option<u64>,
+    },
+}
+
+impl Frame {
+    /// Sequence number to resume from after this frame, if it has one.
+    pub fn seq(&self) -> Option<u64> {
+        match self {
+            Frame::Commit(commit) => Some(commit.seq),
+            Frame::Account(account) => Some(account.seq),
+            Frame::Identity(identity) => Some(identity.seq),
+            Frame::Error { .. } => None,
+            Frame::Other { seq, .. } => *seq,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CommitFrame {
+    pub seq: u64,
+    pub repo: String,
+    pub rev: String,
+    /// CID of the commit block
+    pub commit: Cid,
+    /// The commit was too large to carry its blocks, so `blocks` is empty
+    pub too_big: bool,
+    /// CAR slice with the commit, the changed MST nodes and the new records
+    pub blocks: Vec<u8>,
+    pub ops: Vec<RepoOp>,
+    pub time: String,
+}
+
+/// A change in an account's hosting status.
+#[derive(Debug, Clone)]
+pub struct AccountFrame {
+    pub seq: u64,
+    pub did: String,
+    pub time: String,
+    pub active: bool,
+    /// Why the account is inactive, e.g. `deactivated`, `takendown`, `deleted`
+    pub status: Option<String>,
+}
+
+/// A change to an account's handle or DID document.
+#[derive(Debug, Clone)]
+pub struct IdentityFrame {
+    pub seq: u64,
+    pub did: String,
+    pub time: String,
+    /// The handle the relay last saw for the account; unverified
+    pub handle: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RepoOp {
+    /// `create`, `update` or `delete`
+    pub action: String,
+    /// `collection/rkey`
+    pub path: String,
+    /// New record CID; `None` for deletes
+    pub cid: Option<Cid>,
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Frame> {
+    let header_len = cbor_item_end(bytes, 0)?;
+    let header: Ipld = serde_ipld_dagcbor::from_slice(&bytes[..header_len])
+        .map_err(|e| anyhow!("Invalid frame header: {}", e))?;
+    let body: Ipld = serde_ipld_dagcbor::from_slice(&bytes[header_len..])
+        .map_err(|e| anyhow!("Invalid frame body: {}", e))?;
+    let header = map(&header, "header")?;
+    let body = map(&body, "body")?;
+
+    match int(header, "op")? {
+        -1 => Ok(Frame::Error {
+            error: string(body, "error")?,
+            message: string(body, "message").ok(),
+        }),
+        1 => {
+            let kind = string(header, "t")?;
+            if kind == "#commit" {
+                Ok(Frame::Commit(commit_frame(body)?))
+            } else if kind == "#account" {
+                Ok(Frame::Account(AccountFrame {
+                    seq: u64::try_from(int(body, "seq")?)?,
+                    did: string(body, "did")?,
+                    time: string(body, "time")?,
+                    active: matches!(body.get("active"), Some(Ipld::Bool(true))),
+                    status: string(body, "status").ok(),
+                }))
+            } else if kind == "#identity" {
+                Ok(Frame::Identity(IdentityFrame {
+                    seq: u64::try_from(int(body, "seq")?)?,
+                    did: string(body, "did")?,
+                    time: string(body, "time")?,
+                    handle: string(body, "handle").ok(),
+                }))
+            } else {
+                let seq = int(body, "seq")
+                    .ok()
+                    .and_then(|seq| u64::try_from(seq).ok());
+                Ok(Frame::Other { kind, seq })
+            }
+        }
+        op => Err(anyhow!("Unknown frame op {}", op)),
+    }
+}
+
+fn commit_frame(body: &BTreeMap<String, Ipld>) -> Result<CommitFrame> {
+    let ops = match body.get("ops") {
+        Some(Ipld::List(ops)) => ops
+            .iter()
+            .map(|op| {
+                let op = map(op, "op")?;
+                Ok(RepoOp {
+                    action: string(op, "action")?,
+                    path: string(op, "path")?,
+                    cid: match op.get("cid") {
+                        Some(Ipld::Link(cid)) => Some(*cid),
+                        _ => None,
+                    },
+                })
+            })
+            .collect::<Result<_>>()?,
+        _ => bail!("Commit frame is missing ops"),
+    };
+
+    Ok(CommitFrame {
+        seq: u64::try_from(int(body, "seq")?)?,
+        repo: string(body, "repo")?,
+        rev: string(body, "rev")?,
+        commit: match body.get("commit") {
+            Some(Ipld::Link(cid)) => *cid,
+            _ => bail!("Commit frame is missing the commit CID"),
+        },
+        too_big: matches!(body.get("tooBig"), Some(Ipld::Bool(true))),
+        blocks: match body.get("blocks") {
+            Some(Ipld::Bytes(blocks)) => blocks.clone(),
+            _ => Vec::new(),
+        },
+        ops,
+        time: string(body, "time")?,
+    })
+}
+
+fn map<'a>(value: &'a Ipld, what: &str) -> Result<&'a BTreeMap<String, Ipld>> {
+    match value {
+        Ipld::Map(map) => Ok(map),
+        _ => Err(anyhow!("Frame {} is not a map", what)),
+    }
+}
+
+fn string(map: &BTreeMap<String, Ipld>, key: &str) -> Result<String> {
+    match map.get(key) {
+        Some(Ipld::String(value)) => Ok(value.clone()),
+        _ => Err(anyhow!("Frame field {} is not a string", key)),
+    }
+}
+
+fn int(map: &BTreeMap<String, Ipld>, key: &str) -> Result<i64> {
+    match map.get(key) {
+        Some(Ipld::Integer(value)) => Ok(i64::try_from(*value)?),
+        _ => Err(anyhow!("Frame field {} is not an integer", key)),
+    }
+}
+
+/// Offset just past the CBOR data item starting at `start`. Used to split the
+/// header from the body; DAG-CBOR has no indefinite-length items, so every
+/// item's extent follows from its length arguments.
+fn cbor_item_end(bytes: &[u8], start: usize) -> Result<usize> {
+    let truncated = || anyhow!("Truncated CBOR in frame");
+    let initial = *bytes.get(start).ok_or_else(truncated)?;
+    let major = initial >> 5;
+    let info = initial & 0x1f;
+
+    let (argument, mut end) = match info {
+        0..=23 => (info as u64, start + 1),
+        24..=27 => {
+            let len = 1usize << (info - 24);
+            let argument = bytes
+                .get(start + 1..start + 1 + len)
+                .ok_or_else(truncated)?
+                .iter()
+                .fold(0u64, |acc, byte| (acc << 8) | *byte as u64);
+            (argument, start + 1 + len)
+        }
+        _ => bail!("Indefinite-length CBOR is not valid DAG-CBOR"),
+    };
+
+    match major {
+        // Integers, floats and simple values are just the argument
+        0 | 1 | 7 => {}
+        // Byte and text strings
+        2 | 3 => {
+            end = usize::try_from(argument)
+                .ok()
+                .and_then(|len| end.checked_add(len))
+                .filter(|end| *end <= bytes.len())
+                .ok_or_else(truncated)?;
+        }
+        // Arrays, maps (key and value per entry) and tags wrap further items
+        4 | 5 | 6 => {
+            let items = match major {
+                4 => argument,
+                5 => argument.saturating_mul(2),
+                _ => 1,
+            };
+            for _ in 0..items {
+                end = cbor_item_end(bytes, end)?;
+            }
+        }
+        _ => unreachable!("CBOR major types are three bits"),
+    }
+    Ok(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(header: Ipld, body: Ipld) -> Vec<u8> {
+        let mut bytes = serde_ipld_dagcbor::to_vec(&header).unwrap();
+        bytes.extend(serde_ipld_dagcbor::to_vec(&body).unwrap());
+        bytes
+    }
+
+    fn ipld_map<const N: usize>(entries: [(&str, Ipld); N]) -> Ipld {
+        Ipld::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_cbor_item_end_spans_nested_items() {
+        let value = ipld_map([
+            ("a", Ipld::List(vec![Ipld::Integer(1), Ipld::Integer(-500)])),
+            ("b", Ipld::Bytes(vec![0; 300])),
+            ("c", Ipld::Float(1.5)),
+        ]);
+        let bytes = serde_ipld_dagcbor::to_vec(&value).unwrap();
+        assert_eq!(cbor_item_end(&bytes, 0).unwrap(), bytes.len());
+
+        let mut with_trailer = bytes.clone();
+        with_trailer.push(0x01);
+        assert_eq!(cbor_item_end(&with_trailer, 0).unwrap(), bytes.len());
+        assert!(cbor_item_end(&bytes[..bytes.len() - 1], 0).is_err());
+    }
+
+    #[test]
+    fn test_decode_error_and_other_frames() {
+        let error = frame(
+            ipld_map([("op", Ipld::Integer(-1))]),
+            ipld_map([("error", Ipld::String("FutureCursor".into()))]),
+        );
+        match decode(&error).unwrap() {
+            Frame::Error { error, message } => {
+                assert_eq!(error, "FutureCursor");
+                assert!(message.is_none());
+            }
+            other => panic!("expected an error frame, got {:?}", other),
+        }
+
+        let sync = frame(
+            ipld_map([
+                ("op", Ipld::Integer(1)),
+                ("t", Ipld::String("#sync".into())),
+            ]),
+            ipld_map([
+                ("seq", Ipld::Integer(42)),
+                ("did", Ipld::String("did:plc:test".into())),
+            ]),
+        );
+        let decoded = decode(&sync).unwrap();
+        assert_eq!(decoded.seq(), Some(42));
+        assert!(matches!(decoded, Frame::Other { kind, .. } if kind == "#sync"));
+
+        let identity = frame(
+            ipld_map([
+                ("op", Ipld::Integer(1)),
+                ("t", Ipld::String("#identity".into())),
+            ]),
+            ipld_map([
+                ("seq", Ipld::Integer(44)),
+                ("did", Ipld::String("did:plc:test".into())),
+                ("time", Ipld::String("2024-09-05T06:11:04.870Z".into())),
+                ("handle", Ipld::String("alice.example".into())),
+            ]),
+        );
+        match decode(&identity).unwrap() {
+            Frame::Identity(identity) => {
+                assert_eq!(identity.seq, 44);
+                assert_eq!(identity.handle.as_deref(), Some("alice.example"));
+            }
+            other => panic!("expected an identity frame, got {:?}", other),
+        }
+
+        let account = frame(
+            ipld_map([
+                ("op", Ipld::Integer(1)),
+                ("t", Ipld::String("#account".into())),
+            ]),
+            ipld_map([
+                ("seq", Ipld::Integer(43)),
+                ("did", Ipld::String("did:plc:test".into())),
+                ("time", Ipld::String("2024-09-05T06:11:04.870Z".into())),
+                ("active", Ipld::Bool(false)),
+                ("status", Ipld::String("deleted".into())),
+            ]),
+        );
+        match decode(&account).unwrap() {
+            Frame::Account(account) => {
+                assert_eq!(account.seq, 43);
+                assert!(!account.active);
+                assert_eq!(account.status.as_deref(), Some("deleted"));
+            }
+            other => panic!("expected an account frame, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Repo signing keys, cached per DID.
+//!
+//! A repo's commits are signed with the `#atproto` key of its DID document.
+//! Keys stay cached until the firehose announces an `#identity` event for the
+//! DID, which is how key rotations show up; the next commit then resolves the
+//! document again.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use anyhow::{anyhow, bail, Result};
+use tracing::warn;
+
+use crate::resolve::{self, DidDocument};
+
+/// Multicodec prefix of a compressed secp256k1 public key
+const SECP256K1_PUB: [u8; 2] = [0xe7, 0x01];
+/// Multicodec prefix of a compressed P-256 public key
+const P256_PUB: [u8; 2] = [0x80, 0x24];
+
+/// A repo signing key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PublicKey {
+    K256(k256::ecdsa::VerifyingKey),
+    P256(p256::ecdsa::VerifyingKey),
+}
+
+impl PublicKey {
+    /// Parse a multibase multikey, as in `publicKeyMultibase`.
+    pub fn from_multibase(key: &str) -> Result<Self> {
+        let (_, key) = multibase::decode(key)?;
+        match key.split_at_checked(2) {
+            Some((prefix, key)) if prefix == SECP256K1_PUB => Ok(PublicKey::K256(
+                k256::ecdsa::VerifyingKey::from_sec1_bytes(key)?,
+            )),
+            Some((prefix, key)) if prefix == P256_PUB => Ok(PublicKey::P256(
+                p256::ecdsa::VerifyingKey::from_sec1_bytes(key)?,
+            )),
+            _ => bail!("Unsupported key type"),
+        }
+    }
+
+    /// Check a 64 byte `r || s` signature over the SHA-256 of `signed`.
+    pub fn verify(&self, signed: &[u8], signature: &[u8]) -> Result<()> {
+        use k256::ecdsa::signature::Verifier;
+
+        match self {
+            PublicKey::K256(key) => {
+                key.verify(signed, &k256::ecdsa::Signature::from_slice(signature)?)?
+            }
+            PublicKey::P256(key) => {
+                key.verify(signed, &p256::ecdsa::Signature::from_slice(signature)?)?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Signing keys of the repos seen on the firehose.
+#[derive(Debug, Default)]
+pub struct SigningKeys {
+    keys: Mutex<HashMap<String, PublicKey>>,
+}
+
+impl SigningKeys {
+    /// The signing key of `did`, resolving its DID document if it is not
+    /// cached. `None` if the document is gone or has no usable key; errors
+    /// are resolution failures worth retrying, such as a PLC outage.
+    pub async fn get(&self, did: &str) -> Result<Option<PublicKey>> {
+        if let Some(key) = self.keys.lock().unwrap().get(did) {
+            return Ok(Some(key.clone()));
+        }
+
+        let doc = match resolve::get_did_doc(did).await {
+            Ok(doc) => doc,
+            Err(e) if is_transient(&e) => return Err(e),
+            Err(e) => {
+                warn!("Could not resolve the DID document of {}: {}", did, e);
+                return Ok(None);
+            }
+        };
+        match signing_key(&doc, did) {
+            Ok(key) => {
+                self.insert(did, key.clone());
+                Ok(Some(key))
+            }
+            Err(e) => {
+                warn!("No signing key for {}: {}", did, e);
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn insert(&self, did: &str, key: PublicKey) {
+        self.keys.lock().unwrap().insert(did.to_string(), key);
+    }
+
+    /// Forget the key of `did`, after an identity change.
+    pub fn invalidate(&self, did: &str) {
+        self.keys.lock().unwrap().remove(did);
+    }
+
+    #[cfg(test)]
+    pub fn is_cached(&self, did: &str) -> bool {
+        self.keys.lock().unwrap().contains_key(did)
+    }
+}
+
+/// The `#atproto` verification key of a DID document.
+fn signing_key(doc: &DidDocument, did: &str) -> Result<PublicKey> {
+    let method = doc
+        .verification_method
+        .iter()
+        .find(|method| method.id == "#atproto" || method.id == format!("{}#atproto", did))
+        .ok_or_else(|| anyhow!("No atproto signing key in the DID document"))?;
+    PublicKey::from_multibase(&method.public_key_multibase)
+}
+
+/// Network errors, server errors and rate limits may pass; anything else
+/// (an unknown DID, a malformed document) will not.
+fn is_transient(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<reqwest::Error>()
+        .is_some_and(|e| match e.status() {
+            Some(status) => {
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            None => !e.is_decode(),
+        })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use k256::ecdsa::SigningKey;
+
+    use super::*;
+    use crate::resolve::DidDocumentVerificationMethod;
+
+    pub(crate) fn test_signing_key() -> SigningKey {
+        SigningKey::from_bytes(&[7u8; 32].into()).unwrap()
+    }
+
+    pub(crate) fn multikey(key: &SigningKey) -> String {
+        let mut bytes = SECP256K1_PUB.to_vec();
+        bytes.extend_from_slice(&key.verifying_key().to_encoded_point(true).to_bytes());
+        multibase::encode(multibase::Base::Base58Btc, bytes)
+    }
+
+    /// A cache that already holds the test key for each of `dids`.
+    pub(crate) fn test_keys(dids: &[&str]) -> SigningKeys {
+        let keys = SigningKeys::default();
+        let key = PublicKey::from_multibase(&multikey(&test_signing_key())).unwrap();
+        for did in dids {
+            keys.insert(did, key.clone());
+        }
+        keys
+    }
+
+    #[test]
+    fn test_signing_key_from_did_document() {
+        let method = |id: &str| DidDocumentVerificationMethod {
+            id: id.to_string(),
+            _type: "Multikey".to_string(),
+            controller: "did:plc:test".to_string(),
+            public_key_multibase: multikey(&test_signing_key()),
+        };
+        let doc = |methods| DidDocument {
+            _context: Vec::new(),
+            id: "did:plc:test".to_string(),
+            also_known_as: Vec::new(),
+            verification_method: methods,
+            service: Vec::new(),
+        };
+
+        let expected = PublicKey::K256(*test_signing_key().verifying_key());
+        assert_eq!(
+            signing_key(&doc(vec![method("did:plc:test#atproto")]), "did:plc:test").unwrap(),
+            expected
+        );
+        assert_eq!(
+            signing_key(&doc(vec![method("#atproto")]), "did:plc:test").unwrap(),
+            expected
+        );
+        assert!(signing_key(&doc(vec![method("#other")]), "did:plc:test").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_drops_cached_key() {
+        let keys = test_keys(&["did:plc:test"]);
+        assert!(keys.get("did:plc:test").await.unwrap().is_some());
+
+        keys.invalidate("did:plc:test");
+        assert!(!keys.is_cached("did:plc:test"));
+    }
+}
@@ -0,0 +1,516 @@
+//! Ingestion from a relay's raw firehose (`com.atproto.sync.subscribeRepos`).
+//!
+//! Jetstream hands us JSON we have to take on trust. The firehose carries the
+//! repo blocks behind each signed commit, so every op is checked against them
+//! and the repo's signing key (see [`verify`] and [`keys`]) before it is
+//! passed, shaped like a Jetstream commit event, to the same
+//! [`LexiconIngestor`]s.
+//!
+//! The cursor only moves past a frame once it has been handled: a commit
+//! whose ops were ingested or dead-lettered, or an account event that was
+//! recorded. Anything else ends the connection, and the reconnect replays the
+//! frame.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use futures::{SinkExt, StreamExt};
+use rocketman::{ingestion::LexiconIngestor, types::event::Event};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{error, info, warn};
+
+use crate::{accounts, cursor::HandledCursor, identity};
+
+pub mod frame;
+pub mod keys;
+pub mod verify;
+
+use frame::{AccountFrame, CommitFrame, Frame, IdentityFrame};
+use keys::SigningKeys;
+use verify::VerifiedOp;
+
+pub type Ingestors = HashMap<String, Box<dyn LexiconIngestor + Send + Sync>>;
+
+const DEFAULT_RELAY_URL: &str = "wss://bsky.network";
+
+/// Where cadet reads repo events from, set with `INGEST_SOURCE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IngestSource {
+    Jetstream,
+    Firehose,
+}
+
+impl IngestSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IngestSource::Jetstream => "jetstream",
+            IngestSource::Firehose => "firehose",
+        }
+    }
+
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("INGEST_SOURCE").as_deref() {
+            Err(_) | Ok("") | Ok("jetstream") => Ok(IngestSource::Jetstream),
+            Ok("firehose") => Ok(IngestSource::Firehose),
+            Ok(other) => Err(anyhow!(
+                "Unknown INGEST_SOURCE {:?}, expected jetstream or firehose",
+                other
+            )),
+        }
+    }
+}
+
+pub fn relay_url() -> String {
+    std::env::var("FIREHOSE_RELAY_URL").unwrap_or_else(|_| DEFAULT_RELAY_URL.to_string())
+}
+
+/// Where the firehose's account and identity events go.
+#[async_trait]
+pub trait AccountEvents: Send + Sync {
+    async fn account(&self, event: &AccountFrame) -> Result<()>;
+    /// Handle lookups are slow, so identity events are applied in the
+    /// background, holding up the stream only while too many are running.
+    async fn identity(&self, event: &IdentityFrame);
+}
+
+#[async_trait]
+impl AccountEvents for PgPool {
+    async fn account(&self, event: &AccountFrame) -> Result<()> {
+        accounts::apply_account_event(self, &event.into()).await
+    }
+
+    async fn identity(&self, event: &IdentityFrame) {
+        identity::spawn_apply(self.clone(), event.into()).await;
+    }
+}
+
+/// Consume the firehose of `relay` until the process exits, reconnecting with
+/// backoff and resuming from the last sequence number in `cursor`. Handled
+/// frames are reported to `handled`, which moves `cursor`. Account and
+/// identity events are recorded in `sql`.
+pub async fn run(
+    relay: String,
+    ingestors: Ingestors,
+    sql: PgPool,
+    cursor: Arc<Mutex<Option<u64>>>,
+    handled: Arc<dyn HandledCursor>,
+) {
+    let mut retry_delay = Duration::from_secs(1);
+    let keys = SigningKeys::default();
+
+    loop {
+        let resume_from = *cursor.lock().unwrap();
+        match consume_connection(
+            &relay,
+            &ingestors,
+            &keys,
+            &sql,
+            handled.as_ref(),
+            resume_from,
+        )
+        .await
+        {
+            Ok(()) => {
+                retry_delay = Duration::from_secs(1);
+                info!("Firehose connection closed; reconnecting");
+            }
+            Err(e) => error!("Firehose consumer failed: {}", e),
+        }
+
+        tokio::time::sleep(retry_delay).await;
+        retry_delay = (retry_delay * 2).min(Duration::from_secs(120));
+    }
+}
+
+async fn consume_connection(
+    relay: &str,
+    ingestors: &Ingestors,
+    keys: &SigningKeys,
+    accounts: &dyn AccountEvents,
+    cursor: &dyn HandledCursor,
+    resume_from: Option<u64>,
+) -> Result<()> {
+    let url = subscribe_url(relay, resume_from)?;
+    info!("Connecting to firehose at {}", url);
+    let (socket, _) = connect_async(url).await?;
+
+    let (mut sink, mut read) = socket.split();
+    while let Some(message) = read.next().await {
+        let bytes = match message? {
+            Message::Binary(bytes) => bytes,
+            Message::Close(_) => return Ok(()),
+            Message::Ping(_) => {
+                sink.flush().await?;
+                continue;
+            }
+            _ => continue,
+        };
+
+        let frame = match frame::decode(&bytes) {
+            Ok(frame) => frame,
+            Err(e) => {
+                warn!("Failed to decode firehose frame: {}", e);
+                continue;
+            }
+        };
+        match &frame {
+            Frame::Commit(commit) => handle_commit(commit, ingestors, keys).await?,
+            Frame::Account(account) => accounts.account(account).await.with_context(|| {
+                format!(
+                    "Failed to record account event for {} (seq {})",
+                    account.did, account.seq
+                )
+            })?,
+            Frame::Identity(event) => {
+                // The identity change may be a key rotation
+                keys.invalidate(&event.did);
+                accounts.identity(event).await
+            }
+            Frame::Error { error, message } => {
+                return Err(anyhow!(
+                    "Relay sent error {}: {}",
+                    error,
+                    message.as_deref().unwrap_or("")
+                ));
+            }
+            Frame::Other { .. } => {}
+        }
+
+        if let Some(seq) = frame.seq() {
+            cursor.handled(seq).await;
+        }
+    }
+    Ok(())
+}
+
+fn subscribe_url(relay: &str, cursor: Option<u64>) -> Result<String> {
+    let mut url = url::Url::parse(relay).context("invalid FIREHOSE_RELAY_URL")?;
+    if !url.path().ends_with("com.atproto.sync.subscribeRepos") {
+        url.set_path("/xrpc/com.atproto.sync.subscribeRepos");
+    }
+    if let Some(cursor) = cursor {
+        url.query_pairs_mut()
+            .append_pair("cursor", &cursor.to_string());
+    }
+    Ok(url.to_string())
+}
+
+/// Verify a commit and hand its ops to the ingestors. Commits that fail
+/// verification are dropped whole. Fails if the repo's signing key could not
+/// be resolved for now, or if an op could be neither ingested nor
+/// dead-lettered, after the remaining ops have been tried.
+pub async fn handle_commit(
+    commit: &CommitFrame,
+    ingestors: &Ingestors,
+    keys: &SigningKeys,
+) -> Result<()> {
+    // Most of the network's commits touch nothing we index
+    let relevant = commit.ops.iter().any(|op| {
+        op.path
+            .split_once('/')
+            .is_some_and(|(collection, _)| ingestors.contains_key(collection))
+    });
+    if !relevant {
+        return Ok(());
+    }
+
+    let Some(key) = keys
+        .get(&commit.repo)
+        .await
+        .with_context(|| format!("Failed to resolve the signing key of {}", commit.repo))?
+    else {
+        warn!(
+            "Rejected firehose commit {} of {} (seq {}): no signing key",
+            commit.rev, commit.repo, commit.seq
+        );
+        return Ok(());
+    };
+    let ops = match verify::verify_commit(commit, &key, |c| ingestors.contains_key(c)).await {
+        Ok(ops) => ops,
+        Err(e) => {
+            warn!(
+                "Rejected firehose commit {} of {} (seq {}): {}",
+                commit.rev, commit.repo, commit.seq, e
+            );
+            return Ok(());
+        }
+    };
+
+    let mut unhandled = None;
+    for op in ops {
+        let Some(ingestor) = ingestors.get(&op.collection) else {
+            continue;
+        };
+        let event = match jetstream_event(commit, &op) {
+            Ok(event) => event,
+            Err(e) => {
+                error!(
+                    "Failed to build event for {}/{}: {}",
+                    op.collection, op.rkey, e
+                );
+                continue;
+            }
+        };
+        if let Err(e) = ingestor.ingest(event).await {
+            error!(
+                "Error ingesting {}/{} from {}: {}",
+                op.collection, op.rkey, commit.repo, e
+            );
+            unhandled.get_or_insert(e);
+        }
+    }
+
+    match unhandled {
+        Some(e) => Err(e.context(format!(
+            "Commit {} of {} (seq {}) was not handled",
+            commit.rev, commit.repo, commit.seq
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Shape a verified op like the Jetstream commit event the ingestors expect.
+fn jetstream_event(commit: &CommitFrame, op: &VerifiedOp) -> Result<Event<Value>> {
+    let mut event = json!({
+        "did": commit.repo,
+        "kind": "commit",
+        "commit": {
+            "rev": commit.rev,
+            "operation": op.action,
+            "collection": op.collection,
+            "rkey": op.rkey,
+            "record": op.record,
+            "cid": op.cid.map(|cid| cid.to_string()),
+        }
+    });
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(&commit.time) {
+        event["time_us"] = json!(time.timestamp_micros());
+    }
+    Ok(serde_json::from_value(event)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use async_trait::async_trait;
+    use ipld_core::ipld::Ipld;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::firehose::{keys::tests::test_keys, verify::tests::commit_frame};
+
+    /// Records the events it is given.
+    struct RecordingIngestor(Arc<Mutex<Vec<Event<Value>>>>);
+
+    #[async_trait]
+    impl LexiconIngestor for RecordingIngestor {
+        async fn ingest(&self, message: Event<Value>) -> Result<()> {
+            self.0.lock().unwrap().push(message);
+            Ok(())
+        }
+    }
+
+    /// Fails like a dead-letter wrapper that could not store the failure.
+    struct FailingIngestor;
+
+    #[async_trait]
+    impl LexiconIngestor for FailingIngestor {
+        async fn ingest(&self, _message: Event<Value>) -> Result<()> {
+            Err(anyhow!("database unavailable"))
+        }
+    }
+
+    /// Records the DIDs of account and identity events.
+    #[derive(Default)]
+    struct RecordingAccounts(Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl AccountEvents for RecordingAccounts {
+        async fn account(&self, event: &AccountFrame) -> Result<()> {
+            self.0.lock().unwrap().push(event.did.clone());
+            Ok(())
+        }
+
+        async fn identity(&self, event: &IdentityFrame) {
+            self.0.lock().unwrap().push(event.did.clone());
+        }
+    }
+
+    fn encode_frame(kind: &str, body: Ipld) -> Vec<u8> {
+        let header = Ipld::Map(BTreeMap::from([
+            ("op".to_string(), Ipld::Integer(1)),
+            ("t".to_string(), Ipld::String(kind.to_string())),
+        ]));
+        let mut bytes = serde_ipld_dagcbor::to_vec(&header).unwrap();
+        bytes.extend(serde_ipld_dagcbor::to_vec(&body).unwrap());
+        bytes
+    }
+
+    fn encode_commit(commit: &CommitFrame) -> Vec<u8> {
+        let ops = commit
+            .ops
+            .iter()
+            .map(|op| {
+                Ipld::Map(BTreeMap::from([
+                    ("action".to_string(), Ipld::String(op.action.clone())),
+                    ("path".to_string(), Ipld::String(op.path.clone())),
+                    (
+                        "cid".to_string(),
+                        op.cid.map(Ipld::Link).unwrap_or(Ipld::Null),
+                    ),
+                ]))
+            })
+            .collect();
+        encode_frame(
+            "#commit",
+            Ipld::Map(BTreeMap::from([
+                ("seq".to_string(), Ipld::Integer(commit.seq as i128)),
+                ("rebase".to_string(), Ipld::Bool(false)),
+                ("tooBig".to_string(), Ipld::Bool(commit.too_big)),
+                ("repo".to_string(), Ipld::String(commit.repo.clone())),
+                ("commit".to_string(), Ipld::Link(commit.commit)),
+                ("rev".to_string(), Ipld::String(commit.rev.clone())),
+                ("since".to_string(), Ipld::Null),
+                ("blocks".to_string(), Ipld::Bytes(commit.blocks.clone())),
+                ("ops".to_string(), Ipld::List(ops)),
+                ("blobs".to_string(), Ipld::List(vec![])),
+                ("time".to_string(), Ipld::String(commit.time.clone())),
+            ])),
+        )
+    }
+
+    /// Serve `frames` to the first subscriber, then close. Returns the relay
+    /// URL and the query string the subscriber connected with.
+    async fn fixture_relay(
+        frames: Vec<Vec<u8>>,
+    ) -> (String, tokio::sync::oneshot::Receiver<String>) {
+        use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (query_tx, query_rx) = tokio::sync::oneshot::channel();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let record_query =
+                move |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+                    let _ = query_tx.send(request.uri().query().unwrap_or("").to_string());
+                    Ok(response)
+                };
+            let mut socket = tokio_tungstenite::accept_hdr_async(stream, record_query)
+                .await
+                .unwrap();
+            for frame in frames {
+                socket.send(Message::Binary(frame.into())).await.unwrap();
+            }
+            // The subscriber may already have hung up
+            let _ = socket.close(None).await;
+        });
+
+        (format!("ws://{}", addr), query_rx)
+    }
+
+    #[tokio::test]
+    async fn test_replays_fixture_relay_into_ingestors() {
+        let good = commit_frame("did:plc:test", None).await;
+        let mut tampered = commit_frame("did:plc:other", None).await;
+        tampered.seq = 8;
+        tampered.ops[0].cid = Some(good.commit);
+        let identity = encode_frame(
+            "#identity",
+            Ipld::Map(BTreeMap::from([
+                ("seq".to_string(), Ipld::Integer(9)),
+                ("did".to_string(), Ipld::String("did:plc:test".to_string())),
+            ])),
+        );
+        let (relay, query) = fixture_relay(vec![
+            encode_commit(&good),
+            encode_commit(&tampered),
+            identity,
+        ])
+        .await;
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut ingestors: Ingestors = HashMap::new();
+        ingestors.insert(
+            "fm.teal.alpha.feed.play".to_string(),
+            Box::new(RecordingIngestor(received.clone())),
+        );
+        let cursor = Mutex::new(Some(6));
+        let accounts = RecordingAccounts::default();
+
+        let keys = test_keys(&["did:plc:test", "did:plc:other"]);
+        consume_connection(&relay, &ingestors, &keys, &accounts, &cursor, Some(6))
+            .await
+            .unwrap();
+
+        assert_eq!(query.await.unwrap(), "cursor=6");
+        // The tampered commit is dropped, but the cursor moves past it
+        assert_eq!(*cursor.lock().unwrap(), Some(9));
+        assert_eq!(*accounts.0.lock().unwrap(), ["did:plc:test"]);
+        // The identity event drops the cached key, the other one stays
+        assert!(keys.is_cached("did:plc:other"));
+        assert!(!keys.is_cached("did:plc:test"));
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].did, "did:plc:test");
+        let commit = received[0].commit.as_ref().unwrap();
+        assert_eq!(commit.rkey, "3lb2c4wcg3k2e");
+        assert_eq!(
+            commit.cid.as_deref(),
+            Some(good.ops[0].cid.unwrap().to_string().as_str())
+        );
+        assert_eq!(commit.record.as_ref().unwrap()["trackName"], "Windowlicker");
+    }
+
+    #[tokio::test]
+    async fn test_cursor_stays_before_unhandled_commit() {
+        let commit = commit_frame("did:plc:test", None).await;
+        let (relay, _query) = fixture_relay(vec![encode_commit(&commit)]).await;
+
+        let mut ingestors: Ingestors = HashMap::new();
+        ingestors.insert(
+            "fm.teal.alpha.feed.play".to_string(),
+            Box::new(FailingIngestor),
+        );
+        let cursor = Mutex::new(Some(6));
+
+        let result = consume_connection(
+            &relay,
+            &ingestors,
+            &test_keys(&["did:plc:test"]),
+            &RecordingAccounts::default(),
+            &cursor,
+            Some(6),
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(*cursor.lock().unwrap(), Some(6));
+    }
+
+    #[test]
+    fn test_subscribe_url() {
+        assert_eq!(
+            subscribe_url("wss://bsky.network", Some(42)).unwrap(),
+            "wss://bsky.network/xrpc/com.atproto.sync.subscribeRepos?cursor=42"
+        );
+        assert_eq!(
+            subscribe_url(
+                "wss://relay.example/xrpc/com.atproto.sync.subscribeRepos",
+                None
+            )
+            .unwrap(),
+            "wss://relay.example/xrpc/com.atproto.sync.subscribeRepos"
+        );
+    }
+}
@@ -0,0 +1,465 @@
+//! Checks a `#commit` frame's ops against the blocks it carries.
+//!
+//! Every block must hash to its CID, the commit block must belong to the
+//! frame's repo and carry a valid signature by the repo's signing key (see
+//! [`super::keys`]), and each created or updated record must sit at its path
+//! in the MST under the commit's `data` root with the CID the op claims.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+use base64::Engine;
+use ipld_core::{cid::Cid, ipld::Ipld};
+use iroh_car::CarReader;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use super::{frame::CommitFrame, keys::PublicKey};
+
+const SHA2_256_CODE: u64 = 0x12;
+
+/// A record op that matched the commit's blocks.
+#[derive(Debug, Clone)]
+pub struct VerifiedOp {
+    pub action: String,
+    pub collection: String,
+    pub rkey: String,
+    pub cid: Option<Cid>,
+    /// The record as atproto JSON; `None` for deletes
+    pub record: Option<Value>,
+}
+
+/// Verify the ops of `frame` in collections accepted by `wanted`, with `key`
+/// the repo's signing key. Any mismatch rejects the whole commit.
+pub async fn verify_commit(
+    frame: &CommitFrame,
+    key: &PublicKey,
+    wanted: impl Fn(&str) -> bool,
+) -> Result<Vec<VerifiedOp>> {
+    if frame.too_big {
+        bail!(
+            "Commit {} of {} is too big to carry its blocks",
+            frame.rev,
+            frame.repo
+        );
+    }
+
+    let blocks = read_blocks(&frame.blocks).await?;
+    let commit = match decode_block(&blocks, &frame.commit)? {
+        Ipld::Map(commit) => commit,
+        _ => bail!("Commit block {} is not a map", frame.commit),
+    };
+    match commit.get("did") {
+        Some(Ipld::String(did)) if *did == frame.repo => {}
+        _ => bail!("Commit {} does not belong to {}", frame.commit, frame.repo),
+    }
+    // The signature covers the commit's DAG-CBOR without the `sig` field
+    let mut unsigned = commit.clone();
+    let Some(Ipld::Bytes(signature)) = unsigned.remove("sig") else {
+        bail!("Commit {} is not signed", frame.commit);
+    };
+    key.verify(
+        &serde_ipld_dagcbor::to_vec(&Ipld::Map(unsigned))?,
+        &signature,
+    )
+    .map_err(|e| anyhow!("Commit {} has a bad signature: {}", frame.commit, e))?;
+    let data = match commit.get("data") {
+        Some(Ipld::Link(data)) => *data,
+        _ => bail!("Commit {} has no data root", frame.commit),
+    };
+
+    let mut verified = Vec::new();
+    for op in &frame.ops {
+        let (collection, rkey) = op
+            .path
+            .split_once('/')
+            .ok_or_else(|| anyhow!("Invalid op path {}", op.path))?;
+        if !wanted(collection) {
+            continue;
+        }
+
+        let record = match op.action.as_str() {
+            "create" | "update" => {
+                let cid = op
+                    .cid
+                    .ok_or_else(|| anyhow!("{} of {} has no CID", op.action, op.path))?;
+                match mst_lookup(&blocks, data, &op.path)? {
+                    Lookup::Found(found) if found == cid => {}
+                    Lookup::Found(found) => {
+                        bail!("{} is {} in the tree, not {}", op.path, found, cid)
+                    }
+                    Lookup::Absent => bail!("{} is not in the tree", op.path),
+                    Lookup::Incomplete => bail!("Blocks for {} are missing", op.path),
+                }
+                Some(ipld_to_json(&decode_block(&blocks, &cid)?))
+            }
+            "delete" => {
+                match mst_lookup(&blocks, data, &op.path)? {
+                    Lookup::Found(_) => bail!("Deleted {} is still in the tree", op.path),
+                    Lookup::Absent => {}
+                    // Relays may leave out the nodes proving an absence
+                    Lookup::Incomplete => debug!("Deletion of {} is not provable", op.path),
+                }
+                None
+            }
+            other => bail!("Unknown op action {}", other),
+        };
+
+        verified.push(VerifiedOp {
+            action: op.action.clone(),
+            collection: collection.to_string(),
+            rkey: rkey.to_string(),
+            cid: op.cid,
+            record,
+        });
+    }
+    Ok(verified)
+}
+
+/// Read a CAR slice, checking each block against its CID.
+async fn read_blocks(car: &[u8]) -> Result<HashMap<Cid, Vec<u8>>> {
+    let mut reader = CarReader::new(car).await?;
+    let mut blocks = HashMap::new();
+    while let Some((cid, bytes)) = reader.next_block().await? {
+        let hash = cid.hash();
+        if hash.code() != SHA2_256_CODE {
+            bail!("Block {} uses unsupported hash 0x{:x}", cid, hash.code());
+        }
+        if hash.digest() != Sha256::digest(&bytes).as_slice() {
+            bail!("Block {} does not match its CID", cid);
+        }
+        blocks.insert(cid, bytes);
+    }
+    Ok(blocks)
+}
+
+fn decode_block(blocks: &HashMap<Cid, Vec<u8>>, cid: &Cid) -> Result<Ipld> {
+    let bytes = blocks
+        .get(cid)
+        .ok_or_else(|| anyhow!("Block {} is missing", cid))?;
+    serde_ipld_dagcbor::from_slice(bytes).map_err(|e| anyhow!("Invalid block {}: {}", cid, e))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Lookup {
+    Found(Cid),
+    Absent,
+    /// A node on the path to the key is not in the slice
+    Incomplete,
+}
+
+/// Find `key` in the MST rooted at `root`. Nodes hold entries `{p, k, v, t}`
+/// sorted by key, where each key shares its first `p` bytes with the previous
+/// one; `l` is the subtree left of the first entry and `t` the one right of
+/// its entry.
+fn mst_lookup(blocks: &HashMap<Cid, Vec<u8>>, root: Cid, key: &str) -> Result<Lookup> {
+    let invalid = |cid: &Cid| anyhow!("Invalid MST node {}", cid);
+    let key = key.as_bytes();
+    let mut node_cid = Some(root);
+
+    while let Some(cid) = node_cid {
+        if !blocks.contains_key(&cid) {
+            return Ok(Lookup::Incomplete);
+        }
+        let Ipld::Map(node) = decode_block(blocks, &cid)? else {
+            return Err(invalid(&cid));
+        };
+        let Some(Ipld::List(entries)) = node.get("e") else {
+            return Err(invalid(&cid));
+        };
+
+        let mut next = link(node.get("l"));
+        let mut previous_key: Vec<u8> = Vec::new();
+        for entry in entries {
+            let Ipld::Map(entry) = entry else {
+                return Err(invalid(&cid));
+            };
+            let (Some(Ipld::Integer(prefix)), Some(Ipld::Bytes(suffix))) =
+                (entry.get("p"), entry.get("k"))
+            else {
+                return Err(invalid(&cid));
+            };
+            let prefix = usize::try_from(*prefix)
+                .ok()
+                .filter(|prefix| *prefix <= previous_key.len())
+                .ok_or_else(|| invalid(&cid))?;
+            let mut entry_key = previous_key[..prefix].to_vec();
+            entry_key.extend_from_slice(suffix);
+
+            match key.cmp(&entry_key[..]) {
+                std::cmp::Ordering::Equal => {
+                    return link(entry.get("v"))
+                        .map(Lookup::Found)
+                        .ok_or_else(|| invalid(&cid));
+                }
+                std::cmp::Ordering::Less => break,
+                std::cmp::Ordering::Greater => {
+                    next = link(entry.get("t"));
+                    previous_key = entry_key;
+                }
+            }
+        }
+        node_cid = next;
+    }
+    Ok(Lookup::Absent)
+}
+
+fn link(value: Option<&Ipld>) -> Option<Cid> {
+    match value {
+        Some(Ipld::Link(cid)) => Some(*cid),
+        _ => None,
+    }
+}
+
+/// Convert a record to atproto JSON, with links as `{"$link": cid}` and bytes
+/// as `{"$bytes": base64}`, the same shape Jetstream delivers.
+pub fn ipld_to_json(ipld: &Ipld) -> Value {
+    match ipld {
+        Ipld::Null => Value::Null,
+        Ipld::Bool(b) => Value::Bool(*b),
+        Ipld::Integer(i) => match i64::try_from(*i) {
+            Ok(i) => Value::Number(i.into()),
+            Err(_) => Value::String(i.to_string()),
+        },
+        Ipld::Float(f) => serde_json::Number::from_f64(*f)
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+        Ipld::String(s) => Value::String(s.clone()),
+        Ipld::Bytes(bytes) => serde_json::json!({
+            "$bytes": base64::engine::general_purpose::STANDARD_NO_PAD.encode(bytes)
+        }),
+        Ipld::List(items) => Value::Array(items.iter().map(ipld_to_json).collect()),
+        Ipld::Map(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), ipld_to_json(v)))
+                .collect(),
+        ),
+        Ipld::Link(cid) => serde_json::json!({ "$link": cid.to_string() }),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::BTreeMap;
+
+    use ipld_core::cid::multihash::Multihash;
+    use iroh_car::{CarHeader, CarWriter};
+    use k256::ecdsa::{signature::Signer, Signature, SigningKey};
+    use serde_json::json;
+
+    use super::*;
+    use crate::firehose::{
+        frame::RepoOp,
+        keys::tests::{test_keys, test_signing_key},
+    };
+
+    pub(crate) const PLAY_PATH: &str = "fm.teal.alpha.feed.play/3lb2c4wcg3k2e";
+
+    /// Blocks of a one-record repo plus the CIDs of interest.
+    pub(crate) struct TestRepo {
+        pub blocks: Vec<(Cid, Vec<u8>)>,
+        pub commit: Cid,
+        pub record: Cid,
+    }
+
+    fn put(blocks: &mut Vec<(Cid, Vec<u8>)>, node: &Ipld) -> Cid {
+        let bytes = serde_ipld_dagcbor::to_vec(node).unwrap();
+        let hash = Multihash::<64>::wrap(SHA2_256_CODE, &Sha256::digest(&bytes)).unwrap();
+        let cid = Cid::new_v1(0x71, hash);
+        blocks.push((cid, bytes));
+        cid
+    }
+
+    pub(crate) fn test_repo(did: &str, record: &Ipld) -> TestRepo {
+        test_repo_signed_by(did, record, &test_signing_key())
+    }
+
+    fn test_repo_signed_by(did: &str, record: &Ipld, key: &SigningKey) -> TestRepo {
+        let mut blocks = Vec::new();
+        let record = put(&mut blocks, record);
+        // A single key is alone in its layer, so the root is one leaf node
+        let data = put(
+            &mut blocks,
+            &Ipld::Map(BTreeMap::from([
+                ("l".to_string(), Ipld::Null),
+                (
+                    "e".to_string(),
+                    Ipld::List(vec![Ipld::Map(BTreeMap::from([
+                        ("p".to_string(), Ipld::Integer(0)),
+                        ("k".to_string(), Ipld::Bytes(PLAY_PATH.as_bytes().to_vec())),
+                        ("v".to_string(), Ipld::Link(record)),
+                        ("t".to_string(), Ipld::Null),
+                    ]))]),
+                ),
+            ])),
+        );
+        let mut commit = BTreeMap::from([
+            ("did".to_string(), Ipld::String(did.to_string())),
+            ("version".to_string(), Ipld::Integer(3)),
+            ("data".to_string(), Ipld::Link(data)),
+            ("rev".to_string(), Ipld::String("3lb2c4wcg3k2f".to_string())),
+            ("prev".to_string(), Ipld::Null),
+        ]);
+        let unsigned = serde_ipld_dagcbor::to_vec(&Ipld::Map(commit.clone())).unwrap();
+        let signature: Signature = key.sign(&unsigned);
+        commit.insert(
+            "sig".to_string(),
+            Ipld::Bytes(signature.to_bytes().to_vec()),
+        );
+        let commit = put(&mut blocks, &Ipld::Map(commit));
+        TestRepo {
+            blocks,
+            commit,
+            record,
+        }
+    }
+
+    pub(crate) async fn car_slice(repo: &TestRepo) -> Vec<u8> {
+        let mut writer = CarWriter::new(CarHeader::new_v1(vec![repo.commit]), Vec::new());
+        for (cid, bytes) in &repo.blocks {
+            writer.write(*cid, bytes).await.unwrap();
+        }
+        writer.finish().await.unwrap()
+    }
+
+    pub(crate) fn play_record() -> Ipld {
+        Ipld::Map(BTreeMap::from([
+            (
+                "$type".to_string(),
+                Ipld::String("fm.teal.alpha.feed.play".to_string()),
+            ),
+            (
+                "trackName".to_string(),
+                Ipld::String("Windowlicker".to_string()),
+            ),
+            (
+                "artists".to_string(),
+                Ipld::List(vec![Ipld::Map(BTreeMap::from([(
+                    "artistName".to_string(),
+                    Ipld::String("Aphex Twin".to_string()),
+                )]))]),
+            ),
+            (
+                "playedTime".to_string(),
+                Ipld::String("2023-04-01T12:00:00Z".to_string()),
+            ),
+        ]))
+    }
+
+    pub(crate) async fn commit_frame(did: &str, op_cid: Option<Cid>) -> CommitFrame {
+        let repo = test_repo(did, &play_record());
+        CommitFrame {
+            seq: 7,
+            repo: did.to_string(),
+            rev: "3lb2c4wcg3k2f".to_string(),
+            commit: repo.commit,
+            too_big: false,
+            blocks: car_slice(&repo).await,
+            ops: vec![RepoOp {
+                action: "create".to_string(),
+                path: PLAY_PATH.to_string(),
+                cid: Some(op_cid.unwrap_or(repo.record)),
+            }],
+            time: "2023-04-01T12:00:01Z".to_string(),
+        }
+    }
+
+    fn key() -> PublicKey {
+        PublicKey::K256(*test_signing_key().verifying_key())
+    }
+
+    #[tokio::test]
+    async fn test_verify_commit_returns_record_json() {
+        let frame = commit_frame("did:plc:test", None).await;
+        let ops = verify_commit(&frame, &key(), |_| true).await.unwrap();
+
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops[0].collection, "fm.teal.alpha.feed.play");
+        assert_eq!(ops[0].rkey, "3lb2c4wcg3k2e");
+        let record = ops[0].record.as_ref().unwrap();
+        assert_eq!(record["trackName"], "Windowlicker");
+
+        assert!(verify_commit(&frame, &key(), |_| false)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_verify_commit_rejects_bad_signature() {
+        let keys = test_keys(&["did:plc:test"]);
+        let key = keys.get("did:plc:test").await.unwrap().unwrap();
+        let mut frame = commit_frame("did:plc:test", None).await;
+        assert!(verify_commit(&frame, &key, |_| true).await.is_ok());
+
+        // The same repo, signed by a key that is not the repo's
+        let forger = SigningKey::from_bytes(&[8u8; 32].into()).unwrap();
+        let forged = test_repo_signed_by("did:plc:test", &play_record(), &forger);
+        assert_eq!(forged.record, frame.ops[0].cid.unwrap());
+        frame.commit = forged.commit;
+        frame.blocks = car_slice(&forged).await;
+        let error = verify_commit(&frame, &key, |_| true).await.unwrap_err();
+        assert!(error.to_string().contains("bad signature"));
+    }
+
+    #[tokio::test]
+    async fn test_verify_commit_rejects_mismatches() {
+        // The op claims a record the tree does not hold
+        let other = test_repo("did:plc:test", &Ipld::String("other".into())).record;
+        let frame = commit_frame("did:plc:test", Some(other)).await;
+        assert!(verify_commit(&frame, &key(), |_| true).await.is_err());
+
+        // The commit belongs to another repo
+        let mut frame = commit_frame("did:plc:test", None).await;
+        frame.repo = "did:plc:someone-else".to_string();
+        assert!(verify_commit(&frame, &key(), |_| true).await.is_err());
+
+        // A block was altered in transit
+        let mut frame = commit_frame("did:plc:test", None).await;
+        let position = frame
+            .blocks
+            .windows(b"Windowlicker".len())
+            .position(|window| window == b"Windowlicker")
+            .unwrap();
+        frame.blocks[position] = b'w';
+        assert!(verify_commit(&frame, &key(), |_| true).await.is_err());
+    }
+
+    #[test]
+    fn test_mst_lookup() {
+        let repo = test_repo("did:plc:test", &play_record());
+        let blocks: HashMap<Cid, Vec<u8>> = repo.blocks.iter().cloned().collect();
+        let Ipld::Map(commit) = decode_block(&blocks, &repo.commit).unwrap() else {
+            panic!("commit is a map")
+        };
+        let data = link(commit.get("data")).unwrap();
+
+        assert_eq!(
+            mst_lookup(&blocks, data, PLAY_PATH).unwrap(),
+            Lookup::Found(repo.record)
+        );
+        assert_eq!(
+            mst_lookup(&blocks, data, "fm.teal.alpha.feed.play/3aaaaaaaaaaaa").unwrap(),
+            Lookup::Absent
+        );
+        assert_eq!(
+            mst_lookup(&HashMap::new(), data, PLAY_PATH).unwrap(),
+            Lookup::Incomplete
+        );
+    }
+
+    #[test]
+    fn test_ipld_to_json_uses_atproto_links() {
+        let cid =
+            Cid::try_from("bafyreie5737gdxlw5i64vzichcalba3z2v5n6icifvx5xytvske7mr3hpm").unwrap();
+        let json = ipld_to_json(&Ipld::Map(BTreeMap::from([
+            ("ref".to_string(), Ipld::Link(cid)),
+            ("data".to_string(), Ipld::Bytes(b"hi".to_vec())),
+        ])));
+        assert_eq!(
+            json,
+            json!({ "ref": { "$link": cid.to_string() }, "data": { "$bytes": "aGk" } })
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Handle tracking: `#identity` events from Jetstream or the firehose.
+//!
+//! The handle in an event is only what the relay saw, so it is never stored
+//! as is. The DID document is fetched again and its handle is kept only if it
+//! resolves back to the same DID; a handle that fails either direction is
+//! cleared. A handle that can't be checked right now is left as it is. Only
+//! accounts with a profile are looked up.
+
+use std::sync::{Arc, OnceLock};
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use tokio::sync::Semaphore;
+use tracing::{error, info, warn};
+
+use crate::firehose::frame::IdentityFrame;
+use crate::resolve::{get_did_doc, verified_handle, HandleCheck};
+
+const DEFAULT_LOOKUP_CONCURRENCY: usize = 16;
+
+/// Identity events applied at once, from `IDENTITY_LOOKUP_CONCURRENCY`.
+fn lookup_permits() -> Arc<Semaphore> {
+    static PERMITS: OnceLock<Arc<Semaphore>> = OnceLock::new();
+    PERMITS
+        .get_or_init(|| {
+            let concurrency = std::env::var("IDENTITY_LOOKUP_CONCURRENCY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(DEFAULT_LOOKUP_CONCURRENCY);
+            Arc::new(Semaphore::new(concurrency))
+        })
+        .clone()
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct IdentityEvent {
+    pub did: String,
+    /// The handle the event claims; unverified
+    #[serde(default)]
+    pub handle: Option<String>,
+}
+
+impl IdentityEvent {
+    /// The identity event of a Jetstream message, if it is one.
+    pub fn from_jetstream(message: &str) -> Option<Self> {
+        let mut event: Value = serde_json::from_str(message).ok()?;
+        if event["kind"] != "identity" {
+            return None;
+        }
+        serde_json::from_value(event["identity"].take()).ok()
+    }
+}
+
+impl From<&IdentityFrame> for IdentityEvent {
+    fn from(frame: &IdentityFrame) -> Self {
+        Self {
+            did: frame.did.clone(),
+            handle: frame.handle.clone(),
+        }
+    }
+}
+
+/// Re-verify the handle of the event's account and store the result.
+pub async fn apply_identity_event(pool: &PgPool, event: &IdentityEvent) -> Result<()> {
+    let has_profile: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM profiles WHERE did = $1)")
+            .bind(&event.did)
+            .fetch_one(pool)
+            .await?;
+    if !has_profile {
+        return Ok(());
+    }
+
+    let doc = get_did_doc(&event.did).await?;
+    let check = verified_handle(&event.did, &doc).await;
+    if let HandleCheck::Verified(handle) = &check {
+        if event.handle.as_ref() != Some(handle) {
+            info!(
+                "Identity event for {} claims {:?}, verified handle is {}",
+                event.did, event.handle, handle
+            );
+        }
+    }
+    apply_handle_check(pool, &event.did, &check).await
+}
+
+/// Apply `event` in the background so handle lookups don't hold up the
+/// stream. Waits while `IDENTITY_LOOKUP_CONCURRENCY` lookups are running.
+pub async fn spawn_apply(pool: PgPool, event: IdentityEvent) {
+    let permit = lookup_permits()
+        .acquire_owned()
+        .await
+        .expect("identity lookup semaphore is never closed");
+    tokio::spawn(async move {
+        if let Err(e) = apply_identity_event(&pool, &event).await {
+            error!("Failed to update the handle of {}: {}", event.did, e);
+        }
+        drop(permit);
+    });
+}
+
+/// Store the outcome of a handle check: a verified handle is set, an invalid
+/// one cleared, and one that could not be checked is kept.
+pub async fn apply_handle_check(pool: &PgPool, did: &str, check: &HandleCheck) -> Result<()> {
+    match check {
+        HandleCheck::Verified(handle) => store_handle(pool, did, Some(handle)).await,
+        HandleCheck::Invalid => store_handle(pool, did, None).await,
+        HandleCheck::Unknown => {
+            warn!(
+                "Keeping the stored handle of {} until it can be checked",
+                did
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Set the verified handle of a profile. A handle belongs to one DID at a
+/// time, so any other profile still holding it loses it.
+pub async fn store_handle(pool: &PgPool, did: &str, handle: Option<&str>) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    if let Some(handle) = handle {
+        sqlx::query("UPDATE profiles SET handle = NULL WHERE handle = $1 AND did <> $2")
+            .bind(handle)
+            .bind(did)
+            .execute(&mut *tx)
+            .await?;
+    }
+    sqlx::query("UPDATE profiles SET handle = $2 WHERE did = $1 AND handle IS DISTINCT FROM $2")
+        .bind(did)
+        .bind(handle)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_event_from_jetstream() {
+        let event = IdentityEvent::from_jetstream(
+            r#"{"did":"did:plc:test","time_us":1725516665234703,"kind":"identity","identity":{"did":"did:plc:test","handle":"alice.example","seq":1409752997,"time":"2024-09-05T06:11:04.870Z"}}"#,
+        )
+        .expect("identity event");
+        assert_eq!(event.did, "did:plc:test");
+        assert_eq!(event.handle.as_deref(), Some("alice.example"));
+
+        let without_handle = IdentityEvent::from_jetstream(
+            r#"{"did":"did:plc:test","time_us":1,"kind":"identity","identity":{"did":"did:plc:test","seq":1,"time":"2024-09-05T06:11:04.870Z"}}"#,
+        )
+        .expect("identity event");
+        assert!(without_handle.handle.is_none());
+
+        assert!(IdentityEvent::from_jetstream(
+            r#"{"did":"did:plc:test","time_us":1,"kind":"account","account":{"active":true,"did":"did:plc:test","seq":1}}"#
+        )
+        .is_none());
+    }
+}
@@ -1,13 +1,13 @@
 //! CAR (Content Addressable aRchive) Import Ingestor using atmst
 //!
-//! This module handles importing Teal records from CAR files using the atmst library,
-//! which provides proper AT Protocol-style Merkle Search Tree handling. The CAR import process:
+//! This module handles importing Teal records from CAR files. The CAR import process:
 //!
 //! 1. Receives CAR data via the LexiconIngestor interface (base64 encoded or URL)
-//! 2. Uses atmst::CarImporter to parse the CAR file and extract MST structure
-//! 3. Converts the CarImporter to an MST for proper tree traversal
-//! 4. Iterates through MST nodes to find Teal record types (play, profile, status)
-//! 5. Delegates to existing Teal ingestors using the actual DID and proper rkey
+//! 2. Reads the spooled CAR block by block with [`repo_reader`], keeping only
+//!    the commit and MST nodes in memory
+//! 3. Walks the MST to find Teal record types (play, profile, status), then
+//!    reads just those records' blocks
+//! 4. Delegates to existing Teal ingestors using the actual DID and proper rkey
 //!
 //! ## Usage Example
 //!
@@ -30,19 +30,36 @@
 //! All imported records will be attributed to the DID that initiated the import
 //! and use the original rkey from the AT Protocol MST structure.
 
-use crate::ingestors::car::jobs::{queue_keys, CarImportJob};
-use crate::ingestors::teal::normalize_legacy_record_type;
-use crate::redis_client::RedisClient;
+use crate::ingestors::car::progress::ImportProgress;
+use crate::ingestors::car::repo_reader;
+use crate::ingestors::dead_letter::{clear_failure, clear_failures, record_failure, FailedRecord};
+use crate::ingestors::teal::consolidation::{ConsolidationReport, Merge};
+use crate::ingestors::teal::feed_play::{
+    clean, play_batch_size, uri_mbid_value, PendingPlay, PlayIngestor, AUTO_MATCH_CONFIDENCE,
+    CANDIDATE_MATCH_CONFIDENCE,
+};
+use crate::ingestors::teal::validate::{self, Lexicons};
+use crate::ingestors::teal::{assemble_at_uri, normalize_legacy_record_type};
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use atmst::{mst::Mst, Bytes, CarImporter};
+use atmst::Bytes;
 use base64::Engine;
+use chrono::DateTime;
+use common::car_spool::{max_car_size_bytes, SpoolError, SpooledCar};
+use common::import_preview::{
+    EntityMatchPreview, ExistingPlays, ImportPreview, MatchOutcome, ParseFailure, PlayPreview,
+};
+use common::job_queue::JobQueue;
+use common::jobs::{queue_keys, CarImportJob};
 use futures::StreamExt;
 use jacquard_common::types::value;
-use redis::AsyncCommands;
 use rocketman::{ingestion::LexiconIngestor, types::event::Event};
 use serde_json::Value;
 use sqlx::PgPool;
+use std::collections::{BTreeMap, HashSet};
+use std::io::Cursor;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncSeek};
 use tracing::{info, warn};
 
 /// Helper struct for extracted records
@@ -50,6 +67,8 @@ use tracing::{info, warn};
 pub struct ExtractedRecord {
     pub collection: String,
     pub rkey: String,
+    /// CID of the record in the repository
+    pub cid: String,
     pub data: serde_json::Value,
 }
 
@@ -133,68 +152,47 @@ fn deduplicate_records(records: Vec<ExtractedRecord>) -> Vec<ExtractedRecord> {
 /// CAR Import Ingestor handles importing Teal records from CAR files using atmst
 pub struct CarImportIngestor {
     sql: PgPool,
+    /// Queue that `importRepo` events are turned into jobs on
+    job_queue: Option<Arc<dyn JobQueue>>,
 }
 
 impl CarImportIngestor {
     /// Create a new CAR import ingestor with database connection
     pub fn new(sql: PgPool) -> Self {
-        Self { sql }
+        Self {
+            sql,
+            job_queue: None,
+        }
     }
 
-    /// Helper to get a Redis connection for job queueing
-    pub async fn get_redis_connection(&self) -> Result<redis::aio::MultiplexedConnection> {
-        let redis_url =
-            std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
-        let client = RedisClient::new(&redis_url)?;
-        client
-            .get_connection()
-            .await
-            .map_err(|e| anyhow!("Redis connection error: {}", e))
+    /// Queue import jobs for `importRepo` events on `job_queue`
+    pub fn with_job_queue(mut self, job_queue: Arc<dyn JobQueue>) -> Self {
+        self.job_queue = Some(job_queue);
+        self
     }
 
-    /// Process CAR file data using atmst library and extract Teal records
-    async fn process_car_data(&self, car_data: &[u8], import_id: &str, did: &str) -> Result<()> {
+    /// Process a CAR of `car_len` bytes and import its Teal records
+    async fn process_car_data<R>(
+        &self,
+        car: &mut R,
+        car_len: u64,
+        import_id: &str,
+        did: &str,
+        progress: &mut ImportProgress<'_>,
+    ) -> Result<()>
+    where
+        R: AsyncRead + AsyncSeek + Unpin + Send,
+    {
         info!(
-            "Starting CAR file processing with atmst for import {} (DID: {})",
+            "Starting CAR file processing for import {} (DID: {})",
             import_id, did
         );
-
-        // Convert to Bytes for atmst
-        let car_bytes: Bytes = Bytes::from(car_data.to_vec());
-
-        // Create CarImporter and import the CAR data
-        let mut car_importer = CarImporter::new();
-        car_importer
-            .import_from_bytes(car_bytes.clone())
-            .await
-            .map_err(|e| anyhow!("Failed to import CAR with atmst: {}", e))?;
-
-        info!(
-            "CAR imported successfully. Root CIDs: {:?}, Total blocks: {}",
-            car_importer.roots(),
-            car_importer.len()
-        );
-
-        // Convert CarImporter to MST for proper tree traversal
-        let mst = Mst::from_car_importer(car_importer)
-            .await
-            .map_err(|e| anyhow!("Failed to convert CAR to MST: {}", e))?;
-
-        info!("MST conversion successful, starting record extraction");
-
-        // Create a new CarImporter for data access since the previous one was consumed
-        let mut data_importer = CarImporter::new();
-        data_importer
-            .import_from_bytes(car_bytes)
-            .await
-            .map_err(|e| anyhow!("Failed to re-import CAR for data access: {}", e))?;
-
-        // Extract all records from the MST
-        let records = self
-            .extract_records_from_mst(&mst, &data_importer, did)
-            .await?;
+        let records = self.load_records(car, car_len, progress).await?;
         let extracted_count = records.len();
         let records = deduplicate_records(records);
+        progress
+            .records_skipped((extracted_count - records.len()) as u64)
+            .await;
 
         info!(
             "Extracted {} records from MST ({} after namespace deduplication)",
@@ -202,23 +200,100 @@ impl CarImportIngestor {
             records.len()
         );
 
-        // Process each record through the appropriate ingestor
+        // Plays are written in batches, other records one at a time through
+        // the appropriate ingestor
+        progress.step("Inserting records").await;
+        let play_ingestor = PlayIngestor::new(self.sql.clone());
+        let batch_size = play_batch_size();
+        let mut pending_plays = Vec::with_capacity(batch_size);
+        let mut pending_data = Vec::with_capacity(batch_size);
         let mut processed_count = 0;
         for record in records {
+            if !validate::accept_record(
+                &self.sql,
+                did,
+                &record.collection,
+                &record.rkey,
+                Some(&record.cid),
+                &record.data,
+            )
+            .await
+            {
+                progress.records_skipped(1).await;
+                continue;
+            }
+
+            if stable_collection_for(&record.collection) == Some(STABLE_PLAY_COLLECTION) {
+                let data = normalize_legacy_record_type(&record.data);
+                match value::from_json_value::<types::fm_teal::feed::play::Play>(data) {
+                    Ok(play_record) => {
+                        pending_plays.push(PendingPlay {
+                            record: play_record,
+                            uri: assemble_at_uri(did, STABLE_PLAY_COLLECTION, &record.rkey),
+                            cid: record.cid.clone(),
+                            did: did.to_string(),
+                            rkey: record.rkey.clone(),
+                        });
+                        pending_data.push(record.data);
+                    }
+                    Err(e) => {
+                        warn!("Failed to parse play record {}: {}", record.rkey, e);
+                        self.dead_letter(
+                            did,
+                            &record.collection,
+                            &record.rkey,
+                            &record.cid,
+                            &record.data,
+                            &e.to_string(),
+                        )
+                        .await;
+                        progress.record_failed().await;
+                    }
+                }
+                if pending_plays.len() >= batch_size {
+                    processed_count += self
+                        .write_plays(&play_ingestor, &pending_plays, &pending_data, progress)
+                        .await;
+                    pending_plays.clear();
+                    pending_data.clear();
+                }
+                continue;
+            }
+
             match self.process_extracted_record(&record, import_id, did).await {
                 Ok(()) => {
+                    if let Err(e) =
+                        clear_failure(&self.sql, did, &record.collection, &record.rkey).await
+                    {
+                        warn!("Failed to clear dead letter of {}: {}", record.rkey, e);
+                    }
                     processed_count += 1;
+                    progress.record_inserted().await;
                     if processed_count % 10 == 0 {
                         info!("Processed {} records so far", processed_count);
                     }
                 }
                 Err(e) => {
                     warn!("Failed to process record {}: {}", record.rkey, e);
+                    self.dead_letter(
+                        did,
+                        &record.collection,
+                        &record.rkey,
+                        &record.cid,
+                        &record.data,
+                        &e.to_string(),
+                    )
+                    .await;
+                    progress.record_failed().await;
                     // Continue processing other records
                 }
             }
         }
 
+        processed_count += self
+            .write_plays(&play_ingestor, &pending_plays, &pending_data, progress)
+            .await;
+
         info!(
             "Completed CAR file processing: {} records processed for import {}",
             processed_count, import_id
@@ -227,92 +302,306 @@ impl CarImportIngestor {
         Ok(())
     }
 
-    /// Extract all Teal records from the MST
-    async fn extract_records_from_mst(
+    /// Write a batch of plays, recording the outcome of each. `data` holds the
+    /// raw record of each play, kept for plays that fail. Returns how many
+    /// were stored.
+    async fn write_plays(
         &self,
-        mst: &Mst,
-        car_importer: &CarImporter,
-        _did: &str,
-    ) -> Result<Vec<ExtractedRecord>> {
-        let mut records = Vec::new();
-
-        // Use the MST iterator to traverse all entries
-        let mut stream = mst.iter().into_stream();
-
-        while let Some(result) = stream.next().await {
+        play_ingestor: &PlayIngestor,
+        plays: &[PendingPlay],
+        data: &[Value],
+        progress: &mut ImportProgress<'_>,
+    ) -> usize {
+        let mut stored = Vec::new();
+        let results = play_ingestor.write_batch(plays).await;
+        for ((play, result), data) in plays.iter().zip(results).zip(data) {
             match result {
-                Ok((key, record_cid)) => {
-                    // Check if this is a Teal record based on the key pattern
-                    if is_teal_record_key(&key) {
-                        info!("🎵 Found Teal record: {} -> {}", key, record_cid);
-                        if let Some((collection, rkey)) = parse_teal_key(&key) {
-                            info!("   Collection: {}, rkey: {}", collection, rkey);
-                            // Get the actual record data using the CID
-                            match self.get_record_data(&record_cid, car_importer).await {
-                                Ok(Some(data)) => {
-                                    info!("   ✅ Successfully got record data for {}", record_cid);
-                                    records.push(ExtractedRecord {
-                                        collection,
-                                        rkey,
-                                        data,
-                                    });
-                                }
-                                Ok(None) => {
-                                    warn!("   ❌ No data found for record CID: {}", record_cid);
-                                }
-                                Err(e) => {
-                                    warn!(
-                                        "   ❌ Failed to get record data for {}: {}",
-                                        record_cid, e
-                                    );
-                                }
-                            }
-                        } else {
-                            warn!("   ❌ Failed to parse Teal key: {}", key);
-                        }
-                    }
+                Ok(()) => {
+                    stored.push((play.did.as_str(), play.rkey.as_str()));
+                    progress.record_inserted().await;
                 }
                 Err(e) => {
-                    warn!("Error iterating MST: {}", e);
-                    // Continue with other entries
+                    warn!("Failed to store play record {}: {}", play.rkey, e);
+                    self.dead_letter(
+                        &play.did,
+                        STABLE_PLAY_COLLECTION,
+                        &play.rkey,
+                        &play.cid,
+                        data,
+                        &e.to_string(),
+                    )
+                    .await;
+                    progress.record_failed().await;
                 }
             }
         }
+        if !stored.is_empty() {
+            info!("Stored a batch of {} play records", stored.len());
+        }
+        if let Err(e) = clear_failures(&self.sql, STABLE_PLAY_COLLECTION, &stored).await {
+            warn!("Failed to clear dead letters of stored plays: {}", e);
+        }
+        stored.len()
+    }
 
-        Ok(records)
+    /// Keep a record that could not be imported for `cadet retry-failed`.
+    async fn dead_letter(
+        &self,
+        did: &str,
+        collection: &str,
+        rkey: &str,
+        cid: &str,
+        data: &Value,
+        error: &str,
+    ) {
+        let failed = FailedRecord {
+            did: did.to_string(),
+            collection: collection.to_string(),
+            rkey: rkey.to_string(),
+            rev: String::new(),
+            cid: Some(cid.to_string()),
+            record: Some(data.clone()),
+        };
+        if let Err(e) = record_failure(&self.sql, &failed, error).await {
+            warn!("Failed to dead-letter record {}: {}", rkey, e);
+        }
     }
 
-    /// Get record data from the CAR importer using a CID
-    async fn get_record_data(
+    /// Build a dry-run report of what importing CAR data would do, without writing anything
+    pub async fn preview_car_data<R>(
         &self,
-        cid: &atmst::Cid,
-        car_importer: &CarImporter,
-    ) -> Result<Option<Value>> {
-        // Try to decode the block as CBOR IPLD directly with atmst::Cid
-        info!("🔍 Attempting to decode CBOR for CID: {}", cid);
-        match car_importer.decode_cbor(cid) {
-            Ok(ipld) => {
-                info!("   ✅ Successfully decoded CBOR for CID: {}", cid);
-                // Convert IPLD to JSON for processing by existing ingestors
-                match self.ipld_to_json(&ipld) {
-                    Ok(json) => {
-                        info!("   ✅ Successfully converted IPLD to JSON for CID: {}", cid);
-                        Ok(Some(json))
-                    }
-                    Err(e) => {
-                        warn!(
-                            "   ❌ Failed to convert IPLD to JSON for CID {}: {}",
-                            cid, e
-                        );
-                        Ok(None)
-                    }
+        car: &mut R,
+        car_len: u64,
+        did: &str,
+        progress: &mut ImportProgress<'_>,
+    ) -> Result<ImportPreview>
+    where
+        R: AsyncRead + AsyncSeek + Unpin + Send,
+    {
+        info!("Previewing CAR import for DID: {}", did);
+        let records = self.load_records(car, car_len, progress).await?;
+
+        let mut preview = ImportPreview {
+            did: did.to_string(),
+            ..Default::default()
+        };
+        for record in &records {
+            *preview
+                .records_per_collection
+                .entry(record.collection.clone())
+                .or_insert(0) += 1;
+        }
+        let extracted_count = records.len();
+        let records = deduplicate_records(records);
+        preview.namespace_duplicates = (extracted_count - records.len()) as u64;
+
+        progress.step("Comparing with existing plays").await;
+        let existing = ExistingPlays::load(&self.sql, did).await?;
+        let mut artists: BTreeMap<(String, Option<String>), u64> = BTreeMap::new();
+        let mut releases: BTreeMap<(String, Option<String>), u64> = BTreeMap::new();
+
+        let mut plays = Vec::new();
+        for record in &records {
+            if let Err(violation) =
+                Lexicons::bundled().validate_record(&record.collection, &record.data)
+            {
+                preview.parse_failures.push(ParseFailure {
+                    collection: record.collection.clone(),
+                    rkey: record.rkey.clone(),
+                    error: violation.to_string(),
+                });
+                continue;
+            }
+
+            let data = normalize_legacy_record_type(&record.data);
+            let parsed = match stable_collection_for(&record.collection) {
+                Some(STABLE_PLAY_COLLECTION) => {
+                    value::from_json_value::<types::fm_teal::feed::play::Play>(data)
+                        .map(|play| Some(clean(&play)))
+                        .map_err(|e| e.to_string())
                 }
+                Some(STABLE_PROFILE_COLLECTION) => {
+                    value::from_json_value::<types::fm_teal::actor::profile::Profile>(data)
+                        .map(|_| None)
+                        .map_err(|e| e.to_string())
+                }
+                Some(STABLE_STATUS_COLLECTION) => {
+                    value::from_json_value::<types::fm_teal::actor::status::Status>(data)
+                        .map(|_| None)
+                        .map_err(|e| e.to_string())
+                }
+                _ => Ok(None),
+            };
+
+            let play = match parsed {
+                Ok(Some(play)) => play,
+                Ok(None) => continue,
+                Err(error) => {
+                    preview.parse_failures.push(ParseFailure {
+                        collection: record.collection.clone(),
+                        rkey: record.rkey.clone(),
+                        error,
+                    });
+                    continue;
+                }
+            };
+            plays.push((record, play));
+        }
+
+        // Split credits the way ingest would, keeping MusicBrainz names whole
+        let known =
+            PlayIngestor::known_whole_names_of(&self.sql, plays.iter().map(|(_, play)| play))
+                .await?;
+        for (record, play) in plays {
+            let credits = PlayIngestor::artist_credits(&play, &known);
+            for credit in &credits {
+                *artists.entry(credit.clone()).or_insert(0) += 1;
             }
-            Err(e) => {
-                warn!("   ❌ Failed to decode CBOR for CID {}: {}", cid, e);
-                Ok(None)
+            if let Some(release_name) = &play.release_name {
+                let release_mbid = play
+                    .release_mb_id
+                    .as_ref()
+                    .map(|mbid| uri_mbid_value(mbid).to_string());
+                *releases
+                    .entry((release_name.to_string(), release_mbid))
+                    .or_insert(0) += 1;
+            }
+
+            let uri = assemble_at_uri(did, STABLE_PLAY_COLLECTION, &record.rkey);
+            let played_time = play
+                .played_time
+                .as_ref()
+                .and_then(|t| DateTime::from_timestamp(t.as_ref().timestamp(), 0));
+            let (outcome, existing_uri) = existing.classify(&uri, &play.track_name, played_time);
+            preview.push_play(PlayPreview {
+                rkey: record.rkey.clone(),
+                track_name: play.track_name.to_string(),
+                artist_names: credits.into_iter().map(|(name, _)| name).collect(),
+                release_name: play.release_name.as_ref().map(ToString::to_string),
+                played_time,
+                outcome,
+                existing_uri,
+            });
+        }
+
+        progress.step("Matching artists and releases").await;
+        let play_ingestor = PlayIngestor::new(self.sql.clone());
+        for ((name, mbid), plays) in artists {
+            if mbid.is_some() {
+                preview.artists.push(EntityMatchPreview {
+                    name,
+                    outcome: MatchOutcome::Mbid,
+                    mbid,
+                    matched_name: None,
+                    confidence: None,
+                    plays,
+                });
+                continue;
+            }
+
+            let best = play_ingestor
+                .find_fuzzy_artist_matches(&name, "", None)
+                .await?
+                .into_iter()
+                .next();
+            let outcome = match &best {
+                Some(m) if m.confidence >= AUTO_MATCH_CONFIDENCE => MatchOutcome::Matched,
+                Some(m) if m.confidence >= CANDIDATE_MATCH_CONFIDENCE => MatchOutcome::Candidate,
+                _ => MatchOutcome::New,
+            };
+            preview.artists.push(EntityMatchPreview {
+                name,
+                outcome,
+                mbid: None,
+                matched_name: best.as_ref().map(|m| m.name.clone()),
+                confidence: best.as_ref().map(|m| m.confidence),
+                plays,
+            });
+        }
+
+        for ((name, mbid), plays) in releases {
+            let existing_name = match mbid.as_deref().map(uuid::Uuid::parse_str) {
+                Some(Ok(release_mbid)) => {
+                    sqlx::query_scalar::<_, String>("SELECT name FROM releases WHERE mbid = $1")
+                        .bind(release_mbid)
+                        .fetch_optional(&self.sql)
+                        .await?
+                }
+                _ => None,
+            };
+            let outcome = match (&mbid, &existing_name) {
+                (Some(_), Some(_)) => MatchOutcome::Matched,
+                (Some(_), None) => MatchOutcome::Mbid,
+                (None, _) => MatchOutcome::Unlinked,
+            };
+            preview.releases.push(EntityMatchPreview {
+                name,
+                outcome,
+                mbid,
+                matched_name: existing_name,
+                confidence: None,
+                plays,
+            });
+        }
+
+        info!(
+            "CAR import preview for {}: {} new, {} updated, {} duplicate plays, {} parse failures",
+            did,
+            preview.play_summary.new,
+            preview.play_summary.updated,
+            preview.play_summary.duplicate,
+            preview.parse_failures.len()
+        );
+
+        Ok(preview)
+    }
+
+    /// Read every Teal record from a CAR, before namespace deduplication. The
+    /// CAR is read from `car` block by block rather than loaded into memory.
+    async fn load_records<R>(
+        &self,
+        car: &mut R,
+        car_len: u64,
+        progress: &mut ImportProgress<'_>,
+    ) -> Result<Vec<ExtractedRecord>>
+    where
+        R: AsyncRead + AsyncSeek + Unpin + Send,
+    {
+        progress.set_car_size(car_len);
+        progress.step("Parsing CAR blocks").await;
+        let repo = repo_reader::read_records(car, is_teal_record_key).await?;
+        info!(
+            "Read CAR: {} blocks, {} Teal records",
+            repo.blocks,
+            repo.records.len()
+        );
+        progress.blocks_parsed(repo.blocks).await;
+
+        progress.step("Extracting Teal records").await;
+        let mut records = Vec::with_capacity(repo.records.len());
+        for record in repo.records {
+            let Some((collection, rkey)) = parse_teal_key(&record.key) else {
+                warn!("Failed to parse Teal key: {}", record.key);
+                continue;
+            };
+            let data = match self.ipld_to_json(&record.record) {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("Failed to convert record {} to JSON: {}", record.key, e);
+                    continue;
+                }
+            };
+            if let Some(stable) = stable_collection_for(&collection) {
+                progress.record_extracted(stable).await;
             }
+            records.push(ExtractedRecord {
+                collection,
+                rkey,
+                cid: record.cid.to_string(),
+                data,
+            });
         }
+        Ok(records)
     }
 
     /// Process a single extracted record through the appropriate ingestor
@@ -335,7 +624,7 @@ impl CarImportIngestor {
             Some(STABLE_PLAY_COLLECTION) => {
                 info!("   📀 Processing play record...");
                 let result = self
-                    .process_play_record(&record.data, did, &record.rkey)
+                    .process_play_record(&record.data, did, &record.rkey, &record.cid)
                     .await;
                 if result.is_ok() {
                     info!("   ✅ Successfully processed play record");
@@ -359,7 +648,7 @@ impl CarImportIngestor {
             Some(STABLE_STATUS_COLLECTION) => {
                 info!("   📢 Processing status record...");
                 let result = self
-                    .process_status_record(&record.data, did, &record.rkey)
+                    .process_status_record(&record.data, did, &record.rkey, &record.cid)
                     .await;
                 if result.is_ok() {
                     info!("   ✅ Successfully processed status record");
@@ -376,7 +665,13 @@ impl CarImportIngestor {
     }
 
     /// Process a play record using the existing PlayIngestor
-    async fn process_play_record(&self, data: &Value, did: &str, rkey: &str) -> Result<()> {
+    async fn process_play_record(
+        &self,
+        data: &Value,
+        did: &str,
+        rkey: &str,
+        cid: &str,
+    ) -> Result<()> {
         let data = normalize_legacy_record_type(data);
         let play_record: types::fm_teal::feed::play::Play =
             value::from_json_value::<types::fm_teal::feed::play::Play>(data)?;
@@ -385,13 +680,7 @@ impl CarImportIngestor {
         let uri = super::super::teal::assemble_at_uri(did, STABLE_PLAY_COLLECTION, rkey);
 
         play_ingestor
-            .insert_play(
-                &play_record,
-                &uri,
-                &format!("car-import-{}", uuid::Uuid::new_v4()),
-                did,
-                rkey,
-            )
+            .insert_play(&play_record, &uri, cid, did, rkey)
             .await?;
 
         info!(
@@ -422,7 +711,13 @@ impl CarImportIngestor {
     }
 
     /// Process a status record using the existing ActorStatusIngestor
-    async fn process_status_record(&self, data: &Value, did: &str, rkey: &str) -> Result<()> {
+    async fn process_status_record(
+        &self,
+        data: &Value,
+        did: &str,
+        rkey: &str,
+        cid: &str,
+    ) -> Result<()> {
         let data = normalize_legacy_record_type(data);
         let status_record: types::fm_teal::actor::status::Status =
             value::from_json_value::<types::fm_teal::actor::status::Status>(data)?;
@@ -431,12 +726,7 @@ impl CarImportIngestor {
             super::super::teal::actor_status::ActorStatusIngestor::new(self.sql.clone());
 
         status_ingestor
-            .insert_status(
-                did,
-                rkey,
-                &format!("car-import-{}", uuid::Uuid::new_v4()),
-                &status_record,
-            )
+            .insert_status(did, rkey, cid, &status_record)
             .await?;
 
         info!("Successfully stored status record from CAR import");
@@ -445,7 +735,71 @@ impl CarImportIngestor {
 
     /// Fetch and process a CAR file from a PDS for a given identity
     pub async fn fetch_and_process_identity_car(&self, handle_or_did: &str) -> Result<String> {
+        self.fetch_and_process_identity_car_with_progress(
+            handle_or_did,
+            &mut ImportProgress::detached(),
+        )
+        .await
+    }
+
+    /// Fetch and process a CAR file, reporting each stage to `progress`
+    pub async fn fetch_and_process_identity_car_with_progress(
+        &self,
+        handle_or_did: &str,
+        progress: &mut ImportProgress<'_>,
+    ) -> Result<String> {
+        let (did, mut car_file) = self.fetch_identity_car(handle_or_did, progress).await?;
+
+        // Generate import ID
+        let import_id = uuid::Uuid::new_v4().to_string();
+
+        // Process the CAR data
+        let car_len = car_file.len();
+        self.process_car_data(
+            car_file.reader().await?,
+            car_len,
+            &import_id,
+            &did,
+            progress,
+        )
+        .await?;
+
+        Ok(import_id)
+    }
+
+    /// Fetch a CAR file and preview the import without writing anything
+    pub async fn fetch_and_preview_identity_car_with_progress(
+        &self,
+        handle_or_did: &str,
+        progress: &mut ImportProgress<'_>,
+    ) -> Result<ImportPreview> {
+        let (did, mut car_file) = self.fetch_identity_car(handle_or_did, progress).await?;
+        let car_len = car_file.len();
+        self.preview_car_data(car_file.reader().await?, car_len, &did, progress)
+            .await
+    }
+
+    /// Preview the import of a CAR that aqua kept in the spool directory
+    pub async fn preview_spooled_car_with_progress(
+        &self,
+        did: &str,
+        spool_file: &str,
+        progress: &mut ImportProgress<'_>,
+    ) -> Result<ImportPreview> {
+        let mut car_file = SpooledCar::open_kept(spool_file, max_car_size_bytes()).await?;
+        let car_len = car_file.len();
+        self.preview_car_data(car_file.reader().await?, car_len, did, progress)
+            .await
+    }
+
+    /// Resolve an identity and download its repository CAR from the PDS
+    async fn fetch_identity_car(
+        &self,
+        handle_or_did: &str,
+        progress: &mut ImportProgress<'_>,
+    ) -> Result<(String, SpooledCar)> {
         info!("Fetching CAR file for identity: {}", handle_or_did);
+        progress.step("Resolving identity").await;
 
         // Resolve to DID if needed
         let did = if handle_or_did.starts_with("did:") {
@@ -457,17 +811,13 @@ impl CarImportIngestor {
         // Resolve DID to PDS
         let pds_url = self.resolve_did_to_pds(&did).await?;
         info!("Resolved {} to PDS: {}", did, pds_url);
+        progress.resolved(&did, &pds_url).await;
 
         // Fetch CAR file
-        let car_data = self.fetch_car_from_pds(&pds_url, &did).await?;
+        progress.step("Downloading CAR from PDS").await;
+        let car_file = self.fetch_car_from_pds(&pds_url, &did, progress).await?;
 
-        // Generate import ID
-        let import_id = uuid::Uuid::new_v4().to_string();
-
-        // Process the CAR data
-        self.process_car_data(&car_data, &import_id, &did).await?;
-
-        Ok(import_id)
+        Ok((did, car_file))
     }
 
     /// Resolve handle to DID
@@ -502,8 +852,13 @@ impl CarImportIngestor {
         Err(anyhow!("Could not resolve PDS for DID: {}", did))
     }
 
-    /// Fetch CAR file from PDS
-    async fn fetch_car_from_pds(&self, pds_url: &str, did: &str) -> Result<Vec<u8>> {
+    /// Fetch CAR file from PDS, spooling it to disk
+    async fn fetch_car_from_pds(
+        &self,
+        pds_url: &str,
+        did: &str,
+        progress: &mut ImportProgress<'_>,
+    ) -> Result<SpooledCar> {
         let url = format!("{}/xrpc/com.atproto.sync.getRepo?did={}", pds_url, did);
         let response = reqwest::get(&url).await?;
 
@@ -514,16 +869,27 @@ impl CarImportIngestor {
             ));
         }
 
-        let car_data = response.bytes().await?.to_vec();
-        info!("Fetched CAR file: {} bytes", car_data.len());
+        let mut car_file = SpooledCar::new(max_car_size_bytes())?;
+        car_file.check_declared_len(response.content_length())?;
+        if let Some(length) = response.content_length() {
+            progress.set_car_size(length);
+        }
+
+        let mut chunks = response.bytes_stream();
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(|e| SpoolError::Source(e.into()))?;
+            car_file.write_chunk(&chunk).await?;
+            progress.downloaded(chunk.len() as u64).await;
+        }
+        info!("Fetched CAR file: {} bytes", car_file.len());
 
-        Ok(car_data)
+        Ok(car_file)
     }
 
     /// Helper: Convert IPLD to JSON
     #[allow(clippy::only_used_in_recursion)]
-    fn ipld_to_json(&self, ipld: &atmst::Ipld) -> Result<Value> {
-        use atmst::Ipld;
+    fn ipld_to_json(&self, ipld: &ipld_core::ipld::Ipld) -> Result<Value> {
+        use ipld_core::ipld::Ipld;
 
         match ipld {
             Ipld::Null => Ok(Value::Null),
@@ -576,7 +942,7 @@ impl LexiconIngestor for CarImportIngestor {
             .as_ref()
             .ok_or_else(|| anyhow!("CarImportIngestor requires a record in the commit"))?;
 
-        // Enqueue CAR import job into Redis
+        // Enqueue CAR import job on the configured job queue
         let job = CarImportJob {
             request_id: uuid::Uuid::new_v4(),
             identity: record
@@ -587,11 +953,15 @@ impl LexiconIngestor for CarImportIngestor {
             since: None,
             created_at: chrono::Utc::now(),
             description: None,
+            dry_run: false,
+            spool_file: None,
         };
         let job_payload = serde_json::to_string(&job)?;
-        let mut conn = self.get_redis_connection().await?;
-        // Specify the expected return type to avoid FromRedisValue fallback issues in edition 2024
-        let _: () = conn.lpush(queue_keys::CAR_IMPORT_JOBS, job_payload).await?;
+        self.job_queue
+            .as_ref()
+            .ok_or_else(|| anyhow!("No job queue is configured for CAR imports"))?
+            .queue_job(queue_keys::CAR_IMPORT_JOBS, &job_payload)
+            .await?;
         tracing::info!("Enqueued CAR import job: {}", job.request_id);
 
         Ok(())
@@ -600,40 +970,68 @@ impl LexiconIngestor for CarImportIngestor {
 
 #[allow(dead_code)]
 impl CarImportIngestor {
-    /// Download CAR file from URL
-    async fn download_car_file(&self, url: &str) -> Result<Vec<u8>> {
+    /// Download CAR file from URL, spooling it to disk
+    async fn download_car_file(&self, url: &str) -> Result<SpooledCar> {
         let response = reqwest::get(url).await?;
-        Ok(response.bytes().await?.to_vec())
+        let mut car_file = SpooledCar::new(max_car_size_bytes())?;
+        car_file.check_declared_len(response.content_length())?;
+
+        let mut chunks = response.bytes_stream();
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(|e| SpoolError::Source(e.into()))?;
+            car_file.write_chunk(&chunk).await?;
+        }
+        Ok(car_file)
     }
 
     /// Import CAR data from bytes (public interface)
-    pub async fn import_car_bytes(&self, car_data: &[u8], did: &str) -> Result<String> {
+    pub async fn import_car_bytes(&self, car_data: Bytes, did: &str) -> Result<String> {
         let import_id = uuid::Uuid::new_v4().to_string();
-        self.process_car_data(car_data, &import_id, did).await?;
+        let car_len = car_data.len() as u64;
+        self.process_car_data(
+            &mut Cursor::new(car_data),
+            car_len,
+            &import_id,
+            did,
+            &mut ImportProgress::detached(),
+        )
+        .await?;
         Ok(import_id)
     }
 
     /// Consolidate synthetic artists with MusicBrainz artists
-    pub async fn consolidate_synthetic_artists(&self, min_confidence: f64) -> Result<usize> {
+    pub async fn consolidate_synthetic_artists(
+        &self,
+        min_confidence: f64,
+        dry_run: bool,
+    ) -> Result<Vec<Merge>> {
         let play_ingestor = super::super::teal::feed_play::PlayIngestor::new(self.sql.clone());
         play_ingestor
-            .consolidate_synthetic_artists(min_confidence)
+            .consolidate_synthetic_artists(min_confidence, dry_run, &HashSet::new())
             .await
     }
 
     /// Consolidate duplicate releases
-    pub async fn consolidate_duplicate_releases(&self, min_confidence: f64) -> Result<usize> {
+    pub async fn consolidate_duplicate_releases(
+        &self,
+        min_confidence: f64,
+        dry_run: bool,
+    ) -> Result<Vec<Merge>> {
         let play_ingestor = super::super::teal::feed_play::PlayIngestor::new(self.sql.clone());
         play_ingestor
-            .consolidate_duplicate_releases(min_confidence)
+            .consolidate_duplicate_releases(min_confidence, dry_run)
             .await
     }
 
     /// Consolidate duplicate recordings
-    pub async fn consolidate_duplicate_recordings(&self, min_confidence: f64) -> Result<usize> {
+    pub async fn consolidate_duplicate_recordings(
+        &self,
+        min_confidence: f64,
+        dry_run: bool,
+    ) -> Result<Vec<Merge>> {
         let play_ingestor = super::super::teal::feed_play::PlayIngestor::new(self.sql.clone());
         play_ingestor
-            .consolidate_duplicate_recordings(min_confidence)
+            .consolidate_duplicate_recordings(min_confidence, dry_run)
             .await
     }
 
@@ -646,9 +1044,15 @@ impl CarImportIngestor {
     }
 
     /// Run full batch consolidation for all entity types
-    pub async fn run_full_consolidation(&self) -> Result<()> {
+    pub async fn run_full_consolidation(
+        &self,
+        min_confidence: f64,
+        dry_run: bool,
+    ) -> Result<ConsolidationReport> {
         let play_ingestor = super::super::teal::feed_play::PlayIngestor::new(self.sql.clone());
-        play_ingestor.run_full_consolidation().await
+        play_ingestor
+            .run_full_consolidation(min_confidence, dry_run)
+            .await
     }
 }
 
@@ -657,9 +1061,11 @@ impl CarImportIngestor {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use atmst::{CarBuilder, Ipld};
+    use atmst::{CarBuilder, CarImporter, Ipld};
     use std::collections::BTreeMap;
 
+    const TEST_CID: &str = "bafyreie5737gdxlw5i64vzichcalba3z2v5n6icifvx5xytvske7mr3hpm";
+
     fn create_mock_teal_play_record() -> Ipld {
         let mut record = BTreeMap::new();
         record.insert(
@@ -804,11 +1210,13 @@ mod tests {
             ExtractedRecord {
                 collection: LEGACY_PLAY_COLLECTION.to_string(),
                 rkey: "same-rkey".to_string(),
+                cid: TEST_CID.to_string(),
                 data: legacy_data,
             },
             ExtractedRecord {
                 collection: STABLE_PLAY_COLLECTION.to_string(),
                 rkey: "same-rkey".to_string(),
+                cid: TEST_CID.to_string(),
                 data: stable_data,
             },
         ]);
@@ -825,6 +1233,7 @@ mod tests {
             ExtractedRecord {
                 collection: LEGACY_STATUS_COLLECTION.to_string(),
                 rkey: "same-rkey".to_string(),
+                cid: TEST_CID.to_string(),
                 data: serde_json::json!({
                     "$type": LEGACY_STATUS_COLLECTION,
                     "time": "2024-01-01T00:00:00Z"
@@ -833,6 +1242,7 @@ mod tests {
             ExtractedRecord {
                 collection: STABLE_STATUS_COLLECTION.to_string(),
                 rkey: "same-rkey".to_string(),
+                cid: TEST_CID.to_string(),
                 data: serde_json::json!({
                     "$type": STABLE_STATUS_COLLECTION,
                     "time": "2024-01-01T00:01:00Z"
@@ -841,6 +1251,7 @@ mod tests {
             ExtractedRecord {
                 collection: STABLE_STATUS_COLLECTION.to_string(),
                 rkey: "different-rkey".to_string(),
+                cid: TEST_CID.to_string(),
                 data: serde_json::json!({
                     "$type": STABLE_STATUS_COLLECTION,
                     "time": "2024-01-01T00:00:00Z"
@@ -944,8 +1355,15 @@ mod tests {
         let test_did = "did:plc:test123";
 
         // This should work with our new atmst implementation
+        let car_len = car_bytes.len() as u64;
         let result = ingestor
-            .process_car_data(&car_bytes, &import_id, test_did)
+            .process_car_data(
+                &mut Cursor::new(car_bytes),
+                car_len,
+                &import_id,
+                test_did,
+                &mut ImportProgress::detached(),
+            )
             .await;
 
         // For now, we expect this to work but records might not actually get stored
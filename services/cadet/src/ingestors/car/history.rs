@@ -0,0 +1,88 @@
+use anyhow::Result;
+use common::car_import_jobs::{record_job_history, JobHistoryEntry};
+use common::jobs::{CarImportJob, CarImportJobStatus};
+use sqlx::PgPool;
+
+/// Default number of days finished jobs are kept in `car_import_jobs`.
+pub const DEFAULT_JOB_RETENTION_DAYS: i64 = 30;
+
+/// Default lifetime of a finished job's status key, in seconds.
+pub const DEFAULT_STATUS_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Number of days to keep finished jobs, from `CAR_IMPORT_JOB_RETENTION_DAYS`.
+pub fn job_retention_days() -> i64 {
+    std::env::var("CAR_IMPORT_JOB_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_JOB_RETENTION_DAYS)
+}
+
+/// Lifetime of a finished job's status key, from `CAR_IMPORT_STATUS_TTL_SECS`.
+pub fn status_ttl_secs() -> u64 {
+    std::env::var("CAR_IMPORT_STATUS_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_STATUS_TTL_SECS)
+}
+
+/// Upsert the current state of a job into `car_import_jobs`.
+pub async fn record_job_status(
+    sql: &PgPool,
+    job: &CarImportJob,
+    status: &CarImportJobStatus,
+) -> Result<()> {
+    let did = status
+        .progress
+        .as_ref()
+        .and_then(|p| p.user_did.as_deref())
+        .or_else(|| {
+            job.identity
+                .starts_with("did:")
+                .then_some(job.identity.as_str())
+        });
+    let progress = status
+        .progress
+        .as_ref()
+        .map(serde_json::to_value)
+        .transpose()?;
+    let preview = status
+        .preview
+        .as_ref()
+        .map(serde_json::to_value)
+        .transpose()?;
+
+    record_job_history(
+        sql,
+        &JobHistoryEntry {
+            job_id: job.request_id,
+            identity: &job.identity,
+            did,
+            status: status.status.as_str(),
+            description: job.description.as_deref(),
+            created_at: job.created_at,
+            started_at: status.started_at,
+            completed_at: status.completed_at,
+            error_message: status.error_message.as_deref(),
+            progress,
+            dry_run: job.dry_run,
+            preview,
+        },
+    )
+    .await
+}
+
+/// Delete finished jobs older than `retention_days`. Returns the number of rows removed.
+pub async fn prune_job_history(sql: &PgPool, retention_days: i64) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM car_import_jobs
+        WHERE status IN ('completed', 'failed', 'cancelled')
+          AND completed_at < NOW() - make_interval(days => $1::int)
+        "#,
+    )
+    .bind(retention_days)
+    .execute(sql)
+    .await?;
+
+    Ok(result.rows_affected())
+}
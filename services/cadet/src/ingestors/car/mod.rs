@@ -1,4 +1,6 @@
 pub mod car_import;
-pub mod jobs;
+pub mod history;
+pub mod progress;
+pub mod repo_reader;
 
 pub use car_import::CarImportIngestor;
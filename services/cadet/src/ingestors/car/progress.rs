@@ -0,0 +1,222 @@
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::job_queue::JobQueue;
+use common::jobs::{queue_keys, CarImportJobStatus, JobProgress, JobStatus};
+use tracing::warn;
+use uuid::Uuid;
+
+/// Minimum time between two progress writes while a step is running.
+const PROGRESS_UPDATE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Destination for CAR import progress snapshots.
+#[async_trait]
+pub trait ProgressSink: Send + Sync {
+    async fn publish(&self, progress: &JobProgress);
+}
+
+/// Writes progress snapshots to the job status key read by aqua's
+/// `/api/car/job-status/{job_id}` endpoint.
+pub struct JobStatusSink<'a> {
+    job_queue: &'a dyn JobQueue,
+    status_key: String,
+    created_at: DateTime<Utc>,
+    started_at: DateTime<Utc>,
+}
+
+impl<'a> JobStatusSink<'a> {
+    pub fn new(
+        job_queue: &'a dyn JobQueue,
+        job_id: &Uuid,
+        created_at: DateTime<Utc>,
+        started_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            job_queue,
+            status_key: queue_keys::job_status_key(job_id),
+            created_at,
+            started_at,
+        }
+    }
+}
+
+#[async_trait]
+impl ProgressSink for JobStatusSink<'_> {
+    async fn publish(&self, progress: &JobProgress) {
+        let status = CarImportJobStatus {
+            status: JobStatus::Processing,
+            created_at: self.created_at,
+            started_at: Some(self.started_at),
+            completed_at: None,
+            error_message: None,
+            progress: Some(progress.clone()),
+            preview: None,
+        };
+
+        match serde_json::to_string(&status) {
+            Ok(status_data) => {
+                if let Err(e) = self
+                    .job_queue
+                    .update_job_status(&self.status_key, &status_data, None)
+                    .await
+                {
+                    warn!("Failed to publish CAR import progress: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to serialize CAR import progress: {}", e),
+        }
+    }
+}
+
+/// Accumulates counters for a single CAR import and periodically publishes
+/// them to a [`ProgressSink`].
+pub struct ImportProgress<'a> {
+    progress: JobProgress,
+    sink: Option<&'a dyn ProgressSink>,
+    last_published: Option<Instant>,
+}
+
+impl<'a> ImportProgress<'a> {
+    pub fn new(sink: &'a dyn ProgressSink) -> Self {
+        Self {
+            progress: JobProgress::default(),
+            sink: Some(sink),
+            last_published: None,
+        }
+    }
+
+    /// Track progress without publishing it anywhere.
+    pub fn detached() -> Self {
+        Self {
+            progress: JobProgress::default(),
+            sink: None,
+            last_published: None,
+        }
+    }
+
+    pub fn snapshot(&self) -> JobProgress {
+        self.progress.clone()
+    }
+
+    /// Start a new step and publish it immediately.
+    pub async fn step(&mut self, step: impl Into<String>) {
+        self.progress.step = step.into();
+        self.flush().await;
+    }
+
+    pub async fn resolved(&mut self, did: &str, pds_host: &str) {
+        self.progress.user_did = Some(did.to_string());
+        self.progress.pds_host = Some(pds_host.to_string());
+        self.flush().await;
+    }
+
+    pub fn set_car_size(&mut self, bytes: u64) {
+        self.progress.car_size_bytes = Some(bytes);
+    }
+
+    pub async fn downloaded(&mut self, bytes: u64) {
+        *self.progress.bytes_downloaded.get_or_insert(0) += bytes;
+        self.maybe_flush().await;
+    }
+
+    pub async fn blocks_parsed(&mut self, blocks: u64) {
+        self.progress.blocks_processed = Some(blocks);
+        self.maybe_flush().await;
+    }
+
+    pub async fn record_extracted(&mut self, collection: &str) {
+        *self
+            .progress
+            .records_extracted
+            .get_or_insert_with(Default::default)
+            .entry(collection.to_string())
+            .or_insert(0) += 1;
+        self.maybe_flush().await;
+    }
+
+    pub async fn record_inserted(&mut self) {
+        *self.progress.records_inserted.get_or_insert(0) += 1;
+        self.maybe_flush().await;
+    }
+
+    pub async fn records_skipped(&mut self, count: u64) {
+        *self.progress.records_skipped.get_or_insert(0) += count;
+        self.maybe_flush().await;
+    }
+
+    pub async fn record_failed(&mut self) {
+        *self.progress.records_failed.get_or_insert(0) += 1;
+        self.maybe_flush().await;
+    }
+
+    async fn maybe_flush(&mut self) {
+        let due = self
+            .last_published
+            .is_none_or(|published| published.elapsed() >= PROGRESS_UPDATE_INTERVAL);
+        if due {
+            self.flush().await;
+        }
+    }
+
+    /// Publish the current snapshot regardless of the update interval.
+    pub async fn flush(&mut self) {
+        if let Some(sink) = self.sink {
+            sink.publish(&self.progress).await;
+            self.last_published = Some(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingSink {
+        published: Mutex<Vec<JobProgress>>,
+    }
+
+    #[async_trait]
+    impl ProgressSink for RecordingSink {
+        async fn publish(&self, progress: &JobProgress) {
+            self.published.lock().unwrap().push(progress.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn counts_records_per_collection() {
+        let mut progress = ImportProgress::detached();
+        progress.record_extracted("fm.teal.feed.play").await;
+        progress.record_extracted("fm.teal.feed.play").await;
+        progress.record_extracted("fm.teal.actor.profile").await;
+        progress.record_inserted().await;
+        progress.records_skipped(2).await;
+
+        let snapshot = progress.snapshot();
+        let extracted = snapshot.records_extracted.expect("extracted counts");
+        assert_eq!(extracted["fm.teal.feed.play"], 2);
+        assert_eq!(extracted["fm.teal.actor.profile"], 1);
+        assert_eq!(snapshot.records_inserted, Some(1));
+        assert_eq!(snapshot.records_skipped, Some(2));
+        assert_eq!(snapshot.records_failed, None);
+    }
+
+    #[tokio::test]
+    async fn throttles_counter_updates_but_not_steps() {
+        let sink = RecordingSink::default();
+        let mut progress = ImportProgress::new(&sink);
+
+        progress.step("Fetching CAR").await;
+        for _ in 0..100 {
+            progress.record_inserted().await;
+        }
+        progress.step("Done").await;
+
+        let published = sink.published.lock().unwrap();
+        assert_eq!(published.len(), 2);
+        assert_eq!(published[1].step, "Done");
+        assert_eq!(published[1].records_inserted, Some(100));
+    }
+}
@@ -0,0 +1,308 @@
+//! Reading records out of a repository CAR without loading it into memory.
+//!
+//! The CAR is read block by block, twice. The first pass keeps only the
+//! commit and the MST nodes, which are walked in key order to find the keys
+//! of interest; the second pass picks out the blocks of those keys' records.
+//! Memory use is bounded by the tree and the wanted records, not by the size
+//! of the CAR.
+
+use std::collections::{HashMap, HashSet};
+use std::io::SeekFrom;
+
+use anyhow::{anyhow, Result};
+use ipld_core::{cid::Cid, ipld::Ipld};
+use iroh_car::CarReader;
+use tokio::io::{AsyncRead, AsyncSeek, AsyncSeekExt};
+use tracing::warn;
+
+/// Records found under the wanted keys of a repository.
+#[derive(Debug, Default)]
+pub struct RepoRecords {
+    /// Blocks in the CAR
+    pub blocks: u64,
+    /// Records of the wanted keys, in key order
+    pub records: Vec<RepoRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepoRecord {
+    /// `<collection>/<rkey>`
+    pub key: String,
+    pub cid: Cid,
+    pub record: Ipld,
+}
+
+/// Read the records of the keys accepted by `wanted` from the CAR in `car`.
+/// Keys whose record block is missing or undecodable are skipped.
+pub async fn read_records<R>(car: &mut R, wanted: impl Fn(&str) -> bool) -> Result<RepoRecords>
+where
+    R: AsyncRead + AsyncSeek + Unpin + Send,
+{
+    // First pass: the commit and the tree
+    car.seek(SeekFrom::Start(0)).await?;
+    let mut reader = CarReader::new(&mut *car).await?;
+    let root = *reader
+        .header()
+        .roots()
+        .first()
+        .ok_or_else(|| anyhow!("CAR has no root"))?;
+    let mut nodes = HashMap::new();
+    let mut blocks = 0;
+    while let Some((cid, bytes)) = reader.next_block().await? {
+        blocks += 1;
+        let Ok(block) = serde_ipld_dagcbor::from_slice::<Ipld>(&bytes) else {
+            continue;
+        };
+        if cid == root || is_mst_node(&block) {
+            nodes.insert(cid, block);
+        }
+    }
+    drop(reader);
+
+    let data = match nodes.get(&root) {
+        Some(Ipld::Map(commit)) => match commit.get("data") {
+            Some(Ipld::Link(data)) => *data,
+            _ => return Err(anyhow!("Commit {} has no data root", root)),
+        },
+        _ => return Err(anyhow!("Commit block {} is missing", root)),
+    };
+    let mut keys = Vec::new();
+    walk_mst(&nodes, data, &wanted, &mut keys)?;
+    drop(nodes);
+
+    // Second pass: the wanted records
+    let mut wanted_cids: HashSet<Cid> = keys.iter().map(|(_, cid)| *cid).collect();
+    let mut found = HashMap::new();
+    car.seek(SeekFrom::Start(0)).await?;
+    let mut reader = CarReader::new(&mut *car).await?;
+    while let Some((cid, bytes)) = reader.next_block().await? {
+        if !wanted_cids.remove(&cid) {
+            continue;
+        }
+        match serde_ipld_dagcbor::from_slice::<Ipld>(&bytes) {
+            Ok(record) => {
+                found.insert(cid, record);
+            }
+            Err(e) => warn!("Failed to decode record block {}: {}", cid, e),
+        }
+    }
+
+    let records = keys
+        .into_iter()
+        .filter_map(|(key, cid)| match found.get(&cid) {
+            Some(record) => Some(RepoRecord {
+                key,
+                cid,
+                record: record.clone(),
+            }),
+            None => {
+                warn!("No record block {} for {}", cid, key);
+                None
+            }
+        })
+        .collect();
+    Ok(RepoRecords { blocks, records })
+}
+
+/// MST nodes are maps of exactly `l` (left subtree) and `e` (entries).
+fn is_mst_node(block: &Ipld) -> bool {
+    match block {
+        Ipld::Map(node) => {
+            node.len() == 2
+                && node.contains_key("l")
+                && matches!(node.get("e"), Some(Ipld::List(_)))
+        }
+        _ => false,
+    }
+}
+
+/// Collect the wanted keys under `cid` with their record CIDs, in key order.
+/// Each entry `{p, k, v, t}` shares its first `p` key bytes with the previous
+/// entry; `l` is the subtree left of the first entry and `t` the one right of
+/// its entry. Subtrees missing from the CAR are skipped.
+fn walk_mst(
+    nodes: &HashMap<Cid, Ipld>,
+    cid: Cid,
+    wanted: &impl Fn(&str) -> bool,
+    keys: &mut Vec<(String, Cid)>,
+) -> Result<()> {
+    let invalid = || anyhow!("Invalid MST node {}", cid);
+    let Some(node) = nodes.get(&cid) else {
+        warn!("MST node {} is missing from the CAR", cid);
+        return Ok(());
+    };
+    let Ipld::Map(node) = node else {
+        return Err(invalid());
+    };
+    let Some(Ipld::List(entries)) = node.get("e") else {
+        return Err(invalid());
+    };
+
+    if let Some(left) = link(node.get("l")) {
+        walk_mst(nodes, left, wanted, keys)?;
+    }
+    let mut previous_key: Vec<u8> = Vec::new();
+    for entry in entries {
+        let Ipld::Map(entry) = entry else {
+            return Err(invalid());
+        };
+        let (Some(Ipld::Integer(prefix)), Some(Ipld::Bytes(suffix))) =
+            (entry.get("p"), entry.get("k"))
+        else {
+            return Err(invalid());
+        };
+        let prefix = usize::try_from(*prefix)
+            .ok()
+            .filter(|prefix| *prefix <= previous_key.len())
+            .ok_or_else(invalid)?;
+        let mut key = previous_key[..prefix].to_vec();
+        key.extend_from_slice(suffix);
+
+        let value = link(entry.get("v")).ok_or_else(invalid)?;
+        let key_str = String::from_utf8(key.clone()).map_err(|_| invalid())?;
+        if wanted(&key_str) {
+            keys.push((key_str, value));
+        }
+        if let Some(right) = link(entry.get("t")) {
+            walk_mst(nodes, right, wanted, keys)?;
+        }
+        previous_key = key;
+    }
+    Ok(())
+}
+
+fn link(value: Option<&Ipld>) -> Option<Cid> {
+    match value {
+        Some(Ipld::Link(cid)) => Some(*cid),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::io::Cursor;
+
+    use ipld_core::cid::multihash::Multihash;
+    use iroh_car::{CarHeader, CarWriter};
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    fn put(blocks: &mut Vec<(Cid, Vec<u8>)>, node: &Ipld) -> Cid {
+        let bytes = serde_ipld_dagcbor::to_vec(node).unwrap();
+        let hash = Multihash::<64>::wrap(0x12, &Sha256::digest(&bytes)).unwrap();
+        let cid = Cid::new_v1(0x71, hash);
+        blocks.push((cid, bytes));
+        cid
+    }
+
+    fn entry(prefix: i128, suffix: &str, value: Cid, right: Option<Cid>) -> Ipld {
+        Ipld::Map(BTreeMap::from([
+            ("p".to_string(), Ipld::Integer(prefix)),
+            ("k".to_string(), Ipld::Bytes(suffix.as_bytes().to_vec())),
+            ("v".to_string(), Ipld::Link(value)),
+            ("t".to_string(), right.map(Ipld::Link).unwrap_or(Ipld::Null)),
+        ]))
+    }
+
+    fn node(left: Option<Cid>, entries: Vec<Ipld>) -> Ipld {
+        Ipld::Map(BTreeMap::from([
+            ("l".to_string(), left.map(Ipld::Link).unwrap_or(Ipld::Null)),
+            ("e".to_string(), Ipld::List(entries)),
+        ]))
+    }
+
+    /// A two-level repo of a post and two plays, written with its record
+    /// blocks before the tree, as a PDS may. Returns the CAR and the plays'
+    /// CIDs.
+    async fn test_car() -> (Vec<u8>, Cid, Cid) {
+        let mut blocks = Vec::new();
+        let play_a = put(&mut blocks, &Ipld::String("play a".into()));
+        let play_b = put(&mut blocks, &Ipld::String("play b".into()));
+        let post = put(&mut blocks, &Ipld::String("post".into()));
+
+        let left = put(
+            &mut blocks,
+            &node(
+                None,
+                vec![entry(0, "app.bsky.feed.post/3aaaaaaaaaaaa", post, None)],
+            ),
+        );
+        let right = put(
+            &mut blocks,
+            &node(
+                None,
+                vec![entry(
+                    0,
+                    "fm.teal.alpha.feed.play/3bbbbbbbbbbbb",
+                    play_b,
+                    None,
+                )],
+            ),
+        );
+        let data = put(
+            &mut blocks,
+            &node(
+                Some(left),
+                vec![entry(
+                    0,
+                    "fm.teal.alpha.feed.play/3aaaaaaaaaaaa",
+                    play_a,
+                    Some(right),
+                )],
+            ),
+        );
+        let commit = put(
+            &mut blocks,
+            &Ipld::Map(BTreeMap::from([
+                ("did".to_string(), Ipld::String("did:plc:test".into())),
+                ("data".to_string(), Ipld::Link(data)),
+            ])),
+        );
+
+        let mut writer = CarWriter::new(CarHeader::new_v1(vec![commit]), Vec::new());
+        for (cid, bytes) in &blocks {
+            writer.write(*cid, bytes).await.unwrap();
+        }
+        (writer.finish().await.unwrap(), play_a, play_b)
+    }
+
+    #[tokio::test]
+    async fn test_read_records_in_key_order() {
+        let (car, play_a, play_b) = test_car().await;
+        let mut car = Cursor::new(car);
+        let repo = read_records(&mut car, |key| key.starts_with("fm.teal."))
+            .await
+            .unwrap();
+
+        assert_eq!(repo.blocks, 7);
+        assert_eq!(
+            repo.records,
+            vec![
+                RepoRecord {
+                    key: "fm.teal.alpha.feed.play/3aaaaaaaaaaaa".to_string(),
+                    cid: play_a,
+                    record: Ipld::String("play a".into()),
+                },
+                RepoRecord {
+                    key: "fm.teal.alpha.feed.play/3bbbbbbbbbbbb".to_string(),
+                    cid: play_b,
+                    record: Ipld::String("play b".into()),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_records_needs_the_commit() {
+        let mut blocks = Vec::new();
+        let missing = put(&mut Vec::new(), &Ipld::String("commit".into()));
+        let orphan = put(&mut blocks, &Ipld::String("orphan".into()));
+
+        let mut writer = CarWriter::new(CarHeader::new_v1(vec![missing]), Vec::new());
+        writer.write(orphan, &blocks[0].1).await.unwrap();
+        let mut car = Cursor::new(writer.finish().await.unwrap());
+        assert!(read_records(&mut car, |_| true).await.is_err());
+    }
+}
@@ -0,0 +1,361 @@
+//! Dead-letter store for records that could not be ingested.
+//!
+//! Every live ingestor is wrapped in [`DeadLetter`], so a record that fails to
+//! parse or store lands in `failed_records` instead of being lost with the
+//! log line. Imports record their failures the same way. `cadet retry-failed`
+//! feeds the stored records back through the ingestors, e.g. after a parser
+//! fix.
+//!
+//! A record that is later ingested, live or by an import, drops its dead
+//! letter, so a retry can't overwrite it with the older version. The delete
+//! runs on every successful ingest: a cached set of failed keys would miss
+//! the failures other processes store.
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use rocketman::{ingestion::LexiconIngestor, types::event::Event};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+
+use crate::firehose::Ingestors;
+
+/// Rows fetched per round of `cadet retry-failed`.
+const RETRY_PAGE_SIZE: i64 = 500;
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct FailedRecord {
+    pub did: String,
+    pub collection: String,
+    pub rkey: String,
+    pub rev: String,
+    pub cid: Option<String>,
+    /// `None` when a delete failed
+    pub record: Option<Value>,
+}
+
+impl FailedRecord {
+    /// The failed record of a commit event, if it is one.
+    pub fn from_event(event: &Event<Value>) -> Option<Self> {
+        let commit = event.commit.as_ref()?;
+        Some(Self {
+            did: event.did.clone(),
+            collection: commit.collection.clone(),
+            rkey: commit.rkey.clone(),
+            rev: commit.rev.clone(),
+            cid: commit.cid.clone(),
+            record: commit.record.clone(),
+        })
+    }
+
+    /// Rebuild the Jetstream commit event the ingestors expect.
+    pub fn to_event(&self) -> Result<Event<Value>> {
+        let operation = if self.record.is_some() {
+            "create"
+        } else {
+            "delete"
+        };
+        Ok(serde_json::from_value(json!({
+            "did": self.did,
+            "kind": "commit",
+            "commit": {
+                "rev": self.rev,
+                "operation": operation,
+                "collection": self.collection,
+                "rkey": self.rkey,
+                "record": self.record,
+                "cid": self.cid,
+            }
+        }))?)
+    }
+}
+
+/// Store a failed record, or bump its attempt count if it failed before.
+pub async fn record_failure(pool: &PgPool, failed: &FailedRecord, error: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO failed_records (did, collection, rkey, rev, cid, record, error)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (did, collection, rkey) DO UPDATE SET
+            rev = EXCLUDED.rev,
+            cid = EXCLUDED.cid,
+            record = EXCLUDED.record,
+            error = EXCLUDED.error,
+            attempts = failed_records.attempts + 1,
+            last_failed_at = NOW()
+        "#,
+    )
+    .bind(&failed.did)
+    .bind(&failed.collection)
+    .bind(&failed.rkey)
+    .bind(&failed.rev)
+    .bind(&failed.cid)
+    .bind(&failed.record)
+    .bind(error)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Drop the dead letter of a record that has since been ingested, if it has
+/// one.
+pub async fn clear_failure(pool: &PgPool, did: &str, collection: &str, rkey: &str) -> Result<()> {
+    sqlx::query("DELETE FROM failed_records WHERE did = $1 AND collection = $2 AND rkey = $3")
+        .bind(did)
+        .bind(collection)
+        .bind(rkey)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// [`clear_failure`] for a batch of `(did, rkey)` records of `collection`.
+pub async fn clear_failures(
+    pool: &PgPool,
+    collection: &str,
+    records: &[(&str, &str)],
+) -> Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    let (dids, rkeys): (Vec<&str>, Vec<&str>) = records.iter().copied().unzip();
+    sqlx::query(
+        r#"
+        DELETE FROM failed_records f
+        USING UNNEST($2::text[], $3::text[]) AS r(did, rkey)
+        WHERE f.collection = $1 AND f.did = r.did AND f.rkey = r.rkey
+        "#,
+    )
+    .bind(collection)
+    .bind(&dids)
+    .bind(&rkeys)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Wraps an ingestor so its failures are dead-lettered. A failure that was
+/// stored counts as handled; only a failure to store it is passed on.
+pub struct DeadLetter {
+    inner: Box<dyn LexiconIngestor + Send + Sync>,
+    sql: PgPool,
+}
+
+impl DeadLetter {
+    pub fn wrap(
+        inner: Box<dyn LexiconIngestor + Send + Sync>,
+        sql: &PgPool,
+    ) -> Box<dyn LexiconIngestor + Send + Sync> {
+        Box::new(DeadLetter {
+            inner,
+            sql: sql.clone(),
+        })
+    }
+
+    /// Wrap every ingestor of the map.
+    pub fn wrap_all(ingestors: Ingestors, sql: &PgPool) -> Ingestors {
+        ingestors
+            .into_iter()
+            .map(|(collection, inner)| (collection, DeadLetter::wrap(inner, sql)))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl LexiconIngestor for DeadLetter {
+    async fn ingest(&self, message: Event<Value>) -> Result<()> {
+        let failed = FailedRecord::from_event(&message);
+        match self.inner.ingest(message).await {
+            Ok(()) => {
+                let Some(failed) = failed else {
+                    return Ok(());
+                };
+                // Until it is cleared, a retry could bring back the old version
+                clear_failure(&self.sql, &failed.did, &failed.collection, &failed.rkey)
+                    .await
+                    .map_err(|e| {
+                        anyhow!(
+                            "{}/{} was ingested, but its dead letter could not be cleared: {}",
+                            failed.collection,
+                            failed.rkey,
+                            e
+                        )
+                    })
+            }
+            Err(e) => {
+                let Some(failed) = failed else {
+                    return Err(e);
+                };
+                warn!(
+                    "Dead-lettering {}/{} from {}: {}",
+                    failed.collection, failed.rkey, failed.did, e
+                );
+                record_failure(&self.sql, &failed, &e.to_string())
+                    .await
+                    .map_err(|store_error| {
+                        anyhow!("{} (and it could not be dead-lettered: {})", e, store_error)
+                    })?;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RetrySummary {
+    pub recovered: u64,
+    pub failed: u64,
+    /// Records of collections no ingestor handles
+    pub skipped: u64,
+}
+
+/// Feed dead-lettered records, optionally of one collection, back through
+/// `ingestors`. Records that now ingest are removed; the rest stay with their
+/// new error and attempt count.
+pub async fn retry_failed(
+    pool: &PgPool,
+    ingestors: &Ingestors,
+    collection: Option<&str>,
+) -> Result<RetrySummary> {
+    let mut summary = RetrySummary::default();
+    let mut after_id = 0i64;
+    loop {
+        let rows: Vec<FailedRecordRow> = sqlx::query_as(
+            r#"
+            SELECT id, did, collection, rkey, rev, cid, record
+            FROM failed_records
+            WHERE id > $1 AND ($2::text IS NULL OR collection = $2)
+            ORDER BY id
+            LIMIT $3
+            "#,
+        )
+        .bind(after_id)
+        .bind(collection)
+        .bind(RETRY_PAGE_SIZE)
+        .fetch_all(pool)
+        .await?;
+        let Some(last) = rows.last() else {
+            break;
+        };
+        after_id = last.id;
+
+        for row in rows {
+            let failed = row.record;
+            let Some(ingestor) = ingestors.get(&failed.collection) else {
+                summary.skipped += 1;
+                continue;
+            };
+            let result = match failed.to_event() {
+                Ok(event) => ingestor.ingest(event).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => {
+                    clear_failure(pool, &failed.did, &failed.collection, &failed.rkey).await?;
+                    summary.recovered += 1;
+                }
+                Err(e) => {
+                    error!(
+                        "Retry of {}/{} from {} failed: {}",
+                        failed.collection, failed.rkey, failed.did, e
+                    );
+                    record_failure(pool, &failed, &e.to_string()).await?;
+                    summary.failed += 1;
+                }
+            }
+        }
+    }
+    info!(
+        "Retried dead-lettered records: {} recovered, {} still failing, {} without an ingestor",
+        summary.recovered, summary.failed, summary.skipped
+    );
+    Ok(summary)
+}
+
+#[derive(sqlx::FromRow)]
+struct FailedRecordRow {
+    id: i64,
+    #[sqlx(flatten)]
+    record: FailedRecord,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    struct AcceptingIngestor;
+
+    #[async_trait]
+    impl LexiconIngestor for AcceptingIngestor {
+        async fn ingest(&self, _message: Event<Value>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "requires database connection"]
+    async fn test_ingest_clears_failure_stored_by_another_process() -> Result<()> {
+        let database_url = std::env::var("DATABASE_URL")
+            .unwrap_or_else(|_| "postgresql://localhost/teal_test".to_string());
+        let pool = PgPool::connect(&database_url).await?;
+        let failed = FailedRecord {
+            did: "did:plc:deadletter".to_string(),
+            collection: "fm.teal.feed.play".to_string(),
+            rkey: "3lold".to_string(),
+            rev: "3lrev".to_string(),
+            cid: Some("bafyold".to_string()),
+            record: Some(json!({"$type": "fm.teal.feed.play", "trackName": "Old"})),
+        };
+
+        // The wrappers exist before the failure is stored, as when a CAR
+        // import fails a record while cadet is running
+        let mut ingestors: Ingestors = HashMap::new();
+        ingestors.insert(failed.collection.clone(), Box::new(AcceptingIngestor));
+        let ingestors = DeadLetter::wrap_all(ingestors, &pool);
+        record_failure(&pool, &failed, "import failed").await?;
+
+        let newer = FailedRecord {
+            record: Some(json!({"$type": "fm.teal.feed.play", "trackName": "New"})),
+            ..failed.clone()
+        };
+        ingestors[&failed.collection]
+            .ingest(newer.to_event()?)
+            .await?;
+
+        let remaining: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM failed_records WHERE did = $1 AND collection = $2 AND rkey = $3",
+        )
+        .bind(&failed.did)
+        .bind(&failed.collection)
+        .bind(&failed.rkey)
+        .fetch_one(&pool)
+        .await?;
+        assert_eq!(remaining, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_record_round_trips_through_event() {
+        let failed = FailedRecord {
+            did: "did:plc:test".to_string(),
+            collection: "fm.teal.feed.play".to_string(),
+            rkey: "3ltest".to_string(),
+            rev: "3lrev".to_string(),
+            cid: Some("bafytest".to_string()),
+            record: Some(json!({"$type": "fm.teal.feed.play", "trackName": "Test Song"})),
+        };
+        let event = failed.to_event().unwrap();
+        assert_eq!(FailedRecord::from_event(&event), Some(failed.clone()));
+
+        let deleted = FailedRecord {
+            cid: None,
+            record: None,
+            ..failed
+        };
+        let event = deleted.to_event().unwrap();
+        assert!(event.commit.as_ref().unwrap().record.is_none());
+        assert_eq!(FailedRecord::from_event(&event), Some(deleted));
+    }
+}
@@ -0,0 +1,273 @@
+//! Listening history imported from other services.
+//!
+//! aqua parses the export (e.g. Spotify's extended streaming history) into
+//! `fm.teal.feed.play` records and queues them on `history_import_jobs`. They
+//! are stored in batches through [`PlayIngestor::write_batch`], so they get
+//! the same cleaning and artist matching as plays from the firehose.
+//!
+//! Imported plays get a [`imported_play_uri`] rather than an `at://` URI:
+//! they are not records in the user's repository, so repo exports and
+//! firehose deletes must never treat them as such.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use common::job_queue::{JobQueue, QueuedJob};
+use common::jobs::{
+    imported_play_uri, queue_keys, CarImportJobStatus, HistoryImportJob, JobStatus,
+};
+use jacquard_common::types::value;
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+
+use crate::ingestors::car::history::status_ttl_secs;
+use crate::ingestors::car::progress::{ImportProgress, JobStatusSink};
+use crate::ingestors::teal::feed_play::{play_batch_size, PendingPlay, PlayIngestor};
+use crate::ingestors::teal::{validate, STABLE_FEED_PLAY};
+
+pub struct HistoryImportIngestor {
+    sql: PgPool,
+}
+
+impl HistoryImportIngestor {
+    pub fn new(sql: PgPool) -> Self {
+        Self { sql }
+    }
+
+    /// Insert every play of the job. Plays that fail are counted and skipped;
+    /// the import only fails when none of them could be stored. Failed plays
+    /// are not dead-lettered, as `cadet retry-failed` would replay them as
+    /// repo records; uploading the export again retries them.
+    pub async fn import(
+        &self,
+        job: &HistoryImportJob,
+        progress: &mut ImportProgress<'_>,
+    ) -> Result<u64> {
+        let play_ingestor = PlayIngestor::new(self.sql.clone());
+        let cid = format!("{}-import-{}", job.source, job.request_id);
+        let mut inserted = 0u64;
+
+        progress
+            .step(format!(
+                "Importing {} plays from {}",
+                job.plays.len(),
+                job.source
+            ))
+            .await;
+
+        for chunk in job.plays.chunks(play_batch_size()) {
+            let mut pending = Vec::with_capacity(chunk.len());
+            for play in chunk {
+                progress.record_extracted(STABLE_FEED_PLAY).await;
+                if !validate::accept_record(
+                    &self.sql,
+                    &job.did,
+                    STABLE_FEED_PLAY,
+                    &play.rkey,
+                    Some(&cid),
+                    &play.record,
+                )
+                .await
+                {
+                    progress.records_skipped(1).await;
+                    continue;
+                }
+
+                let play_record = match value::from_json_value::<types::fm_teal::feed::play::Play>(
+                    play.record.clone(),
+                ) {
+                    Ok(play_record) => play_record,
+                    Err(e) => {
+                        warn!("Invalid imported play {}: {}", play.rkey, e);
+                        progress.record_failed().await;
+                        continue;
+                    }
+                };
+                pending.push(PendingPlay {
+                    record: play_record,
+                    uri: imported_play_uri(&job.did, &job.source, &play.rkey),
+                    cid: cid.clone(),
+                    did: job.did.clone(),
+                    rkey: play.rkey.clone(),
+                });
+            }
+
+            let results = play_ingestor.write_batch(&pending).await;
+            for (play, result) in pending.iter().zip(results) {
+                match result {
+                    Ok(()) => {
+                        inserted += 1;
+                        progress.record_inserted().await;
+                    }
+                    Err(e) => {
+                        warn!("Failed to store imported play {}: {}", play.uri, e);
+                        progress.record_failed().await;
+                    }
+                }
+            }
+        }
+
+        if inserted == 0 && !job.plays.is_empty() {
+            return Err(anyhow!(
+                "none of the {} plays could be imported",
+                job.plays.len()
+            ));
+        }
+        Ok(inserted)
+    }
+}
+
+/// Process history import jobs until the process exits.
+pub async fn run_worker(sql: PgPool, job_queue: Arc<dyn JobQueue>) {
+    let ingestor = HistoryImportIngestor::new(sql);
+    info!("Starting history import job worker, polling job queue...");
+
+    loop {
+        let queued = match job_queue.pop_job(queue_keys::HISTORY_IMPORT_JOBS, 10).await {
+            Ok(Some(queued)) => queued,
+            Ok(None) => continue,
+            Err(e) => {
+                error!("Failed to poll history import job queue: {}", e);
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+
+        let job = match serde_json::from_str::<HistoryImportJob>(&queued.payload) {
+            Ok(job) => job,
+            Err(e) => {
+                error!("Failed to parse history import job: {}", e);
+                complete_job(job_queue.as_ref(), &queued).await;
+                continue;
+            }
+        };
+        info!(
+            "Received {} history import job {} for {} ({} plays)",
+            job.source,
+            job.request_id,
+            job.did,
+            job.plays.len()
+        );
+
+        let started_at = Utc::now();
+        let sink = JobStatusSink::new(
+            job_queue.as_ref(),
+            &job.request_id,
+            job.created_at,
+            started_at,
+        );
+        let mut progress = ImportProgress::new(&sink);
+
+        let final_status = match ingestor.import(&job, &mut progress).await {
+            Ok(inserted) => {
+                info!(
+                    "✅ History import job completed: {} ({} plays)",
+                    job.request_id, inserted
+                );
+                let mut snapshot = progress.snapshot();
+                snapshot.step = format!("Imported {} of {} plays", inserted, job.plays.len());
+                CarImportJobStatus {
+                    status: JobStatus::Completed,
+                    created_at: job.created_at,
+                    started_at: Some(started_at),
+                    completed_at: Some(Utc::now()),
+                    error_message: None,
+                    progress: Some(snapshot),
+                    preview: None,
+                }
+            }
+            Err(e) => {
+                error!("❌ History import job failed: {}: {}", job.request_id, e);
+                CarImportJobStatus {
+                    status: JobStatus::Failed,
+                    created_at: job.created_at,
+                    started_at: Some(started_at),
+                    completed_at: Some(Utc::now()),
+                    error_message: Some(e.to_string()),
+                    progress: Some(progress.snapshot()),
+                    preview: None,
+                }
+            }
+        };
+
+        if let Ok(status_data) = serde_json::to_string(&final_status) {
+            if let Err(e) = job_queue
+                .update_job_status(
+                    &queue_keys::job_status_key(&job.request_id),
+                    &status_data,
+                    Some(status_ttl_secs()),
+                )
+                .await
+            {
+                error!("Failed to update history import job status: {}", e);
+            }
+        }
+        complete_job(job_queue.as_ref(), &queued).await;
+    }
+}
+
+/// Remove a handled job from the queue. A job that cannot be removed is run
+/// again once its lease expires, which only re-upserts the same plays.
+async fn complete_job(job_queue: &dyn JobQueue, queued: &QueuedJob) {
+    if let Err(e) = job_queue.complete_job(queued).await {
+        error!("Failed to complete history import job: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::jobs::ImportedPlay;
+    use serde_json::json;
+
+    #[tokio::test]
+    #[ignore = "requires database connection"]
+    async fn test_running_a_job_again_writes_the_same_plays() -> Result<()> {
+        let database_url = std::env::var("DATABASE_URL")
+            .unwrap_or_else(|_| "postgresql://localhost/teal_test".to_string());
+        let pool = PgPool::connect(&database_url).await?;
+        let ingestor = HistoryImportIngestor::new(pool.clone());
+
+        let job = HistoryImportJob {
+            request_id: uuid::Uuid::new_v4(),
+            did: "did:plc:rerunimport".to_string(),
+            source: "spotify".to_string(),
+            created_at: Utc::now(),
+            plays: vec![ImportedPlay {
+                rkey: "3lb2c4wcg3k2e".to_string(),
+                record: json!({
+                    "$type": STABLE_FEED_PLAY,
+                    "trackName": "Windowlicker",
+                    "artists": [{ "artistName": "Aphex Twin" }],
+                    "playedTime": "2023-04-01T12:00:00Z"
+                }),
+            }],
+        };
+        let plays = || {
+            sqlx::query_as::<_, (String, String)>(
+                "SELECT uri, cid FROM plays WHERE did = $1 ORDER BY uri",
+            )
+            .bind(&job.did)
+            .fetch_all(&pool)
+        };
+
+        // A worker that dies before completing the job leaves it to run again
+        ingestor
+            .import(&job, &mut ImportProgress::detached())
+            .await?;
+        let first = plays().await?;
+        ingestor
+            .import(&job, &mut ImportProgress::detached())
+            .await?;
+        let second = plays().await?;
+
+        sqlx::query("DELETE FROM plays WHERE did = $1")
+            .bind(&job.did)
+            .execute(&pool)
+            .await?;
+        assert_eq!(first.len(), 1);
+        assert_eq!(first, second);
+        Ok(())
+    }
+}
@@ -1,2 +1,4 @@
 pub mod car;
+pub mod dead_letter;
+pub mod history_import;
 pub mod teal;
@@ -4,8 +4,9 @@ use rocketman::{ingestion::LexiconIngestor, types::event::Event};
 use serde_json::Value;
 use sqlx::PgPool;
 
-use crate::ingestors::teal::normalize_legacy_record_type;
-use crate::resolve::resolve_identity;
+use crate::identity::apply_handle_check;
+use crate::ingestors::teal::{normalize_legacy_record_type, validate};
+use crate::resolve::{resolve_identity, verified_handle, HandleCheck, PUBLIC_APP_VIEW};
 
 pub struct ActorProfileIngestor {
     sql: PgPool,
@@ -27,9 +28,13 @@ impl ActorProfileIngestor {
     ) -> anyhow::Result<()> {
         dbg!(&profile);
         // TODO: cache the doc for like 8 hours or something
-        let did = resolve_identity(provided_did, "https://public.api.bsky.app").await?;
+        let did = resolve_identity(provided_did, PUBLIC_APP_VIEW).await?;
 
-        let handle = did.doc.also_known_as.first().to_owned();
+        let check = verified_handle(&did.did, &did.doc).await;
+        let handle = match &check {
+            HandleCheck::Verified(handle) => Some(handle.clone()),
+            HandleCheck::Invalid | HandleCheck::Unknown => None,
+        };
 
         let created_time = profile
             .created_at
@@ -67,6 +72,9 @@ impl ActorProfileIngestor {
         )
         .execute(&self.sql)
         .await?;
+        // Updates keep the stored handle, so refresh it (and take it from any
+        // profile that held it before) here
+        apply_handle_check(&self.sql, &did.identity, &check).await?;
         Ok(())
     }
     pub async fn remove_profile(&self, did: &str) -> anyhow::Result<()> {
@@ -87,11 +95,30 @@ impl LexiconIngestor for ActorProfileIngestor {
     async fn ingest(&self, message: Event<Value>) -> anyhow::Result<()> {
         if let Some(commit) = &message.commit {
             if let Some(ref record) = &commit.record {
+                if !validate::accept_record(
+                    &self.sql,
+                    &message.did,
+                    &commit.collection,
+                    &commit.rkey,
+                    commit.cid.as_deref(),
+                    record,
+                )
+                .await
+                {
+                    return Ok(());
+                }
                 let record = parse_profile_record(record)?;
                 if let Some(ref commit) = message.commit {
                     if let Some(ref _cid) = commit.cid {
                         // TODO: verify cid
                         self.insert_profile(&message.did, &record).await?;
+                        validate::clear_rejection(
+                            &self.sql,
+                            &message.did,
+                            &commit.collection,
+                            &commit.rkey,
+                        )
+                        .await;
                     }
                 }
             } else {
@@ -4,7 +4,7 @@ use rocketman::{ingestion::LexiconIngestor, types::event::Event};
 use serde_json::Value;
 use sqlx::PgPool;
 
-use crate::ingestors::teal::{assemble_at_uri, normalize_legacy_record_type};
+use crate::ingestors::teal::{assemble_at_uri, normalize_legacy_record_type, validate};
 
 pub struct ActorStatusIngestor {
     sql: PgPool,
@@ -68,11 +68,30 @@ impl LexiconIngestor for ActorStatusIngestor {
     async fn ingest(&self, message: Event<Value>) -> anyhow::Result<()> {
         if let Some(commit) = &message.commit {
             if let Some(ref record) = &commit.record {
+                if !validate::accept_record(
+                    &self.sql,
+                    &message.did,
+                    &commit.collection,
+                    &commit.rkey,
+                    commit.cid.as_deref(),
+                    record,
+                )
+                .await
+                {
+                    return Ok(());
+                }
                 let record = parse_status_record(record)?;
 
                 if let Some(ref cid) = commit.cid {
                     self.insert_status(&message.did, &commit.rkey, cid, &record)
                         .await?;
+                    validate::clear_rejection(
+                        &self.sql,
+                        &message.did,
+                        &commit.collection,
+                        &commit.rkey,
+                    )
+                    .await;
                 }
             } else {
                 println!("{}: Status {} deleted", message.did, commit.rkey);
@@ -0,0 +1,572 @@
+//! Cleaning track and artist names before they are matched.
+//!
+//! Scrobblers decorate names with "guff" such as `(Remastered 2011)`,
+//! `[Explicit]` or `- Radio Edit` that MusicBrainz leaves out. The rules for
+//! what to strip (guff words, brackets, generic names, thresholds) live in
+//! `packages/cleaning-rules/rules.json`, which amethyst reads too, so the app
+//! and the indexer clean names the same way. Both run the golden corpus in
+//! `packages/cleaning-rules/corpus.json`.
+//!
+//! The rules are built into cadet; `CLEANING_RULES_PATH` loads a different
+//! rules file at startup.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use unicode_normalization::UnicodeNormalization;
+
+const BUNDLED_RULES: &str = include_str!("../../../../../packages/cleaning-rules/rules.json");
+
+/// Version of the rules file format this build understands.
+pub const RULES_VERSION: u32 = 1;
+
+/// Characters stripped from the end of a word before looking it up in the
+/// guff words, so `prod.` counts as `prod`.
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', '!', '?', ';', ':'];
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Thresholds {
+    /// Track names shorter than this keep their remix/feat info
+    pub short_name: usize,
+    /// Names shorter than this with at most three words are common phrases
+    pub common_phrase_length: usize,
+    /// Shorter words are never taken for an artist name
+    pub min_artist_name_length: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Decorative {
+    pub leading: String,
+    pub trailing: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemixRules {
+    pub keywords: Vec<String>,
+    /// Words that make a named remix when an artist name comes with them,
+    /// e.g. `Zomby Mix`
+    pub named_variant_words: Vec<String>,
+    /// Words that aren't an artist name next to a named variant word, e.g.
+    /// `Deluxe Edition`
+    pub generic_variant_words: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionRules {
+    pub keywords: Vec<String>,
+    /// Keywords that only mark a version when a year comes with them
+    pub dated_keywords: Vec<String>,
+    /// Versions that are always kept, being separate recordings
+    pub always_keep: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisambiguationRules {
+    pub remix: RemixRules,
+    pub version: VersionRules,
+}
+
+/// What a bracket or suffix may be kept for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Remix,
+    Feat,
+    Version,
+}
+
+/// Rules for cleaning names, as read from a rules file.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CleaningRules {
+    pub version: u32,
+    pub thresholds: Thresholds,
+    /// Track names common enough to need their remix/feat info kept
+    pub generic_track_names: Vec<String>,
+    /// Words of bracketed content that can be removed, by category
+    pub guff_words: BTreeMap<String, Vec<String>>,
+    /// Opening and closing bracket pairs stripped when their content is guff
+    pub brackets: Vec<(char, char)>,
+    /// Words introducing featured artists, with an optional `.`
+    pub feat_markers: Vec<String>,
+    pub decorative: Decorative,
+    pub disambiguation: DisambiguationRules,
+    /// Words of a `Track - suffix` that make the suffix a remix
+    pub dash_remix_words: Vec<String>,
+    /// Letters folded when names are compared, for letters that don't
+    /// decompose into a base letter and an accent
+    #[serde(default)]
+    pub folded_letters: HashMap<char, String>,
+    /// Artists whose names contain a credit separator, such as
+    /// `Simon & Garfunkel`; lowercase
+    #[serde(default)]
+    pub whole_artist_names: Vec<String>,
+    #[serde(skip)]
+    guff: HashSet<String>,
+}
+
+static RULES: OnceLock<CleaningRules> = OnceLock::new();
+
+/// Load the cleaning rules, from `CLEANING_RULES_PATH` if it is set. Called
+/// at startup so a bad rules file stops cadet before any name is cleaned.
+pub fn init_from_env() -> Result<&'static CleaningRules> {
+    let rules = match std::env::var("CLEANING_RULES_PATH") {
+        Ok(path) if !path.is_empty() => {
+            let json = std::fs::read_to_string(&path)
+                .with_context(|| format!("Could not read cleaning rules from {}", path))?;
+            CleaningRules::from_json(&json)
+                .with_context(|| format!("Invalid cleaning rules in {}", path))?
+        }
+        _ => CleaningRules::from_json(BUNDLED_RULES)?,
+    };
+    Ok(RULES.get_or_init(|| rules))
+}
+
+/// The rules loaded at startup, or the bundled rules if none were.
+pub fn rules() -> &'static CleaningRules {
+    RULES.get_or_init(|| {
+        CleaningRules::from_json(BUNDLED_RULES).expect("bundled cleaning rules are valid")
+    })
+}
+
+pub fn clean_artist_name(name: &str) -> String {
+    rules().clean_artist_name(name)
+}
+
+pub fn clean_track_name(name: &str) -> String {
+    rules().clean_track_name(name)
+}
+
+/// Whether `haystack` contains `word` between non-word characters, like a
+/// `\bword\b` regex.
+fn contains_word(haystack: &str, word: &str) -> bool {
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric() || c == '_');
+    haystack.match_indices(word).any(|(start, found)| {
+        !is_word(haystack[..start].chars().next_back())
+            && !is_word(haystack[start + found.len()..].chars().next())
+    })
+}
+
+/// Combining diacritical marks of Latin, Greek and Cyrillic letters. Kana
+/// voicing marks (U+3099, U+309A) are not among them, `ガ` and `カ` differ.
+fn is_combining_diacritic(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036f}'
+            | '\u{1ab0}'..='\u{1aff}'
+            | '\u{1dc0}'..='\u{1dff}'
+            | '\u{20d0}'..='\u{20ff}'
+            | '\u{fe20}'..='\u{fe2f}'
+    )
+}
+
+/// Whether `s` contains a year, 19XX or 20XX.
+fn has_year(s: &str) -> bool {
+    s.as_bytes().windows(4).any(|w| {
+        (w[0] == b'1' && w[1] == b'9' || w[0] == b'2' && w[1] == b'0')
+            && w[2].is_ascii_digit()
+            && w[3].is_ascii_digit()
+    })
+}
+
+fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Byte range of the first `open`..`close` pair at matched depth, from the
+/// opening bracket to just past the closing one.
+fn find_bracketed(s: &str, open: char, close: char) -> Option<(usize, usize)> {
+    let start = s.find(open)?;
+    let mut depth: usize = 0;
+    for (i, c) in s[start..].char_indices() {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some((start, start + i + close.len_utf8()));
+            }
+        }
+    }
+    None
+}
+
+/// Byte ranges of the top-level `open`..`close` pairs, left to right, up to
+/// the first unbalanced one.
+fn top_level_groups(s: &str, open: char, close: char) -> Vec<(usize, usize)> {
+    let mut groups = Vec::new();
+    let mut from = 0;
+    while let Some((start, end)) = find_bracketed(&s[from..], open, close) {
+        groups.push((from + start, from + end));
+        from += end;
+    }
+    groups
+}
+
+impl CleaningRules {
+    pub fn from_json(json: &str) -> Result<Self> {
+        let mut rules: CleaningRules = serde_json::from_str(json)?;
+        if rules.version != RULES_VERSION {
+            return Err(anyhow!(
+                "Cleaning rules are version {}, expected version {}",
+                rules.version,
+                RULES_VERSION
+            ));
+        }
+        rules.guff = rules.guff_words.values().flatten().cloned().collect();
+        Ok(rules)
+    }
+
+    /// Byte range of the first featuring marker in `s`, from the whitespace
+    /// before it to the end of the whitespace after it. Markers are tried in
+    /// order.
+    fn find_feat_marker(&self, s: &str) -> Option<(usize, usize)> {
+        let skip_whitespace = |from: usize| {
+            s[from..]
+                .char_indices()
+                .find(|(_, c)| !c.is_whitespace())
+                .map_or(s.len(), |(i, _)| from + i)
+        };
+        for marker in &self.feat_markers {
+            let mut prev_whitespace = false;
+            for (i, c) in s.char_indices() {
+                let starts_run = c.is_whitespace() && !prev_whitespace;
+                prev_whitespace = c.is_whitespace();
+                if !starts_run {
+                    continue;
+                }
+                let at = skip_whitespace(i);
+                let Some(candidate) = s.get(at..at + marker.len()) else {
+                    continue;
+                };
+                if !candidate.eq_ignore_ascii_case(marker) {
+                    continue;
+                }
+                let mut after = at + marker.len();
+                if s[after..].starts_with('.') {
+                    after += 1;
+                }
+                let end = skip_whitespace(after);
+                if end > after {
+                    return Some((i, end));
+                }
+            }
+        }
+        None
+    }
+
+    fn is_generic_variant_word(&self, word: &str) -> bool {
+        let numbered = word.starts_with(|c: char| c.is_ascii_digit())
+            && word.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        numbered
+            || self
+                .disambiguation
+                .remix
+                .generic_variant_words
+                .iter()
+                .any(|w| w == word)
+    }
+
+    /// Whether `content` of a bracket or suffix holds `kind` info that tells
+    /// `base_name` apart from other tracks of that name.
+    fn should_keep_for_disambiguation(&self, content: &str, base_name: &str, kind: Kind) -> bool {
+        let content_lower = content.to_lowercase();
+        let base_lower = base_name.to_lowercase();
+        let remix = &self.disambiguation.remix;
+        let version = &self.disambiguation.version;
+        let min_length = self.thresholds.min_artist_name_length;
+
+        if kind == Kind::Version
+            && version
+                .always_keep
+                .iter()
+                .any(|w| contains_word(&content_lower, w))
+        {
+            return true;
+        }
+
+        let is_relevant = match kind {
+            Kind::Remix => remix
+                .keywords
+                .iter()
+                .any(|k| content_lower.contains(k.as_str())),
+            Kind::Feat => self
+                .feat_markers
+                .iter()
+                .any(|k| content_lower.contains(k.as_str())),
+            Kind::Version => {
+                version
+                    .keywords
+                    .iter()
+                    .any(|k| contains_word(&content_lower, k))
+                    || (version
+                        .dated_keywords
+                        .iter()
+                        .any(|k| contains_word(&content_lower, k))
+                        && has_year(&content_lower))
+            }
+        };
+        // "Zomby Mix" names a remix, "Original Mix" doesn't
+        let is_named_variant = kind == Kind::Remix
+            && remix
+                .named_variant_words
+                .iter()
+                .any(|w| contains_word(&content_lower, w))
+            && content_lower.split_whitespace().any(|word| {
+                word.chars().count() >= min_length && !self.is_generic_variant_word(word)
+            });
+        if !is_relevant && !is_named_variant {
+            return false;
+        }
+
+        let is_generic = self
+            .generic_track_names
+            .iter()
+            .any(|w| base_lower == *w || base_lower.starts_with(&format!("{w} ")));
+        let char_count = base_name.chars().count();
+        let is_short = char_count < self.thresholds.short_name;
+        let is_common_phrase = base_name.split_whitespace().count() <= 3
+            && char_count < self.thresholds.common_phrase_length;
+
+        let keywords: Vec<&String> = match kind {
+            Kind::Remix => remix
+                .keywords
+                .iter()
+                .chain(&remix.named_variant_words)
+                .collect(),
+            Kind::Feat => self.feat_markers.iter().collect(),
+            Kind::Version => version
+                .keywords
+                .iter()
+                .chain(&version.dated_keywords)
+                .collect(),
+        };
+        let has_artist_name = content_lower.split_whitespace().any(|word| {
+            word.chars().count() >= min_length
+                && !keywords.iter().any(|k| word.contains(k.as_str()))
+        });
+
+        is_generic || (is_short && is_common_phrase) || has_artist_name
+    }
+
+    fn should_keep(&self, content: &str, base_name: &str) -> bool {
+        [Kind::Remix, Kind::Feat, Kind::Version]
+            .into_iter()
+            .any(|kind| self.should_keep_for_disambiguation(content, base_name, kind))
+    }
+
+    /// Whether bracketed content or a suffix is likely guff: mostly guff
+    /// words, a year, or short and containing a guff word.
+    pub fn is_likely_guff(&self, content: &str) -> bool {
+        let content_lower = content.to_lowercase();
+        let words: Vec<&str> = content_lower.split_whitespace().collect();
+
+        let guff_word_count = words
+            .iter()
+            .filter(|word| {
+                self.guff.contains(**word)
+                    || self
+                        .guff
+                        .contains(word.trim_end_matches(TRAILING_PUNCTUATION))
+            })
+            .count();
+
+        guff_word_count * 2 > words.len()
+            || has_year(&content_lower)
+            || (words.len() <= 2
+                && self
+                    .guff
+                    .iter()
+                    .any(|guff| content_lower.contains(guff.as_str())))
+    }
+
+    /// Clean an artist name: featured artists and guff brackets are removed.
+    /// A leading "The" stays, MusicBrainz lists artists with it.
+    pub fn clean_artist_name(&self, name: &str) -> String {
+        let mut cleaned = name.trim().to_string();
+
+        while let Some((start, _)) = self.find_feat_marker(&cleaned) {
+            cleaned = cleaned[..start].trim().to_string();
+        }
+
+        // Only the first bracket of each kind, artist names don't nest guff
+        for &(open, close) in &self.brackets {
+            if let Some((start, end)) = find_bracketed(&cleaned, open, close) {
+                let content = &cleaned[start + open.len_utf8()..end - close.len_utf8()];
+                if self.is_likely_guff(content) {
+                    cleaned =
+                        collapse_whitespace(&format!("{}{}", &cleaned[..start], &cleaned[end..]));
+                }
+            }
+        }
+
+        cleaned
+    }
+
+    /// Clean a track name: decoration, guff brackets, guff suffixes and
+    /// featured artists are removed unless the track needs them to be told
+    /// apart, e.g. `High (Branchez Remix)`.
+    pub fn clean_track_name(&self, name: &str) -> String {
+        let mut cleaned = name.trim().to_string();
+
+        let leading = &self.decorative.leading;
+        let trailing = &self.decorative.trailing;
+        let stripped = cleaned
+            .trim_start_matches(|c: char| c.is_whitespace() || leading.contains(c))
+            .trim_end_matches(|c: char| c.is_whitespace() || trailing.contains(c));
+        if !stripped.is_empty() {
+            cleaned = stripped.to_string();
+        }
+
+        // Right to left, so removing a group leaves the earlier ones in place
+        for &(open, close) in &self.brackets {
+            let mut removed = false;
+            for (start, end) in top_level_groups(&cleaned, open, close).into_iter().rev() {
+                let content = &cleaned[start + open.len_utf8()..end - close.len_utf8()];
+                let base_name = cleaned[..start].trim();
+                let would_leave_empty = base_name.is_empty() && cleaned[end..].trim().is_empty();
+                if self.is_likely_guff(content)
+                    && !self.should_keep(content, base_name)
+                    && !would_leave_empty
+                {
+                    cleaned = format!("{}{}", &cleaned[..start], &cleaned[end..]);
+                    removed = true;
+                }
+            }
+            if removed {
+                cleaned = collapse_whitespace(&cleaned);
+            }
+        }
+
+        // Scrobblers write "Track - Radio Edit" and "Track - Artist Remix",
+        // MusicBrainz writes "Track (Artist Remix)"
+        if let Some(dash) = cleaned.find(" - ").filter(|&dash| dash > 0) {
+            let base_name = cleaned[..dash].trim();
+            let suffix = cleaned[dash + 3..].trim();
+            if !base_name.is_empty() && !suffix.is_empty() {
+                let suffix_lower = suffix.to_lowercase();
+                if self.is_likely_guff(suffix) && !self.should_keep(suffix, base_name) {
+                    cleaned = base_name.to_string();
+                } else if self
+                    .dash_remix_words
+                    .iter()
+                    .any(|w| contains_word(&suffix_lower, w))
+                {
+                    cleaned = format!("{base_name} ({suffix})");
+                }
+            }
+        }
+
+        if let Some((start, end)) = self.find_feat_marker(&cleaned) {
+            let base_name = cleaned[..start].trim();
+            let feat_content = cleaned[end..].trim();
+            if !self.should_keep_for_disambiguation(feat_content, base_name, Kind::Feat) {
+                cleaned = base_name.to_string();
+            }
+        }
+
+        cleaned.trim().to_string()
+    }
+
+    /// Normalize text for comparison, so names differing only in case,
+    /// accents, width or punctuation compare equal. Aligned with
+    /// normalizeForComparison in musicbrainzCleaner.ts:
+    /// 1. NFKC, folding full-width and half-width forms and ligatures
+    /// 2. Lowercase, then NFD to separate base characters from accents
+    /// 3. Strip combining diacritical marks and fold letters without a
+    ///    decomposition (`ø`, `ł`, `ß`)
+    /// 4. NFC recomposition, so Hangul and kana with voicing marks round-trip
+    /// 5. Keep all Unicode alphanumeric characters (CJK, Cyrillic, etc.),
+    ///    collapse whitespace
+    pub fn normalize_for_comparison(&self, text: &str) -> String {
+        let lower = text.nfkc().collect::<String>().to_lowercase();
+        let mut folded = String::with_capacity(lower.len());
+        for c in lower.nfd().filter(|&c| !is_combining_diacritic(c)) {
+            match self.folded_letters.get(&c) {
+                Some(letters) => folded.push_str(letters),
+                None => folded.push(c),
+            }
+        }
+        let kept: String = folded
+            .nfc()
+            .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+            .collect();
+        collapse_whitespace(&kept)
+    }
+}
+
+/// Normalize text for comparison with the rules loaded at startup.
+pub fn normalize_for_comparison(text: &str) -> String {
+    rules().normalize_for_comparison(text)
+}
+
+/// The key artist names are matched by, stored as `name_folded` on artists
+/// and their aliases.
+pub fn fold_artist_name(name: &str) -> String {
+    normalize_for_comparison(&clean_artist_name(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CORPUS: &str = include_str!("../../../../../packages/cleaning-rules/corpus.json");
+
+    #[derive(Deserialize)]
+    struct Corpus {
+        cases: Vec<CorpusCase>,
+    }
+
+    #[derive(Deserialize)]
+    struct CorpusCase {
+        kind: String,
+        raw: String,
+        cleaned: String,
+    }
+
+    #[test]
+    fn test_golden_corpus() {
+        let rules = CleaningRules::from_json(BUNDLED_RULES).unwrap();
+        let corpus: Corpus = serde_json::from_str(CORPUS).unwrap();
+        assert!(!corpus.cases.is_empty());
+
+        let failures: Vec<String> = corpus
+            .cases
+            .iter()
+            .filter_map(|case| {
+                let cleaned = match case.kind.as_str() {
+                    "artist" => rules.clean_artist_name(&case.raw),
+                    "track" => rules.clean_track_name(&case.raw),
+                    "comparison" => rules.normalize_for_comparison(&case.raw),
+                    kind => return Some(format!("unknown kind {kind} for {:?}", case.raw)),
+                };
+                (cleaned != case.cleaned).then(|| {
+                    format!(
+                        "{} {:?}: expected {:?}, got {:?}",
+                        case.kind, case.raw, case.cleaned, cleaned
+                    )
+                })
+            })
+            .collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_rejects_other_rules_versions() {
+        let mut rules: serde_json::Value = serde_json::from_str(BUNDLED_RULES).unwrap();
+        rules["version"] = serde_json::json!(RULES_VERSION + 1);
+        assert!(CleaningRules::from_json(&rules.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_feat_marker_needs_whole_word() {
+        let rules = CleaningRules::from_json(BUNDLED_RULES).unwrap();
+        assert_eq!(rules.clean_artist_name("Featherweight"), "Featherweight");
+        assert_eq!(rules.clean_artist_name("Loft Feat. Someone"), "Loft");
+        assert_eq!(rules.find_feat_marker("The Aftermath"), None);
+    }
+}
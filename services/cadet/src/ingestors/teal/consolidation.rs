@@ -0,0 +1,233 @@
+//! Consolidation of duplicate artists, releases and recordings.
+//!
+//! `cadet consolidate <preview | run> [min-confidence]` runs it once and
+//! prints a JSON report of what was (or would be) merged. With
+//! `CONSOLIDATION_CRON` set, cadet also runs it on that schedule. Each merge
+//! is recorded in the [merge log](super::merge_log) and can be undone.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use sqlx::{types::Uuid, PgPool};
+use tokio::sync::Mutex;
+use tokio_cron_scheduler::{Job, JobScheduler};
+use tracing::{error, info};
+
+use super::feed_play::PlayIngestor;
+
+/// Confidence merges need when none is given, set with
+/// `CONSOLIDATION_MIN_CONFIDENCE`.
+pub const DEFAULT_MIN_CONFIDENCE: f64 = 0.92;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergedKind {
+    Artist,
+    Release,
+    Recording,
+}
+
+impl MergedKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MergedKind::Artist => "artist",
+            MergedKind::Release => "release",
+            MergedKind::Recording => "recording",
+        }
+    }
+}
+
+/// An artist (by `id`), release or recording (by `mbid`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntityRef {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mbid: Option<Uuid>,
+    pub name: String,
+}
+
+/// `from` merged into `into`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Merge {
+    /// Its `entity_merges` ID, for `cadet unmerge`; not set in a preview
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_id: Option<i64>,
+    pub kind: MergedKind,
+    pub from: EntityRef,
+    pub into: EntityRef,
+    pub confidence: f64,
+    /// Plays moved, or in a preview the plays that would move
+    pub plays: u64,
+}
+
+/// A synthetic artist whose name is a combined credit, split into its artists.
+#[derive(Debug, Clone, Serialize)]
+pub struct Split {
+    /// Its `entity_merges` ID, for `cadet unmerge`; not set in a preview
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_id: Option<i64>,
+    pub from: EntityRef,
+    pub into: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsolidationReport {
+    pub dry_run: bool,
+    pub min_confidence: f64,
+    pub splits: Vec<Split>,
+    pub merges: Vec<Merge>,
+}
+
+impl ConsolidationReport {
+    pub fn count(&self, kind: MergedKind) -> usize {
+        self.merges
+            .iter()
+            .filter(|merge| merge.kind == kind)
+            .count()
+    }
+}
+
+/// What `cadet consolidate` was asked to do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConsolidateCommand {
+    pub dry_run: bool,
+    pub min_confidence: f64,
+}
+
+impl ConsolidateCommand {
+    /// Parse the arguments after `consolidate`: `preview` or `run`, then an
+    /// optional confidence between 0 and 1.
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let dry_run = match args.first().map(String::as_str) {
+            Some("preview") => true,
+            Some("run") => false,
+            Some(other) => return Err(anyhow!("unknown consolidate mode '{}'", other)),
+            None => return Err(anyhow!("missing consolidate mode")),
+        };
+        let min_confidence = match args.get(1) {
+            Some(value) => parse_confidence(value)?,
+            None => min_confidence(),
+        };
+        if args.len() > 2 {
+            return Err(anyhow!("unexpected argument '{}'", args[2]));
+        }
+        Ok(Self {
+            dry_run,
+            min_confidence,
+        })
+    }
+}
+
+fn parse_confidence(value: &str) -> Result<f64> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|confidence| (0.0..=1.0).contains(confidence))
+        .ok_or_else(|| anyhow!("min-confidence must be between 0 and 1, got '{}'", value))
+}
+
+/// Confidence merges need, set with `CONSOLIDATION_MIN_CONFIDENCE`.
+pub fn min_confidence() -> f64 {
+    std::env::var("CONSOLIDATION_MIN_CONFIDENCE")
+        .ok()
+        .and_then(|value| parse_confidence(&value).ok())
+        .unwrap_or(DEFAULT_MIN_CONFIDENCE)
+}
+
+/// Run consolidation on `CONSOLIDATION_CRON` (a six-field crontab with
+/// seconds), if set. Runs that would overlap a previous one are skipped.
+pub async fn schedule_from_env(pool: PgPool) -> Result<Option<JobScheduler>> {
+    let Ok(cron) = std::env::var("CONSOLIDATION_CRON") else {
+        return Ok(None);
+    };
+    let min_confidence = min_confidence();
+    let running = Arc::new(Mutex::new(()));
+
+    let scheduler = JobScheduler::new().await?;
+    let job = Job::new_async(cron.as_str(), move |_uuid, _lock| {
+        let pool = pool.clone();
+        let running = running.clone();
+        Box::pin(async move {
+            let Ok(_guard) = running.try_lock() else {
+                info!("Skipping scheduled consolidation, the last run is still going");
+                return;
+            };
+            let ingestor = PlayIngestor::new(pool);
+            match ingestor.run_full_consolidation(min_confidence, false).await {
+                Ok(report) => match serde_json::to_string(&report) {
+                    Ok(json) => info!("Scheduled consolidation report: {}", json),
+                    Err(e) => error!("Failed to serialize consolidation report: {}", e),
+                },
+                Err(e) => error!("Scheduled consolidation failed: {}", e),
+            }
+        })
+    })?;
+    scheduler.add(job).await?;
+    scheduler.start().await?;
+
+    info!(
+        "Consolidation scheduled with crontab {} (confidence >= {:.2})",
+        cron, min_confidence
+    );
+    Ok(Some(scheduler))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_consolidate_command() {
+        let preview = ConsolidateCommand::parse(&args(&["preview", "0.95"])).unwrap();
+        assert!(preview.dry_run);
+        assert_eq!(preview.min_confidence, 0.95);
+
+        let run = ConsolidateCommand::parse(&args(&["run"])).unwrap();
+        assert!(!run.dry_run);
+
+        assert!(ConsolidateCommand::parse(&args(&[])).is_err());
+        assert!(ConsolidateCommand::parse(&args(&["merge"])).is_err());
+        assert!(ConsolidateCommand::parse(&args(&["run", "92"])).is_err());
+        assert!(ConsolidateCommand::parse(&args(&["run", "0.9", "extra"])).is_err());
+    }
+
+    #[test]
+    fn test_report_serializes_merges() {
+        let report = ConsolidationReport {
+            dry_run: true,
+            min_confidence: 0.92,
+            splits: Vec::new(),
+            merges: vec![Merge {
+                merge_id: None,
+                kind: MergedKind::Artist,
+                from: EntityRef {
+                    id: Some(7),
+                    mbid: None,
+                    name: "Beyonce".to_string(),
+                },
+                into: EntityRef {
+                    id: Some(3),
+                    mbid: Some(Uuid::nil()),
+                    name: "Beyoncé".to_string(),
+                },
+                confidence: 0.95,
+                plays: 12,
+            }],
+        };
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["merges"][0]["kind"], MergedKind::Artist.as_str());
+        assert!(json["merges"][0].get("merge_id").is_none());
+        assert_eq!(json["merges"][0]["from"]["id"], 7);
+        assert!(json["merges"][0]["from"].get("mbid").is_none());
+        assert_eq!(json["merges"][0]["into"]["name"], "Beyoncé");
+        assert_eq!(report.count(MergedKind::Artist), 1);
+        assert_eq!(report.count(MergedKind::Release), 0);
+    }
+}
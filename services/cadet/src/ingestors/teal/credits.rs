@@ -0,0 +1,236 @@
+//! Splitting combined artist strings into artist credits.
+//!
+//! Many scrobblers send every artist of a track as one string, such as
+//! `Daft Punk feat. Pharrell Williams` or `Disclosure & Sam Smith`. These are
+//! split into ordered credits with the join phrase after each artist, the way
+//! MusicBrainz credits them. Names that contain a separator but are one
+//! artist (`Simon & Garfunkel`, `Kool & the Gang`) are left whole: those in
+//! the cleaning rules' `wholeArtistNames`, and those the caller knows, such as
+//! MusicBrainz artists and aliases.
+
+use std::cell::RefCell;
+
+use super::cleaning;
+
+/// One artist of a split credit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credit {
+    pub name: String,
+    /// Text joining this artist to the next one, e.g. ` feat. `; empty for
+    /// the last artist
+    pub join_phrase: String,
+}
+
+/// Separators in the order they are split on. Featured artists are split off
+/// first, so `A & B feat. C` becomes `A & B` and `C` before `A & B` is split.
+const SEPARATOR_GROUPS: &[&[&str]] = &[
+    &[
+        " (feat. ",
+        " [feat. ",
+        " (ft. ",
+        " [ft. ",
+        " (featuring ",
+        " [featuring ",
+        " feat. ",
+        " feat ",
+        " ft. ",
+        " ft ",
+        " featuring ",
+    ],
+    &[" vs. ", " vs ", " x ", " × "],
+    &[" & ", ", "],
+];
+
+/// Whether `name` is one artist despite its separators: listed in the
+/// cleaning rules' `wholeArtistNames`, or known to `keep_whole`.
+fn is_whole_name(name: &str, keep_whole: &dyn Fn(&str) -> bool) -> bool {
+    let lower = name.trim().to_lowercase();
+    cleaning::rules()
+        .whole_artist_names
+        .iter()
+        .any(|whole| *whole == lower)
+        || keep_whole(name)
+}
+
+/// Where to split `name` on one of `separators`: the byte range of the first
+/// separator that leaves an artist on both sides. `& the` and `, the` start a
+/// band name (`Echo & the Bunnymen`) rather than a second artist.
+fn find_separator(name: &str, separators: &[&str]) -> Option<(usize, usize)> {
+    // ASCII lowercasing keeps byte offsets the same
+    let lower = name.to_ascii_lowercase();
+    let mut found: Option<(usize, usize)> = None;
+    for separator in separators {
+        let mut from = 0;
+        while let Some(offset) = lower[from..].find(separator) {
+            let start = from + offset;
+            let end = start + separator.len();
+            from = end;
+            let joins_band_name =
+                matches!(*separator, " & " | ", ") && lower[end..].starts_with("the ");
+            if joins_band_name || name[..start].trim().is_empty() || name[end..].trim().is_empty() {
+                continue;
+            }
+            if found.is_none_or(|(first, _)| start < first) {
+                found = Some((start, end));
+            }
+            break;
+        }
+    }
+    found
+}
+
+fn split_into(
+    name: &str,
+    groups: &[&[&str]],
+    keep_whole: &dyn Fn(&str) -> bool,
+    credits: &mut Vec<Credit>,
+) {
+    let name = name.trim();
+    if !is_whole_name(name, keep_whole) {
+        for (index, separators) in groups.iter().enumerate() {
+            let Some((start, end)) = find_separator(name, separators) else {
+                continue;
+            };
+            let separator = &name[start..end];
+            let bracketed = separator.trim_start().starts_with(['(', '[']);
+            let mut tail = &name[end..];
+            if bracketed {
+                tail = tail.trim_end().trim_end_matches([')', ']']);
+            }
+            split_into(&name[..start], &groups[index..], keep_whole, credits);
+            if let Some(last) = credits.last_mut() {
+                last.join_phrase = if bracketed {
+                    format!(" {}", &separator.trim_start()[1..])
+                } else {
+                    separator.to_string()
+                };
+            }
+            split_into(tail, &groups[index..], keep_whole, credits);
+            return;
+        }
+    }
+    credits.push(Credit {
+        name: name.to_string(),
+        join_phrase: String::new(),
+    });
+}
+
+/// The artists credited by `name`, in order. A name that isn't a combined
+/// credit comes back as its only artist, and so does every part of it that
+/// `keep_whole` accepts.
+pub fn split_credit(name: &str, keep_whole: &dyn Fn(&str) -> bool) -> Vec<Credit> {
+    let mut credits = Vec::new();
+    split_into(name, SEPARATOR_GROUPS, keep_whole, &mut credits);
+    credits
+}
+
+/// Every part of `name` that [`split_credit`] may ask `keep_whole` about,
+/// `name` itself first.
+pub fn credit_parts(name: &str) -> Vec<String> {
+    let parts = RefCell::new(Vec::new());
+    split_credit(name, &|part| {
+        parts.borrow_mut().push(part.to_string());
+        false
+    });
+    parts.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(credit: &str) -> Vec<String> {
+        split_credit(credit, &|_| false)
+            .into_iter()
+            .map(|credit| credit.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_split_featured_and_collaborating_artists() {
+        assert_eq!(
+            split_credit("Daft Punk feat. Pharrell Williams", &|_| false),
+            vec![
+                Credit {
+                    name: "Daft Punk".to_string(),
+                    join_phrase: " feat. ".to_string()
+                },
+                Credit {
+                    name: "Pharrell Williams".to_string(),
+                    join_phrase: String::new()
+                },
+            ]
+        );
+        assert_eq!(names("Disclosure & Sam Smith"), ["Disclosure", "Sam Smith"]);
+        assert_eq!(
+            names("Fred again.. x Skrillex"),
+            ["Fred again..", "Skrillex"]
+        );
+        assert_eq!(
+            names("Silk Sonic, Bruno Mars & Anderson .Paak"),
+            ["Silk Sonic", "Bruno Mars", "Anderson .Paak"]
+        );
+        assert_eq!(
+            names("Calvin Harris FT. Rihanna"),
+            ["Calvin Harris", "Rihanna"]
+        );
+
+        let bracketed = split_credit("Mark Ronson (feat. Bruno Mars)", &|_| false);
+        assert_eq!(
+            bracketed
+                .iter()
+                .map(|credit| (credit.name.as_str(), credit.join_phrase.as_str()))
+                .collect::<Vec<_>>(),
+            [("Mark Ronson", " feat. "), ("Bruno Mars", "")]
+        );
+    }
+
+    #[test]
+    fn test_keep_whole_names() {
+        assert_eq!(names("Simon & Garfunkel"), ["Simon & Garfunkel"]);
+        assert_eq!(names("EARTH, WIND & FIRE"), ["EARTH, WIND & FIRE"]);
+        assert_eq!(names("Tyler, The Creator"), ["Tyler, The Creator"]);
+        assert_eq!(names("Echo & the Bunnymen"), ["Echo & the Bunnymen"]);
+        assert_eq!(
+            names("Bob Marley & The Wailers"),
+            ["Bob Marley & The Wailers"]
+        );
+        assert_eq!(names("Years & Years"), ["Years & Years"]);
+        assert_eq!(names("Now, Now"), ["Now, Now"]);
+        assert_eq!(names("Chloe x Halle"), ["Chloe x Halle"]);
+        assert_eq!(names("Radiohead"), ["Radiohead"]);
+        assert_eq!(names("  & "), ["&"]);
+
+        // Whole names still split from their featured artists
+        assert_eq!(
+            names("Simon & Garfunkel feat. Paul Simon"),
+            ["Simon & Garfunkel", "Paul Simon"]
+        );
+        assert_eq!(
+            names("Kool & the Gang x Lizzo & SZA"),
+            ["Kool & the Gang", "Lizzo", "SZA"]
+        );
+    }
+
+    #[test]
+    fn test_keep_known_names_whole() {
+        let known = |name: &str| name.eq_ignore_ascii_case("Ana & Milo");
+        assert_eq!(
+            split_credit("Ana & Milo feat. Lil Fable", &known)
+                .into_iter()
+                .map(|credit| credit.name)
+                .collect::<Vec<_>>(),
+            ["Ana & Milo", "Lil Fable"]
+        );
+        assert_eq!(
+            credit_parts("Alpha & Beta feat. Gamma"),
+            [
+                "Alpha & Beta feat. Gamma",
+                "Alpha & Beta",
+                "Alpha",
+                "Beta",
+                "Gamma"
+            ]
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! In-process cache of artists, releases and recordings already written by
+//! [`PlayIngestor`](super::feed_play::PlayIngestor), so a backfill doesn't
+//! fuzzy-match the same artist or upsert the same release for every play.
+//!
+//! Entries are only added after the transaction that wrote them commits. The
+//! cache must be cleared whenever entities are merged or deleted, or it would
+//! hand out IDs that no longer exist. That may happen in another process, such
+//! as `cadet consolidate`, so changes go through [`EntityCache::invalidate`],
+//! which bumps `entity_cache_generation`, and ingesters call
+//! [`EntityCache::sync`] before each batch.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use sqlx::{types::Uuid, PgPool};
+
+const DEFAULT_CAPACITY: usize = 10_000;
+
+/// Entries per kind of entity, set with `PLAY_CACHE_CAPACITY`.
+fn capacity() -> usize {
+    std::env::var("PLAY_CACHE_CAPACITY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_CAPACITY)
+}
+
+/// A size-bounded map that keeps recently used entries. Inserts go to a hot
+/// generation; when it fills up it becomes the cold one and the old cold one
+/// is dropped. Hits in the cold generation are promoted.
+struct Generations<K, V> {
+    capacity: usize,
+    hot: HashMap<K, V>,
+    cold: HashMap<K, V>,
+}
+
+impl<K: Eq + Hash, V: Clone> Generations<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(2),
+            hot: HashMap::new(),
+            cold: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        if let Some(value) = self.hot.get(key) {
+            return Some(value.clone());
+        }
+        let (key, value) = self.cold.remove_entry(key)?;
+        self.insert(key, value.clone());
+        Some(value)
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.hot.len() >= self.capacity / 2 && !self.hot.contains_key(&key) {
+            self.cold = std::mem::take(&mut self.hot);
+        }
+        self.hot.insert(key, value);
+    }
+
+    fn clear(&mut self) {
+        self.hot.clear();
+        self.cold.clear();
+    }
+}
+
+/// Artist credit as it appears on a play: name and optional MBID.
+pub type ArtistKey = (String, Option<String>);
+
+pub struct EntityCache {
+    /// Artist credit → `artists_extended.id`
+    artists: Mutex<Generations<ArtistKey, i32>>,
+    /// Release MBID → name it was last upserted with
+    releases: Mutex<Generations<Uuid, String>>,
+    /// Recording MBID → name it was last upserted with
+    recordings: Mutex<Generations<Uuid, String>>,
+    /// Track or release name → discriminant extracted from it
+    discriminants: Mutex<Generations<String, Option<String>>>,
+    /// `entity_cache_generation` when the cache was last synced
+    generation: AtomicI64,
+}
+
+impl EntityCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            artists: Mutex::new(Generations::new(capacity)),
+            releases: Mutex::new(Generations::new(capacity)),
+            recordings: Mutex::new(Generations::new(capacity)),
+            discriminants: Mutex::new(Generations::new(capacity)),
+            generation: AtomicI64::new(i64::MIN),
+        }
+    }
+
+    /// The cache shared by every `PlayIngestor` in the process.
+    pub fn shared() -> Arc<EntityCache> {
+        static SHARED: OnceLock<Arc<EntityCache>> = OnceLock::new();
+        SHARED
+            .get_or_init(|| Arc::new(EntityCache::new(capacity())))
+            .clone()
+    }
+
+    pub fn artist(&self, key: &ArtistKey) -> Option<i32> {
+        self.artists.lock().unwrap().get(key)
+    }
+
+    pub fn insert_artist(&self, key: ArtistKey, id: i32) {
+        self.artists.lock().unwrap().insert(key, id);
+    }
+
+    /// Whether the release was upserted with this name.
+    pub fn has_release(&self, mbid: &Uuid, name: &str) -> bool {
+        self.releases.lock().unwrap().get(mbid).as_deref() == Some(name)
+    }
+
+    pub fn insert_release(&self, mbid: Uuid, name: String) {
+        self.releases.lock().unwrap().insert(mbid, name);
+    }
+
+    /// Whether the recording was upserted with this name.
+    pub fn has_recording(&self, mbid: &Uuid, name: &str) -> bool {
+        self.recordings.lock().unwrap().get(mbid).as_deref() == Some(name)
+    }
+
+    pub fn insert_recording(&self, mbid: Uuid, name: String) {
+        self.recordings.lock().unwrap().insert(mbid, name);
+    }
+
+    pub fn discriminant(&self, name: &str) -> Option<Option<String>> {
+        self.discriminants.lock().unwrap().get(&name.to_string())
+    }
+
+    pub fn insert_discriminant(&self, name: String, discriminant: Option<String>) {
+        self.discriminants
+            .lock()
+            .unwrap()
+            .insert(name, discriminant);
+    }
+
+    pub fn clear(&self) {
+        self.artists.lock().unwrap().clear();
+        self.releases.lock().unwrap().clear();
+        self.recordings.lock().unwrap().clear();
+        self.discriminants.lock().unwrap().clear();
+    }
+
+    /// Clear this cache and, before their next batch, the caches of every
+    /// other process. Called once merged, split or unmerged entities are
+    /// committed.
+    pub async fn invalidate(&self, pool: &PgPool) -> sqlx::Result<()> {
+        sqlx::query("UPDATE entity_cache_generation SET generation = generation + 1")
+            .execute(pool)
+            .await?;
+        self.clear();
+        Ok(())
+    }
+
+    /// Clear the cache if another process invalidated it since the last sync.
+    pub async fn sync(&self, pool: &PgPool) -> sqlx::Result<()> {
+        let generation: i64 = sqlx::query_scalar("SELECT generation FROM entity_cache_generation")
+            .fetch_one(pool)
+            .await?;
+        if self.generation.swap(generation, Ordering::SeqCst) != generation {
+            self.clear();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generations_keep_recent_entries() {
+        let mut cache = Generations::new(4);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        // The hot generation is full, so this rotates "a" and "b" to cold
+        cache.insert("c", 3);
+        assert_eq!(cache.get(&"a"), Some(1));
+        // Promoting "a" filled the hot generation again, dropping "b"
+        cache.insert("d", 4);
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"d"), Some(4));
+    }
+
+    #[test]
+    fn test_release_cache_tracks_names() {
+        let cache = EntityCache::new(16);
+        let mbid = Uuid::nil();
+        assert!(!cache.has_release(&mbid, "Windowlicker"));
+
+        cache.insert_release(mbid, "Windowlicker".to_string());
+        assert!(cache.has_release(&mbid, "Windowlicker"));
+        // A new name for the same release has to be written again
+        assert!(!cache.has_release(&mbid, "Windowlicker EP"));
+
+        cache.clear();
+        assert!(!cache.has_release(&mbid, "Windowlicker"));
+    }
+}
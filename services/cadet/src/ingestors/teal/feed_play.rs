@@ -9,408 +9,159 @@ use jacquard_common::{
 };
 use rocketman::{ingestion::LexiconIngestor, types::event::Event};
 use serde_json::Value;
-use sqlx::{types::Uuid, PgPool};
-use unicode_normalization::UnicodeNormalization;
-
-use super::{assemble_at_uri, normalize_legacy_record_type};
-
-#[derive(Debug, Clone)]
-struct FuzzyMatchCandidate {
-    artist_id: i32,
-    name: String,
-    confidence: f64,
+use sqlx::{types::Uuid, PgConnection, PgPool};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use super::cleaning;
+use super::consolidation::{ConsolidationReport, EntityRef, Merge, MergedKind, Split};
+use super::credits::{credit_parts, split_credit};
+use super::entity_cache::{ArtistKey, EntityCache};
+use super::merge_log;
+use super::play_buffer::PlayBuffer;
+use super::{assemble_at_uri, normalize_legacy_record_type, validate};
+use crate::ingestors::dead_letter::FailedRecord;
+use crate::musicbrainz::{cover_art, enrich, isrc, EntityType};
+
+/// Fuzzy matches at or above this confidence reuse the existing artist.
+pub(crate) const AUTO_MATCH_CONFIDENCE: f64 = 0.92;
+/// Fuzzy matches at or above this confidence are logged as potential matches.
+pub(crate) const CANDIDATE_MATCH_CONFIDENCE: f64 = 0.85;
+/// `plays.mbid_match_method` and `plays.release_match_method` of plays whose
+/// recording or release was matched by name to one already played by the same
+/// artists.
+const FUZZY_MATCH_METHOD: &str = "fuzzy";
+/// Releases or recordings taken from the trigram search for a name, before
+/// they are narrowed down to those the play's artists were played with.
+const FUZZY_CANDIDATES: i64 = 50;
+/// How many seconds a fuzzy matched recording's earlier plays may differ from
+/// the play's duration, when the play has no release to match it by.
+const FUZZY_DURATION_TOLERANCE_SECS: i32 = 5;
+
+/// Releases and recordings, which are matched by name within their artists'
+/// plays when a play has no MBID for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum NamedEntity {
+    Release,
+    Recording,
 }
 
-struct MusicBrainzCleaner;
-
-impl MusicBrainzCleaner {
-    /// Words commonly found in parenthetical/bracketed content that can be removed.
-    /// Kept in sync with apps/amethyst/lib/musicbrainzCleaner.ts GUFF_WORDS.
-    const GUFF_WORDS: &'static [&'static str] = &[
-        "a cappella",
-        "acoustic",
-        "anniversary",
-        "bonus",
-        "censored",
-        "clean",
-        "club",
-        "clubmix",
-        "composition",
-        "cut",
-        "dance",
-        "deluxe",
-        "demo",
-        "dialogue",
-        "dirty",
-        "dub",
-        "edit",
-        "edition",
-        "excerpt",
-        "expanded",
-        "explicit",
-        "extended",
-        "feat",
-        "featuring",
-        "ft",
-        "hd",
-        "hifi",
-        "hi-fi",
-        "instrumental",
-        "interlude",
-        "intro",
-        "karaoke",
-        "live",
-        "long",
-        "main",
-        "master",
-        "maxi",
-        "megamix",
-        "mix",
-        "mono",
-        "official",
-        "orchestral",
-        "original",
-        "outro",
-        "outtake",
-        "outtakes",
-        "piano",
-        "preview",
-        "prod",
-        "produced",
-        "production",
-        "quadraphonic",
-        "radio",
-        "rap",
-        "re-edit",
-        "reedit",
-        "refix",
-        "rehearsal",
-        "reinterpreted",
-        "release",
-        "released",
-        "remake",
-        "remaster",
-        "remastered",
-        "remix",
-        "remixed",
-        "remode",
-        "reprise",
-        "rework",
-        "reworked",
-        "rmx",
-        "session",
-        "short",
-        "single",
-        "skit",
-        "special",
-        "stereo",
-        "studio",
-        "take",
-        "takes",
-        "tape",
-        "track",
-        "tryout",
-        "uncensored",
-        "unknown",
-        "unplugged",
-        "untitled",
-        "ver",
-        "version",
-        "video",
-        "vocal",
-        "vs",
-        "with",
-        "without",
-    ];
-
-    /// Generic track names that commonly need disambiguation info preserved.
-    const GENERIC_TRACK_NAMES: &'static [&'static str] = &[
-        "beat", "dream", "five", "four", "heart", "high", "home", "life", "love", "low", "music",
-        "one", "piece", "song", "sound", "three", "time", "track", "tune", "two",
-    ];
-
-    const SHORT_NAME_THRESHOLD: usize = 15;
-    const COMMON_PHRASE_LENGTH: usize = 20;
-    const MIN_ARTIST_NAME_LENGTH: usize = 4;
-
-    /// Find the byte range of the first matched-depth bracket pair.
-    /// Returns (open_byte, content_start_byte, content_end_byte, close_end_byte).
-    fn find_bracketed(s: &str, open: char, close: char) -> Option<(usize, usize, usize, usize)> {
-        let mut chars = s.char_indices();
-        // Find opening bracket
-        let (open_byte, _) = chars.find(|&(_, c)| c == open)?;
-        let content_start = open_byte + open.len_utf8();
-        let mut depth: usize = 1;
-        for (i, c) in chars {
-            if c == open {
-                depth += 1;
-            } else if c == close {
-                depth -= 1;
-                if depth == 0 {
-                    return Some((open_byte, content_start, i, i + close.len_utf8()));
-                }
-            }
+impl NamedEntity {
+    fn table(self) -> &'static str {
+        match self {
+            NamedEntity::Release => "releases",
+            NamedEntity::Recording => "recordings",
         }
-        None // unbalanced
     }
 
-    /// Check if parenthetical content should be kept for disambiguation.
-    /// Partially mirrors the TS shouldKeepForDisambiguation logic (remix/feat only, not version).
-    fn should_keep_for_disambiguation(content: &str, base_name: &str, kind: &str) -> bool {
-        let content_lower = content.to_lowercase();
-        let base_lower = base_name.to_lowercase();
-
-        let is_relevant = match kind {
-            "remix" => {
-                content_lower.contains("remix")
-                    || content_lower.contains("rmx")
-                    || content_lower.contains("rework")
-                    || content_lower.contains("refix")
-                    || content_lower.contains("remode")
-            }
-            "feat" => {
-                content_lower.contains("feat")
-                    || content_lower.contains("ft")
-                    || content_lower.contains("featuring")
-            }
-            _ => false,
-        };
-        if !is_relevant {
-            return false;
+    /// The column of `plays` that links to the entity.
+    fn play_column(self) -> &'static str {
+        match self {
+            NamedEntity::Release => "release_mbid",
+            NamedEntity::Recording => "recording_mbid",
         }
-
-        // Generic word check
-        let is_generic = Self::GENERIC_TRACK_NAMES
-            .iter()
-            .any(|&w| base_lower == w || base_lower.starts_with(&format!("{w} ")));
-        let char_count = base_name.chars().count();
-        let is_short = char_count < Self::SHORT_NAME_THRESHOLD;
-        let word_count = base_name.split_whitespace().count();
-        let is_common_phrase = word_count <= 3 && char_count < Self::COMMON_PHRASE_LENGTH;
-
-        // Check for artist name in content
-        let keyword_pattern: &[&str] = match kind {
-            "remix" => &["remix", "rmx", "rework", "refix", "remode"],
-            _ => &["feat", "ft", "featuring"],
-        };
-        let has_artist_name = content_lower.split_whitespace().any(|w| {
-            w.chars().count() >= Self::MIN_ARTIST_NAME_LENGTH
-                && !keyword_pattern.iter().any(|kw| w.contains(kw))
-        });
-
-        is_generic || (is_short && is_common_phrase) || has_artist_name
     }
+}
 
-    /// Remove a bracketed section from `s` if it is guff and not needed for disambiguation.
-    /// Works for both () and []. Returns the cleaned string.
-    fn strip_guff_bracket(s: &str, open: char, close: char, check_disambiguation: bool) -> String {
-        if let Some((open_byte, content_start, content_end, close_end)) =
-            Self::find_bracketed(s, open, close)
-        {
-            let content = &s[content_start..content_end];
-            let base_name = s[..open_byte].trim();
-
-            if Self::is_likely_guff(&content.to_lowercase()) {
-                if check_disambiguation {
-                    let keep_remix =
-                        Self::should_keep_for_disambiguation(content, base_name, "remix");
-                    let keep_feat =
-                        Self::should_keep_for_disambiguation(content, base_name, "feat");
-                    if keep_remix || keep_feat {
-                        return s.to_string();
-                    }
-                    // Don't remove if it would leave the name empty
-                    let after = s[close_end..].trim();
-                    if base_name.is_empty() && after.is_empty() {
-                        return s.to_string();
-                    }
-                }
-                let result = format!("{}{}", &s[..open_byte], &s[close_end..]);
-                return result.split_whitespace().collect::<Vec<_>>().join(" ");
-            }
-        }
-        s.to_string()
-    }
-
-    /// Case-insensitive find of `pattern` in `s`, returning the byte offset in `s`.
-    /// Safe for non-ASCII: searches char-by-char in the original string, avoiding
-    /// the to_lowercase() byte-length mismatch problem.
-    fn find_case_insensitive(s: &str, pattern: &str) -> Option<usize> {
-        let pat_lower = pattern.to_lowercase();
-        let pat_char_count = pat_lower.chars().count();
-        if pat_char_count == 0 {
-            return None;
-        }
-        for (byte_pos, _) in s.char_indices() {
-            let candidate: String = s[byte_pos..].chars().take(pat_char_count).collect();
-            if candidate.to_lowercase() == pat_lower {
-                return Some(byte_pos);
-            }
-        }
-        None
-    }
-
-    /// Clean artist name by removing common variations and guff
-    fn clean_artist_name(name: &str) -> String {
-        let mut cleaned = name.trim().to_string();
-
-        // Remove common featuring patterns (take the first match)
-        for pat in &[" feat", " ft.", " featuring"] {
-            if let Some(byte_pos) = Self::find_case_insensitive(&cleaned, pat) {
-                cleaned = cleaned[..byte_pos].trim().to_string();
-                break;
-            }
-        }
-
-        // Remove parenthetical guff (no disambiguation check for artist names)
-        cleaned = Self::strip_guff_bracket(&cleaned, '(', ')', false);
-        cleaned = Self::strip_guff_bracket(&cleaned, '[', ']', false);
-
-        // Don't strip "The " prefix -- MusicBrainz indexes artists with "The"
-        // (e.g., "The Beatles", "The Rolling Stones") and stripping causes
-        // exact search misses in the matching pipeline.
+#[derive(Debug, Clone)]
+pub(crate) struct FuzzyMatchCandidate {
+    pub(crate) artist_id: i32,
+    pub(crate) name: String,
+    pub(crate) confidence: f64,
+}
 
-        cleaned
-    }
+pub struct PlayIngestor {
+    sql: PgPool,
+    cache: Arc<EntityCache>,
+    /// Where live plays wait to be written in batches
+    buffer: Option<Arc<PlayBuffer>>,
+}
 
-    /// Clean track name by removing common variations and guff.
-    /// Includes disambiguation-aware cleaning: preserves remix/feat info for
-    /// short or generic track names (matching TS frontend logic).
-    fn clean_track_name(name: &str) -> String {
-        let mut cleaned = name.trim().to_string();
+const DEFAULT_PLAY_BATCH_SIZE: usize = 250;
 
-        // Strip leading/trailing decorative characters (aligned with TS frontend)
-        let decorative: &[char] = &[
-            '*', '~', '\u{00b7}', '\u{2022}', '\u{2605}', '\u{2606}', '\u{266a}', '\u{266b}', '|',
-            '_', '>', '<',
-        ];
-        cleaned = cleaned
-            .trim_start_matches(|c: char| c.is_whitespace() || decorative.contains(&c))
-            .to_string();
-        cleaned = cleaned
-            .trim_end_matches(|c: char| c.is_whitespace() || decorative.contains(&c))
-            .to_string();
-        cleaned = cleaned.trim().to_string();
-
-        // Remove parenthetical guff (with disambiguation check) -- process all groups
-        loop {
-            let next = Self::strip_guff_bracket(&cleaned, '(', ')', true);
-            if next == cleaned {
-                break;
-            }
-            cleaned = next;
-        }
-        loop {
-            let next = Self::strip_guff_bracket(&cleaned, '[', ']', true);
-            if next == cleaned {
-                break;
-            }
-            cleaned = next;
-        }
-
-        // Remove dash-separated suffixes that look like guff or remix info
-        // Common in scrobbler data: "Song - Single Version", "Song - Artist Remix"
-        if let Some(dash_pos) = cleaned.find(" - ") {
-            let base_name = cleaned[..dash_pos].trim();
-            let suffix = cleaned[dash_pos + 3..].trim();
-            if !base_name.is_empty() && !suffix.is_empty() {
-                let should_keep_remix =
-                    Self::should_keep_for_disambiguation(suffix, base_name, "remix");
-                let should_keep_feat =
-                    Self::should_keep_for_disambiguation(suffix, base_name, "feat");
-                if Self::is_likely_guff(&suffix.to_lowercase())
-                    && !should_keep_remix
-                    && !should_keep_feat
-                {
-                    cleaned = base_name.to_string();
-                }
-            }
-        }
+/// Plays written per transaction by imports, set with `PLAY_BATCH_SIZE`.
+pub fn play_batch_size() -> usize {
+    std::env::var("PLAY_BATCH_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_PLAY_BATCH_SIZE)
+}
 
-        // Remove featuring artists from track titles (with disambiguation check)
-        for pat in &[" feat", " ft.", " featuring"] {
-            if let Some(byte_pos) = Self::find_case_insensitive(&cleaned, pat) {
-                let base_name = cleaned[..byte_pos].trim();
-                let feat_content = cleaned[byte_pos..].trim();
-                if !Self::should_keep_for_disambiguation(feat_content, base_name, "feat") {
-                    cleaned = base_name.to_string();
-                }
-                break;
-            }
+/// Fills in folded names for artists written before they were stored, or
+/// renamed since. Returns how many were folded.
+pub async fn fold_artist_names(pool: &PgPool) -> anyhow::Result<u64> {
+    let mut folded = 0;
+    loop {
+        let batch: Vec<(i32, String)> = sqlx::query_as(
+            "SELECT id, name FROM artists_extended WHERE name_folded IS NULL ORDER BY id LIMIT 1000",
+        )
+        .fetch_all(pool)
+        .await?;
+        if batch.is_empty() {
+            return Ok(folded);
         }
 
-        cleaned
+        let (ids, names): (Vec<i32>, Vec<String>) = batch
+            .into_iter()
+            .map(|(id, name)| (id, cleaning::fold_artist_name(&name)))
+            .unzip();
+        folded += sqlx::query(
+            r#"
+            UPDATE artists_extended ae SET name_folded = f.name_folded
+            FROM UNNEST($1::int[], $2::text[]) AS f(id, name_folded)
+            WHERE ae.id = f.id
+            "#,
+        )
+        .bind(&ids)
+        .bind(&names)
+        .execute(pool)
+        .await?
+        .rows_affected();
     }
+}
 
-    /// Check if parenthetical content is likely "guff" that should be removed
-    fn is_likely_guff(content: &str) -> bool {
-        let content_lower = content.to_lowercase();
-        let words: Vec<&str> = content_lower.split_whitespace().collect();
-
-        // Count guff words (strip trailing punctuation for matching)
-        let guff_word_count = words
-            .iter()
-            .filter(|word| {
-                let stripped = word.trim_end_matches(|c: char| c.is_ascii_punctuation());
-                Self::GUFF_WORDS.contains(word) || Self::GUFF_WORDS.contains(&stripped)
-            })
-            .count();
-
-        // Check for years (19XX or 20XX) -- match 4-digit years only, not "Part 19" etc.
-        let has_year = content_lower.as_bytes().windows(4).any(|w| {
-            (w[0] == b'1' && w[1] == b'9' || w[0] == b'2' && w[1] == b'0')
-                && w[2].is_ascii_digit()
-                && w[3].is_ascii_digit()
-        });
-
-        // >50% guff words, or contains years, or short and contains a guff word
-        guff_word_count > words.len() / 2
-            || has_year
-            || (words.len() <= 2
-                && Self::GUFF_WORDS
-                    .iter()
-                    .any(|&guff| content_lower.contains(guff)))
-    }
+/// A play waiting to be written by [`PlayIngestor::insert_plays`].
+#[derive(Debug, Clone)]
+pub struct PendingPlay {
+    pub record: types::fm_teal::feed::play::Play,
+    pub uri: String,
+    pub cid: String,
+    pub did: String,
+    pub rkey: String,
+}
 
-    /// Normalize text for comparison (remove special chars, lowercase).
-    /// Aligned with frontend normalizeForComparison in musicbrainzCleaner.ts:
-    /// 1. NFD decomposition to separate base characters from accents
-    /// 2. Strip combining diacritical marks (U+0300..U+036F)
-    /// 3. Keep all Unicode alphanumeric characters (CJK, Cyrillic, etc.)
-    /// 4. NFC recomposition, lowercase, whitespace collapse
-    fn normalize_for_comparison(text: &str) -> String {
-        // Step 1+2: NFD decompose, strip combining marks (accents)
-        let stripped: String = text
-            .nfd()
-            .filter(|c| !('\u{0300}'..='\u{036f}').contains(c))
-            .collect();
-        // Step 3+4: keep alphanumeric + whitespace, NFC, lowercase, collapse whitespace
-        stripped
-            .nfc()
-            .filter(|c| c.is_alphanumeric() || c.is_whitespace())
-            .collect::<String>()
-            .to_lowercase()
-            .split_whitespace()
-            .collect::<Vec<&str>>()
-            .join(" ")
-    }
+/// Indices of the last occurrence of each URI, in order. A play written twice
+/// in one batch keeps its last version, and Postgres refuses to upsert the same
+/// row twice in one statement.
+fn last_occurrences<'a>(uris: impl Iterator<Item = &'a str> + Clone) -> Vec<usize> {
+    let last_index: HashMap<&str, usize> = uris
+        .clone()
+        .enumerate()
+        .map(|(index, uri)| (uri, index))
+        .collect();
+    uris.enumerate()
+        .filter(|(index, uri)| last_index[uri] == *index)
+        .map(|(index, _)| index)
+        .collect()
 }
 
-pub struct PlayIngestor {
-    sql: PgPool,
+/// The key a part of a combined credit is looked up by among folded artist
+/// names. Not [`cleaning::fold_artist_name`], which would strip the `feat.`
+/// the part is being split on.
+fn whole_name_key(part: &str) -> String {
+    cleaning::normalize_for_comparison(part)
 }
 
 fn mbid_value(mbid: &str) -> &str {
     mbid.strip_prefix("mbid:").unwrap_or(mbid)
 }
 
-fn uri_mbid_value(mbid: &UriValue) -> &str {
+pub(crate) fn uri_mbid_value(mbid: &UriValue) -> &str {
     mbid_value(mbid.as_str())
 }
 
-fn clean(
-    record: &types::fm_teal::feed::play::Play,
-) -> types::fm_teal::feed::play::Play {
+pub(crate) fn clean(record: &types::fm_teal::feed::play::Play) -> types::fm_teal::feed::play::Play {
     let mut cleaned = record.clone();
 
     // Clean artist MBIDs inside artists vector, if present
@@ -452,14 +203,160 @@ fn clean(
 
 impl PlayIngestor {
     pub fn new(sql: PgPool) -> Self {
-        Self { sql }
+        Self {
+            sql,
+            cache: EntityCache::shared(),
+            buffer: None,
+        }
     }
 
-    /// Batch consolidate synthetic artists that match existing MusicBrainz artists
+    /// Queue ingested plays in `buffer` instead of writing each on its own.
+    pub fn with_buffer(mut self, buffer: Arc<PlayBuffer>) -> Self {
+        self.buffer = Some(buffer);
+        self
+    }
+
+    /// Split synthetic artists whose names are combined credits, such as
+    /// `A feat. B` sent before credits were split at ingest. Each play linked
+    /// to one is linked to its artists instead, in the combined artist's place.
+    /// With `dry_run`, only reports what would be split.
+    pub async fn split_combined_artists(&self, dry_run: bool) -> anyhow::Result<Vec<Split>> {
+        let synthetic: Vec<(i32, String)> =
+            sqlx::query_as("SELECT id, name FROM artists_extended WHERE mbid_type = 'synthetic'")
+                .fetch_all(&self.sql)
+                .await?;
+
+        let known =
+            Self::known_whole_names(&self.sql, synthetic.iter().map(|(_, name)| name.as_str()))
+                .await?;
+        let keep_whole = |part: &str| known.contains(&whole_name_key(part));
+
+        let mut splits = Vec::new();
+        for (combined_id, name) in synthetic {
+            let credits = split_credit(&name, &keep_whole);
+            if credits.len() < 2 {
+                continue;
+            }
+            let mut split = Split {
+                merge_id: None,
+                from: EntityRef {
+                    id: Some(combined_id),
+                    mbid: None,
+                    name: name.clone(),
+                },
+                into: credits.iter().map(|credit| credit.name.clone()).collect(),
+            };
+            if dry_run {
+                splits.push(split);
+                continue;
+            }
+
+            let mut tx = self.sql.begin().await?;
+            let mut artist_ids = Vec::with_capacity(credits.len());
+            for credit in &credits {
+                artist_ids.push(
+                    self.find_or_create_artist_with_fuzzy_matching(
+                        &mut tx,
+                        &credit.name,
+                        None,
+                        "",
+                        None,
+                    )
+                    .await?,
+                );
+            }
+            let credit: String = credits
+                .iter()
+                .map(|credit| format!("{}{}", credit.name, credit.join_phrase))
+                .collect();
+            split.merge_id = Some(
+                merge_log::record_artist_split(&mut tx, combined_id, &artist_ids, &credit).await?,
+            );
+            let names: Vec<&str> = credits.iter().map(|credit| credit.name.as_str()).collect();
+            let offsets: Vec<i16> = (0..credits.len() as i16).collect();
+            let join_phrases: Vec<&str> = credits
+                .iter()
+                .map(|credit| credit.join_phrase.as_str())
+                .collect();
+
+            // Make room after the combined artist for the artists it splits into
+            sqlx::query(
+                r#"
+                    UPDATE play_to_artists_extended later SET position = later.position + $2
+                    FROM play_to_artists_extended combined
+                    WHERE combined.artist_id = $1
+                      AND later.play_uri = combined.play_uri
+                      AND later.position > combined.position
+                "#,
+            )
+            .bind(combined_id)
+            .bind(credits.len() as i16 - 1)
+            .execute(&mut *tx)
+            .await?;
+            let linked = sqlx::query(
+                r#"
+                    INSERT INTO play_to_artists_extended (
+                        play_uri, artist_id, artist_name, position, join_phrase
+                    )
+                    SELECT
+                        combined.play_uri, split.artist_id, split.name,
+                        COALESCE(combined.position, 0) + split.idx,
+                        CASE WHEN split.idx = $6 THEN combined.join_phrase
+                             ELSE split.join_phrase END
+                    FROM play_to_artists_extended combined
+                    CROSS JOIN UNNEST($2::int4[], $3::text[], $4::int2[], $5::text[])
+                        AS split(artist_id, name, idx, join_phrase)
+                    WHERE combined.artist_id = $1
+                    ON CONFLICT (play_uri, artist_id) DO NOTHING
+                "#,
+            )
+            .bind(combined_id)
+            .bind(&artist_ids)
+            .bind(&names)
+            .bind(&offsets)
+            .bind(&join_phrases)
+            .bind(credits.len() as i16 - 1)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query("DELETE FROM play_to_artists_extended WHERE artist_id = $1")
+                .bind(combined_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM artists_extended WHERE id = $1")
+                .bind(combined_id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+
+            splits.push(split);
+            tracing::info!(
+                "✂️ Split '{}' into {} artists ({} play links)",
+                name,
+                credits.len(),
+                linked.rows_affected()
+            );
+        }
+
+        if !dry_run && !splits.is_empty() {
+            sqlx::query!("REFRESH MATERIALIZED VIEW mv_artist_play_counts;")
+                .execute(&self.sql)
+                .await?;
+            // The combined artists may still be cached
+            self.cache.invalidate(&self.sql).await?;
+        }
+        Ok(splits)
+    }
+
+    /// Batch consolidate synthetic artists that match existing MusicBrainz artists.
+    /// Each synthetic artist is merged into its closest match only. With
+    /// `dry_run`, only reports what would be merged, leaving out the artists
+    /// in `split_away` that splitting combined credits would remove first.
     pub async fn consolidate_synthetic_artists(
         &self,
         min_confidence: f64,
-    ) -> anyhow::Result<usize> {
+        dry_run: bool,
+        split_away: &HashSet<i32>,
+    ) -> anyhow::Result<Vec<Merge>> {
         tracing::info!(
             "🔄 Starting batch consolidation of synthetic artists with confidence >= {:.2}",
             min_confidence
@@ -487,11 +384,16 @@ impl PlayIngestor {
         .fetch_all(&self.sql)
         .await?;
 
-        let mut consolidated_count = 0;
+        let mut merges = Vec::new();
+        let mut merged = HashSet::new();
 
         for candidate in consolidation_candidates {
             let synthetic_id = candidate.synthetic_id;
             let target_id = candidate.target_id;
+            // Split before merging when not a dry run, so gone by now
+            if split_away.contains(&synthetic_id) {
+                continue;
+            }
             let similarity = candidate.similarity_score.unwrap_or(0.0) as f64;
 
             // Double-check with our improved similarity calculation
@@ -500,7 +402,44 @@ impl PlayIngestor {
 
             let final_confidence = similarity.max(calculated_similarity);
 
-            if final_confidence >= min_confidence {
+            if final_confidence >= min_confidence && merged.insert(synthetic_id) {
+                let merge = |merge_id: Option<i64>, plays: u64| Merge {
+                    merge_id,
+                    kind: MergedKind::Artist,
+                    from: EntityRef {
+                        id: Some(synthetic_id),
+                        mbid: None,
+                        name: candidate.synthetic_name.clone(),
+                    },
+                    into: EntityRef {
+                        id: Some(target_id),
+                        mbid: candidate.target_mbid,
+                        name: candidate.target_name.clone(),
+                    },
+                    confidence: final_confidence,
+                    plays,
+                };
+                if dry_run {
+                    let plays: i64 = sqlx::query_scalar(
+                        "SELECT COUNT(*) FROM play_to_artists_extended WHERE artist_id = $1",
+                    )
+                    .bind(synthetic_id)
+                    .fetch_one(&self.sql)
+                    .await?;
+                    merges.push(merge(None, plays as u64));
+                    continue;
+                }
+
+                let mut tx = self.sql.begin().await?;
+                let merge_id = merge_log::record_artist_merge(
+                    &mut tx,
+                    synthetic_id,
+                    target_id,
+                    Some(final_confidence),
+                    merge_log::CONSOLIDATION_METHOD,
+                )
+                .await?;
+
                 // Move all play relationships from synthetic artist to MusicBrainz artist
                 let moved_plays = sqlx::query!(
                     r#"
@@ -517,7 +456,7 @@ impl PlayIngestor {
                     candidate.target_name,
                     synthetic_id
                 )
-                .execute(&self.sql)
+                .execute(&mut *tx)
                 .await?;
 
                 // Remove duplicate relationships that couldn't be moved
@@ -525,15 +464,16 @@ impl PlayIngestor {
                     "DELETE FROM play_to_artists_extended WHERE artist_id = $1",
                     synthetic_id
                 )
-                .execute(&self.sql)
+                .execute(&mut *tx)
                 .await?;
 
                 // Remove the synthetic artist
                 sqlx::query!("DELETE FROM artists_extended WHERE id = $1", synthetic_id)
-                    .execute(&self.sql)
+                    .execute(&mut *tx)
                     .await?;
+                tx.commit().await?;
 
-                consolidated_count += 1;
+                merges.push(merge(Some(merge_id), moved_plays.rows_affected()));
 
                 tracing::info!(
                     "✅ Consolidated '{}' → '{}' (confidence: {:.2}, moved {} plays)",
@@ -546,7 +486,7 @@ impl PlayIngestor {
         }
 
         // Refresh materialized views after consolidation
-        if consolidated_count > 0 {
+        if !dry_run && !merges.is_empty() {
             tracing::info!("🔄 Refreshing materialized views after consolidation");
             sqlx::query!("REFRESH MATERIALIZED VIEW mv_artist_play_counts;")
                 .execute(&self.sql)
@@ -554,17 +494,26 @@ impl PlayIngestor {
         }
 
         tracing::info!(
-            "🎉 Batch consolidation complete: {} artists consolidated",
-            consolidated_count
+            "🎉 Batch consolidation complete: {} artists {}",
+            merges.len(),
+            if dry_run {
+                "would be consolidated"
+            } else {
+                "consolidated"
+            }
         );
-        Ok(consolidated_count)
+        // Merged entities may still be cached under their old IDs
+        self.cache.invalidate(&self.sql).await?;
+        Ok(merges)
     }
 
-    /// Find and consolidate duplicate releases/albums (requires matching artist context)
+    /// Find and consolidate duplicate releases/albums (requires matching artist context).
+    /// With `dry_run`, only reports what would be merged.
     pub async fn consolidate_duplicate_releases(
         &self,
         min_confidence: f64,
-    ) -> anyhow::Result<usize> {
+        dry_run: bool,
+    ) -> anyhow::Result<Vec<Merge>> {
         tracing::info!(
             "🔄 Starting release consolidation with confidence >= {:.2} (requires artist context)",
             min_confidence
@@ -602,7 +551,9 @@ impl PlayIngestor {
         .fetch_all(&self.sql)
         .await?;
 
-        let mut consolidated_count = 0;
+        let mut merges = Vec::new();
+        // Each pair is found both ways round, and merged ones are gone
+        let mut removed = HashSet::new();
 
         for candidate in release_candidates {
             let similarity = candidate.similarity_score.unwrap_or(0.0) as f64;
@@ -618,7 +569,11 @@ impl PlayIngestor {
             let final_confidence = similarity.max(cleaned_similarity);
 
             // Require high confidence AND shared artists for album consolidation
-            if final_confidence >= min_confidence && shared_artists > 0 {
+            if final_confidence >= min_confidence
+                && shared_artists > 0
+                && !removed.contains(&candidate.release1_mbid)
+                && !removed.contains(&candidate.release2_mbid)
+            {
                 // Choose the release with more plays as the canonical one
                 let r1_plays: i64 = sqlx::query_scalar!(
                     "SELECT COUNT(*) FROM plays WHERE release_mbid = $1",
@@ -649,6 +604,43 @@ impl PlayIngestor {
                         candidate.release2_name.clone(),
                     )
                 };
+                let (remove_name, remove_plays) = if r1_plays >= r2_plays {
+                    (&candidate.release2_name, r2_plays)
+                } else {
+                    (&candidate.release1_name, r1_plays)
+                };
+                removed.insert(remove_mbid);
+                let merge = |merge_id: Option<i64>, plays: u64| Merge {
+                    merge_id,
+                    kind: MergedKind::Release,
+                    from: EntityRef {
+                        id: None,
+                        mbid: Some(remove_mbid),
+                        name: remove_name.clone(),
+                    },
+                    into: EntityRef {
+                        id: None,
+                        mbid: Some(keep_mbid),
+                        name: keep_name.clone(),
+                    },
+                    confidence: final_confidence,
+                    plays,
+                };
+                if dry_run {
+                    merges.push(merge(None, remove_plays as u64));
+                    continue;
+                }
+
+                let mut tx = self.sql.begin().await?;
+                let merge_id = merge_log::record_play_merge(
+                    &mut tx,
+                    MergedKind::Release,
+                    remove_mbid,
+                    keep_mbid,
+                    Some(final_confidence),
+                    merge_log::CONSOLIDATION_METHOD,
+                )
+                .await?;
 
                 // Update plays to use the canonical release
                 let updated_plays = sqlx::query!(
@@ -657,23 +649,20 @@ impl PlayIngestor {
                     keep_name,
                     remove_mbid
                 )
-                .execute(&self.sql)
+                .execute(&mut *tx)
                 .await?;
 
                 // Remove the duplicate release
                 sqlx::query!("DELETE FROM releases WHERE mbid = $1", remove_mbid)
-                    .execute(&self.sql)
+                    .execute(&mut *tx)
                     .await?;
+                tx.commit().await?;
 
-                consolidated_count += 1;
+                merges.push(merge(Some(merge_id), updated_plays.rows_affected()));
 
                 tracing::info!(
                     "✅ Consolidated releases: '{}' → '{}' (confidence: {:.2}, {} shared artists, updated {} plays)",
-                    if r1_plays >= r2_plays {
-                        &candidate.release2_name
-                    } else {
-                        &candidate.release1_name
-                    },
+                    remove_name,
                     keep_name,
                     final_confidence,
                     shared_artists,
@@ -683,17 +672,26 @@ impl PlayIngestor {
         }
 
         tracing::info!(
-            "🎉 Release consolidation complete: {} releases consolidated",
-            consolidated_count
+            "🎉 Release consolidation complete: {} releases {}",
+            merges.len(),
+            if dry_run {
+                "would be consolidated"
+            } else {
+                "consolidated"
+            }
         );
-        Ok(consolidated_count)
+        // Merged entities may still be cached under their old IDs
+        self.cache.invalidate(&self.sql).await?;
+        Ok(merges)
     }
 
-    /// Find and consolidate duplicate recordings/tracks (requires matching artist context)
+    /// Find and consolidate duplicate recordings/tracks (requires matching artist context).
+    /// With `dry_run`, only reports what would be merged.
     pub async fn consolidate_duplicate_recordings(
         &self,
         min_confidence: f64,
-    ) -> anyhow::Result<usize> {
+        dry_run: bool,
+    ) -> anyhow::Result<Vec<Merge>> {
         tracing::info!(
             "🔄 Starting recording consolidation with confidence >= {:.2} (requires artist context)",
             min_confidence
@@ -731,7 +729,9 @@ impl PlayIngestor {
         .fetch_all(&self.sql)
         .await?;
 
-        let mut consolidated_count = 0;
+        let mut merges = Vec::new();
+        // Each pair is found both ways round, and merged ones are gone
+        let mut removed = HashSet::new();
 
         for candidate in recording_candidates {
             let similarity = candidate.similarity_score.unwrap_or(0.0) as f64;
@@ -747,7 +747,11 @@ impl PlayIngestor {
             let final_confidence = similarity.max(cleaned_similarity);
 
             // Require high confidence AND shared artists for track consolidation
-            if final_confidence >= min_confidence && shared_artists > 0 {
+            if final_confidence >= min_confidence
+                && shared_artists > 0
+                && !removed.contains(&candidate.recording1_mbid)
+                && !removed.contains(&candidate.recording2_mbid)
+            {
                 // Choose the recording with more plays as canonical
                 let r1_plays: i64 = sqlx::query_scalar!(
                     "SELECT COUNT(*) FROM plays WHERE recording_mbid = $1",
@@ -778,6 +782,43 @@ impl PlayIngestor {
                         candidate.recording2_name.clone(),
                     )
                 };
+                let (remove_name, remove_plays) = if r1_plays >= r2_plays {
+                    (&candidate.recording2_name, r2_plays)
+                } else {
+                    (&candidate.recording1_name, r1_plays)
+                };
+                removed.insert(remove_mbid);
+                let merge = |merge_id: Option<i64>, plays: u64| Merge {
+                    merge_id,
+                    kind: MergedKind::Recording,
+                    from: EntityRef {
+                        id: None,
+                        mbid: Some(remove_mbid),
+                        name: remove_name.clone(),
+                    },
+                    into: EntityRef {
+                        id: None,
+                        mbid: Some(keep_mbid),
+                        name: keep_name.clone(),
+                    },
+                    confidence: final_confidence,
+                    plays,
+                };
+                if dry_run {
+                    merges.push(merge(None, remove_plays as u64));
+                    continue;
+                }
+
+                let mut tx = self.sql.begin().await?;
+                let merge_id = merge_log::record_play_merge(
+                    &mut tx,
+                    MergedKind::Recording,
+                    remove_mbid,
+                    keep_mbid,
+                    Some(final_confidence),
+                    merge_log::CONSOLIDATION_METHOD,
+                )
+                .await?;
 
                 // Update plays to use the canonical recording
                 let updated_plays = sqlx::query!(
@@ -785,23 +826,20 @@ impl PlayIngestor {
                     keep_mbid,
                     remove_mbid
                 )
-                .execute(&self.sql)
+                .execute(&mut *tx)
                 .await?;
 
                 // Remove the duplicate recording
                 sqlx::query!("DELETE FROM recordings WHERE mbid = $1", remove_mbid)
-                    .execute(&self.sql)
+                    .execute(&mut *tx)
                     .await?;
+                tx.commit().await?;
 
-                consolidated_count += 1;
+                merges.push(merge(Some(merge_id), updated_plays.rows_affected()));
 
                 tracing::info!(
                     "✅ Consolidated recordings: '{}' → '{}' (confidence: {:.2}, {} shared artists, updated {} plays)",
-                    if r1_plays >= r2_plays {
-                        &candidate.recording2_name
-                    } else {
-                        &candidate.recording1_name
-                    },
+                    remove_name,
                     keep_name,
                     final_confidence,
                     shared_artists,
@@ -811,10 +849,17 @@ impl PlayIngestor {
         }
 
         tracing::info!(
-            "🎉 Recording consolidation complete: {} recordings consolidated",
-            consolidated_count
+            "🎉 Recording consolidation complete: {} recordings {}",
+            merges.len(),
+            if dry_run {
+                "would be consolidated"
+            } else {
+                "consolidated"
+            }
         );
-        Ok(consolidated_count)
+        // Merged entities may still be cached under their old IDs
+        self.cache.invalidate(&self.sql).await?;
+        Ok(merges)
     }
 
     /// Preview consolidation candidates to show what would be merged
@@ -956,31 +1001,57 @@ impl PlayIngestor {
         Ok(())
     }
 
-    /// Run full batch consolidation for all entity types
-    pub async fn run_full_consolidation(&self) -> anyhow::Result<()> {
+    /// Run full batch consolidation for all entity types. With `dry_run`,
+    /// only reports what would be split and merged.
+    pub async fn run_full_consolidation(
+        &self,
+        min_confidence: f64,
+        dry_run: bool,
+    ) -> anyhow::Result<ConsolidationReport> {
         tracing::info!("🚀 Starting full batch consolidation process");
 
         // First, preview what we would consolidate
-        self.preview_consolidation_candidates(0.92).await?;
+        self.preview_consolidation_candidates(min_confidence)
+            .await?;
 
-        let artist_count = self.consolidate_synthetic_artists(0.92).await?;
-        let release_count = self.consolidate_duplicate_releases(0.92).await?;
-        let recording_count = self.consolidate_duplicate_recordings(0.92).await?;
+        let splits = self.split_combined_artists(dry_run).await?;
+        let split_away: HashSet<i32> = splits.iter().filter_map(|split| split.from.id).collect();
+        let mut merges = self
+            .consolidate_synthetic_artists(min_confidence, dry_run, &split_away)
+            .await?;
+        merges.extend(
+            self.consolidate_duplicate_releases(min_confidence, dry_run)
+                .await?,
+        );
+        merges.extend(
+            self.consolidate_duplicate_recordings(min_confidence, dry_run)
+                .await?,
+        );
+        let report = ConsolidationReport {
+            dry_run,
+            min_confidence,
+            splits,
+            merges,
+        };
 
         tracing::info!(
-            "🎉 Full consolidation complete! Artists: {}, Releases: {}, Recordings: {}",
-            artist_count,
-            release_count,
-            recording_count
+            "🎉 Full consolidation complete! Split credits: {}, Artists: {}, Releases: {}, Recordings: {}",
+            report.splits.len(),
+            report.count(MergedKind::Artist),
+            report.count(MergedKind::Release),
+            report.count(MergedKind::Recording)
         );
 
-        Ok(())
+        Ok(report)
     }
 
     /// Generate a synthetic MBID for artists without MusicBrainz data using database function
-    async fn generate_synthetic_mbid(&self, artist_name: &str) -> anyhow::Result<Uuid> {
+    async fn generate_synthetic_mbid(
+        conn: &mut PgConnection,
+        artist_name: &str,
+    ) -> anyhow::Result<Uuid> {
         let result = sqlx::query_scalar!("SELECT generate_synthetic_mbid($1)", artist_name)
-            .fetch_one(&self.sql)
+            .fetch_one(&mut *conn)
             .await?;
 
         result.ok_or_else(|| anyhow!("Failed to generate synthetic MBID"))
@@ -996,17 +1067,23 @@ impl PlayIngestor {
 
     /// Normalize text for fuzzy matching with MusicBrainz-style cleaning
     fn normalize_text(text: &str, is_artist: bool) -> String {
-        let cleaned = if is_artist {
-            MusicBrainzCleaner::clean_artist_name(text)
-        } else {
-            MusicBrainzCleaner::clean_track_name(text)
-        };
+        if is_artist {
+            return cleaning::fold_artist_name(text);
+        }
+        cleaning::normalize_for_comparison(&cleaning::clean_track_name(text))
+    }
 
-        MusicBrainzCleaner::normalize_for_comparison(&cleaned)
+    /// A name cleaned the way MusicBrainz lists it, for searching the mirror
+    pub(crate) fn clean_name(text: &str, is_artist: bool) -> String {
+        if is_artist {
+            cleaning::clean_artist_name(text)
+        } else {
+            cleaning::clean_track_name(text)
+        }
     }
 
     /// Calculate string similarity with MusicBrainz-style cleaning
-    fn calculate_similarity(s1: &str, s2: &str, is_artist: bool) -> f64 {
+    pub(crate) fn calculate_similarity(s1: &str, s2: &str, is_artist: bool) -> f64 {
         let s1_norm = Self::normalize_text(s1, is_artist);
         let s2_norm = Self::normalize_text(s2, is_artist);
 
@@ -1018,9 +1095,12 @@ impl PlayIngestor {
             return 0.0;
         }
 
-        // Calculate basic similarity
-        let max_len = s1_norm.len().max(s2_norm.len()) as f64;
-        let min_len = s1_norm.len().min(s2_norm.len()) as f64;
+        // Calculate basic similarity, in characters so CJK names aren't
+        // measured by their UTF-8 length
+        let s1_len = s1_norm.chars().count();
+        let s2_len = s2_norm.chars().count();
+        let max_len = s1_len.max(s2_len) as f64;
+        let min_len = s1_len.min(s2_len) as f64;
 
         // Character-based similarity
         let common_chars = s1_norm
@@ -1054,57 +1134,63 @@ impl PlayIngestor {
     }
 
     /// Find existing artists that fuzzy match the given name
-    async fn find_fuzzy_artist_matches(
+    pub(crate) async fn find_fuzzy_artist_matches(
         &self,
         artist_name: &str,
         _track_name: &str,
         _album_name: Option<&str>,
     ) -> anyhow::Result<Vec<FuzzyMatchCandidate>> {
         let normalized_name = Self::normalize_text(artist_name, true);
+        let folded = cleaning::fold_artist_name(artist_name);
+        if normalized_name.is_empty() || folded.is_empty() {
+            return Ok(Vec::new());
+        }
 
-        // Search for artists with similar names using trigram similarity
-        let candidates = sqlx::query!(
+        // Search for artists with a MusicBrainz alias that folds the same, or
+        // with a similar folded name; both use their indexes
+        let candidates: Vec<(i32, String, bool)> = sqlx::query_as(
             r#"
-            SELECT
-                ae.id,
-                ae.name
-            FROM artists_extended ae
-            WHERE ae.mbid_type = 'musicbrainz'
-            AND (
-                LOWER(TRIM(ae.name)) = $1
-                OR LOWER(TRIM(ae.name)) LIKE '%' || $1 || '%'
-                OR $1 LIKE '%' || LOWER(TRIM(ae.name)) || '%'
-                OR similarity(LOWER(TRIM(ae.name)), $1) > 0.6
-            )
-            ORDER BY similarity(LOWER(TRIM(ae.name)), $1) DESC
+            SELECT id, name, bool_or(alias_match) AS alias_match FROM (
+                SELECT ae.id, ae.name, TRUE AS alias_match, 1.0::real AS score
+                FROM artist_aliases aa
+                JOIN artists_extended ae ON ae.mbid = aa.artist_mbid
+                WHERE aa.name_folded = $1 AND ae.mbid_type = 'musicbrainz'
+                UNION
+                SELECT ae.id, ae.name, FALSE, similarity(ae.name_folded, $1)
+                FROM artists_extended ae
+                WHERE ae.name_folded % $1
+                  AND similarity(ae.name_folded, $1) > 0.6
+                  AND ae.mbid_type = 'musicbrainz'
+            ) candidates
+            GROUP BY id, name
+            ORDER BY bool_or(alias_match) DESC, MAX(score) DESC
             LIMIT 10
             "#,
-            normalized_name
         )
+        .bind(&folded)
         .fetch_all(&self.sql)
         .await
         .unwrap_or_default();
 
         let mut matches = Vec::new();
 
-        for candidate in candidates {
-            let name_similarity = Self::calculate_similarity(artist_name, &candidate.name, true);
+        for (id, name, alias_match) in candidates {
+            let name_similarity = Self::calculate_similarity(artist_name, &name, true);
 
             // Base confidence from name similarity
             let mut confidence = name_similarity;
 
-            // Boost confidence for exact matches after normalization
-            if Self::normalize_text(artist_name, true)
-                == Self::normalize_text(&candidate.name, true)
-            {
+            // Boost confidence for exact matches after normalization, and for
+            // names MusicBrainz lists as one of the artist's aliases
+            if alias_match || normalized_name == Self::normalize_text(&name, true) {
                 confidence = confidence.max(0.95);
             }
 
             // Additional boost for cleaned matches
-            let cleaned_input = MusicBrainzCleaner::clean_artist_name(artist_name);
-            let cleaned_candidate = MusicBrainzCleaner::clean_artist_name(&candidate.name);
-            if MusicBrainzCleaner::normalize_for_comparison(&cleaned_input)
-                == MusicBrainzCleaner::normalize_for_comparison(&cleaned_candidate)
+            let cleaned_input = cleaning::clean_artist_name(artist_name);
+            let cleaned_candidate = cleaning::clean_artist_name(&name);
+            if cleaning::normalize_for_comparison(&cleaned_input)
+                == cleaning::normalize_for_comparison(&cleaned_candidate)
             {
                 confidence = confidence.max(0.9);
             }
@@ -1112,8 +1198,8 @@ impl PlayIngestor {
             // Lower threshold since we have better cleaning now
             if confidence >= 0.8 {
                 matches.push(FuzzyMatchCandidate {
-                    artist_id: candidate.id,
-                    name: candidate.name,
+                    artist_id: id,
+                    name,
                     confidence,
                 });
             }
@@ -1132,6 +1218,7 @@ impl PlayIngestor {
     /// Try to match an artist to existing MusicBrainz data using fuzzy matching
     async fn find_or_create_artist_with_fuzzy_matching(
         &self,
+        conn: &mut PgConnection,
         artist_name: &str,
         mbid: Option<&str>,
         track_name: &str,
@@ -1139,7 +1226,7 @@ impl PlayIngestor {
     ) -> anyhow::Result<i32> {
         // If we already have an MBID, use it directly
         if let Some(mbid) = mbid {
-            return self.insert_artist_extended(Some(mbid), artist_name).await;
+            return Self::insert_artist_extended(conn, Some(mbid), artist_name).await;
         }
 
         // Try fuzzy matching against existing MusicBrainz artists
@@ -1149,7 +1236,7 @@ impl PlayIngestor {
 
         if let Some(best_match) = matches.first() {
             // Use high confidence threshold for automatic matching
-            if best_match.confidence >= 0.92 {
+            if best_match.confidence >= AUTO_MATCH_CONFIDENCE {
                 tracing::info!(
                     "🔗 Fuzzy matched '{}' to existing artist '{}' (confidence: {:.2})",
                     artist_name,
@@ -1157,19 +1244,10 @@ impl PlayIngestor {
                     best_match.confidence
                 );
 
-                // Update the existing artist name if the new one seems more complete
-                if artist_name.len() > best_match.name.len() && best_match.confidence >= 0.95 {
-                    sqlx::query!(
-                        "UPDATE artists_extended SET name = $1, updated_at = NOW() WHERE id = $2",
-                        artist_name,
-                        best_match.artist_id
-                    )
-                    .execute(&self.sql)
-                    .await?;
-                }
-
+                // Candidates are MusicBrainz artists, whose names come from
+                // MusicBrainz and are never replaced by a client's spelling
                 return Ok(best_match.artist_id);
-            } else if best_match.confidence >= 0.85 {
+            } else if best_match.confidence >= CANDIDATE_MATCH_CONFIDENCE {
                 tracing::debug!(
                     "🤔 Potential match for '{}' -> '{}' (confidence: {:.2}) but below auto-match threshold",
                     artist_name,
@@ -1179,13 +1257,49 @@ impl PlayIngestor {
             }
         }
 
+        // A synthetic artist spelled differently ("Beyonce" for "Beyoncé", or
+        // in full-width) is the same artist, so reuse it
+        let folded = cleaning::fold_artist_name(artist_name);
+        if !folded.is_empty() {
+            let existing = sqlx::query_scalar::<_, i32>(
+                "SELECT id FROM artists_extended WHERE mbid_type = 'synthetic' AND name_folded = $1 ORDER BY id LIMIT 1",
+            )
+            .bind(&folded)
+            .fetch_optional(&mut *conn)
+            .await?;
+            if let Some(id) = existing {
+                return Ok(id);
+            }
+        }
+
         // No good match found, create synthetic artist
-        self.insert_artist_extended(None, artist_name).await
+        Self::insert_artist_extended(conn, None, artist_name).await
+    }
+
+    /// Stores the folded form of an artist's name, which fuzzy matching
+    /// compares against.
+    async fn store_folded_name(
+        conn: &mut PgConnection,
+        artist_id: i32,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "UPDATE artists_extended SET name_folded = $2 WHERE id = $1 AND name_folded IS DISTINCT FROM $2",
+        )
+        .bind(artist_id)
+        .bind(cleaning::fold_artist_name(name))
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
     }
 
     /// Inserts or updates an artist in the database using the extended table.
     /// Returns the internal ID of the artist.
-    async fn insert_artist_extended(&self, mbid: Option<&str>, name: &str) -> anyhow::Result<i32> {
+    async fn insert_artist_extended(
+        conn: &mut PgConnection,
+        mbid: Option<&str>,
+        name: &str,
+    ) -> anyhow::Result<i32> {
         if let Some(mbid) = mbid {
             let artist_uuid = Uuid::parse_str(mbid_value(mbid))?;
             let res = sqlx::query!(
@@ -1199,12 +1313,14 @@ impl PlayIngestor {
                 artist_uuid,
                 name
             )
-            .fetch_one(&self.sql)
+            .fetch_one(&mut *conn)
             .await?;
+            Self::store_folded_name(conn, res.id, name).await?;
+            enrich::enqueue(conn, EntityType::Artist, artist_uuid).await?;
             Ok(res.id)
         } else {
             // Artist without MBID - generate synthetic MBID
-            let synthetic_uuid = self.generate_synthetic_mbid(name).await?;
+            let synthetic_uuid = Self::generate_synthetic_mbid(conn, name).await?;
 
             let res = sqlx::query!(
                 r#"
@@ -1217,26 +1333,20 @@ impl PlayIngestor {
                 synthetic_uuid,
                 name
             )
-            .fetch_one(&self.sql)
+            .fetch_one(&mut *conn)
             .await?;
+            Self::store_folded_name(conn, res.id, name).await?;
             Ok(res.id)
         }
     }
 
     /// Inserts or updates a release in the database.
-    /// Returns the Uuid of the release.
-    async fn insert_release(&self, mbid: &str, name: &str) -> anyhow::Result<Uuid> {
-        let release_uuid = Uuid::parse_str(mbid_value(mbid))?;
-
-        // Extract discriminant from release name for new releases
-        // Prioritize edition-specific patterns for better quality
-        let discriminant = self
-            .extract_edition_discriminant_from_db(name)
-            .await
-            .or_else(|| {
-                futures::executor::block_on(async { self.extract_discriminant_from_db(name).await })
-            });
-
+    async fn insert_release(
+        conn: &mut PgConnection,
+        release_uuid: Uuid,
+        name: &str,
+        discriminant: Option<String>,
+    ) -> anyhow::Result<()> {
         let res = sqlx::query!(
             r#"
                 INSERT INTO releases (mbid, name, discriminant) VALUES ($1, $2, $3)
@@ -1249,30 +1359,24 @@ impl PlayIngestor {
             name,
             discriminant
         )
-        .fetch_all(&self.sql)
+        .fetch_all(&mut *conn)
         .await?;
 
         if !res.is_empty() {
-            // TODO: send request to async scrape data from local MB instance
+            enrich::enqueue(conn, EntityType::Release, release_uuid).await?;
+            cover_art::enqueue(conn, release_uuid).await?;
         }
 
-        Ok(release_uuid)
+        Ok(())
     }
 
     /// Inserts or updates a recording in the database.
-    /// Returns the Uuid of the recording.
-    async fn insert_recording(&self, mbid: &str, name: &str) -> anyhow::Result<Uuid> {
-        let recording_uuid = Uuid::parse_str(mbid_value(mbid))?;
-
-        // Extract discriminant from recording name for new recordings
-        // Prioritize edition-specific patterns for better quality
-        let discriminant = self
-            .extract_edition_discriminant_from_db(name)
-            .await
-            .or_else(|| {
-                futures::executor::block_on(async { self.extract_discriminant_from_db(name).await })
-            });
-
+    async fn insert_recording(
+        conn: &mut PgConnection,
+        recording_uuid: Uuid,
+        name: &str,
+        discriminant: Option<String>,
+    ) -> anyhow::Result<()> {
         let res = sqlx::query!(
             r#"
                 INSERT INTO recordings (mbid, name, discriminant) VALUES ($1, $2, $3)
@@ -1285,32 +1389,163 @@ impl PlayIngestor {
             name,
             discriminant
         )
-        .fetch_all(&self.sql)
+        .fetch_all(&mut *conn)
         .await?;
 
         if !res.is_empty() {
-            // TODO: send request to async scrape data from local MB instance
+            enrich::enqueue(conn, EntityType::Recording, recording_uuid).await?;
         }
 
-        Ok(recording_uuid)
+        Ok(())
     }
 
-    /// Extract discriminant from name using database function
-    async fn extract_discriminant_from_db(&self, name: &str) -> Option<String> {
-        sqlx::query_scalar!("SELECT extract_discriminant($1)", name)
-            .fetch_one(&self.sql)
-            .await
-            .ok()
-            .flatten()
+    /// Releases or recordings named close to `name`, closest first, with the
+    /// same discriminant so live, remastered and deluxe variants stay apart.
+    /// The trigram search is on the cleaned, folded name and runs outside the
+    /// play transaction; [`Self::find_fuzzy_entity_match`] narrows it down.
+    async fn fuzzy_entity_candidates(
+        pool: &PgPool,
+        entity: NamedEntity,
+        name: &str,
+        discriminant: Option<&str>,
+    ) -> anyhow::Result<Vec<(Uuid, String)>> {
+        let key = cleaning::normalize_for_comparison(&cleaning::clean_track_name(name));
+        if key.is_empty() {
+            return Ok(Vec::new());
+        }
+        let query = format!(
+            r#"
+            SELECT e.mbid, e.name FROM {table} e
+            WHERE LOWER(TRIM(e.name)) % $1
+            AND LOWER(TRIM(COALESCE(
+                e.discriminant,
+                extract_edition_discriminant(e.name),
+                extract_discriminant(e.name),
+                ''
+            ))) = LOWER(TRIM(COALESCE($2, '')))
+            ORDER BY similarity(LOWER(TRIM(e.name)), $1) DESC
+            LIMIT $3
+            "#,
+            table = entity.table(),
+        );
+        Ok(sqlx::query_as(&query)
+            .bind(&key)
+            .bind(discriminant)
+            .bind(FUZZY_CANDIDATES)
+            .fetch_all(pool)
+            .await?)
     }
 
-    /// Extract edition-specific discriminant from name using database function
-    async fn extract_edition_discriminant_from_db(&self, name: &str) -> Option<String> {
-        sqlx::query_scalar!("SELECT extract_edition_discriminant($1)", name)
-            .fetch_one(&self.sql)
-            .await
-            .ok()
-            .flatten()
+    /// The one of `candidates` (from [`Self::fuzzy_entity_candidates`]) that
+    /// plays by any of `artist_ids` link to and whose name is closest to
+    /// `name`. Track names like `Intro` recur across albums, so a recording's
+    /// plays must also be on `release` or about `duration` seconds long.
+    async fn find_fuzzy_entity_match(
+        conn: &mut PgConnection,
+        entity: NamedEntity,
+        name: &str,
+        candidates: &[(Uuid, String)],
+        artist_ids: &[i32],
+        release: Option<Uuid>,
+        duration: Option<i32>,
+    ) -> anyhow::Result<Option<(Uuid, f64)>> {
+        let unanchored =
+            entity == NamedEntity::Recording && release.is_none() && duration.is_none();
+        if candidates.is_empty() || artist_ids.is_empty() || unanchored {
+            return Ok(None);
+        }
+        let anchor = match entity {
+            NamedEntity::Release => "",
+            NamedEntity::Recording => "AND (p.release_mbid = $3 OR ABS(p.duration - $4) <= $5)",
+        };
+        let sql = format!(
+            r#"
+            SELECT DISTINCT p.{column} FROM plays p
+            JOIN play_to_artists_extended ptae ON ptae.play_uri = p.uri
+            WHERE p.{column} = ANY($1) AND ptae.artist_id = ANY($2)
+            {anchor}
+            "#,
+            column = entity.play_column(),
+        );
+        let mbids: Vec<Uuid> = candidates.iter().map(|(mbid, _)| *mbid).collect();
+        let mut query = sqlx::query_scalar(&sql).bind(&mbids).bind(artist_ids);
+        if entity == NamedEntity::Recording {
+            query = query
+                .bind(release)
+                .bind(duration)
+                .bind(FUZZY_DURATION_TOLERANCE_SECS);
+        }
+        let played: HashSet<Uuid> = query.fetch_all(&mut *conn).await?.into_iter().collect();
+        Ok(Self::best_fuzzy_match(
+            name,
+            candidates
+                .iter()
+                .filter(|(mbid, _)| played.contains(mbid))
+                .cloned(),
+        ))
+    }
+
+    /// The candidate whose name is closest to `name`, if it reaches
+    /// [`AUTO_MATCH_CONFIDENCE`]. Ties go to the earlier candidate.
+    fn best_fuzzy_match(
+        name: &str,
+        candidates: impl IntoIterator<Item = (Uuid, String)>,
+    ) -> Option<(Uuid, f64)> {
+        candidates
+            .into_iter()
+            .map(|(mbid, candidate)| (mbid, Self::calculate_similarity(name, &candidate, false)))
+            .filter(|(_, confidence)| *confidence >= AUTO_MATCH_CONFIDENCE)
+            .reduce(|best, candidate| {
+                if candidate.1 > best.1 {
+                    candidate
+                } else {
+                    best
+                }
+            })
+    }
+
+    /// Discriminants of `names` from the database functions, preferring
+    /// edition-specific patterns. Names seen recently are served from the cache
+    /// and the rest are looked up in a single query.
+    async fn lookup_discriminants(
+        &self,
+        names: &[&str],
+    ) -> anyhow::Result<HashMap<String, Option<String>>> {
+        let mut discriminants = HashMap::new();
+        let mut missing: Vec<String> = Vec::new();
+        for name in names {
+            if discriminants.contains_key(*name) {
+                continue;
+            }
+            match self.cache.discriminant(name) {
+                Some(discriminant) => {
+                    discriminants.insert(name.to_string(), discriminant);
+                }
+                None => {
+                    discriminants.insert(name.to_string(), None);
+                    missing.push(name.to_string());
+                }
+            }
+        }
+        if missing.is_empty() {
+            return Ok(discriminants);
+        }
+
+        let rows: Vec<(String, Option<String>)> = sqlx::query_as(
+            r#"
+                SELECT name, COALESCE(extract_edition_discriminant(name), extract_discriminant(name))
+                FROM UNNEST($1::text[]) AS name
+            "#,
+        )
+        .bind(&missing)
+        .fetch_all(&self.sql)
+        .await?;
+        for (name, discriminant) in rows {
+            self.cache
+                .insert_discriminant(name.clone(), discriminant.clone());
+            discriminants.insert(name, discriminant);
+        }
+        Ok(discriminants)
     }
 
     // /// Get base name without discriminant using database function
@@ -1323,6 +1558,135 @@ impl PlayIngestor {
     //         .unwrap_or_else(|| name.to_string())
     // }
 
+    /// Artist names and MBIDs a play is credited to. Records without any artist
+    /// information get a generated fallback artist. Parts of combined credits
+    /// whose folded names are in `known` (see [`Self::known_whole_names`]) are
+    /// kept whole.
+    pub(crate) fn artist_credits(
+        play_record: &types::fm_teal::feed::play::Play,
+        known: &HashSet<String>,
+    ) -> Vec<(String, Option<String>)> {
+        Self::credited_artists(play_record, known)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /// The artist names and MBIDs as the record sends them, or `None` if it
+    /// sends no artists.
+    fn sent_artists(play_record: &types::fm_teal::feed::play::Play) -> Option<Vec<ArtistKey>> {
+        if let Some(artists) = &play_record.artists {
+            Some(
+                artists
+                    .iter()
+                    .map(|artist| {
+                        (
+                            artist.artist_name.to_string(),
+                            artist
+                                .artist_mb_id
+                                .as_ref()
+                                .map(|mbid| uri_mbid_value(mbid).to_string()),
+                        )
+                    })
+                    .collect(),
+            )
+        } else {
+            play_record.artist_names.as_ref().map(|artist_names| {
+                artist_names
+                    .iter()
+                    .enumerate()
+                    .map(|(index, artist_name)| {
+                        let artist_mbid = play_record
+                            .artist_mb_ids
+                            .as_ref()
+                            .and_then(|mbid_list| mbid_list.get(index))
+                            .map(|mbid| mbid.as_str().to_string());
+                        (artist_name.to_string(), artist_mbid)
+                    })
+                    .collect()
+            })
+        }
+    }
+
+    /// Like [`Self::artist_credits`], with the join phrase after each artist
+    /// when it is known. Names sent without an MBID are split into their
+    /// artists when they are combined credits such as `A feat. B`.
+    pub(crate) fn credited_artists(
+        play_record: &types::fm_teal::feed::play::Play,
+        known: &HashSet<String>,
+    ) -> Vec<(ArtistKey, Option<String>)> {
+        let Some(sent) = Self::sent_artists(play_record) else {
+            return vec![(
+                (
+                    Self::generate_fallback_artist(&play_record.track_name),
+                    None,
+                ),
+                None,
+            )];
+        };
+
+        let keep_whole = |part: &str| known.contains(&whole_name_key(part));
+        sent.into_iter()
+            .flat_map(|(name, mbid)| match mbid {
+                Some(mbid) => vec![((name, Some(mbid)), None)],
+                None => split_credit(&name, &keep_whole)
+                    .into_iter()
+                    .map(|credit| {
+                        let join_phrase = Some(credit.join_phrase).filter(|p| !p.is_empty());
+                        ((credit.name, None), join_phrase)
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Keys of the MusicBrainz artists and aliases named by a part of one of
+    /// `names` that would otherwise be split, such as `Years & Years` or
+    /// `Nico & Vinz`.
+    pub(crate) async fn known_whole_names<'a>(
+        pool: &PgPool,
+        names: impl IntoIterator<Item = &'a str>,
+    ) -> anyhow::Result<HashSet<String>> {
+        let keys: HashSet<String> = names
+            .into_iter()
+            .map(credit_parts)
+            .filter(|parts| parts.len() > 1)
+            .flatten()
+            .map(|part| whole_name_key(&part))
+            .filter(|key| !key.is_empty())
+            .collect();
+        if keys.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let keys: Vec<String> = keys.into_iter().collect();
+        let known: Vec<String> = sqlx::query_scalar(
+            r#"
+                SELECT name_folded FROM artists_extended
+                WHERE mbid_type = 'musicbrainz' AND name_folded = ANY($1)
+                UNION
+                SELECT name_folded FROM artist_aliases WHERE name_folded = ANY($1)
+            "#,
+        )
+        .bind(&keys)
+        .fetch_all(pool)
+        .await?;
+        Ok(known.into_iter().collect())
+    }
+
+    /// [`Self::known_whole_names`] of the names `records` send without an MBID.
+    pub(crate) async fn known_whole_names_of<'a>(
+        pool: &PgPool,
+        records: impl IntoIterator<Item = &'a types::fm_teal::feed::play::Play>,
+    ) -> anyhow::Result<HashSet<String>> {
+        let sent: Vec<ArtistKey> = records
+            .into_iter()
+            .filter_map(Self::sent_artists)
+            .flatten()
+            .filter(|(_, mbid)| mbid.is_none())
+            .collect();
+        Self::known_whole_names(pool, sent.iter().map(|(name, _)| name.as_str())).await
+    }
+
     pub async fn insert_play(
         &self,
         play_record: &types::fm_teal::feed::play::Play,
@@ -1331,250 +1695,553 @@ impl PlayIngestor {
         did: &str,
         rkey: &str,
     ) -> anyhow::Result<()> {
-        dbg!("ingesting", play_record);
-        let play_record = clean(play_record);
-        let mut parsed_artists: Vec<(i32, String)> = vec![];
-        let mut artist_names_raw: Vec<String> = vec![];
-
-        if let Some(ref artists) = &play_record.artists {
-            for artist in artists {
-                let artist_name = artist.artist_name.clone();
-                artist_names_raw.push(artist_name.as_str().to_owned());
-                let artist_mbid = artist.artist_mb_id.as_ref().map(uri_mbid_value);
-
-                let artist_id = self
-                    .find_or_create_artist_with_fuzzy_matching(
-                        &artist_name,
-                        artist_mbid,
-                        &play_record.track_name,
-                        play_record.release_name.as_deref(),
-                    )
-                    .await?;
-                parsed_artists.push((artist_id, artist_name.as_str().to_owned()));
-            }
-        } else if let Some(artist_names) = &play_record.artist_names {
-            for (index, artist_name) in artist_names.iter().enumerate() {
-                artist_names_raw.push(artist_name.as_str().to_owned());
+        self.insert_plays(&[PendingPlay {
+            record: play_record.clone(),
+            uri: uri.to_string(),
+            cid: cid.to_string(),
+            did: did.to_string(),
+            rkey: rkey.to_string(),
+        }])
+        .await
+    }
 
-                let artist_mbid_opt = if let Some(ref mbid_list) = play_record.artist_mb_ids {
-                    mbid_list.get(index).map(|s| s.as_str())
-                } else {
-                    None
-                };
+    /// Write `plays` in a single transaction. Artists, releases and recordings
+    /// shared by several plays are written once, and ones written recently are
+    /// taken from the [`EntityCache`]. Either every play is stored or none is.
+    pub async fn insert_plays(&self, plays: &[PendingPlay]) -> anyhow::Result<()> {
+        if plays.is_empty() {
+            return Ok(());
+        }
+        let records: Vec<types::fm_teal::feed::play::Play> =
+            plays.iter().map(|play| clean(&play.record)).collect();
+        self.cache.sync(&self.sql).await?;
 
-                let artist_id = self
-                    .find_or_create_artist_with_fuzzy_matching(
-                        artist_name,
-                        artist_mbid_opt,
-                        &play_record.track_name,
-                        play_record.release_name.as_deref(),
-                    )
-                    .await?;
-                parsed_artists.push((artist_id, artist_name.as_str().to_owned()));
+        let names: Vec<&str> = records
+            .iter()
+            .flat_map(|record| {
+                std::iter::once(record.track_name.as_str()).chain(record.release_name.as_deref())
+            })
+            .collect();
+        let discriminants = self.lookup_discriminants(&names).await?;
+        let discriminant_of = |name: &str| discriminants.get(name).cloned().flatten();
+        let known = Self::known_whole_names_of(&self.sql, &records).await?;
+
+        // Releases and recordings named like those plays have no MBID for,
+        // searched before the transaction so it isn't held open meanwhile
+        let mut fuzzy_candidates: HashMap<(NamedEntity, &str), Vec<(Uuid, String)>> =
+            HashMap::new();
+        for record in &records {
+            let mut wanted = Vec::new();
+            if record.release_mb_id.is_none() {
+                if let Some(release_name) = &record.release_name {
+                    wanted.push((NamedEntity::Release, release_name.as_str()));
+                }
             }
-        } else {
-            // No artist information provided - create a fallback artist
-            let fallback_artist_name = Self::generate_fallback_artist(&play_record.track_name);
-            artist_names_raw.push(fallback_artist_name.clone());
-
-            let artist_id = self
-                .find_or_create_artist_with_fuzzy_matching(
-                    &fallback_artist_name,
-                    None,
-                    &play_record.track_name,
-                    play_record.release_name.as_deref(),
+            if record.recording_mb_id.is_none() {
+                wanted.push((NamedEntity::Recording, record.track_name.as_str()));
+            }
+            for (entity, name) in wanted {
+                if fuzzy_candidates.contains_key(&(entity, name)) {
+                    continue;
+                }
+                let candidates = Self::fuzzy_entity_candidates(
+                    &self.sql,
+                    entity,
+                    name,
+                    discriminant_of(name).as_deref(),
                 )
                 .await?;
-            parsed_artists.push((artist_id, fallback_artist_name));
+                fuzzy_candidates.insert((entity, name), candidates);
+            }
         }
 
-        // Insert release if missing
-        let release_mbid_opt = if let Some(release_mbid) = &play_record.release_mb_id {
-            if let Some(release_name) = &play_record.release_name {
-                Some(
-                    self.insert_release(uri_mbid_value(release_mbid), release_name)
-                        .await?,
-                )
-            } else {
-                None
+        let mut tx = self.sql.begin().await?;
+
+        // Entities written by this batch, cached once it commits
+        let mut batch_artists: HashMap<ArtistKey, i32> = HashMap::new();
+        let mut batch_releases: HashMap<Uuid, String> = HashMap::new();
+        let mut batch_recordings: HashMap<Uuid, String> = HashMap::new();
+
+        // Each play's artists in credit order, with the join phrase after each
+        let mut play_artists: Vec<Vec<(i32, String, Option<String>)>> =
+            Vec::with_capacity(records.len());
+        let mut release_mbids: Vec<Option<Uuid>> = Vec::with_capacity(records.len());
+        let mut recording_mbids: Vec<Option<Uuid>> = Vec::with_capacity(records.len());
+
+        for record in &records {
+            let mut artists = Vec::new();
+            for (key, join_phrase) in Self::credited_artists(record, &known) {
+                let cached = batch_artists
+                    .get(&key)
+                    .copied()
+                    .or_else(|| self.cache.artist(&key));
+                let artist_id = match cached {
+                    Some(artist_id) => artist_id,
+                    None => {
+                        let artist_id = self
+                            .find_or_create_artist_with_fuzzy_matching(
+                                &mut tx,
+                                &key.0,
+                                key.1.as_deref(),
+                                &record.track_name,
+                                record.release_name.as_deref(),
+                            )
+                            .await?;
+                        batch_artists.insert(key.clone(), artist_id);
+                        artist_id
+                    }
+                };
+                artists.push((artist_id, key.0, join_phrase));
             }
-        } else {
-            None
-        };
-
-        // Insert recording if missing
-        let recording_mbid_opt = if let Some(recording_mbid) = &play_record.recording_mb_id {
-            let recording_name = play_record.track_name.clone();
-            Some(
-                self.insert_recording(uri_mbid_value(recording_mbid), &recording_name)
-                    .await?,
-            )
-        } else {
-            None
-        };
+            play_artists.push(artists);
+
+            // Insert release if missing
+            let release_mbid = match (&record.release_mb_id, &record.release_name) {
+                (Some(release_mbid), Some(release_name)) => {
+                    let release_uuid = Uuid::parse_str(uri_mbid_value(release_mbid))?;
+                    let release_name = release_name.as_str();
+                    if batch_releases.get(&release_uuid).map(String::as_str) != Some(release_name)
+                        && !self.cache.has_release(&release_uuid, release_name)
+                    {
+                        Self::insert_release(
+                            &mut tx,
+                            release_uuid,
+                            release_name,
+                            discriminant_of(release_name),
+                        )
+                        .await?;
+                        batch_releases.insert(release_uuid, release_name.to_string());
+                    }
+                    Some(release_uuid)
+                }
+                _ => None,
+            };
+            release_mbids.push(release_mbid);
+
+            // Insert recording if missing
+            let recording_mbid = match &record.recording_mb_id {
+                Some(recording_mbid) => {
+                    let recording_uuid = Uuid::parse_str(uri_mbid_value(recording_mbid))?;
+                    let track_name = record.track_name.as_str();
+                    if batch_recordings.get(&recording_uuid).map(String::as_str) != Some(track_name)
+                        && !self.cache.has_recording(&recording_uuid, track_name)
+                    {
+                        Self::insert_recording(
+                            &mut tx,
+                            recording_uuid,
+                            track_name,
+                            discriminant_of(track_name),
+                        )
+                        .await?;
+                        batch_recordings.insert(recording_uuid, track_name.to_string());
+                    }
+                    Some(recording_uuid)
+                }
+                None => None,
+            };
+            recording_mbids.push(recording_mbid);
+        }
 
-        let played_time = play_record.played_time.clone().unwrap_or(Datetime::now());
-        let time_datetime =
-            time::OffsetDateTime::from_unix_timestamp(played_time.as_ref().timestamp())
-                .unwrap_or_else(|_| time::OffsetDateTime::now_utc());
-
-        // Extract discriminants from lexicon fields or infer from names
-        // First try lexicon fields, then extract from names with preference for edition-specific patterns
-        // TODO: Enable when types are updated with discriminant fields
-        // let track_discriminant = play_record.track_discriminant.clone().or_else(|| {
-        let track_discriminant = {
-            // Try edition-specific patterns first, then general patterns
-            futures::executor::block_on(async {
-                self.extract_edition_discriminant_from_db(&play_record.track_name)
-                    .await
-                    .or_else(|| {
-                        futures::executor::block_on(async {
-                            self.extract_discriminant_from_db(&play_record.track_name)
-                                .await
-                        })
-                    })
+        // Plays with only an ISRC take the recording it belongs to, if just one
+        let isrcs: Vec<Option<String>> = records
+            .iter()
+            .map(|record| {
+                record.isrc.as_ref().map(|raw| {
+                    let raw = raw.to_string();
+                    isrc::normalize_isrc(&raw).unwrap_or(raw)
+                })
             })
-        };
+            .collect();
+        let isrc_only: Vec<String> = isrcs
+            .iter()
+            .zip(&recording_mbids)
+            .filter(|(_, recording_mbid)| recording_mbid.is_none())
+            .filter_map(|(isrc, _)| isrc.clone())
+            .collect();
+        let indexed = isrc::unambiguous_recordings(&mut tx, &isrc_only).await?;
+        let mut match_methods: Vec<Option<&str>> = vec![None; records.len()];
+        for (index, play_isrc) in isrcs.iter().enumerate() {
+            if recording_mbids[index].is_some() {
+                continue;
+            }
+            if let Some(recording_mbid) = play_isrc.as_ref().and_then(|code| indexed.get(code)) {
+                recording_mbids[index] = Some(*recording_mbid);
+                match_methods[index] = Some(isrc::INDEX_MATCH_METHOD);
+            }
+        }
+        let mut match_confidences: Vec<Option<f32>> = match_methods
+            .iter()
+            .map(|method| method.map(|_| 1.0))
+            .collect();
 
-        // let release_discriminant = play_record.release_discriminant.clone().or_else(|| {
-        let release_discriminant = {
-            if let Some(release_name) = &play_record.release_name {
-                futures::executor::block_on(async {
-                    // Try edition-specific patterns first, then general patterns
-                    self.extract_edition_discriminant_from_db(release_name)
-                        .await
-                        .or_else(|| {
-                            futures::executor::block_on(async {
-                                self.extract_discriminant_from_db(release_name).await
-                            })
-                        })
-                })
-            } else {
-                None
+        // Plays still without a release or recording take one their artists
+        // were already played with under the same name, rather than leaving
+        // a duplicate for consolidation
+        let mut release_match_methods: Vec<Option<&str>> = vec![None; records.len()];
+        let mut release_match_confidences: Vec<Option<f32>> = vec![None; records.len()];
+        let mut fuzzy_matches = HashMap::new();
+        for (index, record) in records.iter().enumerate() {
+            let mut artist_ids: Vec<i32> =
+                play_artists[index].iter().map(|(id, _, _)| *id).collect();
+            artist_ids.sort_unstable();
+            artist_ids.dedup();
+
+            let mut wanted = Vec::new();
+            if release_mbids[index].is_none() {
+                if let Some(release_name) = &record.release_name {
+                    wanted.push((NamedEntity::Release, release_name.as_str()));
+                }
+            }
+            if recording_mbids[index].is_none() {
+                wanted.push((NamedEntity::Recording, record.track_name.as_str()));
             }
-        };
 
-        // Our main insert into plays with raw artist names and discriminants
-        let artist_names_json = if !artist_names_raw.is_empty() {
-            Some(serde_json::to_value(&artist_names_raw)?)
-        } else {
-            None
+            for (entity, name) in wanted {
+                let (release, duration) = match entity {
+                    NamedEntity::Release => (None, None),
+                    NamedEntity::Recording => {
+                        (release_mbids[index], record.duration.map(|d| d as i32))
+                    }
+                };
+                let key = (entity, name, artist_ids.clone(), release, duration);
+                let found = match fuzzy_matches.get(&key) {
+                    Some(found) => *found,
+                    None => {
+                        let candidates = fuzzy_candidates
+                            .get(&(entity, name))
+                            .map(Vec::as_slice)
+                            .unwrap_or_default();
+                        let found = Self::find_fuzzy_entity_match(
+                            &mut tx,
+                            entity,
+                            name,
+                            candidates,
+                            &artist_ids,
+                            release,
+                            duration,
+                        )
+                        .await?;
+                        fuzzy_matches.insert(key, found);
+                        found
+                    }
+                };
+                let Some((mbid, confidence)) = found else {
+                    continue;
+                };
+                match entity {
+                    NamedEntity::Release => {
+                        release_mbids[index] = Some(mbid);
+                        release_match_methods[index] = Some(FUZZY_MATCH_METHOD);
+                        release_match_confidences[index] = Some(confidence as f32);
+                    }
+                    NamedEntity::Recording => {
+                        recording_mbids[index] = Some(mbid);
+                        match_methods[index] = Some(FUZZY_MATCH_METHOD);
+                        match_confidences[index] = Some(confidence as f32);
+                    }
+                }
+            }
+        }
+
+        let rows = last_occurrences(plays.iter().map(|play| play.uri.as_str()));
+
+        let column = |f: &dyn Fn(usize) -> Option<String>| -> Vec<Option<String>> {
+            rows.iter().map(|index| f(*index)).collect()
         };
-        let isrc = play_record.isrc.as_ref().map(ToString::to_string);
-        let track_name = play_record.track_name.to_string();
-        let release_name = play_record.release_name.as_ref().map(ToString::to_string);
-        let submission_client_agent = play_record
-            .submission_client_agent
-            .as_ref()
-            .map(ToString::to_string);
-        let music_service_base_domain = play_record
-            .music_service_uri
-            .as_ref()
-            .map(|uri| uri.as_str().to_string());
+        let uris: Vec<&str> = rows.iter().map(|i| plays[*i].uri.as_str()).collect();
+        let cids: Vec<&str> = rows.iter().map(|i| plays[*i].cid.as_str()).collect();
+        let dids: Vec<&str> = rows.iter().map(|i| plays[*i].did.as_str()).collect();
+        let rkeys: Vec<&str> = rows.iter().map(|i| plays[*i].rkey.as_str()).collect();
+        let row_isrcs: Vec<Option<String>> = rows.iter().map(|i| isrcs[*i].clone()).collect();
+        let durations: Vec<Option<i32>> = rows
+            .iter()
+            .map(|i| records[*i].duration.map(|d| d as i32))
+            .collect();
+        let track_names: Vec<&str> = rows
+            .iter()
+            .map(|i| records[*i].track_name.as_str())
+            .collect();
+        let played_times: Vec<time::OffsetDateTime> = rows
+            .iter()
+            .map(|i| {
+                let played_time = records[*i].played_time.clone().unwrap_or(Datetime::now());
+                time::OffsetDateTime::from_unix_timestamp(played_time.as_ref().timestamp())
+                    .unwrap_or_else(|_| time::OffsetDateTime::now_utc())
+            })
+            .collect();
+        let row_release_mbids: Vec<Option<Uuid>> = rows.iter().map(|i| release_mbids[*i]).collect();
+        let release_names = column(&|i| records[i].release_name.as_ref().map(ToString::to_string));
+        let row_recording_mbids: Vec<Option<Uuid>> =
+            rows.iter().map(|i| recording_mbids[*i]).collect();
+        let row_match_methods: Vec<Option<&str>> = rows.iter().map(|i| match_methods[*i]).collect();
+        let row_release_match_methods: Vec<Option<&str>> =
+            rows.iter().map(|i| release_match_methods[*i]).collect();
+        let row_release_match_confidences: Vec<Option<f32>> =
+            rows.iter().map(|i| release_match_confidences[*i]).collect();
+        let row_match_confidences: Vec<Option<f32>> =
+            rows.iter().map(|i| match_confidences[*i]).collect();
+        let submission_client_agents = column(&|i| {
+            records[i]
+                .submission_client_agent
+                .as_ref()
+                .map(ToString::to_string)
+        });
+        let music_service_base_domains = column(&|i| {
+            records[i]
+                .music_service_uri
+                .as_ref()
+                .map(|uri| uri.as_str().to_string())
+        });
+        let origin_urls = column(&|i| {
+            records[i]
+                .origin_uri
+                .as_ref()
+                .map(|uri| uri.as_str().to_string())
+        });
+        // Raw artist names, kept as a fallback for plays without artist links
+        let artist_names_raw: Vec<Option<Value>> = rows
+            .iter()
+            .map(|i| {
+                let names: Vec<&str> = play_artists[*i]
+                    .iter()
+                    .map(|(_, name, _)| name.as_str())
+                    .collect();
+                (!names.is_empty()).then(|| serde_json::json!(names))
+            })
+            .collect();
+        let track_discriminants = column(&|i| discriminant_of(records[i].track_name.as_str()));
+        let release_discriminants = column(&|i| {
+            records[i]
+                .release_name
+                .as_ref()
+                .and_then(|name| discriminant_of(name.as_str()))
+        });
 
+        // A replayed record carries no resolver match, so MBIDs the resolver
+        // filled in are kept unless the record now names its own recording.
+        //
+        // Arrays of borrowed or nullable values are bound without the macro's
+        // type check, which only knows `Vec<T>` for `T[]` parameters
         sqlx::query!(
             r#"
                 INSERT INTO plays (
                     uri, cid, did, rkey, isrc, duration, track_name, played_time,
                     processed_time, release_mbid, release_name, recording_mbid,
-                    submission_client_agent, music_service_base_domain, artist_names_raw,
-                    track_discriminant, release_discriminant
-                ) VALUES (
-                    $1, $2, $3, $4, $5, $6, $7, $8,
-                    NOW(), $9, $10, $11, $12, $13, $14, $15, $16
-                ) ON CONFLICT(uri) DO UPDATE SET
+                    submission_client_agent, music_service_base_domain, origin_url,
+                    artist_names_raw, track_discriminant, release_discriminant,
+                    mbid_match_method, mbid_match_confidence,
+                    release_match_method, release_match_confidence
+                )
+                SELECT
+                    uri, cid, did, rkey, isrc, duration, track_name, played_time,
+                    NOW(), release_mbid, release_name, recording_mbid,
+                    submission_client_agent, music_service_base_domain, origin_url,
+                    artist_names_raw, track_discriminant, release_discriminant,
+                    mbid_match_method, mbid_match_confidence,
+                    release_match_method, release_match_confidence
+                FROM UNNEST(
+                    $1::text[], $2::text[], $3::text[], $4::text[], $5::text[], $6::int4[],
+                    $7::text[], $8::timestamptz[], $9::uuid[], $10::text[], $11::uuid[],
+                    $12::text[], $13::text[], $14::text[], $15::jsonb[], $16::text[], $17::text[],
+                    $18::text[], $19::real[], $20::text[], $21::real[]
+                ) AS p(
+                    uri, cid, did, rkey, isrc, duration, track_name, played_time,
+                    release_mbid, release_name, recording_mbid,
+                    submission_client_agent, music_service_base_domain, origin_url,
+                    artist_names_raw, track_discriminant, release_discriminant,
+                    mbid_match_method, mbid_match_confidence,
+                    release_match_method, release_match_confidence
+                )
+                ON CONFLICT(uri) DO UPDATE SET
                     isrc = EXCLUDED.isrc,
                     duration = EXCLUDED.duration,
                     track_name = EXCLUDED.track_name,
                     played_time = EXCLUDED.played_time,
                     processed_time = EXCLUDED.processed_time,
-                    release_mbid = EXCLUDED.release_mbid,
+                    release_mbid = CASE
+                        WHEN (plays.mbid_match_method IS NOT NULL
+                              AND EXCLUDED.mbid_match_method IS NULL)
+                          OR (plays.release_match_method IS NOT NULL
+                              AND EXCLUDED.release_match_method IS NULL)
+                        THEN COALESCE(EXCLUDED.release_mbid, plays.release_mbid)
+                        ELSE EXCLUDED.release_mbid
+                    END,
                     release_name = EXCLUDED.release_name,
-                    recording_mbid = EXCLUDED.recording_mbid,
+                    recording_mbid = CASE
+                        WHEN plays.mbid_match_method IS NOT NULL
+                         AND EXCLUDED.mbid_match_method IS NULL
+                        THEN COALESCE(EXCLUDED.recording_mbid, plays.recording_mbid)
+                        ELSE EXCLUDED.recording_mbid
+                    END,
                     submission_client_agent = EXCLUDED.submission_client_agent,
                     music_service_base_domain = EXCLUDED.music_service_base_domain,
+                    origin_url = EXCLUDED.origin_url,
                     artist_names_raw = EXCLUDED.artist_names_raw,
                     track_discriminant = EXCLUDED.track_discriminant,
-                    release_discriminant = EXCLUDED.release_discriminant;
+                    release_discriminant = EXCLUDED.release_discriminant,
+                    mbid_match_method = CASE
+                        WHEN plays.mbid_match_method IS NOT NULL
+                         AND EXCLUDED.mbid_match_method IS NULL
+                         AND EXCLUDED.recording_mbid IS NULL
+                        THEN plays.mbid_match_method
+                        ELSE EXCLUDED.mbid_match_method
+                    END,
+                    mbid_match_confidence = CASE
+                        WHEN plays.mbid_match_method IS NOT NULL
+                         AND EXCLUDED.mbid_match_method IS NULL
+                         AND EXCLUDED.recording_mbid IS NULL
+                        THEN plays.mbid_match_confidence
+                        ELSE EXCLUDED.mbid_match_confidence
+                    END,
+                    release_match_method = CASE
+                        WHEN plays.release_match_method IS NOT NULL
+                         AND EXCLUDED.release_match_method IS NULL
+                         AND EXCLUDED.release_mbid IS NULL
+                        THEN plays.release_match_method
+                        ELSE EXCLUDED.release_match_method
+                    END,
+                    release_match_confidence = CASE
+                        WHEN plays.release_match_method IS NOT NULL
+                         AND EXCLUDED.release_match_method IS NULL
+                         AND EXCLUDED.release_mbid IS NULL
+                        THEN plays.release_match_confidence
+                        ELSE EXCLUDED.release_match_confidence
+                    END;
             "#,
-            uri,
-            cid,
-            did,
-            rkey,
-            isrc,
-            play_record.duration.map(|d| d as i32),
-            track_name,
-            time_datetime,
-            release_mbid_opt,
-            release_name,
-            recording_mbid_opt,
-            submission_client_agent,
-            music_service_base_domain,
-            artist_names_json,
-            track_discriminant,
-            release_discriminant
+            &uris as _,
+            &cids as _,
+            &dids as _,
+            &rkeys as _,
+            &row_isrcs as _,
+            &durations as _,
+            &track_names as _,
+            &played_times,
+            &row_release_mbids as _,
+            &release_names as _,
+            &row_recording_mbids as _,
+            &submission_client_agents as _,
+            &music_service_base_domains as _,
+            &origin_urls as _,
+            &artist_names_raw as _,
+            &track_discriminants as _,
+            &release_discriminants as _,
+            &row_match_methods as _,
+            &row_match_confidences as _,
+            &row_release_match_methods as _,
+            &row_release_match_confidences as _
         )
-        .execute(&self.sql)
+        .execute(&mut *tx)
+        .await?;
+
+        // Index the ISRC and recording MBID pairs clients sent
+        let client_recording_mbids: Vec<Option<Uuid>> = rows
+            .iter()
+            .map(|i| recording_mbids[*i].filter(|_| match_methods[*i].is_none()))
+            .collect();
+        isrc::index_plays(&mut tx, &dids, &row_isrcs, &client_recording_mbids).await?;
+
+        // Plays without a recording MBID are matched against MusicBrainz later
+        sqlx::query(
+            r#"
+                INSERT INTO mbid_resolution_queue (play_uri)
+                SELECT uri FROM UNNEST($1::text[], $2::uuid[]) AS p(uri, recording_mbid)
+                WHERE recording_mbid IS NULL
+                ON CONFLICT (play_uri) DO NOTHING;
+            "#,
+        )
+        .bind(&uris)
+        .bind(&row_recording_mbids)
+        .execute(&mut *tx)
         .await?;
 
         // Insert plays into the extended join table (supports all artists)
-        for (artist_id, artist_name) in &parsed_artists {
-            sqlx::query!(
-                r#"
-                    INSERT INTO play_to_artists_extended (play_uri, artist_id, artist_name) VALUES
-                    ($1, $2, $3)
-                    ON CONFLICT (play_uri, artist_id) DO NOTHING;
-                "#,
-                uri,
-                artist_id,
-                artist_name
-            )
-            .execute(&self.sql)
-            .await?;
+        let mut link_uris: Vec<&str> = Vec::new();
+        let mut link_artist_ids: Vec<i32> = Vec::new();
+        let mut link_artist_names: Vec<&str> = Vec::new();
+        let mut link_positions: Vec<i16> = Vec::new();
+        let mut link_join_phrases: Vec<Option<&str>> = Vec::new();
+        for index in &rows {
+            let mut linked = HashSet::new();
+            for (position, (artist_id, artist_name, join_phrase)) in
+                play_artists[*index].iter().enumerate()
+            {
+                // A play credits an artist once, at its first position
+                if !linked.insert(*artist_id) {
+                    continue;
+                }
+                link_uris.push(plays[*index].uri.as_str());
+                link_artist_ids.push(*artist_id);
+                link_artist_names.push(artist_name.as_str());
+                link_positions.push(position as i16);
+                link_join_phrases.push(join_phrase.as_deref());
+            }
         }
+        sqlx::query(
+            r#"
+                INSERT INTO play_to_artists_extended (
+                    play_uri, artist_id, artist_name, position, join_phrase
+                )
+                SELECT * FROM UNNEST($1::text[], $2::int4[], $3::text[], $4::int2[], $5::text[])
+                ON CONFLICT (play_uri, artist_id) DO UPDATE SET
+                    position = EXCLUDED.position,
+                    join_phrase = EXCLUDED.join_phrase;
+            "#,
+        )
+        .bind(&link_uris)
+        .bind(&link_artist_ids)
+        .bind(&link_artist_names)
+        .bind(&link_positions)
+        .bind(&link_join_phrases)
+        .execute(&mut *tx)
+        .await?;
 
-        // Refresh materialized views concurrently (if needed, consider if this should be done less frequently)
-        sqlx::query!("REFRESH MATERIALIZED VIEW mv_artist_play_counts;")
-            .execute(&self.sql)
-            .await?;
-        sqlx::query!("REFRESH MATERIALIZED VIEW mv_release_play_counts;")
-            .execute(&self.sql)
-            .await?;
-        sqlx::query!("REFRESH MATERIALIZED VIEW mv_recording_play_counts;")
-            .execute(&self.sql)
-            .await?;
-        sqlx::query!("REFRESH MATERIALIZED VIEW mv_global_play_count;")
-            .execute(&self.sql)
-            .await?;
+        tx.commit().await?;
 
-        // // Optionally check materialised views (consider removing in production for performance)
-        // // For debugging purposes, can keep for now
-        // if cfg!(debug_assertions) {
-        //     // Conditionally compile debug checks
-        //     let artist_counts = sqlx::query!("SELECT * FROM mv_artist_play_counts;")
-        //         .fetch_all(&self.sql)
-        //         .await?;
-        //     dbg!("mv_artist_play_counts: {:?}", artist_counts);
-
-        //     let release_counts = sqlx::query!("SELECT * FROM mv_release_play_counts;")
-        //         .fetch_all(&self.sql)
-        //         .await?;
-        //     dbg!("mv_release_play_counts: {:?}", release_counts);
-
-        //     let recording_counts = sqlx::query!("SELECT * FROM mv_recording_play_counts;")
-        //         .fetch_all(&self.sql)
-        //         .await?;
-        //     dbg!("mv_recording_play_counts: {:?}", recording_counts);
-
-        //     let global_count = sqlx::query!("SELECT * FROM mv_global_play_count;")
-        //         .fetch_all(&self.sql)
-        //         .await?;
-        //     dbg!("mv_global_play_count: {:?}", global_count);
-        // }
+        for (key, artist_id) in batch_artists {
+            self.cache.insert_artist(key, artist_id);
+        }
+        for (mbid, name) in batch_releases {
+            self.cache.insert_release(mbid, name);
+        }
+        for (mbid, name) in batch_recordings {
+            self.cache.insert_recording(mbid, name);
+        }
 
+        let keys: Vec<(&str, &str)> = plays
+            .iter()
+            .map(|play| (play.did.as_str(), play.rkey.as_str()))
+            .collect();
+        validate::clear_rejections(&self.sql, crate::ingestors::teal::STABLE_FEED_PLAY, &keys)
+            .await;
+
+        // The play count views are refreshed on satellite's schedule
+        // (`REFRESH_MV_CRON`); refreshing them for every live play would
+        // rebuild them several times a second.
         Ok(())
     }
 
+    /// Write `plays` as one batch, falling back to one play at a time when the
+    /// batch fails so a single bad play doesn't take the others with it.
+    /// Returns the outcome of each play, in order.
+    pub async fn write_batch(&self, plays: &[PendingPlay]) -> Vec<anyhow::Result<()>> {
+        match self.insert_plays(plays).await {
+            Ok(()) => plays.iter().map(|_| Ok(())).collect(),
+            Err(e) if plays.len() > 1 => {
+                tracing::warn!(
+                    "Batch of {} plays failed, retrying one at a time: {}",
+                    plays.len(),
+                    e
+                );
+                let mut results = Vec::with_capacity(plays.len());
+                for play in plays {
+                    results.push(self.insert_plays(std::slice::from_ref(play)).await);
+                }
+                results
+            }
+            Err(e) => vec![Err(e)],
+        }
+    }
+
     async fn remove_play(&self, uri: &str) -> Result<(), sqlx::Error> {
         sqlx::query!("DELETE FROM play_to_artists WHERE play_uri = $1", uri)
             .execute(&self.sql)
@@ -1591,26 +2258,51 @@ impl LexiconIngestor for PlayIngestor {
     async fn ingest(&self, message: Event<Value>) -> anyhow::Result<()> {
         if let Some(commit) = &message.commit {
             if let Some(ref record) = &commit.record {
+                if !validate::accept_record(
+                    &self.sql,
+                    &message.did,
+                    &commit.collection,
+                    &commit.rkey,
+                    commit.cid.as_deref(),
+                    record,
+                )
+                .await
+                {
+                    return Ok(());
+                }
                 let record = parse_play_record(record)?;
                 if let Some(ref commit) = message.commit {
                     if let Some(ref cid) = commit.cid {
                         // TODO: verify cid
-                        self.insert_play(
-                            &record,
-                            &assemble_at_uri(
-                                &message.did,
-                                crate::ingestors::teal::canonical_collection(&commit.collection),
-                                &commit.rkey,
-                            ),
-                            cid,
+                        let uri = assemble_at_uri(
                             &message.did,
+                            crate::ingestors::teal::canonical_collection(&commit.collection),
                             &commit.rkey,
-                        )
-                        .await?;
+                        );
+                        match (&self.buffer, FailedRecord::from_event(&message)) {
+                            (Some(buffer), Some(failed)) => {
+                                let play = PendingPlay {
+                                    record,
+                                    uri,
+                                    cid: cid.clone(),
+                                    did: message.did.clone(),
+                                    rkey: commit.rkey.clone(),
+                                };
+                                buffer.push(play, failed).await?;
+                            }
+                            _ => {
+                                self.insert_play(&record, &uri, cid, &message.did, &commit.rkey)
+                                    .await?;
+                            }
+                        }
                     }
                 }
             } else {
                 println!("{}: Message {} deleted", message.did, commit.rkey);
+                // A buffered create must not bring the play back afterwards
+                if let Some(buffer) = &self.buffer {
+                    buffer.flush().await?;
+                }
                 let uri = assemble_at_uri(
                     &message.did,
                     crate::ingestors::teal::canonical_collection(&commit.collection),
@@ -1626,7 +2318,7 @@ impl LexiconIngestor for PlayIngestor {
 }
 
 /// Normalize legacy namespace tags before deserializing the generated record type.
-fn parse_play_record(record: &Value) -> anyhow::Result<types::fm_teal::feed::play::Play> {
+pub fn parse_play_record(record: &Value) -> anyhow::Result<types::fm_teal::feed::play::Play> {
     Ok(value::from_json_value::<types::fm_teal::feed::play::Play>(
         normalize_legacy_record_type(record),
     )?)
@@ -1634,7 +2326,7 @@ fn parse_play_record(record: &Value) -> anyhow::Result<types::fm_teal::feed::pla
 
 #[cfg(test)]
 mod tests {
-    use super::parse_play_record;
+    use super::{last_occurrences, parse_play_record, PlayIngestor, Uuid};
     use crate::ingestors::teal::{ALPHA_FEED_PLAY, STABLE_FEED_PLAY};
     use rocketman::types::event::Event;
     use serde_json::{json, Value};
@@ -1669,4 +2361,53 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn similarity_folds_accents_width_and_scripts() {
+        let same = |a: &str, b: &str, is_artist: bool| {
+            PlayIngestor::calculate_similarity(a, b, is_artist) == 1.0
+        };
+        assert!(same("Beyoncé", "Beyonce", true));
+        assert!(same("MØ", "Mo", true));
+        assert!(same("ＹＯＡＳＯＢＩ", "YOASOBI", true));
+        assert!(same("ｱｲﾄﾞﾙ", "アイドル", false));
+        assert!(same("Ёлка", "Елка", true));
+        assert!(same("방탄소년단", "방탄소년단", true));
+        assert!(!same("ガガ", "カカ", true));
+        assert!(!same("Кино", "Кина", true));
+
+        // One different character out of four scores like a Latin name would
+        let cjk = PlayIngestor::calculate_similarity("東京事変", "東京事件", true);
+        let latin = PlayIngestor::calculate_similarity("abcd", "abce", true);
+        assert!((cjk - latin).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fuzzy_match_takes_the_closest_name_above_the_threshold() {
+        let ids: Vec<Uuid> = (1..=3).map(Uuid::from_u128).collect();
+        let candidates = vec![
+            (ids[0], "Hello Word".to_string()),
+            (ids[1], "hello world".to_string()),
+            (ids[2], "Hello Girl".to_string()),
+        ];
+
+        let (mbid, confidence) =
+            PlayIngestor::best_fuzzy_match("Hello World", candidates.clone()).unwrap();
+        assert_eq!(mbid, ids[1]);
+        assert_eq!(confidence, 1.0);
+        assert_eq!(
+            PlayIngestor::best_fuzzy_match("Goodbye World", candidates),
+            None
+        );
+        assert_eq!(
+            PlayIngestor::best_fuzzy_match("Hello World", Vec::new()),
+            None
+        );
+    }
+
+    #[test]
+    fn batch_keeps_last_version_of_each_play() {
+        let uris = ["at://a/1", "at://a/2", "at://a/1", "at://a/3", "at://a/2"];
+        assert_eq!(last_occurrences(uris.into_iter()), vec![2, 3, 4]);
+    }
 }
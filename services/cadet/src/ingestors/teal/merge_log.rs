@@ -0,0 +1,921 @@
+//! Audit log of entity merges, and undoing them.
+//!
+//! Before a merge rewrites anything, the merged-away row and each play link it
+//! is about to rewrite are recorded in `entity_merges` and
+//! `entity_merge_plays`, in the merge's transaction. Splits of combined
+//! credits and synthetic artists given their MusicBrainz ID in place are
+//! logged the same way. `cadet merges [limit]` lists recent merges and
+//! `cadet unmerge <id>` puts the row and links back.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{types::Uuid, PgConnection, PgPool};
+
+use super::consolidation::MergedKind;
+use super::entity_cache::EntityCache;
+
+/// `entity_merges.method` of merges made by consolidation.
+pub const CONSOLIDATION_METHOD: &str = "consolidation";
+/// `entity_merges.method` of synthetic artists merged by MBID resolution.
+pub const RESOLVER_METHOD: &str = "resolver";
+/// `entity_merges.method` of synthetic artists given their MusicBrainz ID in
+/// place by MBID resolution.
+pub const RESOLVER_UPGRADE_METHOD: &str = "resolver_upgrade";
+/// `entity_merges.method` of combined credits split into their artists.
+pub const SPLIT_METHOD: &str = "split";
+
+/// Lock artist `id`, which is about to be merged away or changed, so plays
+/// being linked to it wait for the merge instead of slipping in after its
+/// snapshot.
+async fn lock_artist(conn: &mut PgConnection, id: i32) -> Result<()> {
+    sqlx::query("SELECT 1 FROM artists_extended WHERE id = $1 FOR UPDATE")
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Record that artist `source_id` is about to be merged into `target_id`.
+/// Call it before the links move, in the same transaction.
+pub async fn record_artist_merge(
+    conn: &mut PgConnection,
+    source_id: i32,
+    target_id: i32,
+    confidence: Option<f64>,
+    method: &str,
+) -> Result<i64> {
+    lock_artist(conn, source_id).await?;
+    let merge_id: i64 = sqlx::query_scalar(
+        r#"
+            INSERT INTO entity_merges (
+                entity_type, method, confidence, source_id, source_mbid, source_name,
+                source_row, target_id, target_mbid, target_name
+            )
+            SELECT 'artist', $3, $4, s.id, s.mbid, s.name, to_jsonb(s), t.id, t.mbid, t.name
+            FROM artists_extended s, artists_extended t
+            WHERE s.id = $1 AND t.id = $2
+            RETURNING id
+        "#,
+    )
+    .bind(source_id)
+    .bind(target_id)
+    .bind(method)
+    .bind(confidence.map(|confidence| confidence as f32))
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| anyhow!("artist {} or {} no longer exists", source_id, target_id))?;
+
+    sqlx::query(
+        r#"
+            INSERT INTO entity_merge_plays (
+                merge_id, play_uri, artist_name, position, join_phrase, dropped
+            )
+            SELECT $1, link.play_uri, link.artist_name, link.position, link.join_phrase,
+                EXISTS (
+                    SELECT 1 FROM play_to_artists_extended target
+                    WHERE target.play_uri = link.play_uri AND target.artist_id = $3
+                )
+            FROM play_to_artists_extended link
+            WHERE link.artist_id = $2
+        "#,
+    )
+    .bind(merge_id)
+    .bind(source_id)
+    .bind(target_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(merge_id)
+}
+
+/// Record that release or recording `source` is about to be merged into
+/// `target`. Call it before the plays move, in the same transaction.
+pub async fn record_play_merge(
+    conn: &mut PgConnection,
+    kind: MergedKind,
+    source: Uuid,
+    target: Uuid,
+    confidence: Option<f64>,
+    method: &str,
+) -> Result<i64> {
+    let (table, column) = play_link(kind)?;
+    // Plays being linked to the source wait for the merge, see `lock_artist`
+    sqlx::query(&format!("SELECT 1 FROM {table} WHERE mbid = $1 FOR UPDATE"))
+        .bind(source)
+        .execute(&mut *conn)
+        .await?;
+    let merge_id: i64 = sqlx::query_scalar(&format!(
+        r#"
+            INSERT INTO entity_merges (
+                entity_type, method, confidence, source_mbid, source_name, source_row,
+                target_mbid, target_name
+            )
+            SELECT $3, $4, $5, s.mbid, s.name, to_jsonb(s), t.mbid, t.name
+            FROM {table} s, {table} t
+            WHERE s.mbid = $1 AND t.mbid = $2
+            RETURNING id
+        "#
+    ))
+    .bind(source)
+    .bind(target)
+    .bind(kind.as_str())
+    .bind(method)
+    .bind(confidence.map(|confidence| confidence as f32))
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| {
+        anyhow!(
+            "{} {} or {} no longer exists",
+            kind.as_str(),
+            source,
+            target
+        )
+    })?;
+
+    sqlx::query(&format!(
+        r#"
+            INSERT INTO entity_merge_plays (merge_id, play_uri, release_name)
+            SELECT $1, uri, release_name FROM plays WHERE {column} = $2
+        "#
+    ))
+    .bind(merge_id)
+    .bind(source)
+    .execute(&mut *conn)
+    .await?;
+    Ok(merge_id)
+}
+
+/// Record that artist `source_id`, a combined credit, is about to be split
+/// into `target_ids`, credited as `credit`. Call it before the links move, in
+/// the same transaction.
+pub async fn record_artist_split(
+    conn: &mut PgConnection,
+    source_id: i32,
+    target_ids: &[i32],
+    credit: &str,
+) -> Result<i64> {
+    lock_artist(conn, source_id).await?;
+    let merge_id: i64 = sqlx::query_scalar(
+        r#"
+            INSERT INTO entity_merges (
+                entity_type, method, source_id, source_mbid, source_name, source_row,
+                target_ids, target_name
+            )
+            SELECT 'artist', $2, s.id, s.mbid, s.name, to_jsonb(s), $3, $4
+            FROM artists_extended s
+            WHERE s.id = $1
+            RETURNING id
+        "#,
+    )
+    .bind(source_id)
+    .bind(SPLIT_METHOD)
+    .bind(target_ids)
+    .bind(credit)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| anyhow!("artist {} no longer exists", source_id))?;
+
+    sqlx::query(
+        r#"
+            INSERT INTO entity_merge_plays (merge_id, play_uri, artist_name, position, join_phrase)
+            SELECT $1, play_uri, artist_name, position, join_phrase
+            FROM play_to_artists_extended
+            WHERE artist_id = $2
+        "#,
+    )
+    .bind(merge_id)
+    .bind(source_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(merge_id)
+}
+
+/// Record that synthetic artist `id` is about to be given MusicBrainz ID
+/// `mbid` in place. Returns `None`, recording nothing, if it isn't synthetic
+/// (any more).
+pub async fn record_artist_upgrade(
+    conn: &mut PgConnection,
+    id: i32,
+    mbid: Uuid,
+    name: &str,
+    confidence: Option<f64>,
+) -> Result<Option<i64>> {
+    lock_artist(conn, id).await?;
+    Ok(sqlx::query_scalar(
+        r#"
+            INSERT INTO entity_merges (
+                entity_type, method, confidence, source_id, source_mbid, source_name,
+                source_row, target_id, target_mbid, target_name
+            )
+            SELECT 'artist', $2, $3, s.id, s.mbid, s.name, to_jsonb(s), s.id, $4, $5
+            FROM artists_extended s
+            WHERE s.id = $1 AND s.mbid_type = 'synthetic'
+            RETURNING id
+        "#,
+    )
+    .bind(id)
+    .bind(RESOLVER_UPGRADE_METHOD)
+    .bind(confidence.map(|confidence| confidence as f32))
+    .bind(mbid)
+    .bind(name)
+    .fetch_optional(&mut *conn)
+    .await?)
+}
+
+/// The table of releases or recordings and the column of `plays` linking to it.
+fn play_link(kind: MergedKind) -> Result<(&'static str, &'static str)> {
+    match kind {
+        MergedKind::Release => Ok(("releases", "release_mbid")),
+        MergedKind::Recording => Ok(("recordings", "recording_mbid")),
+        MergedKind::Artist => Err(anyhow!("artists are linked to plays by ID")),
+    }
+}
+
+/// A merge as curators review it.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct MergeEntry {
+    pub id: i64,
+    pub entity_type: String,
+    pub method: String,
+    pub confidence: Option<f32>,
+    pub source_id: Option<i32>,
+    pub source_mbid: Option<Uuid>,
+    pub source_name: String,
+    pub target_id: Option<i32>,
+    /// Artists a split combined artist was split into
+    pub target_ids: Option<Vec<i32>>,
+    pub target_mbid: Option<Uuid>,
+    pub target_name: String,
+    /// Plays the merge rewrote
+    pub plays: i64,
+    pub merged_at: DateTime<Utc>,
+    pub unmerged_at: Option<DateTime<Utc>>,
+}
+
+/// The latest `limit` merges, newest first.
+pub async fn recent_merges(pool: &PgPool, limit: i64) -> Result<Vec<MergeEntry>> {
+    Ok(sqlx::query_as(
+        r#"
+            SELECT
+                m.id, m.entity_type, m.method, m.confidence, m.source_id, m.source_mbid,
+                m.source_name, m.target_id, m.target_ids, m.target_mbid, m.target_name,
+                (SELECT COUNT(*) FROM entity_merge_plays p WHERE p.merge_id = m.id) AS plays,
+                m.merged_at, m.unmerged_at
+            FROM entity_merges m
+            ORDER BY m.merged_at DESC, m.id DESC
+            LIMIT $1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Unmerged {
+    pub merge_id: i64,
+    pub entity_type: String,
+    pub source_name: String,
+    /// Plays linked back to the restored entity
+    pub restored: u64,
+    /// Plays the merge rewrote. Fewer are restored when plays were deleted or
+    /// moved again since, e.g. by a later merge of the target.
+    pub recorded: u64,
+}
+
+#[derive(sqlx::FromRow)]
+struct MergeRow {
+    entity_type: String,
+    method: String,
+    source_id: Option<i32>,
+    source_mbid: Option<Uuid>,
+    source_name: String,
+    source_row: Value,
+    target_id: Option<i32>,
+    target_ids: Option<Vec<i32>>,
+    target_mbid: Option<Uuid>,
+    unmerged: bool,
+}
+
+/// Undo merge `merge_id`: put the merged-away row back and link the plays
+/// the merge rewrote to it again.
+pub async fn unmerge(pool: &PgPool, merge_id: i64) -> Result<Unmerged> {
+    let mut tx = pool.begin().await?;
+    let merge: MergeRow = sqlx::query_as(
+        r#"
+            SELECT entity_type, method, source_id, source_mbid, source_name, source_row,
+                target_id, target_ids, target_mbid, unmerged_at IS NOT NULL AS unmerged
+            FROM entity_merges WHERE id = $1
+            FOR UPDATE
+        "#,
+    )
+    .bind(merge_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| anyhow!("no merge {}", merge_id))?;
+    if merge.unmerged {
+        return Err(anyhow!("merge {} was already undone", merge_id));
+    }
+    let recorded: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM entity_merge_plays WHERE merge_id = $1")
+            .bind(merge_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+    let restored = match merge.entity_type.as_str() {
+        "artist" => match merge.method.as_str() {
+            SPLIT_METHOD => restore_split(&mut tx, merge_id, &merge).await?,
+            RESOLVER_UPGRADE_METHOD => restore_upgrade(&mut tx, merge_id, &merge).await?,
+            _ => restore_artist(&mut tx, merge_id, &merge).await?,
+        },
+        "release" | "recording" => {
+            let kind = if merge.entity_type == "release" {
+                MergedKind::Release
+            } else {
+                MergedKind::Recording
+            };
+            restore_play_links(&mut tx, merge_id, kind, &merge).await?
+        }
+        other => return Err(anyhow!("unknown merged entity type '{}'", other)),
+    };
+
+    sqlx::query("UPDATE entity_merges SET unmerged_at = NOW() WHERE id = $1")
+        .bind(merge_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    if merge.entity_type == "artist" {
+        sqlx::query("REFRESH MATERIALIZED VIEW mv_artist_play_counts")
+            .execute(pool)
+            .await?;
+    }
+    // The target may be cached under names that now belong to the source
+    EntityCache::shared().invalidate(pool).await?;
+
+    Ok(Unmerged {
+        merge_id,
+        entity_type: merge.entity_type,
+        source_name: merge.source_name,
+        restored,
+        recorded: recorded as u64,
+    })
+}
+
+/// Put the merged-away artist back, returning its ID.
+async fn restore_source_artist(
+    conn: &mut PgConnection,
+    source_id: i32,
+    merge: &MergeRow,
+) -> Result<i32> {
+    restore_row(conn, "artists_extended", &merge.source_row).await?;
+    // An artist with the source's MBID may have been created again since
+    Ok(sqlx::query_scalar(
+        r#"
+            SELECT id FROM artists_extended
+            WHERE id = $1 OR mbid = $2
+            ORDER BY id = $1 DESC
+            LIMIT 1
+        "#,
+    )
+    .bind(source_id)
+    .bind(merge.source_mbid)
+    .fetch_one(&mut *conn)
+    .await?)
+}
+
+async fn restore_artist(conn: &mut PgConnection, merge_id: i64, merge: &MergeRow) -> Result<u64> {
+    let (Some(source_id), Some(target_id)) = (merge.source_id, merge.target_id) else {
+        return Err(anyhow!("merge {} has no artist IDs", merge_id));
+    };
+    let source_id = restore_source_artist(conn, source_id, merge).await?;
+    // The plays' links may have moved on since, e.g. by a later merge of the target
+    let target_id = merged_into(target_id, &later_artist_merges(conn, merge_id).await?);
+
+    let moved = sqlx::query(
+        r#"
+            UPDATE play_to_artists_extended link
+            SET artist_id = $2, artist_name = m.artist_name
+            FROM entity_merge_plays m
+            WHERE m.merge_id = $1 AND NOT m.dropped
+              AND link.play_uri = m.play_uri AND link.artist_id = $3
+              AND NOT EXISTS (
+                  SELECT 1 FROM play_to_artists_extended existing
+                  WHERE existing.play_uri = m.play_uri AND existing.artist_id = $2
+              )
+        "#,
+    )
+    .bind(merge_id)
+    .bind(source_id)
+    .bind(target_id)
+    .execute(&mut *conn)
+    .await?;
+    let relinked = sqlx::query(
+        r#"
+            INSERT INTO play_to_artists_extended (
+                play_uri, artist_id, artist_name, position, join_phrase
+            )
+            SELECT m.play_uri, $2, m.artist_name, m.position, m.join_phrase
+            FROM entity_merge_plays m
+            JOIN plays p ON p.uri = m.play_uri
+            WHERE m.merge_id = $1 AND m.dropped
+            ON CONFLICT (play_uri, artist_id) DO NOTHING
+        "#,
+    )
+    .bind(merge_id)
+    .bind(source_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(moved.rows_affected() + relinked.rows_affected())
+}
+
+/// Undo a split: the split artists' links that took the combined artist's
+/// place are removed and the combined artist is linked again.
+async fn restore_split(conn: &mut PgConnection, merge_id: i64, merge: &MergeRow) -> Result<u64> {
+    let (Some(source_id), Some(target_ids)) = (merge.source_id, &merge.target_ids) else {
+        return Err(anyhow!("merge {} has no artist IDs", merge_id));
+    };
+    let source_id = restore_source_artist(conn, source_id, merge).await?;
+    let later = later_artist_merges(conn, merge_id).await?;
+    let split_into: Vec<i32> = target_ids
+        .iter()
+        .map(|id| merged_into(*id, &later))
+        .collect();
+
+    let rows: Vec<(String, Option<i16>, i32, Option<i16>)> = sqlx::query_as(
+        r#"
+            SELECT m.play_uri, m.position, link.artist_id, link.position
+            FROM entity_merge_plays m
+            JOIN play_to_artists_extended link ON link.play_uri = m.play_uri
+            WHERE m.merge_id = $1
+        "#,
+    )
+    .bind(merge_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    let mut plays: BTreeMap<String, (Option<i16>, Vec<CreditLink>)> = BTreeMap::new();
+    for (play_uri, split_position, artist_id, position) in rows {
+        plays
+            .entry(play_uri)
+            .or_insert_with(|| (split_position, Vec::new()))
+            .1
+            .push(CreditLink {
+                artist_id,
+                position,
+            });
+    }
+
+    let mut removed: (Vec<&str>, Vec<i32>) = Default::default();
+    let mut moved: (Vec<&str>, Vec<i32>, Vec<i16>) = Default::default();
+    for (play_uri, (position, links)) in &plays {
+        let unsplit = unsplit(links, *position, &split_into);
+        for artist_id in unsplit.removed {
+            removed.0.push(play_uri);
+            removed.1.push(artist_id);
+        }
+        for (artist_id, position) in unsplit.moved {
+            moved.0.push(play_uri);
+            moved.1.push(artist_id);
+            moved.2.push(position);
+        }
+    }
+    sqlx::query(
+        r#"
+            DELETE FROM play_to_artists_extended link
+            USING UNNEST($1::text[], $2::int4[]) AS removed(play_uri, artist_id)
+            WHERE link.play_uri = removed.play_uri AND link.artist_id = removed.artist_id
+        "#,
+    )
+    .bind(&removed.0)
+    .bind(&removed.1)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query(
+        r#"
+            UPDATE play_to_artists_extended link SET position = moved.position
+            FROM UNNEST($1::text[], $2::int4[], $3::int2[]) AS moved(play_uri, artist_id, position)
+            WHERE link.play_uri = moved.play_uri AND link.artist_id = moved.artist_id
+        "#,
+    )
+    .bind(&moved.0)
+    .bind(&moved.1)
+    .bind(&moved.2)
+    .execute(&mut *conn)
+    .await?;
+    let relinked = sqlx::query(
+        r#"
+            INSERT INTO play_to_artists_extended (
+                play_uri, artist_id, artist_name, position, join_phrase
+            )
+            SELECT m.play_uri, $2, m.artist_name, m.position, m.join_phrase
+            FROM entity_merge_plays m
+            JOIN plays p ON p.uri = m.play_uri
+            WHERE m.merge_id = $1
+            ON CONFLICT (play_uri, artist_id) DO NOTHING
+        "#,
+    )
+    .bind(merge_id)
+    .bind(source_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(relinked.rows_affected())
+}
+
+/// A play's link to an artist.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CreditLink {
+    artist_id: i32,
+    position: Option<i16>,
+}
+
+/// How undoing a split changes one play's links.
+#[derive(Debug, Default, PartialEq)]
+struct Unsplit {
+    /// Split artists whose links took the combined artist's place
+    removed: Vec<i32>,
+    /// Artists credited after them, with the positions they had before
+    moved: Vec<(i32, i16)>,
+}
+
+/// Undo, in a play's `links`, the split of the combined artist it credited at
+/// `position` into `split_into`. The split artists took positions `position`
+/// to `position + extra` and the artists after them moved `extra` along; a
+/// combined artist without a position was split into positions from 0 and
+/// moved nothing.
+fn unsplit(links: &[CreditLink], position: Option<i16>, split_into: &[i32]) -> Unsplit {
+    let first = position.unwrap_or(0);
+    let extra = split_into.len().saturating_sub(1) as i16;
+    let last = first + extra;
+
+    let mut unsplit = Unsplit::default();
+    for link in links {
+        let Some(at) = link.position else {
+            continue;
+        };
+        if (first..=last).contains(&at) && split_into.contains(&link.artist_id) {
+            unsplit.removed.push(link.artist_id);
+        } else if position.is_some() && at > last {
+            unsplit.moved.push((link.artist_id, at - extra));
+        }
+    }
+    unsplit
+}
+
+/// Artist merges made after `merge_id` and not undone, as `(source, target)`
+/// pairs, oldest first.
+async fn later_artist_merges(conn: &mut PgConnection, merge_id: i64) -> Result<Vec<(i32, i32)>> {
+    Ok(sqlx::query_as(
+        r#"
+            SELECT source_id, target_id FROM entity_merges
+            WHERE id > $1 AND entity_type = 'artist' AND method = ANY($2)
+              AND unmerged_at IS NULL
+              AND source_id IS NOT NULL AND target_id IS NOT NULL
+            ORDER BY id
+        "#,
+    )
+    .bind(merge_id)
+    .bind([CONSOLIDATION_METHOD, RESOLVER_METHOD])
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+/// The artist that artist `id`'s links belong to after the `later` merges.
+fn merged_into(id: i32, later: &[(i32, i32)]) -> i32 {
+    later.iter().fold(
+        id,
+        |id, &(source, target)| if source == id { target } else { id },
+    )
+}
+
+/// Undo an in-place upgrade: the artist is synthetic again, under its old
+/// MBID. No play links changed.
+async fn restore_upgrade(conn: &mut PgConnection, merge_id: i64, merge: &MergeRow) -> Result<u64> {
+    let (Some(id), Some(mbid)) = (merge.source_id, merge.target_mbid) else {
+        return Err(anyhow!("merge {} has no artist ID or MBID", merge_id));
+    };
+    let restored = sqlx::query(
+        r#"
+            UPDATE artists_extended a
+            SET mbid = s.mbid, mbid_type = s.mbid_type, updated_at = NOW()
+            FROM jsonb_populate_record(NULL::artists_extended, $3) s
+            WHERE a.id = $1 AND a.mbid = $2
+        "#,
+    )
+    .bind(id)
+    .bind(mbid)
+    .bind(&merge.source_row)
+    .execute(&mut *conn)
+    .await?;
+    if restored.rows_affected() == 0 {
+        return Err(anyhow!(
+            "artist {} no longer has MusicBrainz ID {}",
+            id,
+            mbid
+        ));
+    }
+    Ok(0)
+}
+
+async fn restore_play_links(
+    conn: &mut PgConnection,
+    merge_id: i64,
+    kind: MergedKind,
+    merge: &MergeRow,
+) -> Result<u64> {
+    let (table, column) = play_link(kind)?;
+    let (Some(source), Some(target)) = (merge.source_mbid, merge.target_mbid) else {
+        return Err(anyhow!("merge {} has no MBIDs", merge_id));
+    };
+    restore_row(conn, table, &merge.source_row).await?;
+
+    let release_name = if kind == MergedKind::Release {
+        ", release_name = m.release_name"
+    } else {
+        ""
+    };
+    let restored = sqlx::query(&format!(
+        r#"
+            UPDATE plays p SET {column} = $2{release_name}
+            FROM entity_merge_plays m
+            WHERE m.merge_id = $1 AND p.uri = m.play_uri AND p.{column} = $3
+        "#
+    ))
+    .bind(merge_id)
+    .bind(source)
+    .bind(target)
+    .execute(&mut *conn)
+    .await?;
+    Ok(restored.rows_affected())
+}
+
+/// Insert `row`, a row of `table` as `to_jsonb` gave it, unless one with the
+/// same key is back already.
+async fn restore_row(conn: &mut PgConnection, table: &str, row: &Value) -> Result<()> {
+    let columns: Vec<(String, bool)> = sqlx::query_as(
+        r#"
+            SELECT column_name::text, is_generated <> 'NEVER'
+            FROM information_schema.columns
+            WHERE table_schema = current_schema() AND table_name = $1
+            ORDER BY ordinal_position
+        "#,
+    )
+    .bind(table)
+    .fetch_all(&mut *conn)
+    .await?;
+    let columns = restored_columns(&columns, row);
+    if columns.is_empty() {
+        return Err(anyhow!("snapshot is not a row of {}", table));
+    }
+    let columns = columns
+        .iter()
+        .map(|column| format!("\"{}\"", column))
+        .collect::<Vec<_>>()
+        .join(", ");
+    sqlx::query(&format!(
+        r#"
+            INSERT INTO {table} ({columns})
+            SELECT {columns} FROM jsonb_populate_record(NULL::{table}, $1)
+            ON CONFLICT DO NOTHING
+        "#
+    ))
+    .bind(row)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Of a table's `(name, generated)` columns, those to fill from snapshot `row`.
+/// Generated columns are left to the database and columns added since the
+/// snapshot to their defaults; snapshot keys of dropped columns are ignored.
+fn restored_columns<'a>(columns: &'a [(String, bool)], row: &Value) -> Vec<&'a str> {
+    let Value::Object(row) = row else {
+        return Vec::new();
+    };
+    columns
+        .iter()
+        .filter(|(column, generated)| !generated && row.contains_key(column))
+        .map(|(column, _)| column.as_str())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn link(artist_id: i32, position: i16) -> CreditLink {
+        CreditLink {
+            artist_id,
+            position: Some(position),
+        }
+    }
+
+    /// Split the artist credited at `position` as the split ingest does,
+    /// making room after it and linking `split_into` in its place.
+    fn split(links: &[CreditLink], combined: i32, split_into: &[i32]) -> Vec<CreditLink> {
+        let position = links
+            .iter()
+            .find(|link| link.artist_id == combined)
+            .unwrap()
+            .position;
+        let extra = split_into.len() as i16 - 1;
+        let mut split: Vec<CreditLink> = links
+            .iter()
+            .filter(|link| link.artist_id != combined)
+            .map(|link| match (link.position, position) {
+                (Some(at), Some(position)) if at > position => {
+                    self::link(link.artist_id, at + extra)
+                }
+                _ => *link,
+            })
+            .collect();
+        for (idx, artist_id) in split_into.iter().enumerate() {
+            split.push(link(*artist_id, position.unwrap_or(0) + idx as i16));
+        }
+        split
+    }
+
+    /// Apply `unsplit` and link `combined` at `position` again, as
+    /// `restore_split` does.
+    fn restore(
+        links: &[CreditLink],
+        unsplit: Unsplit,
+        combined: i32,
+        position: Option<i16>,
+    ) -> Vec<CreditLink> {
+        let mut restored: Vec<CreditLink> = links
+            .iter()
+            .filter(|link| !unsplit.removed.contains(&link.artist_id))
+            .map(
+                |link| match unsplit.moved.iter().find(|(id, _)| *id == link.artist_id) {
+                    Some((id, at)) => self::link(*id, *at),
+                    None => *link,
+                },
+            )
+            .collect();
+        restored.push(CreditLink {
+            artist_id: combined,
+            position,
+        });
+        restored.sort_by_key(|link| (link.position, link.artist_id));
+        restored
+    }
+
+    #[test]
+    fn test_unsplit_restores_a_credit_split_in_the_middle() {
+        // X, "A feat. B & C", Y
+        let credits = vec![link(1, 0), link(2, 1), link(3, 2)];
+        let split_into = [10, 11, 12];
+        let links = split(&credits, 2, &split_into);
+
+        let unsplit = unsplit(&links, Some(1), &split_into);
+        assert_eq!(
+            unsplit,
+            Unsplit {
+                removed: vec![10, 11, 12],
+                moved: vec![(3, 2)],
+            }
+        );
+        assert_eq!(restore(&links, unsplit, 2, Some(1)), credits);
+    }
+
+    #[test]
+    fn test_unsplit_leaves_links_outside_the_split() {
+        // The play already credited B after the combined artist, so the
+        // split didn't link it again
+        let links = vec![link(1, 0), link(10, 1), link(4, 3), link(11, 4)];
+        assert_eq!(
+            unsplit(&links, Some(1), &[10, 11]),
+            Unsplit {
+                removed: vec![10],
+                moved: vec![(4, 2), (11, 3)],
+            }
+        );
+
+        // Without a position the split artists were linked from 0 and
+        // nothing moved along
+        let links = vec![link(10, 0), link(11, 1), link(1, 5)];
+        assert_eq!(
+            unsplit(&links, None, &[10, 11]),
+            Unsplit {
+                removed: vec![10, 11],
+                moved: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_unsplit_after_the_split_artists_were_merged_again() {
+        let credits = vec![link(1, 0), link(2, 1), link(3, 2)];
+        let links = split(&credits, 2, &[10, 11]);
+        // Consolidation then merges split artist 11 into 20, keeping its
+        // position
+        let later = [(11, 20)];
+        let links: Vec<CreditLink> = links
+            .iter()
+            .map(|link| CreditLink {
+                artist_id: merged_into(link.artist_id, &later),
+                ..*link
+            })
+            .collect();
+
+        let split_into: Vec<i32> = [10, 11].iter().map(|id| merged_into(*id, &later)).collect();
+        assert_eq!(split_into, vec![10, 20]);
+        let unsplit = unsplit(&links, Some(1), &split_into);
+        assert_eq!(restore(&links, unsplit, 2, Some(1)), credits);
+    }
+
+    #[test]
+    fn test_merged_into_follows_later_merges() {
+        assert_eq!(merged_into(1, &[]), 1);
+        assert_eq!(merged_into(1, &[(2, 3)]), 1);
+        assert_eq!(merged_into(1, &[(1, 2), (2, 3)]), 3);
+        // Links merged into an artist move on when it is merged in turn
+        assert_eq!(merged_into(2, &[(2, 1), (1, 3)]), 3);
+        assert_eq!(merged_into(3, &[(1, 2), (3, 4), (2, 5)]), 4);
+    }
+
+    #[test]
+    fn test_restored_columns_from_snapshot() {
+        let columns: Vec<(String, bool)> = [
+            ("id", false),
+            ("mbid", false),
+            ("name", false),
+            ("name_normalized", true),
+            ("play_count", false),
+            ("mbid_type", false),
+        ]
+        .iter()
+        .map(|(column, generated)| (column.to_string(), *generated))
+        .collect();
+        // As to_jsonb gave it before mbid_type was added, and with a column
+        // dropped since
+        let row = json!({
+            "id": 7,
+            "mbid": null,
+            "name": "Aphex Twin",
+            "name_normalized": "aphex twin",
+            "play_count": 3,
+            "legacy": "dropped",
+        });
+
+        assert_eq!(
+            restored_columns(&columns, &row),
+            vec!["id", "mbid", "name", "play_count"]
+        );
+        assert!(restored_columns(&columns, &json!(null)).is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires database connection"]
+    async fn test_restore_row_round_trips_snapshot() -> Result<()> {
+        let database_url = std::env::var("DATABASE_URL")
+            .unwrap_or_else(|_| "postgresql://localhost/teal_test".to_string());
+        let pool = PgPool::connect(&database_url).await?;
+        let mut tx = pool.begin().await?;
+
+        let (id, snapshot): (i32, Value) = sqlx::query_as(
+            r#"
+                INSERT INTO artists_extended (name, mbid, mbid_type, play_count)
+                VALUES ('Merge Log Round Trip', gen_random_uuid(), 'synthetic', 3)
+                RETURNING id, to_jsonb(artists_extended)
+            "#,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM artists_extended WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        restore_row(&mut tx, "artists_extended", &snapshot).await?;
+        // Restoring it again changes nothing
+        restore_row(&mut tx, "artists_extended", &snapshot).await?;
+        let restored: Value =
+            sqlx::query_scalar("SELECT to_jsonb(a) FROM artists_extended a WHERE id = $1")
+                .bind(id)
+                .fetch_one(&mut *tx)
+                .await?;
+        assert_eq!(restored, snapshot);
+
+        tx.rollback().await?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_play_link() {
+        assert_eq!(
+            play_link(MergedKind::Release).unwrap(),
+            ("releases", "release_mbid")
+        );
+        assert_eq!(
+            play_link(MergedKind::Recording).unwrap(),
+            ("recordings", "recording_mbid")
+        );
+        assert!(play_link(MergedKind::Artist).is_err());
+    }
+}
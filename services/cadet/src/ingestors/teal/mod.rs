@@ -1,6 +1,13 @@
 pub mod actor_profile;
 pub mod actor_status;
+pub mod cleaning;
+pub mod consolidation;
+pub mod credits;
+pub mod entity_cache;
 pub mod feed_play;
+pub mod merge_log;
+pub mod play_buffer;
+pub mod validate;
 
 use serde_json::Value;
 
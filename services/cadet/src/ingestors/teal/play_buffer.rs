@@ -0,0 +1,254 @@
+//! Micro-batching of live plays.
+//!
+//! Writing each live play in its own transaction costs a round of entity
+//! lookups and a commit per event. [`PlayBuffer`] collects the plays of
+//! consecutive events and writes them through [`PlayIngestor::write_batch`]
+//! once `LIVE_PLAY_BATCH_SIZE` are waiting or every `LIVE_PLAY_FLUSH_MS`.
+//!
+//! The buffer is also the [`HandledCursor`] of the live stream: while plays
+//! are waiting, handled events only move the cursor once their batch has
+//! committed, so a crash replays them instead of losing them. A batch is
+//! written with the buffer locked, so no event is reported past a play that
+//! is still being written.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use super::feed_play::{PendingPlay, PlayIngestor};
+use crate::cursor::HandledCursor;
+use crate::ingestors::dead_letter::{record_failure, FailedRecord};
+
+const DEFAULT_BATCH_SIZE: usize = 100;
+const DEFAULT_FLUSH_MS: u64 = 500;
+
+/// Live plays written per transaction, set with `LIVE_PLAY_BATCH_SIZE`.
+pub fn live_batch_size() -> usize {
+    std::env::var("LIVE_PLAY_BATCH_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_BATCH_SIZE)
+}
+
+/// Longest a live play waits to be written, set with `LIVE_PLAY_FLUSH_MS`.
+pub fn flush_interval() -> Duration {
+    Duration::from_millis(
+        std::env::var("LIVE_PLAY_FLUSH_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|ms| *ms > 0)
+            .unwrap_or(DEFAULT_FLUSH_MS),
+    )
+}
+
+#[derive(Default)]
+struct Buffered {
+    plays: Vec<PendingPlay>,
+    /// What to dead-letter for each play if it can't be written
+    failed: Vec<FailedRecord>,
+    /// The last event handled while plays were waiting
+    through: Option<u64>,
+}
+
+pub struct PlayBuffer {
+    ingestor: PlayIngestor,
+    sql: PgPool,
+    /// The cursor the ingest loop stores
+    cursor: Arc<Mutex<Option<u64>>>,
+    max_plays: usize,
+    buffered: tokio::sync::Mutex<Buffered>,
+}
+
+impl PlayBuffer {
+    pub fn new(sql: PgPool, cursor: Arc<Mutex<Option<u64>>>) -> Self {
+        Self {
+            ingestor: PlayIngestor::new(sql.clone()),
+            sql,
+            cursor,
+            max_plays: live_batch_size(),
+            buffered: Default::default(),
+        }
+    }
+
+    /// Write the waiting plays every [`flush_interval`].
+    pub fn spawn_flusher(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(flush_interval());
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.flush().await {
+                    error!("Failed to write buffered plays: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Queue a play. A full buffer is written before the call returns; if it
+    /// can't be, the play is refused so the buffer stays bounded.
+    pub async fn push(&self, play: PendingPlay, failed: FailedRecord) -> Result<()> {
+        let mut buffered = self.buffered.lock().await;
+        if buffered.plays.len() >= self.max_plays {
+            // An earlier flush failed and left the buffer full
+            self.flush_locked(&mut buffered).await?;
+        }
+        buffered.plays.push(play);
+        buffered.failed.push(failed);
+        if buffered.plays.len() >= self.max_plays {
+            if let Err(e) = self.flush_locked(&mut buffered).await {
+                warn!("Failed to write a full play buffer, will retry: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the waiting plays, then move the cursor past the events handled
+    /// meanwhile.
+    pub async fn flush(&self) -> Result<()> {
+        let mut buffered = self.buffered.lock().await;
+        self.flush_locked(&mut buffered).await
+    }
+
+    /// Plays that fail on their own are dead-lettered. Fails, keeping the
+    /// plays and the cursor where they are, if one could be neither written
+    /// nor dead-lettered.
+    async fn flush_locked(&self, buffered: &mut Buffered) -> Result<()> {
+        if buffered.plays.is_empty() {
+            return Ok(());
+        }
+        let results = self.ingestor.write_batch(&buffered.plays).await;
+        for ((play, failed), result) in buffered.plays.iter().zip(&buffered.failed).zip(results) {
+            let Err(e) = result else {
+                continue;
+            };
+            warn!(
+                "Dead-lettering {}/{} from {}: {}",
+                failed.collection, play.rkey, play.did, e
+            );
+            record_failure(&self.sql, failed, &e.to_string())
+                .await
+                .map_err(|store_error| {
+                    anyhow!(
+                        "{} was not written ({}) and could not be dead-lettered: {}",
+                        play.uri,
+                        e,
+                        store_error
+                    )
+                })?;
+        }
+
+        buffered.plays.clear();
+        buffered.failed.clear();
+        if let Some(through) = buffered.through.take() {
+            *self.cursor.lock().unwrap() = Some(through);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl HandledCursor for PlayBuffer {
+    async fn handled(&self, position: u64) {
+        let mut buffered = self.buffered.lock().await;
+        if buffered.plays.is_empty() {
+            *self.cursor.lock().unwrap() = Some(position);
+        } else {
+            buffered.through = Some(position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+    use crate::ingestors::teal::feed_play::parse_play_record;
+
+    /// A pool whose connections are always refused.
+    fn unreachable_pool() -> PgPool {
+        PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(200))
+            .connect_lazy("postgresql://127.0.0.1:1/teal_test")
+            .unwrap()
+    }
+
+    fn play(rkey: &str) -> (PendingPlay, FailedRecord) {
+        let record = json!({
+            "$type": "fm.teal.feed.play",
+            "trackName": "Windowlicker",
+            "artists": [{"artistName": "Aphex Twin"}],
+            "playedTime": "2023-04-01T12:00:00Z",
+        });
+        let failed = FailedRecord {
+            did: "did:plc:test".to_string(),
+            collection: "fm.teal.feed.play".to_string(),
+            rkey: rkey.to_string(),
+            rev: "3lrev".to_string(),
+            cid: Some("bafytest".to_string()),
+            record: Some(record.clone()),
+        };
+        let play = PendingPlay {
+            record: parse_play_record(&record).unwrap(),
+            uri: format!("at://did:plc:test/fm.teal.feed.play/{}", rkey),
+            cid: "bafytest".to_string(),
+            did: "did:plc:test".to_string(),
+            rkey: rkey.to_string(),
+        };
+        (play, failed)
+    }
+
+    #[tokio::test]
+    async fn test_handled_moves_cursor_when_nothing_is_buffered() {
+        let cursor = Arc::new(Mutex::new(Some(5)));
+        let buffer = PlayBuffer::new(unreachable_pool(), cursor.clone());
+
+        buffer.handled(6).await;
+        assert_eq!(*cursor.lock().unwrap(), Some(6));
+        // Nothing to write, so nothing to fail
+        buffer.flush().await.unwrap();
+        assert_eq!(*cursor.lock().unwrap(), Some(6));
+    }
+
+    #[tokio::test]
+    async fn test_failed_flush_keeps_cursor_and_plays() {
+        let cursor = Arc::new(Mutex::new(Some(5)));
+        let buffer = PlayBuffer::new(unreachable_pool(), cursor.clone());
+
+        let (play, failed) = play("3lb2c4wcg3k2e");
+        buffer.push(play, failed).await.unwrap();
+        buffer.handled(6).await;
+        buffer.handled(7).await;
+        assert_eq!(*cursor.lock().unwrap(), Some(5));
+
+        // Neither the batch nor its dead letters can be written
+        assert!(buffer.flush().await.is_err());
+        assert_eq!(*cursor.lock().unwrap(), Some(5));
+        let buffered = buffer.buffered.lock().await;
+        assert_eq!(buffered.plays.len(), 1);
+        assert_eq!(buffered.through, Some(7));
+    }
+
+    #[tokio::test]
+    async fn test_full_buffer_refuses_plays_it_cannot_write() {
+        let cursor = Arc::new(Mutex::new(None));
+        let mut buffer = PlayBuffer::new(unreachable_pool(), cursor.clone());
+        buffer.max_plays = 2;
+
+        for rkey in ["3laaaaaaaaaa2", "3laaaaaaaaaa3"] {
+            let (play, failed) = play(rkey);
+            buffer.push(play, failed).await.unwrap();
+        }
+        let (play, failed) = play("3laaaaaaaaaa4");
+        assert!(buffer.push(play, failed).await.is_err());
+        assert_eq!(buffer.buffered.lock().await.plays.len(), 2);
+        assert_eq!(*cursor.lock().unwrap(), None);
+    }
+}
@@ -0,0 +1,585 @@
+//! Lexicon constraint validation for incoming Teal records.
+//!
+//! The generated `types::fm_teal` structs only check a record's shape. The
+//! constraints (`maxLength`, `maxGraphemes`, formats, required fields, ...)
+//! are enforced here, straight from the lexicon JSON in `lexicons/fm.teal`, so
+//! a lexicon change takes effect without touching this file. Records that
+//! violate their lexicon are counted, logged and stored in `rejected_records`
+//! instead of being ingested, until a valid version of them is ingested.
+//!
+//! Refs to lexicons that aren't bundled (e.g. `app.bsky.richtext.facet`) and
+//! open unions are accepted as they are.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use sqlx::PgPool;
+use tracing::{error, warn};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::canonical_collection;
+
+const LEXICON_DOCUMENTS: [&str; 5] = [
+    include_str!("../../../../../lexicons/fm.teal/feed/defs.json"),
+    include_str!("../../../../../lexicons/fm.teal/feed/play.json"),
+    include_str!("../../../../../lexicons/fm.teal/actor/defs.json"),
+    include_str!("../../../../../lexicons/fm.teal/actor/profile.json"),
+    include_str!("../../../../../lexicons/fm.teal/actor/status.json"),
+];
+
+/// A lexicon constraint a record breaks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// Field that failed, e.g. `artists[0].artistName`
+    pub path: String,
+    /// Lexicon keyword that failed, e.g. `maxLength`
+    pub constraint: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+impl std::error::Error for Violation {}
+
+fn violation(path: &str, constraint: &'static str, message: impl Into<String>) -> Violation {
+    Violation {
+        path: path.to_string(),
+        constraint,
+        message: message.into(),
+    }
+}
+
+/// Lexicon definitions, keyed by `nsid#name`.
+pub struct Lexicons {
+    defs: HashMap<String, Value>,
+}
+
+impl Lexicons {
+    /// The `fm.teal` lexicons built into cadet.
+    pub fn bundled() -> &'static Lexicons {
+        static BUNDLED: OnceLock<Lexicons> = OnceLock::new();
+        BUNDLED.get_or_init(|| {
+            Lexicons::from_documents(&LEXICON_DOCUMENTS).expect("bundled lexicons are valid")
+        })
+    }
+
+    pub fn from_documents(documents: &[&str]) -> Result<Self> {
+        let mut defs = HashMap::new();
+        for document in documents {
+            let document: Value = serde_json::from_str(document)?;
+            let nsid = document["id"]
+                .as_str()
+                .ok_or_else(|| anyhow!("Lexicon document has no id"))?;
+            let Some(doc_defs) = document["defs"].as_object() else {
+                return Err(anyhow!("Lexicon {} has no defs", nsid));
+            };
+            for (name, def) in doc_defs {
+                defs.insert(format!("{}#{}", nsid, name), def.clone());
+            }
+        }
+        Ok(Self { defs })
+    }
+
+    /// Validate a record of `collection`. Collections without a bundled
+    /// lexicon pass.
+    pub fn validate_record(&self, collection: &str, record: &Value) -> Result<(), Violation> {
+        let nsid = canonical_collection(collection);
+        match self.defs.get(&format!("{}#main", nsid)) {
+            Some(def) => self.validate(nsid, def, record, ""),
+            None => Ok(()),
+        }
+    }
+
+    fn resolve<'a>(&'a self, nsid: &'a str, reference: &'a str) -> Option<(&'a str, &'a Value)> {
+        let (doc, name) = match reference.split_once('#') {
+            Some(("", name)) => (nsid, name),
+            Some((doc, name)) => (doc, name),
+            None => (reference, "main"),
+        };
+        self.defs
+            .get(&format!("{}#{}", doc, name))
+            .map(|def| (doc, def))
+    }
+
+    fn validate(
+        &self,
+        nsid: &str,
+        schema: &Value,
+        value: &Value,
+        path: &str,
+    ) -> Result<(), Violation> {
+        match schema["type"].as_str().unwrap_or("unknown") {
+            "record" => self.validate(nsid, &schema["record"], value, path),
+            "object" => self.validate_object(nsid, schema, value, path),
+            "string" => validate_string(schema, value, path),
+            "integer" => validate_integer(schema, value, path),
+            "boolean" => match value.is_boolean() {
+                true => Ok(()),
+                false => Err(violation(path, "type", "expected a boolean")),
+            },
+            "array" => {
+                let Some(items) = value.as_array() else {
+                    return Err(violation(path, "type", "expected an array"));
+                };
+                check_count(schema, "minLength", "maxLength", items.len(), path, "items")?;
+                for (index, item) in items.iter().enumerate() {
+                    self.validate(
+                        nsid,
+                        &schema["items"],
+                        item,
+                        &format!("{}[{}]", path, index),
+                    )?;
+                }
+                Ok(())
+            }
+            "ref" => match schema["ref"]
+                .as_str()
+                .and_then(|reference| self.resolve(nsid, reference))
+            {
+                Some((doc, def)) => self.validate(doc, def, value, path),
+                None => Ok(()),
+            },
+            "union" => self.validate_union(nsid, schema, value, path),
+            "blob" => {
+                if !value.is_object() {
+                    return Err(violation(path, "type", "expected a blob"));
+                }
+                match (schema["maxSize"].as_u64(), value["size"].as_u64()) {
+                    (Some(max), Some(size)) if size > max => Err(violation(
+                        path,
+                        "maxSize",
+                        format!("blob is {} bytes, at most {} allowed", size, max),
+                    )),
+                    _ => Ok(()),
+                }
+            }
+            // bytes, cid-link, unknown and anything newer
+            _ => Ok(()),
+        }
+    }
+
+    fn validate_object(
+        &self,
+        nsid: &str,
+        schema: &Value,
+        value: &Value,
+        path: &str,
+    ) -> Result<(), Violation> {
+        let Some(object) = value.as_object() else {
+            return Err(violation(path, "type", "expected an object"));
+        };
+        let nullable = |key: &str| {
+            schema["nullable"]
+                .as_array()
+                .is_some_and(|nullable| nullable.iter().any(|n| n == key))
+        };
+
+        for required in schema["required"].as_array().into_iter().flatten() {
+            let Some(key) = required.as_str() else {
+                continue;
+            };
+            match object.get(key) {
+                Some(Value::Null) if !nullable(key) => {
+                    return Err(violation(&join(path, key), "required", "is required"))
+                }
+                None => return Err(violation(&join(path, key), "required", "is required")),
+                _ => {}
+            }
+        }
+
+        let Some(properties) = schema["properties"].as_object() else {
+            return Ok(());
+        };
+        for (key, property) in properties {
+            match object.get(key) {
+                None => {}
+                Some(Value::Null) if nullable(key) => {}
+                Some(field) => self.validate(nsid, property, field, &join(path, key))?,
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_union(
+        &self,
+        nsid: &str,
+        schema: &Value,
+        value: &Value,
+        path: &str,
+    ) -> Result<(), Violation> {
+        let Some(record_type) = value["$type"].as_str() else {
+            return Err(violation(path, "type", "union member has no $type"));
+        };
+        let refs = schema["refs"].as_array().into_iter().flatten();
+        let member = refs.filter_map(Value::as_str).find(|reference| {
+            let full = match reference.strip_prefix('#') {
+                Some(name) => format!("{}#{}", nsid, name),
+                None => reference.to_string(),
+            };
+            full == record_type || full.strip_suffix("#main") == Some(record_type)
+        });
+        match member.and_then(|reference| self.resolve(nsid, reference)) {
+            Some((doc, def)) => self.validate(doc, def, value, path),
+            None if member.is_none() && schema["closed"].as_bool() == Some(true) => Err(violation(
+                path,
+                "closed",
+                format!("{} is not a member of this union", record_type),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn check_count(
+    schema: &Value,
+    min_key: &'static str,
+    max_key: &'static str,
+    count: usize,
+    path: &str,
+    unit: &str,
+) -> Result<(), Violation> {
+    if let Some(min) = schema[min_key].as_u64() {
+        if (count as u64) < min {
+            return Err(violation(
+                path,
+                min_key,
+                format!("has {} {}, at least {} required", count, unit, min),
+            ));
+        }
+    }
+    if let Some(max) = schema[max_key].as_u64() {
+        if count as u64 > max {
+            return Err(violation(
+                path,
+                max_key,
+                format!("has {} {}, at most {} allowed", count, unit, max),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_string(schema: &Value, value: &Value, path: &str) -> Result<(), Violation> {
+    let Some(string) = value.as_str() else {
+        return Err(violation(path, "type", "expected a string"));
+    };
+    // Lexicon lengths are UTF-8 bytes
+    check_count(
+        schema,
+        "minLength",
+        "maxLength",
+        string.len(),
+        path,
+        "bytes",
+    )?;
+    if schema.get("minGraphemes").is_some() || schema.get("maxGraphemes").is_some() {
+        let graphemes = string.graphemes(true).count();
+        check_count(
+            schema,
+            "minGraphemes",
+            "maxGraphemes",
+            graphemes,
+            path,
+            "graphemes",
+        )?;
+    }
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.iter().any(|allowed| allowed == value) {
+            return Err(violation(
+                path,
+                "enum",
+                format!("{:?} is not allowed", string),
+            ));
+        }
+    }
+    match schema["format"].as_str() {
+        Some(format) if !matches_format(format, string) => Err(violation(
+            path,
+            "format",
+            format!("{:?} is not a valid {}", string, format),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn validate_integer(schema: &Value, value: &Value, path: &str) -> Result<(), Violation> {
+    let Some(integer) = value.as_i64() else {
+        return Err(violation(path, "type", "expected an integer"));
+    };
+    if let Some(minimum) = schema["minimum"].as_i64() {
+        if integer < minimum {
+            return Err(violation(
+                path,
+                "minimum",
+                format!("{} is below the minimum of {}", integer, minimum),
+            ));
+        }
+    }
+    if let Some(maximum) = schema["maximum"].as_i64() {
+        if integer > maximum {
+            return Err(violation(
+                path,
+                "maximum",
+                format!("{} is above the maximum of {}", integer, maximum),
+            ));
+        }
+    }
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.iter().any(|allowed| allowed == value) {
+            return Err(violation(
+                path,
+                "enum",
+                format!("{} is not allowed", integer),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `value` matches a lexicon string format. Formats not checked here
+/// pass.
+fn matches_format(format: &str, value: &str) -> bool {
+    match format {
+        "datetime" => chrono::DateTime::parse_from_rfc3339(value).is_ok(),
+        "uri" => value.len() <= 8192 && is_uri(value),
+        "at-uri" => value.starts_with("at://") && is_uri(value),
+        "did" => value
+            .strip_prefix("did:")
+            .and_then(|rest| rest.split_once(':'))
+            .is_some_and(|(method, id)| {
+                !method.is_empty()
+                    && method.chars().all(|c| c.is_ascii_lowercase())
+                    && !id.is_empty()
+            }),
+        _ => true,
+    }
+}
+
+/// `scheme:rest`, with no whitespace.
+fn is_uri(value: &str) -> bool {
+    let Some((scheme, rest)) = value.split_once(':') else {
+        return false;
+    };
+    let mut scheme_chars = scheme.chars();
+    scheme_chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && scheme_chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        && !rest.is_empty()
+        && !value.chars().any(char::is_whitespace)
+}
+
+/// Whether a record may be ingested. A record that violates its lexicon is
+/// counted in `cadet_records_rejected_total`, logged and stored in
+/// `rejected_records`.
+pub async fn accept_record(
+    pool: &PgPool,
+    did: &str,
+    collection: &str,
+    rkey: &str,
+    cid: Option<&str>,
+    record: &Value,
+) -> bool {
+    let Err(violation) = Lexicons::bundled().validate_record(collection, record) else {
+        return true;
+    };
+
+    let collection = canonical_collection(collection);
+    metrics::counter!(
+        "cadet_records_rejected_total",
+        "collection" => collection.to_string(),
+        "constraint" => violation.constraint,
+    )
+    .increment(1);
+    warn!(
+        "Rejected {}/{} from {}: {}",
+        collection, rkey, did, violation
+    );
+
+    if let Err(e) = store_rejection(pool, did, collection, rkey, cid, &violation).await {
+        error!(
+            "Failed to store rejection of {}/{}: {}",
+            collection, rkey, e
+        );
+    }
+    false
+}
+
+/// Drop the rejection of a record once a valid version of it has been
+/// ingested. The delete runs whether or not the record was rejected, as
+/// other processes (imports, another cadet) store rejections too.
+pub async fn clear_rejection(pool: &PgPool, did: &str, collection: &str, rkey: &str) {
+    clear_rejections(pool, collection, &[(did, rkey)]).await;
+}
+
+/// [`clear_rejection`] for a batch of `(did, rkey)` records of `collection`.
+pub async fn clear_rejections(pool: &PgPool, collection: &str, records: &[(&str, &str)]) {
+    if records.is_empty() {
+        return;
+    }
+    let collection = canonical_collection(collection);
+    let (dids, rkeys): (Vec<&str>, Vec<&str>) = records.iter().copied().unzip();
+    if let Err(e) = sqlx::query(
+        r#"
+        DELETE FROM rejected_records r
+        USING UNNEST($2::text[], $3::text[]) AS k(did, rkey)
+        WHERE r.collection = $1 AND r.did = k.did AND r.rkey = k.rkey
+        "#,
+    )
+    .bind(collection)
+    .bind(&dids)
+    .bind(&rkeys)
+    .execute(pool)
+    .await
+    {
+        warn!(
+            "Failed to clear rejections of {} {} records: {}",
+            records.len(),
+            collection,
+            e
+        );
+    }
+}
+
+async fn store_rejection(
+    pool: &PgPool,
+    did: &str,
+    collection: &str,
+    rkey: &str,
+    cid: Option<&str>,
+    violation: &Violation,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO rejected_records (did, collection, rkey, cid, path, constraint_name, message)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (did, collection, rkey) DO UPDATE SET
+            cid = EXCLUDED.cid,
+            path = EXCLUDED.path,
+            constraint_name = EXCLUDED.constraint_name,
+            message = EXCLUDED.message,
+            rejected_at = NOW()
+        "#,
+    )
+    .bind(did)
+    .bind(collection)
+    .bind(rkey)
+    .bind(cid)
+    .bind(&violation.path)
+    .bind(violation.constraint)
+    .bind(&violation.message)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn play(fields: Value) -> Value {
+        let mut record = json!({
+            "$type": "fm.teal.feed.play",
+            "trackName": "Windowlicker",
+            "artists": [{"artistName": "Aphex Twin"}],
+            "playedTime": "2024-01-01T12:00:00Z",
+            "recordingMbId": "mbid:0d4c7a4a-0f3c-4c4e-a7a5-b1d9ad0bd7a2",
+        });
+        record
+            .as_object_mut()
+            .unwrap()
+            .extend(fields.as_object().unwrap().clone());
+        record
+    }
+
+    fn check(record: &Value) -> Result<(), Violation> {
+        Lexicons::bundled().validate_record("fm.teal.feed.play", record)
+    }
+
+    #[test]
+    fn test_valid_play_passes() {
+        assert_eq!(check(&play(json!({}))), Ok(()));
+        // Legacy collections are validated against the stable lexicon
+        assert_eq!(
+            Lexicons::bundled().validate_record("fm.teal.alpha.feed.play", &play(json!({}))),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_play_violations() {
+        let long = check(&play(json!({"trackName": "a".repeat(1_000_000)}))).unwrap_err();
+        assert_eq!(
+            (long.path.as_str(), long.constraint),
+            ("trackName", "maxLength")
+        );
+
+        let empty = check(&play(json!({"trackName": ""}))).unwrap_err();
+        assert_eq!(empty.constraint, "minLength");
+
+        let mut missing = play(json!({}));
+        missing.as_object_mut().unwrap().remove("trackName");
+        assert_eq!(check(&missing).unwrap_err().constraint, "required");
+
+        let date = check(&play(json!({"playedTime": "yesterday"}))).unwrap_err();
+        assert_eq!(
+            (date.path.as_str(), date.constraint),
+            ("playedTime", "format")
+        );
+
+        let artist = check(&play(json!({"artists": [{"artistMbId": "mbid:x"}]}))).unwrap_err();
+        assert_eq!(artist.path, "artists[0].artistName");
+
+        let names = check(&play(json!({"artistNames": "Aphex Twin"}))).unwrap_err();
+        assert_eq!(
+            (names.path.as_str(), names.constraint),
+            ("artistNames", "type")
+        );
+    }
+
+    #[test]
+    fn test_maxlength_counts_bytes_and_graphemes_separately() {
+        // 64 four-byte emoji: 256 bytes is allowed, one more is not
+        let emoji = "🎵".repeat(64);
+        assert_eq!(check(&play(json!({ "trackName": emoji }))), Ok(()));
+        let over = format!("{}a", emoji);
+        assert_eq!(
+            check(&play(json!({ "trackName": over })))
+                .unwrap_err()
+                .constraint,
+            "maxLength"
+        );
+    }
+
+    #[test]
+    fn test_format_checks() {
+        assert!(matches_format(
+            "uri",
+            "mbid:0d4c7a4a-0f3c-4c4e-a7a5-b1d9ad0bd7a2"
+        ));
+        assert!(matches_format("uri", "https://open.spotify.com/track/1"));
+        assert!(!matches_format("uri", "not a uri"));
+        assert!(matches_format("did", "did:plc:k644h4rq5bjfzcetgsa6tuby"));
+        assert!(!matches_format("did", "did:plc"));
+        assert!(matches_format("datetime", "2024-01-01T12:00:00.000+02:00"));
+        assert!(!matches_format("datetime", "2024-01-01 12:00"));
+    }
+}
@@ -3,9 +3,10 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use cursor::load_cursor;
+use cursor::HandledCursor;
+use firehose::IngestSource;
 use metrics_exporter_prometheus::PrometheusBuilder;
-use tracing::error;
+use tracing::{error, info, warn};
 
 use rocketman::{
     connection::JetstreamConnection,
@@ -14,16 +15,23 @@ use rocketman::{
     options::JetstreamOptions,
 };
 
+mod accounts;
 mod cursor;
 mod db;
+mod firehose;
+mod identity;
 mod ingestors;
-mod redis_client;
+mod musicbrainz;
 mod resolve;
 
-fn setup_tracing() {
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .init();
+/// Logs go to stdout, or to stderr when stdout carries a command's output.
+fn setup_tracing(to_stderr: bool) {
+    let builder = tracing_subscriber::fmt().with_max_level(tracing::Level::INFO);
+    if to_stderr {
+        builder.with_writer(std::io::stderr).init();
+    } else {
+        builder.init();
+    }
 }
 
 fn setup_metrics() {
@@ -40,18 +48,141 @@ fn setup_metrics() {
 async fn main() {
     dotenvy::dotenv().ok();
 
-    setup_tracing();
+    setup_tracing(matches!(
+        std::env::args().nth(1).as_deref(),
+        Some("consolidate" | "merges" | "unmerge")
+    ));
     setup_metrics();
 
     let pool = db::init_pool()
         .await
         .expect("Could not get PostgreSQL pool");
 
-    let opts = JetstreamOptions::builder()
-        .wanted_collections(ingestors::teal::wanted_collections())
-        .build();
+    let source = IngestSource::from_env().unwrap_or_else(|e| {
+        error!("{}", e);
+        std::process::exit(1);
+    });
+
+    match ingestors::teal::cleaning::init_from_env() {
+        Ok(rules) => info!("Name cleaning rules version {}", rules.version),
+        Err(e) => {
+            error!("{:#}", e);
+            std::process::exit(1);
+        }
+    }
+
+    // `cadet rewind <timestamp | seq>` makes the next start replay from there
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("rewind") {
+        let Some(point) = args.get(2) else {
+            error!("Usage: cadet rewind <RFC 3339 timestamp | Unix microseconds | firehose seq>");
+            std::process::exit(1);
+        };
+        let result = match cursor::parse_replay_point(source, point) {
+            Ok(replay_from) => cursor::request_replay(&pool, source, replay_from)
+                .await
+                .map(|()| replay_from),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(replay_from) => info!(
+                "{} will replay from {} the next time cadet starts",
+                source.as_str(),
+                replay_from
+            ),
+            Err(e) => {
+                error!("Failed to rewind {}: {}", source.as_str(), e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // `cadet consolidate <preview | run> [min-confidence]` merges duplicate
+    // artists, releases and recordings and prints what it merged as JSON
+    if args.get(1).map(String::as_str) == Some("consolidate") {
+        use ingestors::teal::consolidation::ConsolidateCommand;
+
+        let command = ConsolidateCommand::parse(&args[2..]).unwrap_or_else(|e| {
+            error!("{}", e);
+            error!("Usage: cadet consolidate <preview | run> [min-confidence]");
+            std::process::exit(1);
+        });
+        let ingestor = ingestors::teal::feed_play::PlayIngestor::new(pool.clone());
+        match ingestor
+            .run_full_consolidation(command.min_confidence, command.dry_run)
+            .await
+        {
+            Ok(report) => match serde_json::to_string_pretty(&report) {
+                Ok(json) => println!("{}", json),
+                Err(e) => {
+                    error!("Failed to serialize consolidation report: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            Err(e) => {
+                error!("Consolidation failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // `cadet merges [limit]` prints the latest merges as JSON for review
+    if args.get(1).map(String::as_str) == Some("merges") {
+        let limit = match args.get(2).map(|limit| limit.parse::<i64>()) {
+            None => 50,
+            Some(Ok(limit)) if limit > 0 => limit,
+            Some(_) => {
+                error!("Usage: cadet merges [limit]");
+                std::process::exit(1);
+            }
+        };
+        match ingestors::teal::merge_log::recent_merges(&pool, limit).await {
+            Ok(merges) => match serde_json::to_string_pretty(&merges) {
+                Ok(json) => println!("{}", json),
+                Err(e) => {
+                    error!("Failed to serialize merges: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            Err(e) => {
+                error!("Failed to list merges: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-    let jetstream = JetstreamConnection::new(opts);
+    // `cadet unmerge <merge id>` restores the merged-away entity and its plays
+    if args.get(1).map(String::as_str) == Some("unmerge") {
+        let Some(merge_id) = args.get(2).and_then(|id| id.parse::<i64>().ok()) else {
+            error!("Usage: cadet unmerge <merge id>");
+            std::process::exit(1);
+        };
+        match ingestors::teal::merge_log::unmerge(&pool, merge_id).await {
+            Ok(unmerged) => {
+                if unmerged.restored < unmerged.recorded {
+                    warn!(
+                        "Only {} of {} plays went back to '{}', the rest changed since the merge",
+                        unmerged.restored, unmerged.recorded, unmerged.source_name
+                    );
+                }
+                match serde_json::to_string_pretty(&unmerged) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => {
+                        error!("Failed to serialize unmerge result: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to unmerge {}: {}", merge_id, e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
     let mut ingestors: HashMap<String, Box<dyn LexiconIngestor + Send + Sync>> = HashMap::new();
 
@@ -89,9 +220,15 @@ async fn main() {
         );
     }
 
+    // Built once and shared by the importRepo ingestor and the job workers
+    let job_queue = common::job_queue::job_queue_from_env(pool.clone());
+    let mut import_repo_ingestor = ingestors::car::CarImportIngestor::new(pool.clone());
+    if let Ok(job_queue) = &job_queue {
+        import_repo_ingestor = import_repo_ingestor.with_job_queue(job_queue.clone());
+    }
     ingestors.insert(
         "com.atproto.repo.importRepo".to_string(),
-        Box::new(ingestors::car::CarImportIngestor::new(pool.clone())),
+        Box::new(import_repo_ingestor),
     );
 
     ingestors.insert(
@@ -99,92 +236,142 @@ async fn main() {
         Box::new(DefaultLexiconIngestor),
     );
 
+    // `cadet retry-failed [collection]` re-ingests dead-lettered records
+    if args.get(1).map(String::as_str) == Some("retry-failed") {
+        let collection = args.get(2).map(String::as_str);
+        match ingestors::dead_letter::retry_failed(&pool, &ingestors, collection).await {
+            Ok(summary) if summary.failed > 0 => std::process::exit(1),
+            Ok(_) => {}
+            Err(e) => {
+                error!("Failed to retry dead-lettered records: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // Records that fail to ingest are kept in failed_records
+    let mut ingestors = ingestors::dead_letter::DeadLetter::wrap_all(ingestors, &pool);
+
     // CAR import job worker
     let car_ingestor = ingestors::car::CarImportIngestor::new(pool.clone());
-    let redis_url =
-        std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+    let job_history_pool = pool.clone();
+
+    match job_queue {
+        Ok(job_queue) => {
+            // Listening history imports (e.g. Spotify exports) share the queue backend
+            tokio::spawn(ingestors::history_import::run_worker(
+                pool.clone(),
+                job_queue.clone(),
+            ));
 
-    match redis_client::RedisClient::new(&redis_url) {
-        Ok(redis_client) => {
             // Spawn CAR import job processing task
             tokio::spawn(async move {
                 use chrono::Utc;
-                use ingestors::car::jobs::{
-                    queue_keys, CarImportJob, CarImportJobStatus, JobProgress, JobStatus,
-                };
+                use common::jobs::{queue_keys, CarImportJob, CarImportJobStatus, JobStatus};
+                use ingestors::car::history::{record_job_status, status_ttl_secs};
+                use ingestors::car::progress::{ImportProgress, JobStatusSink};
                 use tracing::{error, info};
 
-                info!("Starting CAR import job worker, polling Redis queue...");
+                info!("Starting CAR import job worker, polling job queue...");
 
                 loop {
                     // Block for up to 10 seconds waiting for jobs
-                    match redis_client.pop_job(queue_keys::CAR_IMPORT_JOBS, 10).await {
-                        Ok(Some(job_data)) => {
-                            info!("Received CAR import job: {}", job_data);
+                    match job_queue.pop_job(queue_keys::CAR_IMPORT_JOBS, 10).await {
+                        Ok(Some(queued)) => {
+                            info!("Received CAR import job: {}", queued.payload);
 
                             // Parse job
-                            match serde_json::from_str::<CarImportJob>(&job_data) {
+                            match serde_json::from_str::<CarImportJob>(&queued.payload) {
                                 Ok(job) => {
-                                    // Update status to processing
+                                    let started_at = Utc::now();
+                                    let status_key = queue_keys::job_status_key(&job.request_id);
+                                    let sink = JobStatusSink::new(
+                                        job_queue.as_ref(),
+                                        &job.request_id,
+                                        job.created_at,
+                                        started_at,
+                                    );
+                                    let mut progress = ImportProgress::new(&sink);
+                                    progress.step("Starting CAR fetch and processing").await;
+
                                     let processing_status = CarImportJobStatus {
                                         status: JobStatus::Processing,
                                         created_at: job.created_at,
-                                        started_at: Some(Utc::now()),
+                                        started_at: Some(started_at),
                                         completed_at: None,
                                         error_message: None,
-                                        progress: Some(JobProgress {
-                                            step: "Starting CAR fetch and processing".to_string(),
-                                            user_did: None,
-                                            pds_host: None,
-                                            car_size_bytes: None,
-                                            blocks_processed: None,
-                                        }),
+                                        progress: Some(progress.snapshot()),
+                                        preview: None,
                                     };
-
-                                    let status_key = queue_keys::job_status_key(&job.request_id);
-                                    if let Ok(status_data) =
-                                        serde_json::to_string(&processing_status)
+                                    if let Err(e) = record_job_status(
+                                        &job_history_pool,
+                                        &job,
+                                        &processing_status,
+                                    )
+                                    .await
                                     {
-                                        let _ = redis_client
-                                            .update_job_status(&status_key, &status_data)
-                                            .await;
+                                        error!("Failed to record CAR import job history: {}", e);
                                     }
 
-                                    // Process the job
-                                    match car_ingestor
-                                        .fetch_and_process_identity_car(&job.identity)
-                                        .await
-                                    {
-                                        Ok(import_id) => {
+                                    // Process the job, or only preview it for dry runs
+                                    let result = if job.dry_run {
+                                        let preview =
+                                            match &job.spool_file {
+                                                Some(spool_file) => {
+                                                    car_ingestor
+                                                        .preview_spooled_car_with_progress(
+                                                            &job.identity,
+                                                            spool_file,
+                                                            &mut progress,
+                                                        )
+                                                        .await
+                                                }
+                                                None => car_ingestor
+                                                    .fetch_and_preview_identity_car_with_progress(
+                                                        &job.identity,
+                                                        &mut progress,
+                                                    )
+                                                    .await,
+                                            };
+                                        preview.map(|preview| {
+                                            (
+                                                "CAR import preview completed".to_string(),
+                                                Some(preview),
+                                            )
+                                        })
+                                    } else {
+                                        car_ingestor
+                                            .fetch_and_process_identity_car_with_progress(
+                                                &job.identity,
+                                                &mut progress,
+                                            )
+                                            .await
+                                            .map(|import_id| {
+                                                (
+                                                    format!("CAR import completed: {}", import_id),
+                                                    None,
+                                                )
+                                            })
+                                    };
+
+                                    let final_status = match result {
+                                        Ok((step, preview)) => {
                                             info!(
                                                 "✅ CAR import job completed successfully: {}",
                                                 job.request_id
                                             );
 
-                                            let completed_status = CarImportJobStatus {
+                                            let mut snapshot = progress.snapshot();
+                                            snapshot.step = step;
+                                            CarImportJobStatus {
                                                 status: JobStatus::Completed,
                                                 created_at: job.created_at,
-                                                started_at: processing_status.started_at,
+                                                started_at: Some(started_at),
                                                 completed_at: Some(Utc::now()),
                                                 error_message: None,
-                                                progress: Some(JobProgress {
-                                                    step: format!(
-                                                        "CAR import completed: {}",
-                                                        import_id
-                                                    ),
-                                                    user_did: None,
-                                                    pds_host: None,
-                                                    car_size_bytes: None,
-                                                    blocks_processed: None,
-                                                }),
-                                            };
-
-                                            if let Ok(status_data) =
-                                                serde_json::to_string(&completed_status)
-                                            {
-                                                let _ = redis_client
-                                                    .update_job_status(&status_key, &status_data)
-                                                    .await;
+                                                progress: Some(snapshot),
+                                                preview,
                                             }
                                         }
                                         Err(e) => {
@@ -193,35 +380,50 @@ async fn main() {
                                                 job.request_id, e
                                             );
 
-                                            let failed_status = CarImportJobStatus {
+                                            CarImportJobStatus {
                                                 status: JobStatus::Failed,
                                                 created_at: job.created_at,
-                                                started_at: processing_status.started_at,
+                                                started_at: Some(started_at),
                                                 completed_at: Some(Utc::now()),
                                                 error_message: Some(e.to_string()),
-                                                progress: None,
-                                            };
-
-                                            if let Ok(status_data) =
-                                                serde_json::to_string(&failed_status)
-                                            {
-                                                let _ = redis_client
-                                                    .update_job_status(&status_key, &status_data)
-                                                    .await;
+                                                progress: Some(progress.snapshot()),
+                                                preview: None,
                                             }
                                         }
+                                    };
+
+                                    if let Ok(status_data) = serde_json::to_string(&final_status) {
+                                        let _ = job_queue
+                                            .update_job_status(
+                                                &status_key,
+                                                &status_data,
+                                                Some(status_ttl_secs()),
+                                            )
+                                            .await;
+                                    }
+                                    if let Err(e) =
+                                        record_job_status(&job_history_pool, &job, &final_status)
+                                            .await
+                                    {
+                                        error!("Failed to record CAR import job history: {}", e);
                                     }
                                 }
                                 Err(e) => {
                                     error!("Failed to parse CAR import job: {}", e);
                                 }
                             }
+
+                            // Only now is the job gone from the queue: a Postgres job
+                            // whose worker died is picked up again after its lease
+                            if let Err(e) = job_queue.complete_job(&queued).await {
+                                error!("Failed to complete CAR import job: {}", e);
+                            }
                         }
                         Ok(None) => {
                             // Timeout, continue polling
                         }
                         Err(e) => {
-                            error!("Failed to poll Redis queue: {}", e);
+                            error!("Failed to poll CAR import job queue: {}", e);
                             // Sleep before retrying to avoid tight loop
                             tokio::time::sleep(std::time::Duration::from_secs(5)).await;
                         }
@@ -230,52 +432,245 @@ async fn main() {
             });
         }
         Err(e) => {
-            error!("Failed to connect to Redis for CAR import jobs: {}", e);
-            error!("CAR import job worker will not be available");
+            error!("Failed to set up the CAR import job queue: {}", e);
+            error!("CAR import and history import job workers will not be available");
         }
     }
 
-    // tracks the last message we've processed
-    // TODO: read from db/config so we can resume from where we left off in case of crash
-    let cursor: Arc<Mutex<Option<u64>>> = Arc::new(Mutex::new(load_cursor().await));
+    // Prune finished CAR import jobs past the retention window
+    let retention_pool = pool.clone();
+    tokio::spawn(async move {
+        use common::job_queue::{prune_abandoned_jobs, prune_expired_job_statuses};
+        use ingestors::car::history::{job_retention_days, prune_job_history};
 
-    // get channels
-    let msg_rx = jetstream.get_msg_rx();
-    let reconnect_tx = jetstream.get_reconnect_tx();
+        let retention_days = job_retention_days();
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            match prune_job_history(&retention_pool, retention_days).await {
+                Ok(0) => {}
+                Ok(pruned) => tracing::info!(
+                    "🧹 Pruned {} CAR import jobs older than {} days",
+                    pruned,
+                    retention_days
+                ),
+                Err(e) => error!("Failed to prune CAR import job history: {}", e),
+            }
+            if let Err(e) = prune_expired_job_statuses(&retention_pool).await {
+                error!("Failed to prune expired job statuses: {}", e);
+            }
+            match prune_abandoned_jobs(&retention_pool).await {
+                Ok(0) => {}
+                Ok(pruned) => tracing::warn!(
+                    "🧹 Dropped {} jobs that ran out of attempts without completing",
+                    pruned
+                ),
+                Err(e) => error!("Failed to prune abandoned jobs: {}", e),
+            }
+        }
+    });
 
-    // Spawn a task to process messages from the queue.
-    // bleh at this clone
-    let c_cursor = cursor.clone();
+    // Purge accounts that have stayed deleted past the grace period
+    let purge_pool = pool.clone();
     tokio::spawn(async move {
-        while let Ok(message) = msg_rx.recv_async().await {
-            if let Err(e) =
-                handler::handle_message(message, &ingestors, reconnect_tx.clone(), c_cursor.clone())
-                    .await
-            {
-                error!("Error processing message: {}", e);
-            };
+        let grace_days = accounts::deletion_grace_days();
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            match accounts::purge_deleted_accounts(&purge_pool, grace_days).await {
+                Ok(0) => {}
+                Ok(purged) => info!(
+                    "🧹 Purged {} accounts deleted more than {} days ago",
+                    purged, grace_days
+                ),
+                Err(e) => error!("Failed to purge deleted accounts: {}", e),
+            }
+        }
+    });
+
+    // Fold artist names written before folding, or renamed since
+    let fold_pool = pool.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            match ingestors::teal::feed_play::fold_artist_names(&fold_pool).await {
+                Ok(0) => {}
+                Ok(folded) => info!("🔤 Folded {} artist names for fuzzy matching", folded),
+                Err(e) => error!("Failed to fold artist names: {}", e),
+            }
         }
     });
 
+    // Merge duplicate artists, releases and recordings on CONSOLIDATION_CRON
+    let _consolidation_scheduler =
+        match ingestors::teal::consolidation::schedule_from_env(pool.clone()).await {
+            Ok(scheduler) => scheduler,
+            Err(e) => {
+                error!("Failed to schedule consolidation: {}", e);
+                None
+            }
+        };
+
+    // Fill in recordings, releases and artists from MusicBrainz, sharing the
+    // client's rate limit
+    match musicbrainz::MusicBrainzClient::from_env() {
+        Some(Ok(client)) => {
+            let client = Arc::new(client);
+            let config = musicbrainz::enrich::EnrichmentConfig::from_env();
+            tokio::spawn(musicbrainz::enrich::run_worker(
+                pool.clone(),
+                client.clone(),
+                config.clone(),
+            ));
+            // Match plays sent without MBIDs to MusicBrainz recordings
+            tokio::spawn(musicbrainz::resolver::run_worker(
+                pool.clone(),
+                client,
+                config,
+            ));
+        }
+        Some(Err(e)) => error!("Failed to set up MusicBrainz enrichment: {}", e),
+        None => info!("MUSICBRAINZ_URL is not set; MusicBrainz enrichment is off"),
+    }
+
+    // Front cover art of releases from the Cover Art Archive or a mirror
+    match musicbrainz::cover_art::CoverArtClient::from_env() {
+        Some(Ok(client)) => {
+            tokio::spawn(musicbrainz::cover_art::run_worker(
+                pool.clone(),
+                Arc::new(client),
+                musicbrainz::enrich::EnrichmentConfig::from_env(),
+            ));
+        }
+        Some(Err(e)) => error!("Failed to set up cover art lookups: {}", e),
+        None => info!("COVER_ART_URL is not set; cover art lookups are off"),
+    }
+
+    // the last event whose ingestion has finished
+    let stored_cursor = cursor::load_cursor(&pool, source)
+        .await
+        .unwrap_or_else(|e| {
+            error!("Failed to load the {} cursor: {}", source.as_str(), e);
+            std::process::exit(1);
+        });
+    match stored_cursor {
+        Some(cursor) => info!("Resuming {} from cursor {}", source.as_str(), cursor),
+        None => info!("No {} cursor stored, starting from now", source.as_str()),
+    }
+    let cursor: Arc<Mutex<Option<u64>>> = Arc::new(Mutex::new(stored_cursor));
+
+    // Live plays are written in batches, and the cursor only moves past a
+    // play once its batch has committed
+    let play_buffer = Arc::new(ingestors::teal::play_buffer::PlayBuffer::new(
+        pool.clone(),
+        cursor.clone(),
+    ));
+    play_buffer.clone().spawn_flusher();
+    for collection in [
+        ingestors::teal::STABLE_FEED_PLAY,
+        ingestors::teal::ALPHA_FEED_PLAY,
+    ] {
+        let ingestor = ingestors::teal::feed_play::PlayIngestor::new(pool.clone())
+            .with_buffer(play_buffer.clone());
+        ingestors.insert(
+            collection.to_string(),
+            ingestors::dead_letter::DeadLetter::wrap(Box::new(ingestor), &pool),
+        );
+    }
+
     // store cursor every so often
     let c_cursor = cursor.clone();
+    let cursor_pool = pool.clone();
     tokio::spawn(async move {
+        let mut last_stored = stored_cursor;
         loop {
-            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            tokio::time::sleep(cursor::store_interval()).await;
             let cursor_to_store: Option<u64> = {
                 let cursor_guard = c_cursor.lock().unwrap();
                 *cursor_guard
             };
-            if let Some(cursor) = cursor_to_store {
-                if let Err(e) = cursor::store_cursor(cursor).await {
-                    error!("Error storing cursor: {}", e);
+            if let Some(cursor) = cursor_to_store.filter(|c| Some(*c) != last_stored) {
+                match cursor::store_cursor(&cursor_pool, source, cursor).await {
+                    Ok(()) => last_stored = Some(cursor),
+                    Err(e) => error!("Error storing cursor: {}", e),
                 }
             }
         }
     });
 
-    if let Err(e) = jetstream.connect(cursor.clone()).await {
-        error!("Failed to connect to Jetstream: {}", e);
-        std::process::exit(1);
+    match source {
+        IngestSource::Firehose => {
+            // The firehose carries every collection; only dispatch ours
+            let wanted = ingestors::teal::wanted_collections();
+            ingestors.retain(|collection, _| wanted.contains(collection));
+
+            firehose::run(
+                firehose::relay_url(),
+                ingestors,
+                pool.clone(),
+                cursor,
+                play_buffer,
+            )
+            .await;
+        }
+        IngestSource::Jetstream => {
+            let opts = JetstreamOptions::builder()
+                .wanted_collections(ingestors::teal::wanted_collections())
+                .build();
+
+            let jetstream = JetstreamConnection::new(opts);
+
+            // get channels
+            let msg_rx = jetstream.get_msg_rx();
+            let reconnect_tx = jetstream.get_reconnect_tx();
+
+            // rocketman moves `stream_cursor` as it reads events. It is only
+            // copied to `cursor` once an event has been handled, and
+            // reconnects use `resume_cursor`, a few seconds before that.
+            let rewind_secs = cursor::rewind_secs();
+            let resume_at = move |c: u64| cursor::resume_point(source, c, rewind_secs);
+            let stream_cursor = Arc::new(Mutex::new(stored_cursor));
+            let resume_cursor = Arc::new(Mutex::new(stored_cursor.map(resume_at)));
+
+            // Spawn a task to process messages from the queue.
+            let c_resume_cursor = resume_cursor.clone();
+            let account_pool = pool.clone();
+            tokio::spawn(async move {
+                while let Ok(message) = msg_rx.recv_async().await {
+                    // rocketman only dispatches commits; account and identity
+                    // events are ours
+                    if let Some(event) = accounts::AccountEvent::from_jetstream(&message) {
+                        if let Err(e) = accounts::apply_account_event(&account_pool, &event).await {
+                            error!("Failed to record account event for {}: {}", event.did, e);
+                        }
+                    } else if let Some(event) = identity::IdentityEvent::from_jetstream(&message) {
+                        identity::spawn_apply(account_pool.clone(), event).await;
+                    }
+                    match handler::handle_message(
+                        message,
+                        &ingestors,
+                        reconnect_tx.clone(),
+                        stream_cursor.clone(),
+                    )
+                    .await
+                    {
+                        Ok(()) => {
+                            let handled = *stream_cursor.lock().unwrap();
+                            if let Some(handled) = handled {
+                                play_buffer.handled(handled).await;
+                                *c_resume_cursor.lock().unwrap() = Some(resume_at(handled));
+                            }
+                        }
+                        Err(e) => error!("Error processing message: {}", e),
+                    }
+                }
+            });
+
+            if let Err(e) = jetstream.connect(resume_cursor).await {
+                error!("Failed to connect to Jetstream: {}", e);
+                std::process::exit(1);
+            }
+        }
     }
 }
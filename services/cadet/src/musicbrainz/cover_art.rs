@@ -0,0 +1,529 @@
+//! Front cover art of releases from the Cover Art Archive.
+//!
+//! Releases are queued in `cover_art_queue` as they are ingested. The worker
+//! asks the archive at `COVER_ART_URL` (coverartarchive.org or a mirror) for
+//! the release's front image, falling back to its release group's, and keeps
+//! the image and thumbnail URLs in `cover_art` and `cover_art_thumbnails`.
+//! Retries and refreshes follow the MusicBrainz enrichment settings.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use sqlx::{types::Uuid, PgConnection, PgPool};
+use tokio::sync::Mutex;
+use tokio::time::{Interval, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+use super::enrich::{EnrichmentConfig, MAX_RETRY_DELAY_SECS};
+use super::{url_from_env, USER_AGENT};
+
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(30);
+const STALE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const CLAIM_BATCH: i64 = 100;
+const DEFAULT_REQUESTS_PER_SEC: f64 = 1.0;
+
+/// Whether `COVER_ART_URL` is set, i.e. whether the cover art worker runs.
+pub fn configured() -> bool {
+    static CONFIGURED: OnceLock<bool> = OnceLock::new();
+    *CONFIGURED.get_or_init(|| url_from_env("COVER_ART_URL").is_some())
+}
+
+/// Where a release's cover art came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverArtSource {
+    Release,
+    ReleaseGroup,
+}
+
+impl CoverArtSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CoverArtSource::Release => "release",
+            CoverArtSource::ReleaseGroup => "release_group",
+        }
+    }
+
+    /// Path segment of the archive's API for this source.
+    fn path(&self) -> &'static str {
+        match self {
+            CoverArtSource::Release => "release",
+            CoverArtSource::ReleaseGroup => "release-group",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thumbnail {
+    /// Longest side in pixels
+    pub size: i32,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrontImage {
+    pub image_url: String,
+    /// Smallest first
+    pub thumbnails: Vec<Thumbnail>,
+}
+
+#[derive(Deserialize)]
+struct ImageList {
+    #[serde(default)]
+    images: Vec<Image>,
+}
+
+#[derive(Deserialize)]
+struct Image {
+    #[serde(default)]
+    front: bool,
+    image: String,
+    #[serde(default)]
+    thumbnails: HashMap<String, String>,
+}
+
+/// Thumbnail size of a key in the archive's `thumbnails`; `small` and `large`
+/// are the older names of 250 and 500.
+fn thumbnail_size(key: &str) -> Option<i32> {
+    match key {
+        "small" => Some(250),
+        "large" => Some(500),
+        size => size.parse().ok(),
+    }
+}
+
+fn front_image(list: ImageList) -> Option<FrontImage> {
+    let image = list.images.into_iter().find(|image| image.front)?;
+    let mut thumbnails = BTreeMap::new();
+    for (key, url) in image.thumbnails {
+        let Some(size) = thumbnail_size(&key) else {
+            continue;
+        };
+        // Prefer the numeric key when both name the same size
+        if key.parse::<i32>().is_ok() || !thumbnails.contains_key(&size) {
+            thumbnails.insert(size, url);
+        }
+    }
+    Some(FrontImage {
+        image_url: image.image,
+        thumbnails: thumbnails
+            .into_iter()
+            .map(|(size, url)| Thumbnail { size, url })
+            .collect(),
+    })
+}
+
+pub struct CoverArtClient {
+    http: reqwest::Client,
+    base_url: String,
+    /// A release and its release group are two requests, so lookups are
+    /// paced per request rather than per release
+    throttle: Mutex<Interval>,
+}
+
+impl CoverArtClient {
+    pub fn new(base_url: &str, requests_per_sec: f64) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(Duration::from_secs(30))
+            .build()?;
+        let mut throttle =
+            tokio::time::interval(Duration::from_secs_f64(1.0 / requests_per_sec.max(0.01)));
+        throttle.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Ok(Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            throttle: Mutex::new(throttle),
+        })
+    }
+
+    /// Client for `COVER_ART_URL` at `COVER_ART_RATE_LIMIT` requests per
+    /// second, or `None` when cover art is not set up.
+    pub fn from_env() -> Option<Result<Self>> {
+        let requests_per_sec = std::env::var("COVER_ART_RATE_LIMIT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_REQUESTS_PER_SEC);
+        url_from_env("COVER_ART_URL").map(|url| Self::new(&url, requests_per_sec))
+    }
+
+    /// The front image of a release or release group; `None` if it has none.
+    pub async fn front_image(
+        &self,
+        source: CoverArtSource,
+        mbid: Uuid,
+    ) -> Result<Option<FrontImage>> {
+        self.throttle.lock().await.tick().await;
+        let response = self
+            .http
+            .get(format!("{}/{}/{}", self.base_url, source.path(), mbid))
+            .send()
+            .await?;
+
+        match response.status() {
+            // No such release, or no art for it
+            reqwest::StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(front_image(response.json().await?)),
+            status => Err(anyhow!(
+                "Cover Art Archive request for {} {} failed with {}",
+                source.as_str(),
+                mbid,
+                status
+            )),
+        }
+    }
+}
+
+/// Queue `release` for a cover art lookup unless it has had one.
+pub async fn enqueue(conn: &mut PgConnection, release: Uuid) -> Result<()> {
+    if !configured() {
+        return Ok(());
+    }
+    sqlx::query(
+        r#"
+            INSERT INTO cover_art_queue (release_mbid)
+            SELECT $1
+            WHERE NOT EXISTS (SELECT 1 FROM cover_art WHERE release_mbid = $1)
+            ON CONFLICT (release_mbid) DO NOTHING
+        "#,
+    )
+    .bind(release)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Queue `release` again if no art was found for it. Called once its release
+/// group is known, which may have art the release itself lacks.
+pub async fn retry_not_found(conn: &mut PgConnection, release: Uuid) -> Result<()> {
+    if !configured() {
+        return Ok(());
+    }
+    sqlx::query(
+        r#"
+            INSERT INTO cover_art_queue (release_mbid)
+            SELECT release_mbid FROM cover_art WHERE release_mbid = $1 AND NOT found
+            ON CONFLICT (release_mbid) DO NOTHING
+        "#,
+    )
+    .bind(release)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Queue releases ingested before cover art was set up.
+async fn enqueue_missing(pool: &PgPool) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+            INSERT INTO cover_art_queue (release_mbid)
+            SELECT mbid FROM releases
+            EXCEPT
+            SELECT release_mbid FROM cover_art
+            ON CONFLICT (release_mbid) DO NOTHING
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Queue lookups older than `ttl_days` to be made again, so art added to the
+/// archive since shows up.
+async fn enqueue_stale(pool: &PgPool, ttl_days: i32) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+            INSERT INTO cover_art_queue (release_mbid)
+            SELECT release_mbid FROM cover_art
+            WHERE fetched_at < NOW() - make_interval(days => $1)
+            ON CONFLICT (release_mbid) DO NOTHING
+        "#,
+    )
+    .bind(ttl_days)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+#[derive(sqlx::FromRow)]
+struct QueuedRelease {
+    release_mbid: Uuid,
+    release_group_mbid: Option<Uuid>,
+    attempts: i32,
+}
+
+async fn due_releases(pool: &PgPool, max_attempts: i32) -> Result<Vec<QueuedRelease>> {
+    Ok(sqlx::query_as(
+        r#"
+            SELECT q.release_mbid, r.release_group_mbid, q.attempts
+            FROM cover_art_queue q
+            LEFT JOIN releases r ON r.mbid = q.release_mbid
+            WHERE q.next_attempt_at <= NOW() AND q.attempts < $1
+            ORDER BY q.next_attempt_at
+            LIMIT $2
+        "#,
+    )
+    .bind(max_attempts)
+    .bind(CLAIM_BATCH)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Look up queued releases until the process exits.
+pub async fn run_worker(pool: PgPool, client: Arc<CoverArtClient>, config: EnrichmentConfig) {
+    info!("Starting cover art worker");
+    match enqueue_missing(&pool).await {
+        Ok(0) => {}
+        Ok(queued) => info!("Queued {} releases for cover art", queued),
+        Err(e) => error!("Failed to queue releases for cover art: {}", e),
+    }
+
+    let mut last_stale_check = Instant::now();
+
+    loop {
+        if last_stale_check.elapsed() >= STALE_CHECK_INTERVAL {
+            last_stale_check = Instant::now();
+            if let Err(e) = enqueue_stale(&pool, config.cache_ttl_days).await {
+                error!("Failed to queue stale cover art: {}", e);
+            }
+        }
+
+        let due = match due_releases(&pool, config.max_attempts).await {
+            Ok(due) => due,
+            Err(e) => {
+                error!("Failed to read the cover art queue: {}", e);
+                tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+                continue;
+            }
+        };
+        if due.is_empty() {
+            tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+            continue;
+        }
+
+        for queued in due {
+            if let Err(e) = fetch(&pool, &client, &queued).await {
+                warn!(
+                    "Cover art lookup of release {} failed (attempt {}): {}",
+                    queued.release_mbid,
+                    queued.attempts + 1,
+                    e
+                );
+                if let Err(e) = record_attempt(&pool, queued.release_mbid, &e.to_string()).await {
+                    error!("Failed to record cover art attempt: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// The front image of the release, or else of its release group.
+async fn lookup(
+    client: &CoverArtClient,
+    queued: &QueuedRelease,
+) -> Result<Option<(CoverArtSource, FrontImage)>> {
+    if let Some(image) = client
+        .front_image(CoverArtSource::Release, queued.release_mbid)
+        .await?
+    {
+        return Ok(Some((CoverArtSource::Release, image)));
+    }
+    let Some(release_group) = queued.release_group_mbid else {
+        return Ok(None);
+    };
+    Ok(client
+        .front_image(CoverArtSource::ReleaseGroup, release_group)
+        .await?
+        .map(|image| (CoverArtSource::ReleaseGroup, image)))
+}
+
+async fn fetch(pool: &PgPool, client: &CoverArtClient, queued: &QueuedRelease) -> Result<()> {
+    let found = lookup(client, queued).await;
+    metrics::counter!(
+        "cadet_cover_art_lookups_total",
+        "outcome" => match &found {
+            Ok(Some((source, _))) => source.as_str(),
+            Ok(None) => "not_found",
+            Err(_) => "error",
+        }
+    )
+    .increment(1);
+
+    let found = found?;
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        r#"
+            INSERT INTO cover_art (release_mbid, found, source, image_url, fetched_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            ON CONFLICT (release_mbid) DO UPDATE SET
+                found = EXCLUDED.found,
+                source = EXCLUDED.source,
+                image_url = EXCLUDED.image_url,
+                fetched_at = EXCLUDED.fetched_at
+        "#,
+    )
+    .bind(queued.release_mbid)
+    .bind(found.is_some())
+    .bind(found.as_ref().map(|(source, _)| source.as_str()))
+    .bind(found.as_ref().map(|(_, image)| image.image_url.as_str()))
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("DELETE FROM cover_art_thumbnails WHERE release_mbid = $1")
+        .bind(queued.release_mbid)
+        .execute(&mut *tx)
+        .await?;
+    if let Some((_, image)) = &found {
+        let (sizes, urls): (Vec<i32>, Vec<&str>) = image
+            .thumbnails
+            .iter()
+            .map(|thumbnail| (thumbnail.size, thumbnail.url.as_str()))
+            .unzip();
+        sqlx::query(
+            r#"
+                INSERT INTO cover_art_thumbnails (release_mbid, size, url)
+                SELECT $1, size, url FROM UNNEST($2::int4[], $3::text[]) AS t(size, url)
+            "#,
+        )
+        .bind(queued.release_mbid)
+        .bind(&sizes)
+        .bind(&urls)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    sqlx::query("DELETE FROM cover_art_queue WHERE release_mbid = $1")
+        .bind(queued.release_mbid)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn record_attempt(pool: &PgPool, release: Uuid, error: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+            UPDATE cover_art_queue SET
+                attempts = attempts + 1,
+                last_error = $2,
+                next_attempt_at = NOW() + make_interval(
+                    secs => LEAST(60 * power(2, LEAST(attempts, 20)), $3)
+                )
+            WHERE release_mbid = $1
+        "#,
+    )
+    .bind(release)
+    .bind(error)
+    .bind(MAX_RETRY_DELAY_SECS as f64)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::super::tests::stub_server;
+    use super::*;
+
+    #[test]
+    fn test_front_image_thumbnails() {
+        let list: ImageList = serde_json::from_value(json!({
+            "images": [
+                {
+                    "front": false,
+                    "image": "http://coverartarchive.org/release/x/2.jpg",
+                    "thumbnails": {}
+                },
+                {
+                    "front": true,
+                    "image": "http://coverartarchive.org/release/x/1.jpg",
+                    "thumbnails": {
+                        "1200": "http://coverartarchive.org/release/x/1-1200.jpg",
+                        "250": "http://coverartarchive.org/release/x/1-250.jpg",
+                        "500": "http://coverartarchive.org/release/x/1-500.jpg",
+                        "large": "http://coverartarchive.org/release/x/1-large.jpg",
+                        "small": "http://coverartarchive.org/release/x/1-small.jpg"
+                    }
+                }
+            ]
+        }))
+        .unwrap();
+
+        let image = front_image(list).expect("front image");
+        assert_eq!(
+            image.image_url,
+            "http://coverartarchive.org/release/x/1.jpg"
+        );
+        assert_eq!(
+            image
+                .thumbnails
+                .iter()
+                .map(|thumbnail| (thumbnail.size, thumbnail.url.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (250, "http://coverartarchive.org/release/x/1-250.jpg"),
+                (500, "http://coverartarchive.org/release/x/1-500.jpg"),
+                (1200, "http://coverartarchive.org/release/x/1-1200.jpg"),
+            ]
+        );
+
+        let back_only: ImageList = serde_json::from_value(json!({
+            "images": [{"front": false, "image": "http://example.com/back.jpg"}]
+        }))
+        .unwrap();
+        assert_eq!(front_image(back_only), None);
+    }
+
+    #[tokio::test]
+    async fn test_front_image_against_stub_server() {
+        let release = "0b0d0d0b-0000-4000-8000-000000000002";
+        let url = stub_server(move |path| {
+            if path == format!("/release/{}", release) {
+                (
+                    200,
+                    json!({"images": [{
+                        "front": true,
+                        "image": "http://mirror.example/front.jpg",
+                        "thumbnails": {"small": "http://mirror.example/front-250.jpg"}
+                    }]})
+                    .to_string(),
+                )
+            } else if path.starts_with("/release/") {
+                (404, String::new())
+            } else {
+                (502, String::new())
+            }
+        });
+        let client = CoverArtClient::new(&url, 100.0).unwrap();
+
+        let image = client
+            .front_image(CoverArtSource::Release, Uuid::parse_str(release).unwrap())
+            .await
+            .unwrap()
+            .expect("front image");
+        assert_eq!(image.image_url, "http://mirror.example/front.jpg");
+        assert_eq!(
+            image.thumbnails,
+            vec![Thumbnail {
+                size: 250,
+                url: "http://mirror.example/front-250.jpg".to_string()
+            }]
+        );
+        assert_eq!(
+            client
+                .front_image(CoverArtSource::Release, Uuid::nil())
+                .await
+                .unwrap(),
+            None
+        );
+        assert!(client
+            .front_image(CoverArtSource::ReleaseGroup, Uuid::nil())
+            .await
+            .is_err());
+    }
+}
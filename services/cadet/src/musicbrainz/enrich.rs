@@ -0,0 +1,419 @@
+//! Background enrichment of recordings, releases and artists.
+//!
+//! Ingestion queues each MBID it writes in `mb_enrichment_queue`. The worker
+//! looks them up, fills in the canonical data and keeps the response in
+//! `mb_cache`. Failed lookups are retried with exponential backoff until
+//! `MUSICBRAINZ_MAX_ATTEMPTS`; cached entries are looked up again after
+//! `MUSICBRAINZ_CACHE_TTL_DAYS`.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serde_json::Value;
+use sqlx::{types::Uuid, PgConnection, PgPool};
+use tracing::{error, info, warn};
+
+use super::{
+    cover_art, credit_string, isrc, Artist, ArtistCredit, EntityType, Lookup, MusicBrainzClient,
+    Recording, Release,
+};
+use crate::ingestors::teal::cleaning;
+
+/// How long to wait before checking an empty queue again.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// How often cache entries older than the TTL are queued again.
+const STALE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Queue entries taken per round.
+const CLAIM_BATCH: i64 = 100;
+/// Longest wait between retries of a failed lookup.
+pub(super) const MAX_RETRY_DELAY_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone)]
+pub struct EnrichmentConfig {
+    pub max_attempts: i32,
+    pub cache_ttl_days: i32,
+}
+
+impl EnrichmentConfig {
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        }
+        Self {
+            max_attempts: var("MUSICBRAINZ_MAX_ATTEMPTS", 8),
+            cache_ttl_days: var("MUSICBRAINZ_CACHE_TTL_DAYS", 30),
+        }
+    }
+}
+
+/// Queue `mbid` for enrichment unless it has been looked up already. Does
+/// nothing when MusicBrainz is not set up, as no worker would drain the queue;
+/// the worker queues anything missed when it starts.
+pub async fn enqueue(conn: &mut PgConnection, entity: EntityType, mbid: Uuid) -> Result<()> {
+    if !super::configured() {
+        return Ok(());
+    }
+    sqlx::query(
+        r#"
+            INSERT INTO mb_enrichment_queue (entity_type, mbid)
+            SELECT $1, $2
+            WHERE NOT EXISTS (SELECT 1 FROM mb_cache WHERE entity_type = $1 AND mbid = $2)
+            ON CONFLICT (entity_type, mbid) DO NOTHING
+        "#,
+    )
+    .bind(entity.as_str())
+    .bind(mbid)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Queue everything ingested before enrichment was set up.
+async fn enqueue_unenriched(pool: &PgPool) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+            INSERT INTO mb_enrichment_queue (entity_type, mbid)
+            SELECT 'recording', mbid FROM recordings
+            UNION ALL
+            SELECT 'release', mbid FROM releases
+            UNION ALL
+            SELECT 'artist', mbid FROM artists_extended
+            WHERE mbid_type = 'musicbrainz' AND mbid IS NOT NULL
+            EXCEPT
+            SELECT entity_type, mbid FROM mb_cache
+            ON CONFLICT (entity_type, mbid) DO NOTHING
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Queue cache entries older than `ttl_days` to be looked up again.
+async fn enqueue_stale(pool: &PgPool, ttl_days: i32) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+            INSERT INTO mb_enrichment_queue (entity_type, mbid)
+            SELECT entity_type, mbid FROM mb_cache
+            WHERE fetched_at < NOW() - make_interval(days => $1)
+            ON CONFLICT (entity_type, mbid) DO NOTHING
+        "#,
+    )
+    .bind(ttl_days)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+#[derive(sqlx::FromRow)]
+struct QueuedEntity {
+    entity_type: String,
+    mbid: Uuid,
+    attempts: i32,
+}
+
+async fn due_entities(pool: &PgPool, max_attempts: i32) -> Result<Vec<QueuedEntity>> {
+    Ok(sqlx::query_as(
+        r#"
+            SELECT entity_type, mbid, attempts FROM mb_enrichment_queue
+            WHERE next_attempt_at <= NOW() AND attempts < $1
+            ORDER BY next_attempt_at
+            LIMIT $2
+        "#,
+    )
+    .bind(max_attempts)
+    .bind(CLAIM_BATCH)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Look up queued entities until the process exits.
+pub async fn run_worker(pool: PgPool, client: Arc<MusicBrainzClient>, config: EnrichmentConfig) {
+    info!("Starting MusicBrainz enrichment worker");
+    match enqueue_unenriched(&pool).await {
+        Ok(0) => {}
+        Ok(queued) => info!("Queued {} entities for MusicBrainz enrichment", queued),
+        Err(e) => error!("Failed to queue unenriched entities: {}", e),
+    }
+
+    let mut last_stale_check = Instant::now();
+
+    loop {
+        if last_stale_check.elapsed() >= STALE_CHECK_INTERVAL {
+            last_stale_check = Instant::now();
+            if let Err(e) = enqueue_stale(&pool, config.cache_ttl_days).await {
+                error!("Failed to queue stale MusicBrainz cache entries: {}", e);
+            }
+        }
+
+        let due = match due_entities(&pool, config.max_attempts).await {
+            Ok(due) => due,
+            Err(e) => {
+                error!("Failed to read the MusicBrainz enrichment queue: {}", e);
+                tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+                continue;
+            }
+        };
+        if due.is_empty() {
+            tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+            continue;
+        }
+
+        for queued in due {
+            let Ok(entity) = queued.entity_type.parse::<EntityType>() else {
+                warn!("Dropping queued {} {}", queued.entity_type, queued.mbid);
+                let _ = dequeue(&pool, &queued.entity_type, queued.mbid).await;
+                continue;
+            };
+            if let Err(e) = enrich(&pool, &client, entity, queued.mbid).await {
+                warn!(
+                    "MusicBrainz enrichment of {} {} failed (attempt {}): {}",
+                    entity.as_str(),
+                    queued.mbid,
+                    queued.attempts + 1,
+                    e
+                );
+                if let Err(e) = record_attempt(&pool, entity, queued.mbid, &e.to_string()).await {
+                    error!("Failed to record MusicBrainz enrichment attempt: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Look up one entity and store what MusicBrainz knows about it.
+async fn enrich(
+    pool: &PgPool,
+    client: &MusicBrainzClient,
+    entity: EntityType,
+    mbid: Uuid,
+) -> Result<()> {
+    let lookup = client.lookup(entity, mbid).await;
+    metrics::counter!(
+        "cadet_musicbrainz_lookups_total",
+        "entity" => entity.as_str(),
+        "outcome" => match &lookup {
+            Ok(Lookup::Found(_)) => "found",
+            Ok(Lookup::NotFound) => "not_found",
+            Err(_) => "error",
+        }
+    )
+    .increment(1);
+
+    let lookup = lookup?;
+
+    let mut tx = pool.begin().await?;
+    let response = match lookup {
+        Lookup::Found(response) => {
+            apply(&mut tx, entity, mbid, &response).await?;
+            Some(response)
+        }
+        Lookup::NotFound => None,
+    };
+    sqlx::query(
+        r#"
+            INSERT INTO mb_cache (entity_type, mbid, found, response, fetched_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            ON CONFLICT (entity_type, mbid) DO UPDATE SET
+                found = EXCLUDED.found,
+                response = EXCLUDED.response,
+                fetched_at = EXCLUDED.fetched_at
+        "#,
+    )
+    .bind(entity.as_str())
+    .bind(mbid)
+    .bind(response.is_some())
+    .bind(&response)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM mb_enrichment_queue WHERE entity_type = $1 AND mbid = $2")
+        .bind(entity.as_str())
+        .bind(mbid)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Write the canonical data of a MusicBrainz response to the entity's row.
+async fn apply(
+    conn: &mut PgConnection,
+    entity: EntityType,
+    mbid: Uuid,
+    response: &Value,
+) -> Result<()> {
+    match entity {
+        EntityType::Recording => {
+            let recording: Recording = serde_json::from_value(response.clone())?;
+            sqlx::query(
+                r#"
+                    UPDATE recordings SET
+                        mb_name = $2,
+                        mb_artist_credit = $3,
+                        length_ms = $4,
+                        isrcs = $5,
+                        enriched_at = NOW()
+                    WHERE mbid = $1
+                "#,
+            )
+            .bind(mbid)
+            .bind(&recording.title)
+            .bind(credit_string(&recording.artist_credit))
+            .bind(recording.length)
+            .bind(&recording.isrcs)
+            .execute(&mut *conn)
+            .await?;
+            isrc::index_recording(conn, mbid, &recording.isrcs).await?;
+            update_sort_names(conn, &recording.artist_credit).await?;
+        }
+        EntityType::Release => {
+            let release: Release = serde_json::from_value(response.clone())?;
+            sqlx::query(
+                r#"
+                    UPDATE releases SET
+                        mb_name = $2,
+                        mb_artist_credit = $3,
+                        release_date = $4,
+                        release_group_mbid = $5,
+                        enriched_at = NOW()
+                    WHERE mbid = $1
+                "#,
+            )
+            .bind(mbid)
+            .bind(&release.title)
+            .bind(credit_string(&release.artist_credit))
+            .bind(release.date.filter(|date| !date.is_empty()))
+            .bind(release.release_group.as_ref().map(|group| group.id))
+            .execute(&mut *conn)
+            .await?;
+            if release.release_group.is_some() {
+                cover_art::retry_not_found(conn, mbid).await?;
+            }
+            update_sort_names(conn, &release.artist_credit).await?;
+        }
+        EntityType::Artist => {
+            let artist: Artist = serde_json::from_value(response.clone())?;
+            sqlx::query(
+                r#"
+                    UPDATE artists_extended SET
+                        mb_name = $2,
+                        sort_name = $3,
+                        enriched_at = NOW(),
+                        updated_at = NOW()
+                    WHERE mbid = $1
+                "#,
+            )
+            .bind(mbid)
+            .bind(&artist.name)
+            .bind(&artist.sort_name)
+            .execute(&mut *conn)
+            .await?;
+            replace_aliases(conn, mbid, &artist).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Keep the artist's MusicBrainz name and aliases, folded, so fuzzy matching
+/// finds the artist under any of them.
+async fn replace_aliases(conn: &mut PgConnection, mbid: Uuid, artist: &Artist) -> Result<()> {
+    let mut seen = HashSet::new();
+    let mut names = Vec::new();
+    let mut folded = Vec::new();
+    let mut locales = Vec::new();
+    let aliases = artist
+        .aliases
+        .iter()
+        .map(|alias| (alias.name.as_str(), alias.locale.as_deref()));
+    for (name, locale) in std::iter::once((artist.name.as_str(), None)).chain(aliases) {
+        let name_folded = cleaning::fold_artist_name(name);
+        if name_folded.is_empty() || !seen.insert(name) {
+            continue;
+        }
+        names.push(name);
+        folded.push(name_folded);
+        locales.push(locale);
+    }
+
+    sqlx::query("DELETE FROM artist_aliases WHERE artist_mbid = $1")
+        .bind(mbid)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(
+        r#"
+            INSERT INTO artist_aliases (artist_mbid, name, name_folded, locale)
+            SELECT a.mbid, n.name, n.name_folded, n.locale
+            FROM artists_extended a, UNNEST($2::text[], $3::text[], $4::text[])
+                AS n(name, name_folded, locale)
+            WHERE a.mbid = $1
+            ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(mbid)
+    .bind(&names)
+    .bind(&folded)
+    .bind(&locales)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Credits carry each artist's sort name, which saves a lookup per artist.
+async fn update_sort_names(conn: &mut PgConnection, credits: &[ArtistCredit]) -> Result<()> {
+    let (mbids, sort_names): (Vec<Uuid>, Vec<&str>) = credits
+        .iter()
+        .filter_map(|credit| Some((credit.artist.id, credit.artist.sort_name.as_deref()?)))
+        .unzip();
+    if mbids.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(
+        r#"
+            UPDATE artists_extended a SET sort_name = c.sort_name, updated_at = NOW()
+            FROM UNNEST($1::uuid[], $2::text[]) AS c(mbid, sort_name)
+            WHERE a.mbid = c.mbid AND a.sort_name IS DISTINCT FROM c.sort_name
+        "#,
+    )
+    .bind(&mbids)
+    .bind(&sort_names)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Count a failed attempt and push the next one back, doubling the delay
+/// from a minute up to a day.
+async fn record_attempt(pool: &PgPool, entity: EntityType, mbid: Uuid, error: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+            UPDATE mb_enrichment_queue SET
+                attempts = attempts + 1,
+                last_error = $3,
+                next_attempt_at = NOW() + make_interval(
+                    secs => LEAST(60 * power(2, LEAST(attempts, 20)), $4)
+                )
+            WHERE entity_type = $1 AND mbid = $2
+        "#,
+    )
+    .bind(entity.as_str())
+    .bind(mbid)
+    .bind(error)
+    .bind(MAX_RETRY_DELAY_SECS as f64)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn dequeue(pool: &PgPool, entity_type: &str, mbid: Uuid) -> Result<()> {
+    sqlx::query("DELETE FROM mb_enrichment_queue WHERE entity_type = $1 AND mbid = $2")
+        .bind(entity_type)
+        .bind(mbid)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
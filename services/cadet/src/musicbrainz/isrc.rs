@@ -0,0 +1,118 @@
+//! The ISRC index: which recordings each ISRC belongs to.
+//!
+//! Streaming clients often know a track's ISRC but not its MBID. The index in
+//! `isrc_recordings` is filled from MusicBrainz enrichment and from pairs that
+//! enough accounts' plays agree on, and plays with only an ISRC take the recording MBID from it
+//! when the ISRC points at exactly one recording.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use sqlx::{types::Uuid, PgConnection};
+
+pub use common::isrc::normalize_isrc;
+
+/// `plays.mbid_match_method` of plays whose recording came from the index.
+pub const INDEX_MATCH_METHOD: &str = "isrc_index";
+
+/// How many accounts must send the same ISRC and recording MBID pair before
+/// plays add it to the index, so one client can't misattribute an ISRC.
+pub const PLAY_REPORTERS: i64 = 3;
+
+/// Index the ISRCs MusicBrainz lists for a recording.
+pub async fn index_recording(
+    conn: &mut PgConnection,
+    recording: Uuid,
+    isrcs: &[String],
+) -> Result<()> {
+    let isrcs: Vec<String> = isrcs
+        .iter()
+        .filter_map(|isrc| normalize_isrc(isrc))
+        .collect();
+    if isrcs.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(
+        r#"
+            INSERT INTO isrc_recordings (isrc, recording_mbid, source)
+            SELECT isrc, $2, 'musicbrainz' FROM UNNEST($1::text[]) AS isrc
+            ON CONFLICT (isrc, recording_mbid, source) DO NOTHING
+        "#,
+    )
+    .bind(&isrcs)
+    .bind(recording)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Record the ISRC and recording MBID pairs that clients sent together, and
+/// index those that [`PLAY_REPORTERS`] accounts have now sent.
+pub async fn index_plays(
+    conn: &mut PgConnection,
+    dids: &[&str],
+    isrcs: &[Option<String>],
+    recordings: &[Option<Uuid>],
+) -> Result<()> {
+    let reported: Vec<(String, Uuid)> = sqlx::query_as(
+        r#"
+            INSERT INTO isrc_recording_reporters (isrc, recording_mbid, did)
+            SELECT DISTINCT isrc, recording_mbid, did
+            FROM UNNEST($1::text[], $2::text[], $3::uuid[]) AS p(did, isrc, recording_mbid)
+            WHERE isrc IS NOT NULL AND recording_mbid IS NOT NULL
+            ON CONFLICT (isrc, recording_mbid, did) DO NOTHING
+            RETURNING isrc, recording_mbid
+        "#,
+    )
+    .bind(dids)
+    .bind(isrcs)
+    .bind(recordings)
+    .fetch_all(&mut *conn)
+    .await?;
+    if reported.is_empty() {
+        return Ok(());
+    }
+    let (isrcs, recordings): (Vec<String>, Vec<Uuid>) = reported.into_iter().unzip();
+    sqlx::query(
+        r#"
+            INSERT INTO isrc_recordings (isrc, recording_mbid, source)
+            SELECT p.isrc, p.recording_mbid, 'play'
+            FROM (SELECT DISTINCT * FROM UNNEST($1::text[], $2::uuid[])) AS p(isrc, recording_mbid)
+            WHERE (
+                SELECT COUNT(*) FROM isrc_recording_reporters r
+                WHERE r.isrc = p.isrc AND r.recording_mbid = p.recording_mbid
+            ) >= $3
+            ON CONFLICT (isrc, recording_mbid, source) DO NOTHING
+        "#,
+    )
+    .bind(&isrcs)
+    .bind(&recordings)
+    .bind(PLAY_REPORTERS)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// The recording of each of `isrcs` that belongs to exactly one recording.
+pub async fn unambiguous_recordings(
+    conn: &mut PgConnection,
+    isrcs: &[String],
+) -> Result<HashMap<String, Uuid>> {
+    if isrcs.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let rows: Vec<(String, Uuid)> = sqlx::query_as(
+        r#"
+            SELECT i.isrc, MIN(i.recording_mbid::text)::uuid
+            FROM isrc_recordings i
+            JOIN recordings r ON r.mbid = i.recording_mbid
+            WHERE i.isrc = ANY($1)
+            GROUP BY i.isrc
+            HAVING COUNT(DISTINCT i.recording_mbid) = 1
+        "#,
+    )
+    .bind(isrcs)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows.into_iter().collect())
+}
@@ -0,0 +1,425 @@
+//! MusicBrainz lookups against a self-hosted mirror.
+//!
+//! Plays only carry the names a client sent. [`enrich`] queues every
+//! recording, release and artist MBID it sees and looks them up here, through
+//! the JSON web service (`/ws/2`) of the mirror at `MUSICBRAINZ_URL`. The
+//! public musicbrainz.org works too, at its limit of one request per second.
+
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::types::Uuid;
+use tokio::sync::Mutex;
+use tokio::time::{Interval, MissedTickBehavior};
+
+pub mod cover_art;
+pub mod enrich;
+pub mod isrc;
+pub mod resolver;
+
+const DEFAULT_REQUESTS_PER_SEC: f64 = 1.0;
+
+const USER_AGENT: &str = concat!(
+    "teal-cadet/",
+    env!("CARGO_PKG_VERSION"),
+    " ( https://teal.fm )"
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EntityType {
+    Recording,
+    Release,
+    Artist,
+}
+
+impl EntityType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntityType::Recording => "recording",
+            EntityType::Release => "release",
+            EntityType::Artist => "artist",
+        }
+    }
+
+    /// Subqueries to include in a lookup of this entity.
+    fn includes(&self) -> Option<&'static str> {
+        match self {
+            EntityType::Recording => Some("artist-credits+isrcs"),
+            EntityType::Release => Some("artist-credits+release-groups"),
+            EntityType::Artist => Some("aliases"),
+        }
+    }
+}
+
+impl FromStr for EntityType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "recording" => Ok(EntityType::Recording),
+            "release" => Ok(EntityType::Release),
+            "artist" => Ok(EntityType::Artist),
+            other => Err(anyhow!("Unknown MusicBrainz entity type {}", other)),
+        }
+    }
+}
+
+/// A service URL from `var`, or `None` when it is unset or blank.
+fn url_from_env(var: &str) -> Option<String> {
+    std::env::var(var)
+        .ok()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+}
+
+/// Whether `MUSICBRAINZ_URL` is set, i.e. whether the enrichment worker and
+/// the resolver run.
+pub fn configured() -> bool {
+    static CONFIGURED: OnceLock<bool> = OnceLock::new();
+    *CONFIGURED.get_or_init(|| url_from_env("MUSICBRAINZ_URL").is_some())
+}
+
+#[derive(Debug)]
+pub enum Lookup {
+    Found(Value),
+    /// The MBID doesn't exist (or was merged away) in MusicBrainz
+    NotFound,
+}
+
+pub struct MusicBrainzClient {
+    http: reqwest::Client,
+    base_url: String,
+    /// Shared by everything using the client, so together they stay under
+    /// the mirror's rate limit
+    throttle: Mutex<Interval>,
+}
+
+impl MusicBrainzClient {
+    pub fn new(base_url: &str, requests_per_sec: f64) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(Duration::from_secs(30))
+            .build()?;
+        let mut throttle =
+            tokio::time::interval(Duration::from_secs_f64(1.0 / requests_per_sec.max(0.01)));
+        throttle.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Ok(Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            throttle: Mutex::new(throttle),
+        })
+    }
+
+    /// Client for `MUSICBRAINZ_URL` at `MUSICBRAINZ_RATE_LIMIT` requests per
+    /// second, or `None` when MusicBrainz is not set up.
+    pub fn from_env() -> Option<Result<Self>> {
+        let requests_per_sec = std::env::var("MUSICBRAINZ_RATE_LIMIT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_REQUESTS_PER_SEC);
+        url_from_env("MUSICBRAINZ_URL").map(|url| Self::new(&url, requests_per_sec))
+    }
+
+    /// `GET /ws/2/{path}` as JSON; `None` for a 404.
+    async fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<Option<Value>> {
+        self.throttle.lock().await.tick().await;
+        let response = self
+            .http
+            .get(format!("{}/ws/2/{}", self.base_url, path))
+            .query(&[("fmt", "json")])
+            .query(query)
+            .send()
+            .await?;
+
+        match response.status() {
+            reqwest::StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(response.json().await?)),
+            // 503 is how MusicBrainz says "slow down"; either way, try again later
+            status => Err(anyhow!(
+                "MusicBrainz request for {} failed with {}",
+                path,
+                status
+            )),
+        }
+    }
+
+    pub async fn lookup(&self, entity: EntityType, mbid: Uuid) -> Result<Lookup> {
+        let query: Vec<(&str, &str)> = entity
+            .includes()
+            .map(|includes| ("inc", includes))
+            .into_iter()
+            .collect();
+        Ok(
+            match self
+                .get(&format!("{}/{}", entity.as_str(), mbid), &query)
+                .await?
+            {
+                Some(response) => Lookup::Found(response),
+                None => Lookup::NotFound,
+            },
+        )
+    }
+
+    /// Recordings with this ISRC, with their credits and releases.
+    pub async fn isrc_recordings(&self, isrc: &str) -> Result<Vec<Recording>> {
+        let response = self
+            .get(
+                &format!("isrc/{}", isrc),
+                &[("inc", "artist-credits+releases")],
+            )
+            .await?;
+        recordings_of(response)
+    }
+
+    /// Recordings matching a Lucene `query`, best first.
+    pub async fn search_recordings(&self, query: &str, limit: u32) -> Result<Vec<Recording>> {
+        let limit = limit.to_string();
+        let response = self
+            .get("recording", &[("query", query), ("limit", &limit)])
+            .await?;
+        recordings_of(response)
+    }
+}
+
+fn recordings_of(response: Option<Value>) -> Result<Vec<Recording>> {
+    match response.map(|mut response| response["recordings"].take()) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(recordings) => Ok(serde_json::from_value(recordings)?),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CreditedArtist {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(rename = "sort-name")]
+    pub sort_name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ArtistCredit {
+    /// Name as credited, which may differ from the artist's own name
+    pub name: String,
+    #[serde(default)]
+    pub joinphrase: String,
+    pub artist: CreditedArtist,
+}
+
+/// The credit as MusicBrainz displays it, e.g. `Daft Punk feat. Pharrell Williams`.
+pub fn credit_string(credits: &[ArtistCredit]) -> Option<String> {
+    if credits.is_empty() {
+        return None;
+    }
+    Some(
+        credits
+            .iter()
+            .map(|credit| format!("{}{}", credit.name, credit.joinphrase))
+            .collect(),
+    )
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Recording {
+    pub id: Uuid,
+    pub title: String,
+    /// Length in milliseconds
+    pub length: Option<i32>,
+    #[serde(default, rename = "artist-credit")]
+    pub artist_credit: Vec<ArtistCredit>,
+    #[serde(default)]
+    pub isrcs: Vec<String>,
+    /// Only in searches and ISRC lookups
+    #[serde(default)]
+    pub releases: Vec<ReleaseRef>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseRef {
+    pub id: Uuid,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseGroup {
+    pub id: Uuid,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub title: String,
+    /// `YYYY`, `YYYY-MM` or `YYYY-MM-DD`
+    pub date: Option<String>,
+    #[serde(rename = "release-group")]
+    pub release_group: Option<ReleaseGroup>,
+    #[serde(default, rename = "artist-credit")]
+    pub artist_credit: Vec<ArtistCredit>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Artist {
+    pub name: String,
+    #[serde(rename = "sort-name")]
+    pub sort_name: Option<String>,
+    /// Other names and spellings, e.g. in other scripts
+    #[serde(default)]
+    pub aliases: Vec<Alias>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Alias {
+    pub name: String,
+    pub locale: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    use serde_json::json;
+
+    use super::*;
+
+    /// A web service stub answering `GET` requests with `respond(path)`'s
+    /// status and body. Returns its base URL.
+    pub(super) fn stub_server(respond: impl Fn(&str) -> (u16, String) + Send + 'static) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                // Skip the headers
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let path = request_line.split(' ').nth(1).unwrap_or("/");
+                let (status, body) = respond(path);
+                write!(
+                    stream,
+                    "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_lookup_against_stub_server() {
+        let found = "0b0d0d0b-0000-4000-8000-000000000001";
+        let url = stub_server(move |path| {
+            if path.starts_with(&format!("/ws/2/recording/{}?", found)) {
+                assert!(path.contains("fmt=json"));
+                (
+                    200,
+                    json!({"id": found, "title": "One More Time"}).to_string(),
+                )
+            } else if path.starts_with("/ws/2/recording/") {
+                (404, json!({"error": "Not Found"}).to_string())
+            } else {
+                (503, json!({"error": "Rate limited"}).to_string())
+            }
+        });
+        let client = MusicBrainzClient::new(&format!("{}/", url), 100.0).unwrap();
+
+        match client
+            .lookup(EntityType::Recording, Uuid::parse_str(found).unwrap())
+            .await
+            .unwrap()
+        {
+            Lookup::Found(value) => assert_eq!(value["title"], "One More Time"),
+            other => panic!("expected a recording, got {:?}", other),
+        }
+        assert!(matches!(
+            client
+                .lookup(EntityType::Recording, Uuid::nil())
+                .await
+                .unwrap(),
+            Lookup::NotFound
+        ));
+        assert!(client
+            .lookup(EntityType::Release, Uuid::nil())
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_recording_and_credit_string() {
+        let recording: Recording = serde_json::from_value(json!({
+            "id": "0b0d0d0b-0000-4000-8000-000000000001",
+            "title": "Get Lucky",
+            "length": 369000,
+            "isrcs": ["USQX91300108"],
+            "artist-credit": [
+                {
+                    "name": "Daft Punk",
+                    "joinphrase": " feat. ",
+                    "artist": {
+                        "id": "056e4f3e-d505-4dad-8ec1-d04f521cbb56",
+                        "name": "Daft Punk",
+                        "sort-name": "Daft Punk"
+                    }
+                },
+                {
+                    "name": "Pharrell",
+                    "joinphrase": "",
+                    "artist": {
+                        "id": "149e6720-4e4a-41a4-afca-6d29083fc091",
+                        "name": "Pharrell Williams",
+                        "sort-name": "Williams, Pharrell"
+                    }
+                }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(recording.length, Some(369000));
+        assert_eq!(recording.isrcs, vec!["USQX91300108"]);
+        assert_eq!(
+            credit_string(&recording.artist_credit).as_deref(),
+            Some("Daft Punk feat. Pharrell")
+        );
+        assert_eq!(
+            recording.artist_credit[1].artist.sort_name.as_deref(),
+            Some("Williams, Pharrell")
+        );
+        assert_eq!(credit_string(&[]), None);
+        assert_eq!(
+            "release".parse::<EntityType>().unwrap(),
+            EntityType::Release
+        );
+    }
+    #[test]
+    fn test_parse_artist_aliases() {
+        let artist: Artist = serde_json::from_value(json!({
+            "name": "宇多田ヒカル",
+            "sort-name": "Utada, Hikaru",
+            "aliases": [
+                { "name": "Utada Hikaru", "locale": "en", "type": "Artist name" },
+                { "name": "Hikki", "locale": null }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(artist.aliases.len(), 2);
+        assert_eq!(artist.aliases[0].name, "Utada Hikaru");
+        assert_eq!(artist.aliases[0].locale.as_deref(), Some("en"));
+        assert_eq!(artist.aliases[1].locale, None);
+
+        let artist: Artist =
+            serde_json::from_value(json!({ "name": "Daft Punk", "sort-name": "Daft Punk" }))
+                .unwrap();
+        assert!(artist.aliases.is_empty());
+    }
+}
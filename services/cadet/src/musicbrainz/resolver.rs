@@ -0,0 +1,654 @@
+//! MBID resolution for plays submitted without MusicBrainz IDs.
+//!
+//! Plays without a recording MBID are queued in `mbid_resolution_queue`. The
+//! worker tries the play's ISRC first (against the ISRC index, then the mirror)
+//! and otherwise searches the mirror by cleaned track and artist names. Each
+//! candidate is scored on title, artists, release and duration; a match at or
+//! above [`AUTO_MATCH_CONFIDENCE`] sets the play's recording (and release)
+//! MBID and upgrades the play's synthetic artists to their MusicBrainz IDs.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use sqlx::{types::Uuid, PgConnection, PgPool};
+use tracing::{error, info, warn};
+
+use super::enrich::{self, EnrichmentConfig, MAX_RETRY_DELAY_SECS};
+use super::{isrc, EntityType, MusicBrainzClient, Recording, ReleaseRef};
+use crate::ingestors::teal::entity_cache::EntityCache;
+use crate::ingestors::teal::feed_play::{PlayIngestor, AUTO_MATCH_CONFIDENCE};
+use crate::ingestors::teal::merge_log;
+
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(30);
+const CLAIM_BATCH: i64 = 100;
+/// Search results scored per play.
+const SEARCH_LIMIT: u32 = 10;
+
+const TITLE_WEIGHT: f64 = 0.45;
+const ARTIST_WEIGHT: f64 = 0.35;
+const RELEASE_WEIGHT: f64 = 0.1;
+const DURATION_WEIGHT: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMethod {
+    Isrc,
+    Search,
+}
+
+impl MatchMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchMethod::Isrc => "isrc",
+            MatchMethod::Search => "search",
+        }
+    }
+}
+
+/// What a play says about its track.
+#[derive(Debug, Clone, Default)]
+pub struct PlayQuery {
+    pub track_name: String,
+    pub artist_names: Vec<String>,
+    pub release_name: Option<String>,
+    pub isrc: Option<String>,
+    /// Seconds
+    pub duration: Option<i32>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Resolution {
+    Matched(Match),
+    /// Nothing reached the threshold; the best candidate's confidence, if any
+    Unmatched(Option<f64>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub recording: Recording,
+    /// Set only when the play names a release and it matches one of the
+    /// recording's releases
+    pub release: Option<ReleaseRef>,
+    pub confidence: f64,
+    pub method: MatchMethod,
+}
+
+/// How well `recording` fits `play`, from 0 to 1, and the recording's release
+/// that best fits the play's release name.
+fn score<'a>(play: &PlayQuery, recording: &'a Recording) -> (f64, Option<(&'a ReleaseRef, f64)>) {
+    let title = PlayIngestor::calculate_similarity(&play.track_name, &recording.title, false);
+
+    let mut total = title * TITLE_WEIGHT;
+    let mut weights = TITLE_WEIGHT;
+
+    // Each named artist should appear in the credit, under the credited
+    // name or the artist's own
+    if !play.artist_names.is_empty() {
+        let artist = play
+            .artist_names
+            .iter()
+            .map(|name| {
+                recording
+                    .artist_credit
+                    .iter()
+                    .flat_map(|credit| [&credit.name, &credit.artist.name])
+                    .map(|credited| PlayIngestor::calculate_similarity(name, credited, true))
+                    .fold(0.0, f64::max)
+            })
+            .sum::<f64>()
+            / play.artist_names.len() as f64;
+        total += artist * ARTIST_WEIGHT;
+        weights += ARTIST_WEIGHT;
+    }
+
+    let release = play.release_name.as_deref().and_then(|release_name| {
+        recording
+            .releases
+            .iter()
+            .map(|release| {
+                let similarity =
+                    PlayIngestor::calculate_similarity(release_name, &release.title, false);
+                (release, similarity)
+            })
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+    });
+    if let Some((_, similarity)) = release {
+        total += similarity * RELEASE_WEIGHT;
+        weights += RELEASE_WEIGHT;
+    }
+
+    if let (Some(duration), Some(length)) = (play.duration, recording.length) {
+        let difference = (duration - length / 1000).abs();
+        let fit = match difference {
+            0..=3 => 1.0,
+            4..=10 => 0.5,
+            _ => 0.0,
+        };
+        total += fit * DURATION_WEIGHT;
+        weights += DURATION_WEIGHT;
+    }
+
+    (total / weights, release)
+}
+
+/// The best scoring candidate. An ISRC is strong evidence on its own, but
+/// names still count so mislabelled ISRCs don't win outright.
+fn best_match(play: &PlayQuery, candidates: Vec<Recording>, method: MatchMethod) -> Option<Match> {
+    candidates
+        .into_iter()
+        .map(|recording| {
+            let (confidence, release) = score(play, &recording);
+            let confidence = match method {
+                MatchMethod::Isrc => 0.5 + confidence * 0.5,
+                MatchMethod::Search => confidence,
+            };
+            let release = release
+                .filter(|(_, similarity)| *similarity >= AUTO_MATCH_CONFIDENCE)
+                .map(|(release, _)| release.clone());
+            Match {
+                recording,
+                release,
+                confidence,
+                method,
+            }
+        })
+        .max_by(|a, b| {
+            a.confidence
+                .partial_cmp(&b.confidence)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+}
+
+/// Escape a phrase for a quoted Lucene term.
+fn lucene_phrase(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// MusicBrainz search query for the play, or `None` when it names no artist
+/// (a title alone matches far too many recordings).
+fn search_query(play: &PlayQuery) -> Option<String> {
+    let artist = play.artist_names.first()?;
+    Some(format!(
+        "recording:{} AND artist:{}",
+        lucene_phrase(&PlayIngestor::clean_name(&play.track_name, false)),
+        lucene_phrase(&PlayIngestor::clean_name(artist, true)),
+    ))
+}
+
+/// Recordings in the ISRC index for `isrc` that were enriched earlier.
+async fn cached_isrc_recordings(pool: &PgPool, isrc: &str) -> Result<Vec<Recording>> {
+    let responses: Vec<serde_json::Value> = sqlx::query_scalar(
+        r#"
+            SELECT c.response FROM mb_cache c
+            WHERE c.entity_type = 'recording' AND c.found
+              AND c.mbid IN (SELECT recording_mbid FROM isrc_recordings WHERE isrc = $1)
+        "#,
+    )
+    .bind(isrc)
+    .fetch_all(pool)
+    .await?;
+    Ok(responses
+        .into_iter()
+        .filter_map(|response| serde_json::from_value(response).ok())
+        .collect())
+}
+
+/// The best match for `play` at or above [`AUTO_MATCH_CONFIDENCE`].
+pub async fn resolve(
+    pool: &PgPool,
+    client: &MusicBrainzClient,
+    play: &PlayQuery,
+) -> Result<Resolution> {
+    let mut best_confidence: Option<f64> = None;
+
+    if let Some(isrc) = play.isrc.as_deref().and_then(isrc::normalize_isrc) {
+        let mut candidates = cached_isrc_recordings(pool, &isrc).await?;
+        if candidates.is_empty() {
+            candidates = client.isrc_recordings(&isrc).await?;
+        }
+        if let Some(found) = best_match(play, candidates, MatchMethod::Isrc) {
+            if found.confidence >= AUTO_MATCH_CONFIDENCE {
+                return Ok(Resolution::Matched(found));
+            }
+            best_confidence = Some(found.confidence);
+        }
+    }
+
+    if let Some(query) = search_query(play) {
+        let candidates = client.search_recordings(&query, SEARCH_LIMIT).await?;
+        if let Some(found) = best_match(play, candidates, MatchMethod::Search) {
+            if found.confidence >= AUTO_MATCH_CONFIDENCE {
+                return Ok(Resolution::Matched(found));
+            }
+            best_confidence =
+                Some(best_confidence.map_or(found.confidence, |best| best.max(found.confidence)));
+        }
+    }
+
+    Ok(Resolution::Unmatched(best_confidence))
+}
+
+#[derive(sqlx::FromRow)]
+struct QueuedPlay {
+    uri: String,
+    attempts: i32,
+    track_name: String,
+    release_name: Option<String>,
+    isrc: Option<String>,
+    duration: Option<i32>,
+}
+
+#[derive(sqlx::FromRow)]
+struct PlayArtist {
+    artist_id: i32,
+    artist_name: String,
+    mbid_type: String,
+}
+
+/// Queue plays ingested before resolution was set up.
+async fn enqueue_unresolved(pool: &PgPool) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+            INSERT INTO mbid_resolution_queue (play_uri)
+            SELECT uri FROM plays
+            WHERE recording_mbid IS NULL AND mbid_resolved_at IS NULL
+            ON CONFLICT (play_uri) DO NOTHING
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+async fn due_plays(pool: &PgPool, max_attempts: i32) -> Result<Vec<QueuedPlay>> {
+    Ok(sqlx::query_as(
+        r#"
+            SELECT q.play_uri AS uri, q.attempts, p.track_name, p.release_name, p.isrc, p.duration
+            FROM mbid_resolution_queue q
+            JOIN plays p ON p.uri = q.play_uri
+            WHERE q.next_attempt_at <= NOW() AND q.attempts < $1
+            ORDER BY q.next_attempt_at
+            LIMIT $2
+        "#,
+    )
+    .bind(max_attempts)
+    .bind(CLAIM_BATCH)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Resolve queued plays until the process exits.
+pub async fn run_worker(pool: PgPool, client: Arc<MusicBrainzClient>, config: EnrichmentConfig) {
+    info!("Starting MBID resolution worker");
+    match enqueue_unresolved(&pool).await {
+        Ok(0) => {}
+        Ok(queued) => info!("Queued {} plays for MBID resolution", queued),
+        Err(e) => error!("Failed to queue unresolved plays: {}", e),
+    }
+
+    loop {
+        let due = match due_plays(&pool, config.max_attempts).await {
+            Ok(due) => due,
+            Err(e) => {
+                error!("Failed to read the MBID resolution queue: {}", e);
+                tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+                continue;
+            }
+        };
+        if due.is_empty() {
+            tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+            continue;
+        }
+
+        for queued in due {
+            if let Err(e) = resolve_play(&pool, &client, &queued).await {
+                warn!(
+                    "MBID resolution of {} failed (attempt {}): {}",
+                    queued.uri,
+                    queued.attempts + 1,
+                    e
+                );
+                if let Err(e) = record_attempt(&pool, &queued.uri, &e.to_string()).await {
+                    error!("Failed to record MBID resolution attempt: {}", e);
+                }
+            }
+        }
+    }
+}
+
+async fn resolve_play(
+    pool: &PgPool,
+    client: &MusicBrainzClient,
+    queued: &QueuedPlay,
+) -> Result<()> {
+    let artists: Vec<PlayArtist> = sqlx::query_as(
+        r#"
+            SELECT pa.artist_id, pa.artist_name, ae.mbid_type
+            FROM play_to_artists_extended pa
+            JOIN artists_extended ae ON ae.id = pa.artist_id
+            WHERE pa.play_uri = $1
+        "#,
+    )
+    .bind(&queued.uri)
+    .fetch_all(pool)
+    .await?;
+    let play = PlayQuery {
+        track_name: queued.track_name.clone(),
+        artist_names: artists.iter().map(|a| a.artist_name.clone()).collect(),
+        release_name: queued.release_name.clone(),
+        isrc: queued.isrc.clone(),
+        duration: queued.duration,
+    };
+
+    let resolution = resolve(pool, client, &play).await;
+    metrics::counter!(
+        "cadet_mbid_resolutions_total",
+        "outcome" => match &resolution {
+            Ok(Resolution::Matched(found)) => found.method.as_str(),
+            Ok(Resolution::Unmatched(_)) => "unmatched",
+            Err(_) => "error",
+        }
+    )
+    .increment(1);
+
+    let mut merged = false;
+    let mut tx = pool.begin().await?;
+    match resolution? {
+        Resolution::Matched(found) => {
+            info!(
+                "🔗 Resolved {} to recording {} '{}' by {} (confidence: {:.2})",
+                queued.uri,
+                found.recording.id,
+                found.recording.title,
+                found.method.as_str(),
+                found.confidence
+            );
+            merged = apply_match(&mut tx, &queued.uri, &found, &artists).await?;
+        }
+        Resolution::Unmatched(best_confidence) => {
+            sqlx::query(
+                r#"
+                    UPDATE plays SET mbid_match_confidence = $2, mbid_resolved_at = NOW()
+                    WHERE uri = $1
+                "#,
+            )
+            .bind(&queued.uri)
+            .bind(best_confidence.map(|confidence| confidence as f32))
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    sqlx::query("DELETE FROM mbid_resolution_queue WHERE play_uri = $1")
+        .bind(&queued.uri)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    if merged {
+        // The play cache may still hold the merged artist's ID
+        EntityCache::shared().invalidate(pool).await?;
+    }
+    Ok(())
+}
+
+/// Point the play at the matched recording and release, and give its
+/// synthetic artists the MBIDs of the artists they match in the credit.
+/// Returns whether a synthetic artist was merged into an existing one.
+async fn apply_match(
+    conn: &mut PgConnection,
+    uri: &str,
+    found: &Match,
+    artists: &[PlayArtist],
+) -> Result<bool> {
+    let recording = &found.recording;
+    sqlx::query(
+        "INSERT INTO recordings (mbid, name) VALUES ($1, $2) ON CONFLICT (mbid) DO NOTHING",
+    )
+    .bind(recording.id)
+    .bind(&recording.title)
+    .execute(&mut *conn)
+    .await?;
+    enrich::enqueue(conn, EntityType::Recording, recording.id).await?;
+    if let Some(release) = &found.release {
+        sqlx::query(
+            "INSERT INTO releases (mbid, name) VALUES ($1, $2) ON CONFLICT (mbid) DO NOTHING",
+        )
+        .bind(release.id)
+        .bind(&release.title)
+        .execute(&mut *conn)
+        .await?;
+        enrich::enqueue(conn, EntityType::Release, release.id).await?;
+    }
+    sqlx::query(
+        r#"
+            UPDATE plays SET
+                recording_mbid = $2,
+                release_mbid = COALESCE(release_mbid, $3),
+                mbid_match_confidence = $4,
+                mbid_match_method = $5,
+                mbid_resolved_at = NOW()
+            WHERE uri = $1
+        "#,
+    )
+    .bind(uri)
+    .bind(recording.id)
+    .bind(found.release.as_ref().map(|release| release.id))
+    .bind(found.confidence as f32)
+    .bind(found.method.as_str())
+    .execute(&mut *conn)
+    .await?;
+
+    let mut merged = false;
+    for artist in artists.iter().filter(|a| a.mbid_type == "synthetic") {
+        let credited = recording
+            .artist_credit
+            .iter()
+            .map(|credit| {
+                let similarity =
+                    PlayIngestor::calculate_similarity(&artist.artist_name, &credit.name, true)
+                        .max(PlayIngestor::calculate_similarity(
+                            &artist.artist_name,
+                            &credit.artist.name,
+                            true,
+                        ));
+                (credit, similarity)
+            })
+            .filter(|(_, similarity)| *similarity >= AUTO_MATCH_CONFIDENCE)
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        let Some((credit, similarity)) = credited else {
+            continue;
+        };
+        merged |= upgrade_synthetic_artist(
+            conn,
+            artist.artist_id,
+            credit.artist.id,
+            &credit.artist.name,
+            similarity,
+        )
+        .await?;
+        enrich::enqueue(conn, EntityType::Artist, credit.artist.id).await?;
+    }
+    Ok(merged)
+}
+
+/// Give a synthetic artist its MusicBrainz ID. When another row already has
+/// that ID, the synthetic artist's plays move there instead and the synthetic
+/// row is removed; returns whether that happened. Either way the change is
+/// recorded in the merge log with `confidence`.
+async fn upgrade_synthetic_artist(
+    conn: &mut PgConnection,
+    synthetic_id: i32,
+    mbid: Uuid,
+    name: &str,
+    confidence: f64,
+) -> Result<bool> {
+    let existing: Option<i32> =
+        sqlx::query_scalar("SELECT id FROM artists_extended WHERE mbid = $1")
+            .bind(mbid)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+    let Some(target_id) = existing.filter(|id| *id != synthetic_id) else {
+        let recorded =
+            merge_log::record_artist_upgrade(conn, synthetic_id, mbid, name, Some(confidence))
+                .await?;
+        if recorded.is_none() {
+            return Ok(false);
+        }
+        sqlx::query(
+            r#"
+                UPDATE artists_extended
+                SET mbid = $2, mbid_type = 'musicbrainz', updated_at = NOW()
+                WHERE id = $1 AND mbid_type = 'synthetic'
+            "#,
+        )
+        .bind(synthetic_id)
+        .bind(mbid)
+        .execute(&mut *conn)
+        .await?;
+        return Ok(false);
+    };
+
+    merge_log::record_artist_merge(
+        conn,
+        synthetic_id,
+        target_id,
+        Some(confidence),
+        merge_log::RESOLVER_METHOD,
+    )
+    .await?;
+    sqlx::query(
+        r#"
+            UPDATE play_to_artists_extended
+            SET artist_id = $1, artist_name = $2
+            WHERE artist_id = $3
+            AND NOT EXISTS (
+                SELECT 1 FROM play_to_artists_extended existing
+                WHERE existing.play_uri = play_to_artists_extended.play_uri
+                AND existing.artist_id = $1
+            )
+        "#,
+    )
+    .bind(target_id)
+    .bind(name)
+    .bind(synthetic_id)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query("DELETE FROM play_to_artists_extended WHERE artist_id = $1")
+        .bind(synthetic_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM artists_extended WHERE id = $1")
+        .bind(synthetic_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(true)
+}
+
+async fn record_attempt(pool: &PgPool, uri: &str, error: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+            UPDATE mbid_resolution_queue SET
+                attempts = attempts + 1,
+                last_error = $2,
+                next_attempt_at = NOW() + make_interval(
+                    secs => LEAST(60 * power(2, LEAST(attempts, 20)), $3)
+                )
+            WHERE play_uri = $1
+        "#,
+    )
+    .bind(uri)
+    .bind(error)
+    .bind(MAX_RETRY_DELAY_SECS as f64)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn recording(title: &str, artist: &str, length: Option<i32>, releases: &[&str]) -> Recording {
+        serde_json::from_value(json!({
+            "id": Uuid::new_v4(),
+            "title": title,
+            "length": length,
+            "artist-credit": [{
+                "name": artist,
+                "joinphrase": "",
+                "artist": {"id": Uuid::new_v4(), "name": artist, "sort-name": artist}
+            }],
+            "releases": releases
+                .iter()
+                .map(|title| json!({"id": Uuid::new_v4(), "title": title}))
+                .collect::<Vec<_>>()
+        }))
+        .unwrap()
+    }
+
+    fn play(track: &str, artist: &str, release: Option<&str>, duration: Option<i32>) -> PlayQuery {
+        PlayQuery {
+            track_name: track.to_string(),
+            artist_names: vec![artist.to_string()],
+            release_name: release.map(str::to_string),
+            isrc: None,
+            duration,
+        }
+    }
+
+    #[test]
+    fn test_best_match_prefers_matching_names_and_duration() {
+        let play = play(
+            "Harder, Better, Faster, Stronger",
+            "Daft Punk",
+            Some("Discovery"),
+            Some(224),
+        );
+        let candidates = vec![
+            recording(
+                "Harder, Better, Faster, Stronger (Alive 2007)",
+                "Daft Punk",
+                Some(321_000),
+                &["Alive 2007"],
+            ),
+            recording(
+                "Harder, Better, Faster, Stronger",
+                "Daft Punk",
+                Some(224_000),
+                &["Discovery"],
+            ),
+        ];
+
+        let found = best_match(&play, candidates, MatchMethod::Search).unwrap();
+        assert_eq!(found.recording.length, Some(224_000));
+        assert!(found.confidence >= AUTO_MATCH_CONFIDENCE);
+        assert_eq!(found.release.unwrap().title, "Discovery");
+    }
+
+    #[test]
+    fn test_wrong_artist_is_not_confident() {
+        let play = play("Intro", "The xx", None, None);
+        let found = best_match(
+            &play,
+            vec![recording("Intro", "M83", Some(308_000), &[])],
+            MatchMethod::Search,
+        )
+        .unwrap();
+        assert!(found.confidence < AUTO_MATCH_CONFIDENCE);
+        assert!(found.release.is_none());
+    }
+
+    #[test]
+    fn test_search_query_needs_an_artist() {
+        let mut query = play("Say \"Hello\"", "Artist", None, None);
+        assert_eq!(
+            search_query(&query).as_deref(),
+            Some(r#"recording:"Say \"Hello\"" AND artist:"Artist""#)
+        );
+        query.artist_names.clear();
+        assert!(search_query(&query).is_none());
+    }
+}
@@ -4,6 +4,10 @@
 use serde::{Deserialize, Serialize};
 
 use anyhow::{anyhow, Result};
+use tracing::warn;
+
+/// AppView used to resolve handles to DIDs.
+pub const PUBLIC_APP_VIEW: &str = "https://public.api.bsky.app";
 
 // should be same as regex /^did:[a-z]+:[\S\s]+/
 fn is_did(did: &str) -> bool {
@@ -73,13 +77,14 @@ async fn resolve_handle(handle: &str, resolver_app_view: &str) -> Result<String,
         resolver_app_view, handle
     ))
     .await?
+    .error_for_status()?
     .json::<ResolvedHandle>()
     .await?;
 
     Ok(res.did)
 }
 
-async fn get_did_doc(did: &str) -> Result<DidDocument> {
+pub async fn get_did_doc(did: &str) -> Result<DidDocument> {
     // get the specific did spec
     // did:plc:abcd1e -> plc
     let parts: Vec<&str> = did.split(':').collect();
@@ -98,7 +103,7 @@ async fn get_did_doc(did: &str) -> Result<DidDocument> {
         }
         "web" => {
             if !is_valid_domain(parts[2]) {
-                todo!("Error for domain in did:web is not valid");
+                return Err(anyhow!("Invalid domain in DID: {}", did));
             };
             let ident = parts[2];
             let res = reqwest::get(format!("https://{}/.well-known/did.json", ident))
@@ -109,7 +114,52 @@ async fn get_did_doc(did: &str) -> Result<DidDocument> {
 
             Ok(res)
         }
-        _ => todo!("Identifier not supported"),
+        _ => Err(anyhow!("Unsupported DID method: {}", did)),
+    }
+}
+
+/// The handle a DID document claims: its first `at://` `alsoKnownAs` entry.
+fn claimed_handle(doc: &DidDocument) -> Option<String> {
+    doc.also_known_as
+        .iter()
+        .find_map(|aka| aka.strip_prefix("at://"))
+        .map(str::to_ascii_lowercase)
+        .filter(|handle| is_valid_domain(handle))
+}
+
+/// Outcome of checking the handle of a DID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandleCheck {
+    /// The DID document claims the handle and it resolves back to the DID
+    Verified(String),
+    /// The document claims no valid handle, or the handle does not resolve
+    /// to the DID
+    Invalid,
+    /// The handle could not be resolved, e.g. during an AppView outage
+    Unknown,
+}
+
+/// Check the handle of `did` both ways: `doc` claims it and it resolves back
+/// to `did`.
+pub async fn verified_handle(did: &str, doc: &DidDocument) -> HandleCheck {
+    let Some(handle) = claimed_handle(doc) else {
+        return HandleCheck::Invalid;
+    };
+    match resolve_handle(&handle, PUBLIC_APP_VIEW).await {
+        Ok(resolved) if resolved == did => HandleCheck::Verified(handle),
+        Ok(resolved) => {
+            warn!("Handle {} of {} resolves to {}", handle, did, resolved);
+            HandleCheck::Invalid
+        }
+        // The AppView answers 400 for handles that don't resolve
+        Err(e) if e.status() == Some(reqwest::StatusCode::BAD_REQUEST) => {
+            warn!("Handle {} of {} does not resolve", handle, did);
+            HandleCheck::Invalid
+        }
+        Err(e) => {
+            warn!("Could not resolve handle {} of {}: {}", handle, did, e);
+            HandleCheck::Unknown
+        }
     }
 }
 
@@ -214,6 +264,28 @@ fn test_match_did() {
     assert!(!is_did("did:example")); // missing identifier part
 }
 
+#[test]
+fn test_claimed_handle() {
+    let doc = |also_known_as: &[&str]| DidDocument {
+        _context: Vec::new(),
+        id: "did:plc:test".to_string(),
+        also_known_as: also_known_as.iter().map(|aka| aka.to_string()).collect(),
+        verification_method: Vec::new(),
+        service: Vec::new(),
+    };
+
+    assert_eq!(
+        claimed_handle(&doc(&["at://Alice.Example", "at://bob.example"])),
+        Some("alice.example".to_string())
+    );
+    assert_eq!(
+        claimed_handle(&doc(&["https://alice.example", "at://alice.example"])),
+        Some("alice.example".to_string())
+    );
+    assert_eq!(claimed_handle(&doc(&["at://not a handle"])), None);
+    assert_eq!(claimed_handle(&doc(&[])), None);
+}
+
 #[test]
 fn test_valid_domain() {
     // Test cases
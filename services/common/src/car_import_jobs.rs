@@ -0,0 +1,62 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// The state of a job as it is written to `car_import_jobs`.
+#[derive(Debug, Clone)]
+pub struct JobHistoryEntry<'a> {
+    pub job_id: Uuid,
+    pub identity: &'a str,
+    pub did: Option<&'a str>,
+    pub status: &'a str,
+    pub description: Option<&'a str>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub error_message: Option<&'a str>,
+    pub progress: Option<Value>,
+    pub dry_run: bool,
+    /// Report of a completed dry run
+    pub preview: Option<Value>,
+}
+
+/// Insert or update the history row for a job. The DID, start time, progress
+/// and preview of earlier updates are kept when a later one doesn't know them.
+pub async fn record_job_history(sql: &PgPool, entry: &JobHistoryEntry<'_>) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO car_import_jobs (
+            job_id, identity, did, status, description,
+            created_at, started_at, completed_at, error_message, progress,
+            dry_run, preview
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        ON CONFLICT (job_id) DO UPDATE SET
+            did = COALESCE(EXCLUDED.did, car_import_jobs.did),
+            status = EXCLUDED.status,
+            started_at = COALESCE(EXCLUDED.started_at, car_import_jobs.started_at),
+            completed_at = EXCLUDED.completed_at,
+            error_message = EXCLUDED.error_message,
+            progress = COALESCE(EXCLUDED.progress, car_import_jobs.progress),
+            preview = COALESCE(EXCLUDED.preview, car_import_jobs.preview)
+        "#,
+    )
+    .bind(entry.job_id)
+    .bind(entry.identity)
+    .bind(entry.did)
+    .bind(entry.status)
+    .bind(entry.description)
+    .bind(entry.created_at)
+    .bind(entry.started_at)
+    .bind(entry.completed_at)
+    .bind(entry.error_message)
+    .bind(&entry.progress)
+    .bind(entry.dry_run)
+    .bind(&entry.preview)
+    .execute(sql)
+    .await?;
+
+    Ok(())
+}
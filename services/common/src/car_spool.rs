@@ -0,0 +1,201 @@
+//! Spooling of uploaded and fetched CAR files to disk.
+//!
+//! CAR files are written chunk by chunk to a temp file instead of being
+//! buffered in memory, and rejected as soon as they grow past the configured
+//! maximum size. aqua spools uploads and PDS fetches, cadet spools the
+//! repositories its import jobs fetch. An upload can be kept in
+//! `CAR_IMPORT_SPOOL_DIR` and handed over to a cadet job by file name.
+
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use tempfile::TempPath;
+use tokio::fs::File;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+/// Default maximum size of an imported CAR (512 MiB).
+pub const DEFAULT_MAX_CAR_SIZE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Maximum CAR size in bytes, from `CAR_IMPORT_MAX_BYTES`.
+pub fn max_car_size_bytes() -> u64 {
+    std::env::var("CAR_IMPORT_MAX_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_CAR_SIZE_BYTES)
+}
+
+/// Directory shared by aqua and cadet for spooled CARs, from `CAR_IMPORT_SPOOL_DIR`.
+fn spool_dir() -> Option<PathBuf> {
+    std::env::var_os("CAR_IMPORT_SPOOL_DIR").map(PathBuf::from)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SpoolError {
+    #[error("CAR file exceeds the maximum size of {limit} bytes")]
+    TooLarge { limit: u64 },
+    #[error("failed to spool CAR file: {0}")]
+    Io(#[from] std::io::Error),
+    /// The upstream body (multipart field or PDS response) failed mid-stream.
+    #[error("failed to read CAR file: {0}")]
+    Source(anyhow::Error),
+    /// Handing a CAR over to cadet needs a spool directory both can reach.
+    #[error("CAR_IMPORT_SPOOL_DIR is not set")]
+    NotShared,
+    #[error("invalid spool file name: {0}")]
+    InvalidName(String),
+}
+
+/// A CAR file written to a temp file, which is removed once this is dropped
+/// unless it was [kept](SpooledCar::keep).
+pub struct SpooledCar {
+    file: File,
+    path: TempPath,
+    len: u64,
+    limit: u64,
+}
+
+impl SpooledCar {
+    pub fn new(limit: u64) -> Result<Self, SpoolError> {
+        let file = match spool_dir() {
+            Some(dir) => tempfile::NamedTempFile::new_in(dir)?,
+            None => tempfile::NamedTempFile::new()?,
+        };
+        let (file, path) = file.into_parts();
+        Ok(Self {
+            file: File::from_std(file),
+            path,
+            len: 0,
+            limit,
+        })
+    }
+
+    /// Open a CAR kept by another process in the spool directory. The file is
+    /// removed once the returned spool is dropped.
+    pub async fn open_kept(name: &str, limit: u64) -> Result<Self, SpoolError> {
+        if Path::new(name).file_name().and_then(|n| n.to_str()) != Some(name) {
+            return Err(SpoolError::InvalidName(name.to_string()));
+        }
+        let path = spool_dir().ok_or(SpoolError::NotShared)?.join(name);
+        let file = File::open(&path).await?;
+        let path = TempPath::from_path(path);
+        let len = file.metadata().await?.len();
+        if len > limit {
+            return Err(SpoolError::TooLarge { limit });
+        }
+        Ok(Self {
+            file,
+            path,
+            len,
+            limit,
+        })
+    }
+
+    /// Flush the CAR and keep it in the spool directory past this spool's
+    /// lifetime, returning the file name to pass to [`SpooledCar::open_kept`].
+    pub async fn keep(mut self) -> Result<String, SpoolError> {
+        let dir = spool_dir().ok_or(SpoolError::NotShared)?;
+        if self.path.parent() != Some(dir.as_path()) {
+            return Err(SpoolError::NotShared);
+        }
+        self.file.flush().await?;
+        self.file.sync_all().await?;
+        let name = self
+            .path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map(str::to_string)
+            .ok_or_else(|| SpoolError::InvalidName(self.path.display().to_string()))?;
+        self.path.keep().map_err(|e| SpoolError::Io(e.error))?;
+        Ok(name)
+    }
+
+    /// Reject up front when the sender declares a size over the limit.
+    pub fn check_declared_len(&self, declared: Option<u64>) -> Result<(), SpoolError> {
+        match declared {
+            Some(len) if len > self.limit => Err(SpoolError::TooLarge { limit: self.limit }),
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn write_chunk(&mut self, chunk: &Bytes) -> Result<(), SpoolError> {
+        let len = self.len + chunk.len() as u64;
+        if len > self.limit {
+            return Err(SpoolError::TooLarge { limit: self.limit });
+        }
+        self.file.write_all(chunk).await?;
+        self.len = len;
+        Ok(())
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Flush pending writes and return a reader positioned at the start of the CAR.
+    pub async fn reader(&mut self) -> Result<&mut File, SpoolError> {
+        self.file.flush().await?;
+        self.file.seek(SeekFrom::Start(0)).await?;
+        Ok(&mut self.file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_spool_round_trip() {
+        let mut spool = SpooledCar::new(16).unwrap();
+        spool
+            .write_chunk(&Bytes::from_static(b"hello "))
+            .await
+            .unwrap();
+        spool
+            .write_chunk(&Bytes::from_static(b"world"))
+            .await
+            .unwrap();
+
+        assert_eq!(spool.len(), 11);
+        let mut data = Vec::new();
+        spool
+            .reader()
+            .await
+            .unwrap()
+            .read_to_end(&mut data)
+            .await
+            .unwrap();
+        assert_eq!(data, b"hello world");
+    }
+
+    #[tokio::test]
+    async fn test_spool_rejects_oversized_input() {
+        let mut spool = SpooledCar::new(8).unwrap();
+        spool
+            .write_chunk(&Bytes::from_static(b"12345"))
+            .await
+            .unwrap();
+
+        let err = spool
+            .write_chunk(&Bytes::from_static(b"6789"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SpoolError::TooLarge { limit: 8 }));
+        assert_eq!(spool.len(), 5);
+
+        assert!(spool.check_declared_len(Some(9)).is_err());
+        assert!(spool.check_declared_len(Some(8)).is_ok());
+        assert!(spool.check_declared_len(None).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_open_kept_rejects_paths() {
+        for name in ["../car", "/tmp/car", "a/b", ""] {
+            assert!(matches!(
+                SpooledCar::open_kept(name, 16).await,
+                Err(SpoolError::InvalidName(_))
+            ));
+        }
+    }
+}
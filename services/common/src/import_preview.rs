@@ -0,0 +1,345 @@
+//! Dry-run preview of a CAR import.
+//!
+//! A preview walks the repository exactly like a real import but only reads
+//! from the database, so support can see what an import would do to a user's
+//! history before anything is written. cadet builds it in dry-run import jobs
+//! and aqua reads it back from the job status.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+/// What a dry-run import would do with a repository.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportPreview {
+    pub did: String,
+    /// Records found per collection NSID, including legacy alpha collections
+    pub records_per_collection: BTreeMap<String, u64>,
+    /// Alpha records dropped because an identical stable record exists
+    pub namespace_duplicates: u64,
+    pub play_summary: PlaySummary,
+    pub plays: Vec<PlayPreview>,
+    pub parse_failures: Vec<ParseFailure>,
+    pub artists: Vec<EntityMatchPreview>,
+    pub releases: Vec<EntityMatchPreview>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlaySummary {
+    pub new: u64,
+    pub updated: u64,
+    pub duplicate: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlayOutcome {
+    /// No existing row, the play would be inserted
+    New,
+    /// A row with the same URI exists but its track or play time differ
+    Updated,
+    /// An equivalent row already exists, either at this URI or another one
+    Duplicate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayPreview {
+    pub rkey: String,
+    pub track_name: String,
+    pub artist_names: Vec<String>,
+    pub release_name: Option<String>,
+    pub played_time: Option<DateTime<Utc>>,
+    pub outcome: PlayOutcome,
+    /// The `plays` row this play would update or duplicate
+    pub existing_uri: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParseFailure {
+    pub collection: String,
+    pub rkey: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchOutcome {
+    /// The record carries a MusicBrainz ID, which would be used as is
+    Mbid,
+    /// An existing entity would be reused
+    Matched,
+    /// A close match exists but is below the automatic match threshold
+    Candidate,
+    /// A new (synthetic) entity would be created
+    New,
+    /// The name would only be stored on the play, without a linked entity
+    Unlinked,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntityMatchPreview {
+    pub name: String,
+    pub outcome: MatchOutcome,
+    pub mbid: Option<String>,
+    pub matched_name: Option<String>,
+    pub confidence: Option<f64>,
+    /// Number of previewed plays credited with this name
+    pub plays: u64,
+}
+
+/// A stored play, as needed to classify imported plays.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ExistingPlay {
+    pub uri: String,
+    pub track_name: String,
+    /// Credited artist names, in credit order
+    pub artist_names: Vec<String>,
+    pub played_time: Option<DateTime<Utc>>,
+}
+
+impl ExistingPlay {
+    /// The plays of `did`, or only those played within `between` (inclusive).
+    pub async fn fetch(
+        sql: &PgPool,
+        did: &str,
+        between: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    ) -> anyhow::Result<Vec<Self>> {
+        Ok(sqlx::query_as::<_, Self>(
+            r#"
+            SELECT p.uri, p.track_name, p.played_time,
+                   COALESCE(
+                       array_agg(pta.artist_name ORDER BY pta.position)
+                           FILTER (WHERE pta.artist_name IS NOT NULL),
+                       '{}'
+                   ) AS artist_names
+            FROM plays p
+            LEFT JOIN play_to_artists_extended pta ON pta.play_uri = p.uri
+            WHERE p.did = $1
+              AND ($2::timestamptz IS NULL OR p.played_time BETWEEN $2 AND $3)
+            GROUP BY p.uri
+            "#,
+        )
+        .bind(did)
+        .bind(between.map(|(from, _)| from))
+        .bind(between.map(|(_, to)| to))
+        .fetch_all(sql)
+        .await?)
+    }
+}
+
+/// Plays already stored for a DID, indexed for classification.
+#[derive(Debug, Default)]
+pub struct ExistingPlays {
+    by_uri: HashMap<String, (String, Option<i64>)>,
+    by_content: HashMap<(String, i64), String>,
+    /// Play times (Unix seconds) and credited artists per track, sorted by time
+    by_track: HashMap<String, Vec<(i64, Vec<String>)>>,
+}
+
+impl ExistingPlays {
+    pub async fn load(sql: &PgPool, did: &str) -> anyhow::Result<Self> {
+        Ok(Self::from_rows(ExistingPlay::fetch(sql, did, None).await?))
+    }
+
+    pub fn from_rows(rows: Vec<ExistingPlay>) -> Self {
+        let mut existing = Self::default();
+        for row in rows {
+            existing.insert(row);
+        }
+        for plays in existing.by_track.values_mut() {
+            plays.sort_unstable_by_key(|(played_at, _)| *played_at);
+        }
+        existing
+    }
+
+    fn insert(&mut self, row: ExistingPlay) {
+        let key = content_key(&row.track_name);
+        let played_at = row.played_time.map(|t| t.timestamp());
+        if let Some(played_at) = played_at {
+            self.by_content
+                .entry((key.clone(), played_at))
+                .or_insert_with(|| row.uri.clone());
+            let artists = row.artist_names.iter().map(|a| content_key(a)).collect();
+            self.by_track
+                .entry(key.clone())
+                .or_default()
+                .push((played_at, artists));
+        }
+        self.by_uri.insert(row.uri, (key, played_at));
+    }
+
+    /// Decide what importing a play at `uri` would do, returning the affected
+    /// existing row if there is one.
+    pub fn classify(
+        &self,
+        uri: &str,
+        track_name: &str,
+        played_time: Option<DateTime<Utc>>,
+    ) -> (PlayOutcome, Option<String>) {
+        let key = content_key(track_name);
+        let played_at = played_time.map(|t| t.timestamp());
+
+        if let Some((existing_track, existing_played_at)) = self.by_uri.get(uri) {
+            let outcome = if *existing_track == key && *existing_played_at == played_at {
+                PlayOutcome::Duplicate
+            } else {
+                PlayOutcome::Updated
+            };
+            return (outcome, Some(uri.to_string()));
+        }
+
+        match played_at.and_then(|at| self.by_content.get(&(key, at))) {
+            Some(existing_uri) => (PlayOutcome::Duplicate, Some(existing_uri.clone())),
+            None => (PlayOutcome::New, None),
+        }
+    }
+
+    /// Whether a play of `track_name` by one of `artist_names` exists within
+    /// `window` of `played_time`, for sources whose timestamps drift from the
+    /// ones teal clients record. Plays without credited artists on either
+    /// side match on the track alone.
+    pub fn has_play_near(
+        &self,
+        track_name: &str,
+        artist_names: &[&str],
+        played_time: DateTime<Utc>,
+        window: chrono::Duration,
+    ) -> bool {
+        let Some(plays) = self.by_track.get(&content_key(track_name)) else {
+            return false;
+        };
+        let artists: Vec<String> = artist_names.iter().map(|a| content_key(a)).collect();
+        let at = played_time.timestamp();
+        let window = window.num_seconds().abs();
+        let start = plays.partition_point(|(t, _)| *t < at - window);
+        plays[start..]
+            .iter()
+            .take_while(|(t, _)| *t <= at + window)
+            .any(|(_, existing)| same_artist(&artists, existing))
+    }
+}
+
+/// Whether two normalised credits share an artist. A name also matches a
+/// longer one it starts a word of, so "aphex twin" matches the unsplit
+/// credit "aphex twin feat. x".
+fn same_artist(a: &[String], b: &[String]) -> bool {
+    if a.is_empty() || b.is_empty() {
+        return true;
+    }
+    let starts = |long: &str, short: &str| {
+        long.strip_prefix(short)
+            .is_some_and(|rest| rest.is_empty() || !rest.starts_with(char::is_alphanumeric))
+    };
+    a.iter()
+        .any(|x| b.iter().any(|y| starts(x, y) || starts(y, x)))
+}
+
+fn content_key(track_name: &str) -> String {
+    track_name.trim().to_lowercase()
+}
+
+impl ImportPreview {
+    pub fn push_play(&mut self, play: PlayPreview) {
+        match play.outcome {
+            PlayOutcome::New => self.play_summary.new += 1,
+            PlayOutcome::Updated => self.play_summary.updated += 1,
+            PlayOutcome::Duplicate => self.play_summary.duplicate += 1,
+        }
+        self.plays.push(play);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(ts: i64) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp(ts, 0)
+    }
+
+    fn play(uri: &str, track_name: &str, artist_names: &[&str], ts: i64) -> ExistingPlay {
+        ExistingPlay {
+            uri: uri.to_string(),
+            track_name: track_name.to_string(),
+            artist_names: artist_names.iter().map(|a| a.to_string()).collect(),
+            played_time: at(ts),
+        }
+    }
+
+    fn existing() -> ExistingPlays {
+        ExistingPlays::from_rows(vec![
+            play("at://did:plc:a/fm.teal.feed.play/1", "Song", &["Band"], 100),
+            play("at://did:plc:a/fm.teal.feed.play/2", "Other", &[], 200),
+        ])
+    }
+
+    #[test]
+    fn classifies_plays_against_existing_rows() {
+        let existing = existing();
+
+        assert_eq!(
+            existing.classify("at://did:plc:a/fm.teal.feed.play/1", "song ", at(100)),
+            (
+                PlayOutcome::Duplicate,
+                Some("at://did:plc:a/fm.teal.feed.play/1".to_string())
+            )
+        );
+        assert_eq!(
+            existing
+                .classify("at://did:plc:a/fm.teal.feed.play/1", "Song (Live)", at(100))
+                .0,
+            PlayOutcome::Updated
+        );
+        assert_eq!(
+            existing.classify("at://did:plc:a/fm.teal.feed.play/9", "Other", at(200)),
+            (
+                PlayOutcome::Duplicate,
+                Some("at://did:plc:a/fm.teal.feed.play/2".to_string())
+            )
+        );
+        assert_eq!(
+            existing.classify("at://did:plc:a/fm.teal.feed.play/9", "Other", None),
+            (PlayOutcome::New, None)
+        );
+    }
+
+    #[test]
+    fn finds_plays_of_the_same_artist_near_a_time() {
+        let existing = existing();
+        let window = chrono::Duration::seconds(30);
+        let near = |track, artists: &[&str], ts| {
+            existing.has_play_near(track, artists, at(ts).unwrap(), window)
+        };
+
+        assert!(near("song", &["Band"], 120));
+        assert!(near("Song", &["band & friend"], 80));
+        assert!(near("Song", &[], 100));
+        assert!(!near("Song", &["Bandit"], 100));
+        assert!(!near("Song", &["Another Band"], 100));
+        assert!(!near("Song", &["Band"], 131));
+        // No credited artists stored, so the track alone matches
+        assert!(near("Other", &["Anyone"], 200));
+    }
+
+    #[test]
+    fn summary_counts_follow_outcomes() {
+        let mut preview = ImportPreview::default();
+        for outcome in [PlayOutcome::New, PlayOutcome::New, PlayOutcome::Duplicate] {
+            preview.push_play(PlayPreview {
+                rkey: "r".into(),
+                track_name: "t".into(),
+                artist_names: vec![],
+                release_name: None,
+                played_time: None,
+                outcome,
+                existing_uri: None,
+            });
+        }
+
+        assert_eq!(preview.play_summary.new, 2);
+        assert_eq!(preview.play_summary.duplicate, 1);
+        assert_eq!(preview.play_summary.updated, 0);
+    }
+}
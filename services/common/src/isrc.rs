@@ -0,0 +1,42 @@
+//! International Standard Recording Codes as clients send them in plays.
+
+/// An ISRC in its canonical form (`USQX91300108`), or `None` if `isrc` isn't
+/// one. Clients also send them hyphenated or lowercase.
+pub fn normalize_isrc(isrc: &str) -> Option<String> {
+    let isrc: String = isrc
+        .chars()
+        .filter(|c| *c != '-' && !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    let bytes = isrc.as_bytes();
+    let valid = bytes.len() == 12
+        && bytes[..2].iter().all(u8::is_ascii_uppercase)
+        && bytes[2..5].iter().all(u8::is_ascii_alphanumeric)
+        && bytes[5..].iter().all(u8::is_ascii_digit);
+    valid.then_some(isrc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_isrc() {
+        assert_eq!(
+            normalize_isrc("USQX91300108").as_deref(),
+            Some("USQX91300108")
+        );
+        assert_eq!(
+            normalize_isrc("us-qx9-13-00108").as_deref(),
+            Some("USQX91300108")
+        );
+        assert_eq!(
+            normalize_isrc(" GB AYE 69 00531 ").as_deref(),
+            Some("GBAYE6900531")
+        );
+        assert_eq!(normalize_isrc("USQX9130010"), None);
+        assert_eq!(normalize_isrc("1SQX91300108"), None);
+        assert_eq!(normalize_isrc("USQX9130010A"), None);
+        assert_eq!(normalize_isrc(""), None);
+    }
+}
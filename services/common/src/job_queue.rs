@@ -0,0 +1,318 @@
+//! Job queue backends for CAR and history import jobs.
+//!
+//! Jobs and their status keys live either in Redis (the default) or in
+//! Postgres, selected with `JOB_QUEUE_BACKEND`. aqua queues jobs and reads
+//! their status, cadet runs them; both build the queue from the same config.
+//!
+//! Postgres jobs are delivered at least once. Completing a job is not part of
+//! the transactions that write its results (a CAR import alone commits batch
+//! after batch), so a worker that dies in between leaves the job to be run
+//! again once its lease expires. Every job must therefore be idempotent: CAR
+//! imports upsert records under their `at://` URI and repository CID, history
+//! imports upsert plays under deterministic `teal-import://` URIs with a CID
+//! derived from the job.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+use crate::redis_client::RedisClient;
+
+/// How often an empty Postgres queue is polled while waiting for a job.
+const PG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default time a worker has to finish a Postgres job before another worker
+/// may claim it again.
+pub const DEFAULT_JOB_LEASE_SECS: u64 = 60 * 60;
+
+/// Default number of times a Postgres job is claimed before it is given up on.
+pub const DEFAULT_JOB_MAX_ATTEMPTS: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobQueueBackend {
+    Redis,
+    Postgres,
+}
+
+impl std::str::FromStr for JobQueueBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "redis" => Ok(Self::Redis),
+            "postgres" | "postgresql" | "pg" => Ok(Self::Postgres),
+            other => Err(anyhow!("unknown job queue backend: {}", other)),
+        }
+    }
+}
+
+impl JobQueueBackend {
+    /// Backend from `JOB_QUEUE_BACKEND`, defaulting to Redis.
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("JOB_QUEUE_BACKEND") {
+            Ok(value) if !value.trim().is_empty() => value.trim().parse(),
+            _ => Ok(Self::Redis),
+        }
+    }
+}
+
+/// A job taken from a queue. Pass it to [`JobQueue::complete_job`] once it has
+/// been handled.
+#[derive(Debug)]
+pub struct QueuedJob {
+    pub payload: String,
+    /// `job_queue.id` of a leased Postgres job
+    lease_id: Option<i64>,
+}
+
+#[async_trait]
+pub trait JobQueue: Send + Sync {
+    async fn queue_job(&self, queue_key: &str, job_data: &str) -> Result<()>;
+    /// Take the oldest job, waiting up to `timeout_seconds` for one to arrive
+    async fn pop_job(&self, queue_key: &str, timeout_seconds: u64) -> Result<Option<QueuedJob>>;
+    /// Remove a job once it has completed, failed for good or been dropped.
+    /// A Postgres job that is never completed is handed out again once its
+    /// lease expires.
+    async fn complete_job(&self, job: &QueuedJob) -> Result<()>;
+    /// Store a job status, expiring it after `ttl_seconds` when given
+    async fn update_job_status(
+        &self,
+        status_key: &str,
+        status_data: &str,
+        ttl_seconds: Option<u64>,
+    ) -> Result<()>;
+    async fn get_job_status(&self, status_key: &str) -> Result<Option<String>>;
+}
+
+/// Redis pops jobs with `BRPOP`, so each job is handed out at most once.
+#[async_trait]
+impl JobQueue for RedisClient {
+    async fn queue_job(&self, queue_key: &str, job_data: &str) -> Result<()> {
+        RedisClient::queue_job(self, queue_key, job_data).await
+    }
+
+    async fn pop_job(&self, queue_key: &str, timeout_seconds: u64) -> Result<Option<QueuedJob>> {
+        Ok(RedisClient::pop_job(self, queue_key, timeout_seconds)
+            .await?
+            .map(|payload| QueuedJob {
+                payload,
+                lease_id: None,
+            }))
+    }
+
+    async fn complete_job(&self, _job: &QueuedJob) -> Result<()> {
+        Ok(())
+    }
+
+    async fn update_job_status(
+        &self,
+        status_key: &str,
+        status_data: &str,
+        ttl_seconds: Option<u64>,
+    ) -> Result<()> {
+        match ttl_seconds {
+            Some(ttl) => {
+                self.update_job_status_with_ttl(status_key, status_data, ttl)
+                    .await
+            }
+            None => RedisClient::update_job_status(self, status_key, status_data).await,
+        }
+    }
+
+    async fn get_job_status(&self, status_key: &str) -> Result<Option<String>> {
+        RedisClient::get_job_status(self, status_key).await
+    }
+}
+
+/// Job queue on the `job_queue` and `job_queue_statuses` tables.
+///
+/// Workers lease jobs with `FOR UPDATE SKIP LOCKED`, so several cadet
+/// instances can share a queue without handing the same job out twice. A job
+/// stays in the table until it is completed; if its worker dies, the job is
+/// claimed again once the lease expires, up to `JOB_MAX_ATTEMPTS` times.
+pub struct PgJobQueue {
+    sql: PgPool,
+    lease: Duration,
+    max_attempts: i32,
+}
+
+impl PgJobQueue {
+    pub fn new(sql: PgPool) -> Self {
+        Self {
+            sql,
+            lease: Duration::from_secs(
+                std::env::var("JOB_LEASE_SECS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(DEFAULT_JOB_LEASE_SECS),
+            ),
+            max_attempts: job_max_attempts(),
+        }
+    }
+
+    async fn try_pop_job(&self, queue_key: &str) -> Result<Option<QueuedJob>> {
+        let row = sqlx::query_as::<_, (i64, String)>(
+            r#"
+            UPDATE job_queue
+            SET locked_until = NOW() + make_interval(secs => $2),
+                attempts = attempts + 1
+            WHERE id = (
+                SELECT id FROM job_queue
+                WHERE queue = $1
+                  AND (locked_until IS NULL OR locked_until < NOW())
+                  AND attempts < $3
+                ORDER BY id
+                FOR UPDATE SKIP LOCKED
+                LIMIT 1
+            )
+            RETURNING id, payload
+            "#,
+        )
+        .bind(queue_key)
+        .bind(self.lease.as_secs_f64())
+        .bind(self.max_attempts)
+        .fetch_optional(&self.sql)
+        .await?;
+        Ok(row.map(|(id, payload)| QueuedJob {
+            payload,
+            lease_id: Some(id),
+        }))
+    }
+}
+
+#[async_trait]
+impl JobQueue for PgJobQueue {
+    async fn queue_job(&self, queue_key: &str, job_data: &str) -> Result<()> {
+        sqlx::query("INSERT INTO job_queue (queue, payload) VALUES ($1, $2)")
+            .bind(queue_key)
+            .bind(job_data)
+            .execute(&self.sql)
+            .await?;
+        Ok(())
+    }
+
+    async fn pop_job(&self, queue_key: &str, timeout_seconds: u64) -> Result<Option<QueuedJob>> {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(timeout_seconds);
+        loop {
+            if let Some(job) = self.try_pop_job(queue_key).await? {
+                return Ok(Some(job));
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Ok(None);
+            }
+            tokio::time::sleep(PG_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn complete_job(&self, job: &QueuedJob) -> Result<()> {
+        if let Some(id) = job.lease_id {
+            sqlx::query("DELETE FROM job_queue WHERE id = $1")
+                .bind(id)
+                .execute(&self.sql)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn update_job_status(
+        &self,
+        status_key: &str,
+        status_data: &str,
+        ttl_seconds: Option<u64>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO job_queue_statuses (status_key, status_data, updated_at, expires_at)
+            VALUES ($1, $2, NOW(), NOW() + make_interval(secs => $3))
+            ON CONFLICT (status_key) DO UPDATE SET
+                status_data = EXCLUDED.status_data,
+                updated_at = EXCLUDED.updated_at,
+                expires_at = EXCLUDED.expires_at
+            "#,
+        )
+        .bind(status_key)
+        .bind(status_data)
+        .bind(ttl_seconds.map(|ttl| ttl as f64))
+        .execute(&self.sql)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_job_status(&self, status_key: &str) -> Result<Option<String>> {
+        Ok(sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT status_data FROM job_queue_statuses
+            WHERE status_key = $1 AND (expires_at IS NULL OR expires_at > NOW())
+            "#,
+        )
+        .bind(status_key)
+        .fetch_optional(&self.sql)
+        .await?)
+    }
+}
+
+/// Claims per Postgres job before it is given up on, from `JOB_MAX_ATTEMPTS`.
+pub fn job_max_attempts() -> i32 {
+    std::env::var("JOB_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|attempts| *attempts > 0)
+        .unwrap_or(DEFAULT_JOB_MAX_ATTEMPTS)
+}
+
+/// Build the configured job queue.
+pub fn job_queue_from_env(sql: PgPool) -> Result<Arc<dyn JobQueue>> {
+    Ok(match JobQueueBackend::from_env()? {
+        JobQueueBackend::Redis => {
+            let redis_url =
+                std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+            Arc::new(RedisClient::new(&redis_url)?)
+        }
+        JobQueueBackend::Postgres => Arc::new(PgJobQueue::new(sql)),
+    })
+}
+
+/// Delete expired rows from `job_queue_statuses`. Redis expires its keys itself.
+pub async fn prune_expired_job_statuses(sql: &PgPool) -> Result<u64> {
+    let result = sqlx::query(
+        "DELETE FROM job_queue_statuses WHERE expires_at IS NOT NULL AND expires_at < NOW()",
+    )
+    .execute(sql)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Delete Postgres jobs whose last allowed lease expired without the job being
+/// completed, e.g. because it crashed its worker every time.
+pub async fn prune_abandoned_jobs(sql: &PgPool) -> Result<u64> {
+    let result = sqlx::query("DELETE FROM job_queue WHERE attempts >= $1 AND locked_until < NOW()")
+        .bind(job_max_attempts())
+        .execute(sql)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_job_queue_backend() {
+        assert_eq!(
+            "redis".parse::<JobQueueBackend>().unwrap(),
+            JobQueueBackend::Redis
+        );
+        assert_eq!(
+            "Postgres".parse::<JobQueueBackend>().unwrap(),
+            JobQueueBackend::Postgres
+        );
+        assert_eq!(
+            "pg".parse::<JobQueueBackend>().unwrap(),
+            JobQueueBackend::Postgres
+        );
+        assert!("sqs".parse::<JobQueueBackend>().is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! CAR and history import jobs as aqua queues them and cadet runs them.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::import_preview::ImportPreview;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CarImportJob {
+    pub request_id: Uuid,
+    pub identity: String,
+    pub since: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub description: Option<String>,
+    /// Only report what the import would do, without writing anything
+    #[serde(default)]
+    pub dry_run: bool,
+    /// A CAR uploaded to aqua and kept in the shared spool directory, read
+    /// instead of fetching the identity's repository from its PDS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spool_file: Option<String>,
+}
+
+/// URI scheme of imported plays. They were never records in the user's
+/// repository, so they are kept out of the `at://` namespace that repo
+/// exports, firehose deletes and record lookups work on.
+pub const IMPORTED_PLAY_URI_SCHEME: &str = "teal-import://";
+
+/// URI of a play imported from another service's history export.
+pub fn imported_play_uri(did: &str, source: &str, rkey: &str) -> String {
+    format!("{IMPORTED_PLAY_URI_SCHEME}{did}/{source}/{rkey}")
+}
+
+/// Plays parsed from another service's listening history export, queued for
+/// cadet's play ingestor. Large uploads are split over several jobs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryImportJob {
+    pub request_id: Uuid,
+    pub did: String,
+    /// Service the history was exported from, e.g. `spotify`
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+    pub plays: Vec<ImportedPlay>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedPlay {
+    pub rkey: String,
+    /// `fm.teal.feed.play` record
+    pub record: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CarImportJobStatus {
+    pub status: JobStatus,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub error_message: Option<String>,
+    pub progress: Option<JobProgress>,
+    /// Report of a completed dry-run job
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<ImportPreview>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum JobStatus {
+    Pending,
+    Processing,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Lowercase name used for the `car_import_jobs.status` column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Pending => "pending",
+            JobStatus::Processing => "processing",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl std::str::FromStr for JobStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pending" => Ok(JobStatus::Pending),
+            "processing" => Ok(JobStatus::Processing),
+            "completed" => Ok(JobStatus::Completed),
+            "failed" => Ok(JobStatus::Failed),
+            "cancelled" => Ok(JobStatus::Cancelled),
+            other => Err(format!("unknown job status: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobProgress {
+    pub step: String,
+    pub user_did: Option<String>,
+    pub pds_host: Option<String>,
+    pub car_size_bytes: Option<u64>,
+    pub blocks_processed: Option<u64>,
+    pub bytes_downloaded: Option<u64>,
+    /// Teal records found in the repository, keyed by stable collection NSID
+    pub records_extracted: Option<BTreeMap<String, u64>>,
+    pub records_inserted: Option<u64>,
+    /// Records dropped before insertion, e.g. alpha/stable namespace duplicates
+    pub records_skipped: Option<u64>,
+    pub records_failed: Option<u64>,
+}
+
+pub mod queue_keys {
+    use uuid::Uuid;
+
+    pub const CAR_IMPORT_JOBS: &str = "car_import_jobs";
+    pub const CAR_IMPORT_STATUS_PREFIX: &str = "car_import_status";
+    /// History imports share the CAR import status keys
+    pub const HISTORY_IMPORT_JOBS: &str = "history_import_jobs";
+
+    pub fn job_status_key(job_id: &Uuid) -> String {
+        format!("{}:{}", CAR_IMPORT_STATUS_PREFIX, job_id)
+    }
+}
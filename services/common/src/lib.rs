@@ -0,0 +1,9 @@
+//! Code shared by cadet and aqua.
+
+pub mod car_import_jobs;
+pub mod car_spool;
+pub mod import_preview;
+pub mod isrc;
+pub mod job_queue;
+pub mod jobs;
+pub mod redis_client;
@@ -16,6 +16,13 @@ impl RedisClient {
         Ok(conn)
     }
 
+    /// Push a job to the Redis queue
+    pub async fn queue_job(&self, queue_key: &str, job_data: &str) -> Result<()> {
+        let mut conn = self.get_connection().await?;
+        let _: () = conn.lpush(queue_key, job_data).await?;
+        Ok(())
+    }
+
     /// Pop a job from the Redis queue (blocking)
     pub async fn pop_job(&self, queue_key: &str, timeout_seconds: u64) -> Result<Option<String>> {
         let mut conn = self.get_connection().await?;
@@ -36,4 +43,23 @@ impl RedisClient {
         let _: () = conn.set(status_key, status_data).await?;
         Ok(())
     }
+
+    /// Update job status in Redis and expire the key after `ttl_seconds`
+    pub async fn update_job_status_with_ttl(
+        &self,
+        status_key: &str,
+        status_data: &str,
+        ttl_seconds: u64,
+    ) -> Result<()> {
+        let mut conn = self.get_connection().await?;
+        let _: () = conn.set_ex(status_key, status_data, ttl_seconds).await?;
+        Ok(())
+    }
+
+    /// Get job status from Redis
+    pub async fn get_job_status(&self, status_key: &str) -> Result<Option<String>> {
+        let mut conn = self.get_connection().await?;
+        let status: Option<String> = conn.get(status_key).await?;
+        Ok(status)
+    }
 }
@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use colored::*;
+use std::path::PathBuf;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+/// Download an actor's teal records as a CAR file from aqua's export endpoint,
+/// authenticated with a service auth token of `identity`
+pub async fn export_car(
+    identity: String,
+    aqua_url: String,
+    output: Option<PathBuf>,
+    token: String,
+) -> Result<()> {
+    let url = format!(
+        "{}/api/car/export/{}",
+        aqua_url.trim_end_matches('/'),
+        identity
+    );
+    let output =
+        output.unwrap_or_else(|| PathBuf::from(format!("{}.car", identity.replace(':', "-"))));
+
+    println!("{} Exporting teal records for {}...", "📦".blue(), identity);
+
+    let mut response = reqwest::Client::new()
+        .get(&url)
+        .bearer_auth(&token)
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach aqua at {}", url))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("Export failed with {}: {}", status, body);
+    }
+
+    let mut file = fs::File::create(&output)
+        .await
+        .with_context(|| format!("Failed to create {:?}", output))?;
+    let mut written = 0u64;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+    }
+    file.flush().await?;
+
+    println!("{} CAR file written!", "✅".green());
+    println!("  {} {:?}", "File:".bold(), output);
+    println!("  {} {} bytes", "Size:".bold(), written);
+
+    Ok(())
+}
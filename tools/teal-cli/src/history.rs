@@ -0,0 +1,98 @@
+use anyhow::{Context, Result};
+use colored::*;
+use serde_json::Value;
+use std::path::PathBuf;
+use tokio::fs;
+
+/// Upload listening history export files to aqua's import endpoint for
+/// `source` (`spotify`, `listenbrainz` or `lastfm`), authenticated with a
+/// service auth token of `identity`
+pub async fn import_history(
+    source: &str,
+    identity: String,
+    files: Vec<PathBuf>,
+    aqua_url: String,
+    token: String,
+    min_ms_played: Option<u64>,
+) -> Result<()> {
+    let url = format!("{}/api/import/{}", aqua_url.trim_end_matches('/'), source);
+
+    let mut form = reqwest::multipart::Form::new().text("did", identity.clone());
+    if let Some(min_ms_played) = min_ms_played {
+        form = form.text("min_ms_played", min_ms_played.to_string());
+    }
+    for path in &files {
+        let data = fs::read(path)
+            .await
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "history".to_string());
+        form = form.part(
+            "history_file",
+            reqwest::multipart::Part::bytes(data).file_name(file_name),
+        );
+    }
+
+    println!(
+        "{} Uploading {} {} history files for {}...",
+        "🎧".blue(),
+        files.len(),
+        source,
+        identity
+    );
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .bearer_auth(&token)
+        .multipart(form)
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach aqua at {}", url))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("Import failed with {}: {}", status, body);
+    }
+
+    let body: Value = response.json().await?;
+    let summary = &body["summary"];
+    let job_ids: Vec<&str> = body["job_ids"]
+        .as_array()
+        .map(|ids| ids.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    println!("{} History queued for import!", "✅".green());
+    println!("  {} {}", "Job IDs:".bold(), job_ids.join(", "));
+    println!(
+        "  {} {}",
+        "DID:".bold(),
+        body["did"].as_str().unwrap_or("?")
+    );
+    println!("  {} {}", "Entries:".bold(), summary["entries"]);
+    println!("  {} {}", "Plays:".bold(), summary["plays"]);
+    println!(
+        "  {} {}",
+        "Skipped (too short):".bold(),
+        summary["skipped_short"]
+    );
+    println!(
+        "  {} {}",
+        "Skipped (not tracks):".bold(),
+        summary["skipped_non_track"]
+    );
+    println!(
+        "  {} {}",
+        "Skipped (already imported):".bold(),
+        summary["skipped_existing"]
+    );
+    for job_id in job_ids {
+        println!(
+            "Check progress at {}/api/car/job-status/{}",
+            aqua_url.trim_end_matches('/'),
+            job_id
+        );
+    }
+
+    Ok(())
+}
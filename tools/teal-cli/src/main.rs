@@ -4,6 +4,8 @@ use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
 mod crypto;
+mod export;
+mod history;
 
 #[derive(Parser)]
 #[command(name = "teal")]
@@ -63,6 +65,87 @@ enum Commands {
         #[arg(short, long)]
         backup_dir: Option<PathBuf>,
     },
+
+    /// Export an actor's teal records from aqua as a CAR file
+    ExportCar {
+        /// DID or handle of the actor
+        identity: String,
+
+        /// Base URL of the aqua service
+        #[arg(long, default_value = "http://localhost:3000")]
+        aqua_url: String,
+
+        /// Output file (defaults to <did>.car in the current directory)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Service auth token for aqua, from `com.atproto.server.getServiceAuth`
+        /// with aqua's did:web as the audience
+        #[arg(long)]
+        token: String,
+    },
+
+    /// Import Spotify extended streaming history (endsong_*.json /
+    /// Streaming_History_Audio_*.json) through aqua
+    ImportSpotify {
+        /// DID or handle of the actor the plays belong to
+        identity: String,
+
+        /// History files from the Spotify data export
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Base URL of the aqua service
+        #[arg(long, default_value = "http://localhost:3000")]
+        aqua_url: String,
+
+        /// Service auth token for aqua, from `com.atproto.server.getServiceAuth`
+        /// with aqua's did:web as the audience
+        #[arg(long)]
+        token: String,
+
+        /// Drop streams shorter than this many milliseconds (aqua defaults to 30000)
+        #[arg(long)]
+        min_ms_played: Option<u64>,
+    },
+
+    /// Import a ListenBrainz listens export (JSON or JSON lines) through aqua
+    ImportListenbrainz {
+        /// DID or handle of the actor the plays belong to
+        identity: String,
+
+        /// Files from the ListenBrainz export
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Base URL of the aqua service
+        #[arg(long, default_value = "http://localhost:3000")]
+        aqua_url: String,
+
+        /// Service auth token for aqua, from `com.atproto.server.getServiceAuth`
+        /// with aqua's did:web as the audience
+        #[arg(long)]
+        token: String,
+    },
+
+    /// Import a Last.fm scrobble dump (recent tracks JSON or CSV) through aqua
+    ImportLastfm {
+        /// DID or handle of the actor the plays belong to
+        identity: String,
+
+        /// Scrobble dump files
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Base URL of the aqua service
+        #[arg(long, default_value = "http://localhost:3000")]
+        aqua_url: String,
+
+        /// Service auth token for aqua, from `com.atproto.server.getServiceAuth`
+        /// with aqua's did:web as the audience
+        #[arg(long)]
+        token: String,
+    },
 }
 
 fn get_default_keys_dir() -> PathBuf {
@@ -98,5 +181,33 @@ async fn main() -> Result<()> {
             let keys_dir = get_default_keys_dir();
             crypto::rotate_key(keys_dir, name, backup_dir).await
         }
+        Commands::ExportCar {
+            identity,
+            aqua_url,
+            output,
+            token,
+        } => export::export_car(identity, aqua_url, output, token).await,
+        Commands::ImportSpotify {
+            identity,
+            files,
+            aqua_url,
+            token,
+            min_ms_played,
+        } => {
+            history::import_history("spotify", identity, files, aqua_url, token, min_ms_played)
+                .await
+        }
+        Commands::ImportListenbrainz {
+            identity,
+            files,
+            aqua_url,
+            token,
+        } => history::import_history("listenbrainz", identity, files, aqua_url, token, None).await,
+        Commands::ImportLastfm {
+            identity,
+            files,
+            aqua_url,
+            token,
+        } => history::import_history("lastfm", identity, files, aqua_url, token, None).await,
     }
 }